@@ -4,7 +4,7 @@ use serde::{Deserialize, Serialize};
 use std::{fmt::Display, sync::Arc};
 
 use super::{
-    sql::{SqlDelete, SqlInsert, SqlSelect, SqlUpdate},
+    sql::{SqlDelete, SqlInsert, SqlSelect, SqlUpdate, SqlWindow},
     stmt::Stmt,
     AstNode, Identifier, Literal, Span, Spanned,
 };
@@ -31,6 +31,8 @@ pub enum Operation {
     NotIn,
     Like,
     NotLike,
+    Exists,
+    NotExists,
 }
 
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize, Hash)]
@@ -216,6 +218,7 @@ pub enum Expr {
     Call {
         callee: Box<Expr>,
         args: Vec<Expr>,
+        over: Option<Box<SqlWindow>>,
         #[serde(skip)]
         #[derivative(PartialEq = "ignore")]
         #[derivative(Hash = "ignore")]
@@ -330,7 +333,14 @@ impl Display for Expr {
                 Literal::Num(n) => write!(f, "Num({:?})", n),
                 Literal::Bool(b) => write!(f, "{}", b),
                 Literal::Undefined => write!(f, "undefined"),
-                Literal::Object(o) => write!(f, "{:?}", o),
+                Literal::Object(o) => {
+                    let mut fields = o
+                        .iter()
+                        .map(|(key, value)| format!("{}: {}", key, value))
+                        .collect::<Vec<_>>();
+                    fields.sort();
+                    write!(f, "Object({})", fields.join(", "))
+                }
                 Literal::Array(a) => write!(
                     f,
                     "Array({})",
@@ -393,7 +403,9 @@ impl Display for Expr {
             } => {
                 write!(f, "{} {:?} {}", left, operation, right)
             }
-            Expr::Call { callee, args, .. } => {
+            Expr::Call {
+                callee, args, over, ..
+            } => {
                 write!(
                     f,
                     "{}({})",
@@ -402,7 +414,11 @@ impl Display for Expr {
                         .map(|x| x.to_string())
                         .collect::<Vec<_>>()
                         .join(", ")
-                )
+                )?;
+                match over {
+                    Some(window) => write!(f, " {}", window),
+                    None => Ok(()),
+                }
             }
             Expr::Get { object, name, .. } => write!(f, "{}.{}", object, name),
             Expr::FieldPath { head, tail, .. } => {
@@ -457,10 +473,17 @@ impl Expr {
             | Expr::Unary { expr, .. }
             | Expr::Assignment { expr, .. } => expr.walk(visitor),
             //
-            Expr::Call { callee, args, .. } => {
+            Expr::Call {
+                callee, args, over, ..
+            } => {
                 let rcallee = callee.walk(visitor);
                 let rargs = args
                     .iter()
+                    .chain(over.iter().flat_map(|window| window.partition_by.iter()))
+                    .chain(
+                        over.iter()
+                            .flat_map(|window| window.order_by.iter().map(|key| key.expr.as_ref())),
+                    )
                     .map(|x| x.walk(visitor))
                     .fold(None, |acc, x| acc.or(x));
 
@@ -489,10 +512,93 @@ impl Expr {
     }
 }
 
+impl Expr {
+    /// Rebuilds the expression top-down. Where `rewriter` returns a
+    /// replacement for a node, the replacement takes its place as is;
+    /// other nodes are rebuilt from their rewritten children. Queries and
+    /// function bodies are left untouched.
+    pub fn rewrite<E>(
+        &self,
+        rewriter: &mut impl FnMut(&Expr) -> Result<Option<Expr>, E>,
+    ) -> Result<Expr, E> {
+        if let Some(replacement) = rewriter(self)? {
+            return Ok(replacement);
+        }
+        let mut expr = self.clone();
+        match &mut expr {
+            Expr::Select { .. }
+            | Expr::Insert { .. }
+            | Expr::Delete { .. }
+            | Expr::Update { .. }
+            | Expr::Variable { .. }
+            | Expr::FieldPath { .. }
+            | Expr::Function { .. } => (),
+            Expr::Literal { value, .. } => match value {
+                Literal::Object(fields) => {
+                    for field in fields.values_mut() {
+                        **field = field.rewrite(rewriter)?;
+                    }
+                }
+                Literal::Array(items) => {
+                    for item in items.iter_mut() {
+                        *item = item.rewrite(rewriter)?;
+                    }
+                }
+                _ => (),
+            },
+            Expr::Binary { left, right, .. } | Expr::Logical { left, right, .. } => {
+                **left = left.rewrite(rewriter)?;
+                **right = right.rewrite(rewriter)?;
+            }
+            Expr::Grouping { expr, .. }
+            | Expr::Unary { expr, .. }
+            | Expr::Assignment { expr, .. } => {
+                **expr = expr.rewrite(rewriter)?;
+            }
+            Expr::Call {
+                callee, args, over, ..
+            } => {
+                **callee = callee.rewrite(rewriter)?;
+                for arg in args.iter_mut() {
+                    *arg = arg.rewrite(rewriter)?;
+                }
+                if let Some(window) = over {
+                    for key in window.partition_by.iter_mut() {
+                        *key = key.rewrite(rewriter)?;
+                    }
+                    for key in window.order_by.iter_mut() {
+                        *key.expr = key.expr.rewrite(rewriter)?;
+                    }
+                }
+            }
+            Expr::Between {
+                lower,
+                upper,
+                subject,
+                ..
+            } => {
+                **lower = lower.rewrite(rewriter)?;
+                **upper = upper.rewrite(rewriter)?;
+                **subject = subject.rewrite(rewriter)?;
+            }
+            Expr::Get { object, .. } => {
+                **object = object.rewrite(rewriter)?;
+            }
+            Expr::Set { object, value, .. } => {
+                **object = object.rewrite(rewriter)?;
+                **value = value.rewrite(rewriter)?;
+            }
+        }
+        Ok(expr)
+    }
+}
+
 #[cfg(test)]
 pub mod test {
     use std::collections::HashSet;
 
+    use rustc_hash::FxHashMap;
+
     use crate::ast::expr::Expr;
 
     use super::*;
@@ -610,6 +716,7 @@ pub mod test {
                         id: 3,
                     },
                 ],
+                over: None,
                 span: Span::default(),
                 id: 4,
             };
@@ -797,6 +904,7 @@ pub mod test {
                 id: 15,
             }),
             args: vec![],
+            over: None,
             span: Span::default(),
             id: 16,
         };
@@ -957,6 +1065,7 @@ pub mod test {
                 id: 24,
             }),
             args: vec![],
+            over: None,
             span: test_span,
             id: 25,
         };
@@ -1048,6 +1157,29 @@ pub mod test {
         assert_eq!(binary_expr.to_string(), "(Num(1.0) Add Num(2.0))");
     }
 
+    #[test]
+    fn test_object_literal_display() {
+        let literal = |value: Literal| {
+            Box::new(Expr::Literal {
+                value,
+                raw: "".to_string(),
+                span: Span::default(),
+                id: 0,
+            })
+        };
+        let mut fields = FxHashMap::default();
+        fields.insert(
+            "title".to_string(),
+            literal(Literal::Str(Arc::new("Dune".to_string()))),
+        );
+        fields.insert("year".to_string(), literal(Literal::Num(1965.0)));
+
+        assert_eq!(
+            literal(Literal::Object(fields)).to_string(),
+            "Object(title: Str(\"Dune\"), year: Num(1965.0))"
+        );
+    }
+
     #[test]
     fn test_field_path_display() {
         let field_path = Expr::FieldPath {
@@ -1123,6 +1255,7 @@ pub mod test {
                     id: 3,
                 },
             ],
+            over: None,
             span: Span::default(),
             id: 4,
         };
@@ -1149,6 +1282,38 @@ pub mod test {
         }
     }
 
+    #[test]
+    fn test_expr_rewrite() {
+        let expr = Expr::Grouping {
+            expr: Box::new(create_simple_add_expr(1, 1.0, 2.0)),
+            span: Span::default(),
+            id: 2,
+        };
+
+        let rewritten = expr
+            .rewrite(&mut |e| match e {
+                Expr::Literal {
+                    value: Literal::Num(n),
+                    ..
+                } if *n == 2.0 => Ok::<_, ()>(Some(Expr::Literal {
+                    value: Literal::Num(5.0),
+                    raw: "5".to_string(),
+                    span: Span::default(),
+                    id: 0,
+                })),
+                _ => Ok(None),
+            })
+            .unwrap();
+        assert_eq!(rewritten.to_string(), "((Num(1.0) Add Num(5.0)))");
+        assert_eq!(expr.to_string(), "((Num(1.0) Add Num(2.0)))");
+
+        let failed = expr.rewrite(&mut |e| match e {
+            Expr::Literal { .. } => Err("literal"),
+            _ => Ok(None),
+        });
+        assert_eq!(failed, Err("literal"));
+    }
+
     #[test]
     fn identical_exprs_should_be_equal_when_ids_are_different() {
         let e0 = create_simple_add_expr(0, 1.0, 2.0);
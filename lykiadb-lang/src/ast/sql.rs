@@ -1,6 +1,8 @@
+use std::fmt::{Display, Formatter};
+
 use serde::{Deserialize, Serialize};
 
-use super::{expr::Expr, Identifier};
+use super::{expr::Expr, Identifier, Span, Spanned};
 
 // Enums
 
@@ -50,7 +52,51 @@ pub enum SqlOrdering {
     Desc,
 }
 
+#[derive(Debug, Eq, PartialEq, Serialize, Deserialize, Clone, Copy, Hash)]
+#[serde(tag = "@type")]
+pub enum SqlFrameUnit {
+    #[serde(rename = "SqlFrameUnit::Rows")]
+    Rows,
+    #[serde(rename = "SqlFrameUnit::Range")]
+    Range,
+}
+
+#[derive(Debug, Eq, PartialEq, Serialize, Deserialize, Clone, Copy, Hash)]
+#[serde(tag = "@type")]
+pub enum SqlTransactionMode {
+    #[serde(rename = "SqlTransactionMode::ReadOnly")]
+    ReadOnly,
+    #[serde(rename = "SqlTransactionMode::ReadWrite")]
+    ReadWrite,
+}
+
 //
+#[derive(Debug, Eq, PartialEq, Serialize, Deserialize, Clone, Hash)]
+#[serde(tag = "@type")]
+pub struct SqlFieldPath {
+    pub head: Identifier,
+    pub tail: Vec<Identifier>,
+}
+
+impl Spanned for SqlFieldPath {
+    fn get_span(&self) -> Span {
+        match self.tail.last() {
+            Some(last) => self.head.span.merge(&last.span),
+            None => self.head.span,
+        }
+    }
+}
+
+impl Display for SqlFieldPath {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.head)?;
+        for ident in &self.tail {
+            write!(f, ".{}", ident)?;
+        }
+        Ok(())
+    }
+}
+
 #[derive(Debug, Eq, PartialEq, Serialize, Deserialize, Clone, Hash)]
 #[serde(tag = "@type")]
 pub struct SqlCollectionIdentifier {
@@ -85,6 +131,76 @@ pub struct SqlOrderByClause {
     pub ordering: SqlOrdering,
 }
 
+#[derive(Debug, Eq, PartialEq, Serialize, Deserialize, Clone, Hash)]
+#[serde(tag = "@type")]
+pub enum SqlFrameBound {
+    #[serde(rename = "SqlFrameBound::UnboundedPreceding")]
+    UnboundedPreceding,
+    #[serde(rename = "SqlFrameBound::Preceding")]
+    Preceding { offset: Box<Expr> },
+    #[serde(rename = "SqlFrameBound::CurrentRow")]
+    CurrentRow,
+    #[serde(rename = "SqlFrameBound::Following")]
+    Following { offset: Box<Expr> },
+    #[serde(rename = "SqlFrameBound::UnboundedFollowing")]
+    UnboundedFollowing,
+}
+
+impl Display for SqlFrameBound {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        match self {
+            SqlFrameBound::UnboundedPreceding => write!(f, "unbounded preceding"),
+            SqlFrameBound::Preceding { offset } => write!(f, "{} preceding", offset),
+            SqlFrameBound::CurrentRow => write!(f, "current row"),
+            SqlFrameBound::Following { offset } => write!(f, "{} following", offset),
+            SqlFrameBound::UnboundedFollowing => write!(f, "unbounded following"),
+        }
+    }
+}
+
+#[derive(Debug, Eq, PartialEq, Serialize, Deserialize, Clone, Hash)]
+#[serde(tag = "@type")]
+pub struct SqlWindowFrame {
+    pub unit: SqlFrameUnit,
+    pub start: SqlFrameBound,
+    pub end: SqlFrameBound,
+}
+
+/// The rows a window function is computed over: those of the same
+/// partition, in order, and for aggregates only those within the frame.
+#[derive(Debug, Eq, PartialEq, Serialize, Deserialize, Clone, Hash)]
+#[serde(tag = "@type")]
+pub struct SqlWindow {
+    pub partition_by: Vec<Expr>,
+    pub order_by: Vec<SqlOrderByClause>,
+    pub frame: Option<SqlWindowFrame>,
+}
+
+impl Display for SqlWindow {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        let mut clauses = vec![];
+        if !self.partition_by.is_empty() {
+            let keys: Vec<String> = self.partition_by.iter().map(|e| e.to_string()).collect();
+            clauses.push(format!("partition by {}", keys.join(", ")));
+        }
+        if !self.order_by.is_empty() {
+            let keys: Vec<String> = self
+                .order_by
+                .iter()
+                .map(|key| format!("{} {:?}", key.expr, key.ordering))
+                .collect();
+            clauses.push(format!("order by {}", keys.join(", ")));
+        }
+        if let Some(frame) = &self.frame {
+            clauses.push(format!(
+                "{:?} between {} and {}",
+                frame.unit, frame.start, frame.end
+            ));
+        }
+        write!(f, "over ({})", clauses.join(" "))
+    }
+}
+
 #[derive(Debug, Eq, PartialEq, Serialize, Deserialize, Clone, Hash)]
 #[serde(tag = "@type")]
 pub struct SqlSelectCompound {
@@ -150,9 +266,24 @@ pub struct SqlSelectCore {
     pub compound: Option<Box<SqlSelectCompound>>,
 }
 
+#[derive(Debug, Eq, PartialEq, Serialize, Deserialize, Clone, Hash)]
+#[serde(tag = "@type")]
+pub struct SqlCommonTableExpression {
+    pub name: Identifier,
+    pub query: Box<SqlSelect>,
+}
+
+#[derive(Debug, Eq, PartialEq, Serialize, Deserialize, Clone, Hash)]
+#[serde(tag = "@type")]
+pub struct SqlWith {
+    pub recursive: bool,
+    pub ctes: Vec<SqlCommonTableExpression>,
+}
+
 #[derive(Debug, Eq, PartialEq, Serialize, Deserialize, Clone, Hash)]
 #[serde(tag = "@type")]
 pub struct SqlSelect {
+    pub with: Option<SqlWith>,
     pub core: SqlSelectCore,
     pub order_by: Option<Vec<SqlOrderByClause>>,
     pub limit: Option<SqlLimitClause>,
@@ -172,14 +303,43 @@ pub enum SqlValues {
 pub struct SqlInsert {
     pub collection: SqlCollectionIdentifier,
     pub values: SqlValues,
+    pub on_conflict: Option<Box<SqlOnConflict>>,
+    pub returning: Option<Vec<SqlProjection>>,
+}
+
+/// What an INSERT does with a document whose target fields collide with
+/// those of a stored document. The incoming document is in scope as
+/// `excluded`.
+#[derive(Debug, Eq, PartialEq, Serialize, Deserialize, Clone, Hash)]
+#[serde(tag = "@type")]
+pub struct SqlOnConflict {
+    pub target: Vec<SqlFieldPath>,
+    pub action: SqlConflictAction,
+}
+
+#[derive(Debug, Eq, PartialEq, Serialize, Deserialize, Clone, Hash)]
+#[serde(tag = "@type")]
+pub enum SqlConflictAction {
+    #[serde(rename = "SqlConflictAction::Nothing")]
+    Nothing,
+    #[serde(rename = "SqlConflictAction::Update")]
+    Update { assignments: Vec<SqlAssignment> },
+}
+
+#[derive(Debug, Eq, PartialEq, Serialize, Deserialize, Clone, Hash)]
+#[serde(tag = "@type")]
+pub struct SqlAssignment {
+    pub path: SqlFieldPath,
+    pub expr: Box<Expr>,
 }
 
 #[derive(Debug, Eq, PartialEq, Serialize, Deserialize, Clone, Hash)]
 #[serde(tag = "@type")]
 pub struct SqlUpdate {
     pub collection: SqlCollectionIdentifier,
-    pub assignments: Vec<Expr>,
+    pub assignments: Vec<SqlAssignment>,
     pub r#where: Option<Box<Expr>>,
+    pub returning: Option<Vec<SqlProjection>>,
 }
 
 #[derive(Debug, Eq, PartialEq, Serialize, Deserialize, Clone, Hash)]
@@ -187,4 +347,62 @@ pub struct SqlUpdate {
 pub struct SqlDelete {
     pub collection: SqlCollectionIdentifier,
     pub r#where: Option<Box<Expr>>,
+    pub returning: Option<Vec<SqlProjection>>,
+}
+
+#[derive(Debug, Eq, PartialEq, Serialize, Deserialize, Clone, Hash)]
+#[serde(tag = "@type")]
+pub struct SqlCreateCollection {
+    pub collection: SqlCollectionIdentifier,
+    pub fields: Vec<SqlFieldDefinition>,
+}
+
+#[derive(Debug, Eq, PartialEq, Serialize, Deserialize, Clone, Hash)]
+#[serde(tag = "@type")]
+pub enum SqlFieldConstraint {
+    #[serde(rename = "SqlFieldConstraint::PrimaryKey")]
+    PrimaryKey,
+    #[serde(rename = "SqlFieldConstraint::Unique")]
+    Unique,
+    #[serde(rename = "SqlFieldConstraint::Default")]
+    Default { expr: Box<Expr> },
+    #[serde(rename = "SqlFieldConstraint::References")]
+    References {
+        collection: Box<SqlCollectionIdentifier>,
+        field: SqlFieldPath,
+        on_delete: SqlReferentialAction,
+    },
+}
+
+#[derive(Debug, Eq, PartialEq, Serialize, Deserialize, Clone, Hash)]
+#[serde(tag = "@type")]
+pub enum SqlReferentialAction {
+    #[serde(rename = "SqlReferentialAction::Restrict")]
+    Restrict,
+    #[serde(rename = "SqlReferentialAction::Cascade")]
+    Cascade,
+    #[serde(rename = "SqlReferentialAction::SetNull")]
+    SetNull,
+}
+
+#[derive(Debug, Eq, PartialEq, Serialize, Deserialize, Clone, Hash)]
+#[serde(tag = "@type")]
+pub struct SqlFieldDefinition {
+    pub path: SqlFieldPath,
+    pub constraints: Vec<SqlFieldConstraint>,
+}
+
+#[derive(Debug, Eq, PartialEq, Serialize, Deserialize, Clone, Hash)]
+#[serde(tag = "@type")]
+pub struct SqlDropCollection {
+    pub collection: SqlCollectionIdentifier,
+}
+
+#[derive(Debug, Eq, PartialEq, Serialize, Deserialize, Clone, Hash)]
+#[serde(tag = "@type")]
+pub struct SqlCreateIndex {
+    pub name: Identifier,
+    pub unique: bool,
+    pub collection: SqlCollectionIdentifier,
+    pub fields: Vec<SqlFieldPath>,
 }
@@ -1,7 +1,14 @@
 use derivative::Derivative;
 use serde::{Deserialize, Serialize};
 
-use super::{expr::Expr, Identifier, Span, Spanned};
+use super::{
+    expr::Expr,
+    sql::{
+        SqlCollectionIdentifier, SqlCreateCollection, SqlCreateIndex, SqlDropCollection,
+        SqlTransactionMode,
+    },
+    Identifier, Span, Spanned,
+};
 
 #[derive(Debug, Serialize, Deserialize, Clone, Derivative)]
 #[serde(tag = "@type")]
@@ -82,6 +89,77 @@ pub enum Stmt {
         #[derivative(Hash = "ignore")]
         span: Span,
     },
+    #[serde(rename = "Stmt::Begin")]
+    Begin {
+        mode: Option<SqlTransactionMode>,
+        #[serde(skip)]
+        #[derivative(PartialEq = "ignore")]
+        #[derivative(Hash = "ignore")]
+        span: Span,
+    },
+    #[serde(rename = "Stmt::Commit")]
+    Commit {
+        #[serde(skip)]
+        #[derivative(PartialEq = "ignore")]
+        #[derivative(Hash = "ignore")]
+        span: Span,
+    },
+    #[serde(rename = "Stmt::Rollback")]
+    Rollback {
+        #[serde(skip)]
+        #[derivative(PartialEq = "ignore")]
+        #[derivative(Hash = "ignore")]
+        span: Span,
+    },
+    #[serde(rename = "Stmt::Vacuum")]
+    Vacuum {
+        collection: Option<SqlCollectionIdentifier>,
+        #[serde(skip)]
+        #[derivative(PartialEq = "ignore")]
+        #[derivative(Hash = "ignore")]
+        span: Span,
+    },
+    #[serde(rename = "Stmt::CreateCollection")]
+    CreateCollection {
+        command: SqlCreateCollection,
+        #[serde(skip)]
+        #[derivative(PartialEq = "ignore")]
+        #[derivative(Hash = "ignore")]
+        span: Span,
+    },
+    #[serde(rename = "Stmt::DropCollection")]
+    DropCollection {
+        command: SqlDropCollection,
+        #[serde(skip)]
+        #[derivative(PartialEq = "ignore")]
+        #[derivative(Hash = "ignore")]
+        span: Span,
+    },
+    #[serde(rename = "Stmt::CreateIndex")]
+    CreateIndex {
+        command: SqlCreateIndex,
+        #[serde(skip)]
+        #[derivative(PartialEq = "ignore")]
+        #[derivative(Hash = "ignore")]
+        span: Span,
+    },
+    #[serde(rename = "Stmt::Explain")]
+    Explain {
+        expr: Box<Expr>,
+        #[serde(skip)]
+        #[derivative(PartialEq = "ignore")]
+        #[derivative(Hash = "ignore")]
+        span: Span,
+    },
+    #[serde(rename = "Stmt::Set")]
+    Set {
+        name: Identifier,
+        value: Box<Expr>,
+        #[serde(skip)]
+        #[derivative(PartialEq = "ignore")]
+        #[derivative(Hash = "ignore")]
+        span: Span,
+    },
 }
 
 impl Spanned for Stmt {
@@ -96,6 +174,15 @@ impl Spanned for Stmt {
             Stmt::If { span, .. } => *span,
             Stmt::Loop { span, .. } => *span,
             Stmt::Return { span, .. } => *span,
+            Stmt::Begin { span, .. } => *span,
+            Stmt::Commit { span } => *span,
+            Stmt::Rollback { span } => *span,
+            Stmt::Vacuum { span, .. } => *span,
+            Stmt::CreateCollection { span, .. } => *span,
+            Stmt::DropCollection { span, .. } => *span,
+            Stmt::CreateIndex { span, .. } => *span,
+            Stmt::Explain { span, .. } => *span,
+            Stmt::Set { span, .. } => *span,
         }
     }
 }
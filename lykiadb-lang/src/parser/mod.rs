@@ -126,6 +126,14 @@ impl<'a> Parser<'a> {
         match_next!(self, &kw!(Break), break_statement);
         match_next!(self, &kw!(Continue), continue_statement);
         match_next!(self, &kw!(Return), return_statement);
+        match_next!(self, &skw!(Begin), begin_statement);
+        match_next!(self, &skw!(Commit), commit_statement);
+        match_next!(self, &skw!(Rollback), rollback_statement);
+        match_next!(self, &skw!(Vacuum), vacuum_statement);
+        match_next!(self, &skw!(Create), create_statement);
+        match_next!(self, &skw!(Drop), drop_statement);
+        match_next!(self, &skw!(Explain), explain_statement);
+        match_next!(self, &skw!(Set), set_statement);
         if self.peek_next_all_of(&[sym!(LeftBrace), Identifier { dollar: false }, sym!(Colon)])
             || self.peek_next_all_of(&[sym!(LeftBrace), Str, sym!(Colon)])
             || self.peek_next_all_of(&[sym!(LeftBrace), Num, sym!(Colon)])
@@ -296,6 +304,174 @@ impl<'a> Parser<'a> {
         Ok(Box::new(Stmt::Continue { span: tok.span }))
     }
 
+    fn begin_statement(&mut self) -> ParseResult<Box<Stmt>> {
+        let begin_tok = self.peek_bw(1);
+        self.match_next(&skw!(Transaction));
+
+        let mode = if self.match_next(&skw!(Read)) {
+            if self.match_next(&skw!(Only)) {
+                Some(SqlTransactionMode::ReadOnly)
+            } else {
+                self.expected(&skw!(Write))?;
+                Some(SqlTransactionMode::ReadWrite)
+            }
+        } else {
+            None
+        };
+
+        let last_tok = self.peek_bw(1);
+        self.expected(&sym!(Semicolon))?;
+        Ok(Box::new(Stmt::Begin {
+            mode,
+            span: self.get_merged_span(&begin_tok.span, &last_tok.span),
+        }))
+    }
+
+    fn commit_statement(&mut self) -> ParseResult<Box<Stmt>> {
+        let commit_tok = self.peek_bw(1);
+        self.match_next(&skw!(Transaction));
+        let last_tok = self.peek_bw(1);
+        self.expected(&sym!(Semicolon))?;
+        Ok(Box::new(Stmt::Commit {
+            span: self.get_merged_span(&commit_tok.span, &last_tok.span),
+        }))
+    }
+
+    fn rollback_statement(&mut self) -> ParseResult<Box<Stmt>> {
+        let rollback_tok = self.peek_bw(1);
+        self.match_next(&skw!(Transaction));
+        let last_tok = self.peek_bw(1);
+        self.expected(&sym!(Semicolon))?;
+        Ok(Box::new(Stmt::Rollback {
+            span: self.get_merged_span(&rollback_tok.span, &last_tok.span),
+        }))
+    }
+
+    fn set_statement(&mut self) -> ParseResult<Box<Stmt>> {
+        let set_tok = self.peek_bw(1);
+        let name = self
+            .expected(&Identifier { dollar: false })?
+            .extract_identifier()
+            .unwrap();
+        self.expected(&sym!(Equal))?;
+        let value = self.expression()?;
+        let last_tok = self.peek_bw(1);
+        self.expected(&sym!(Semicolon))?;
+        Ok(Box::new(Stmt::Set {
+            name,
+            value,
+            span: self.get_merged_span(&set_tok.span, &last_tok.span),
+        }))
+    }
+
+    fn vacuum_statement(&mut self) -> ParseResult<Box<Stmt>> {
+        let vacuum_tok = self.peek_bw(1);
+        let collection = self.sql_collection_identifier()?;
+        let last_tok = self.peek_bw(1);
+        self.expected(&sym!(Semicolon))?;
+        Ok(Box::new(Stmt::Vacuum {
+            collection,
+            span: self.get_merged_span(&vacuum_tok.span, &last_tok.span),
+        }))
+    }
+
+    fn create_statement(&mut self) -> ParseResult<Box<Stmt>> {
+        let create_tok = self.peek_bw(1);
+        if self.cmp_tok(&skw!(Unique)) || self.cmp_tok(&skw!(Index)) {
+            return self.create_index_statement(create_tok.span);
+        }
+        self.expected(&skw!(Collection))?;
+        let collection = self.sql_collection_name()?;
+
+        let mut fields = vec![];
+        if self.match_next(&sym!(LeftParen)) {
+            loop {
+                fields.push(self.sql_field_definition()?);
+                if !self.match_next(&sym!(Comma)) {
+                    break;
+                }
+            }
+            self.expected(&sym!(RightParen))?;
+        }
+
+        let last_tok = self.peek_bw(1);
+        self.expected(&sym!(Semicolon))?;
+        Ok(Box::new(Stmt::CreateCollection {
+            command: SqlCreateCollection { collection, fields },
+            span: self.get_merged_span(&create_tok.span, &last_tok.span),
+        }))
+    }
+
+    fn create_index_statement(&mut self, create_span: Span) -> ParseResult<Box<Stmt>> {
+        let unique = self.match_next(&skw!(Unique));
+        self.expected(&skw!(Index))?;
+        let name = self
+            .expected(&Identifier { dollar: false })?
+            .extract_identifier()
+            .unwrap();
+        self.expected(&skw!(On))?;
+        let collection = self.sql_collection_name()?;
+
+        self.expected(&sym!(LeftParen))?;
+        let mut fields = vec![];
+        loop {
+            fields.push(self.sql_field_path()?);
+            if !self.match_next(&sym!(Comma)) {
+                break;
+            }
+        }
+        self.expected(&sym!(RightParen))?;
+
+        let last_tok = self.peek_bw(1);
+        self.expected(&sym!(Semicolon))?;
+        Ok(Box::new(Stmt::CreateIndex {
+            command: SqlCreateIndex {
+                name,
+                unique,
+                collection,
+                fields,
+            },
+            span: self.get_merged_span(&create_span, &last_tok.span),
+        }))
+    }
+
+    fn drop_statement(&mut self) -> ParseResult<Box<Stmt>> {
+        let drop_tok = self.peek_bw(1);
+        self.expected(&skw!(Collection))?;
+        let collection = self.sql_collection_name()?;
+        let last_tok = self.peek_bw(1);
+        self.expected(&sym!(Semicolon))?;
+        Ok(Box::new(Stmt::DropCollection {
+            command: SqlDropCollection { collection },
+            span: self.get_merged_span(&drop_tok.span, &last_tok.span),
+        }))
+    }
+
+    fn explain_statement(&mut self) -> ParseResult<Box<Stmt>> {
+        let explain_tok = self.peek_bw(1);
+        if ![
+            skw!(With),
+            skw!(Select),
+            skw!(Insert),
+            skw!(Update),
+            skw!(Delete),
+        ]
+        .iter()
+        .any(|tok| self.cmp_tok(tok))
+        {
+            return Err(ParseError::UnexpectedToken {
+                token: self.peek_bw(0).clone(),
+            });
+        }
+        let expr = self.expression()?;
+        let last_tok = self.peek_bw(1);
+        self.expected(&sym!(Semicolon))?;
+        Ok(Box::new(Stmt::Explain {
+            expr,
+            span: self.get_merged_span(&explain_tok.span, &last_tok.span),
+        }))
+    }
+
     fn expression_statement(&mut self) -> ParseResult<Box<Stmt>> {
         let expr = self.expression()?;
         let span = expr.get_span();
@@ -570,6 +746,29 @@ impl<'a> Parser<'a> {
     }
 
     fn unary(&mut self) -> ParseResult<Box<Expr>> {
+        if self.match_next_one_of(&[skw!(Exists), skw!(Not)]) {
+            let token = (*self.peek_bw(1)).clone();
+            let operation = if token.tok_type == skw!(Not) {
+                self.expected(&skw!(Exists))?;
+                Operation::NotExists
+            } else {
+                Operation::Exists
+            };
+            let left_paren = self.expected(&sym!(LeftParen))?.span;
+            let query = self.sql_select_inner()?;
+            let right_paren = self.expected(&sym!(RightParen))?.span;
+            let span = self.get_merged_span(&token.span, &right_paren);
+            return Ok(Box::new(Expr::Unary {
+                operation,
+                expr: Box::new(Expr::Select {
+                    query,
+                    span: self.get_merged_span(&left_paren, &right_paren),
+                    id: self.get_expr_id(),
+                }),
+                span,
+                id: self.get_expr_id(),
+            }));
+        }
         if self.match_next_one_of(&[sym!(Minus), sym!(Bang)]) {
             let token = (*self.peek_bw(1)).clone();
             let unary = self.unary()?;
@@ -612,7 +811,8 @@ impl<'a> Parser<'a> {
             if !name.dollar {
                 let next_tok = &self.peek_bw(0).tok_type;
 
-                if (next_tok == &sym!(Dot) || next_tok != &sym!(LeftParen))
+                if (next_tok == &sym!(Dot)
+                    || (next_tok != &sym!(LeftParen) && next_tok != &sym!(DoubleColon)))
                     && self.in_select_depth > 0
                 {
                     let head = name.clone();
@@ -646,12 +846,19 @@ impl<'a> Parser<'a> {
             }
         }
 
-        let paren = self.expected(&sym!(RightParen))?.clone();
+        self.expected(&sym!(RightParen))?;
+
+        let over = if self.in_select_depth > 0 && self.match_next(&skw!(Over)) {
+            Some(Box::new(self.sql_window()?))
+        } else {
+            None
+        };
 
         Ok(Box::new(Expr::Call {
             callee: callee.clone(),
-            span: self.get_merged_span(&(callee).get_span(), &paren.span),
+            span: self.get_merged_span(&(callee).get_span(), &self.peek_bw(1).span),
             args: arguments,
+            over,
             id: self.get_expr_id(),
         }))
     }
@@ -893,9 +1100,13 @@ impl<'a> Parser<'a> {
 }
 
 use crate::ast::sql::{
-    SqlCollectionIdentifier, SqlCompoundOperator, SqlDelete, SqlDistinct, SqlExpressionSource,
-    SqlFrom, SqlInsert, SqlJoinType, SqlLimitClause, SqlOrderByClause, SqlOrdering, SqlProjection,
-    SqlSelect, SqlSelectCompound, SqlSelectCore, SqlSource, SqlUpdate, SqlValues,
+    SqlAssignment, SqlCollectionIdentifier, SqlCommonTableExpression, SqlCompoundOperator,
+    SqlConflictAction, SqlCreateCollection, SqlCreateIndex, SqlDelete, SqlDistinct,
+    SqlDropCollection, SqlExpressionSource, SqlFieldConstraint, SqlFieldDefinition, SqlFieldPath,
+    SqlFrameBound, SqlFrameUnit, SqlFrom, SqlInsert, SqlJoinType, SqlLimitClause, SqlOnConflict,
+    SqlOrderByClause, SqlOrdering, SqlProjection, SqlReferentialAction, SqlSelect,
+    SqlSelectCompound, SqlSelectCore, SqlSource, SqlTransactionMode, SqlUpdate, SqlValues,
+    SqlWindow, SqlWindowFrame, SqlWith,
 };
 
 macro_rules! optional_with_expected {
@@ -921,7 +1132,7 @@ impl Parser<'_> {
         self.expected(&skw!(Into))?;
 
         if let Some(collection) = self.sql_collection_identifier()? {
-            let values = if self.cmp_tok(&skw!(Select)) {
+            let values = if self.cmp_select_start() {
                 let select_inner = self.sql_select_inner();
 
                 if select_inner.is_err() {
@@ -945,8 +1156,15 @@ impl Parser<'_> {
                     token: self.peek_bw(0).clone(),
                 });
             };
+            let on_conflict = self.sql_on_conflict()?;
+            let returning = self.sql_returning()?;
             Ok(Box::new(Expr::Insert {
-                command: SqlInsert { collection, values },
+                command: SqlInsert {
+                    collection,
+                    values,
+                    on_conflict,
+                    returning,
+                },
                 span: Span::default(),
                 id: self.get_expr_id(),
             }))
@@ -962,32 +1180,32 @@ impl Parser<'_> {
             return self.sql_delete();
         }
 
-        let collection = self.sql_collection_identifier()?;
+        let Some(collection) = self.sql_collection_identifier()? else {
+            return Err(ParseError::UnexpectedToken {
+                token: self.peek_bw(0).clone(),
+            });
+        };
 
         self.expected(&skw!(Set))?;
 
-        let mut assignments: Vec<Expr> = vec![];
-
-        loop {
-            self.expected(&Identifier { dollar: false })?;
-            self.expected(&sym!(Equal))?;
-            assignments.push(*self.expression()?);
-            if !self.match_next(&sym!(Comma)) {
-                break;
-            }
-        }
+        // Values and the condition refer to the fields of the documents
+        self.in_select_depth += 1;
+        let assignments = self.sql_assignments()?;
 
         let r#where = if self.match_next(&skw!(Where)) {
             Some(self.expression()?)
         } else {
             None
         };
+        self.in_select_depth -= 1;
+        let returning = self.sql_returning()?;
 
         Ok(Box::new(Expr::Update {
             command: SqlUpdate {
-                collection: collection.unwrap(),
+                collection,
                 assignments,
                 r#where,
+                returning,
             },
             span: Span::default(),
             id: self.get_expr_id(),
@@ -1003,15 +1221,21 @@ impl Parser<'_> {
 
         if let Some(collection) = self.sql_collection_identifier()? {
             let r#where = if self.match_next(&skw!(Where)) {
-                Some(self.expression()?)
+                // The condition refers to the fields of the documents
+                self.in_select_depth += 1;
+                let r#where = self.expression();
+                self.in_select_depth -= 1;
+                Some(r#where?)
             } else {
                 None
             };
+            let returning = self.sql_returning()?;
 
             Ok(Box::new(Expr::Delete {
                 command: SqlDelete {
                     collection,
                     r#where,
+                    returning,
                 },
                 span: Span::default(),
                 id: self.get_expr_id(),
@@ -1023,7 +1247,183 @@ impl Parser<'_> {
         }
     }
 
+    fn sql_assignments(&mut self) -> ParseResult<Vec<SqlAssignment>> {
+        let mut assignments: Vec<SqlAssignment> = vec![];
+        loop {
+            let path = self.sql_field_path()?;
+            self.expected(&sym!(Equal))?;
+            assignments.push(SqlAssignment {
+                path,
+                expr: self.expression()?,
+            });
+            if !self.match_next(&sym!(Comma)) {
+                break;
+            }
+        }
+        Ok(assignments)
+    }
+
+    fn sql_on_conflict(&mut self) -> ParseResult<Option<Box<SqlOnConflict>>> {
+        if !self.match_next(&skw!(On)) {
+            return Ok(None);
+        }
+        self.expected(&skw!(Conflict))?;
+
+        self.expected(&sym!(LeftParen))?;
+        let mut target = vec![];
+        loop {
+            target.push(self.sql_field_path()?);
+            if !self.match_next(&sym!(Comma)) {
+                break;
+            }
+        }
+        self.expected(&sym!(RightParen))?;
+
+        self.expected(&skw!(Do))?;
+        let action = if self.match_next(&skw!(Nothing)) {
+            SqlConflictAction::Nothing
+        } else {
+            self.expected(&skw!(Update))?;
+            self.expected(&skw!(Set))?;
+            // Values refer to the fields of the stored and incoming documents
+            self.in_select_depth += 1;
+            let assignments = self.sql_assignments();
+            self.in_select_depth -= 1;
+            SqlConflictAction::Update {
+                assignments: assignments?,
+            }
+        };
+
+        Ok(Some(Box::new(SqlOnConflict { target, action })))
+    }
+
+    fn sql_order_by_keys(&mut self) -> ParseResult<Vec<SqlOrderByClause>> {
+        let mut ordering: Vec<SqlOrderByClause> = vec![];
+
+        loop {
+            let order_expr = self.expression()?;
+            let order = if self.match_next(&skw!(Desc)) {
+                SqlOrdering::Desc
+            } else {
+                self.match_next(&skw!(Asc));
+                SqlOrdering::Asc
+            };
+            ordering.push(SqlOrderByClause {
+                expr: order_expr,
+                ordering: order,
+            });
+            if !self.match_next(&sym!(Comma)) {
+                break;
+            }
+        }
+
+        Ok(ordering)
+    }
+
+    fn sql_window(&mut self) -> ParseResult<SqlWindow> {
+        self.expected(&sym!(LeftParen))?;
+
+        let mut partition_by = vec![];
+        if self.match_next(&skw!(Partition)) {
+            self.expected(&skw!(By))?;
+            loop {
+                partition_by.push(*self.expression()?);
+                if !self.match_next(&sym!(Comma)) {
+                    break;
+                }
+            }
+        }
+
+        let order_by = if self.match_next(&skw!(Order)) {
+            self.expected(&skw!(By))?;
+            self.sql_order_by_keys()?
+        } else {
+            vec![]
+        };
+
+        let unit = if self.match_next(&skw!(Rows)) {
+            Some(SqlFrameUnit::Rows)
+        } else if self.match_next(&skw!(Range)) {
+            Some(SqlFrameUnit::Range)
+        } else {
+            None
+        };
+        let frame = match unit {
+            // A frame given by its start ends at the current row
+            Some(unit) if !self.match_next(&skw!(Between)) => Some(SqlWindowFrame {
+                unit,
+                start: self.sql_frame_bound()?,
+                end: SqlFrameBound::CurrentRow,
+            }),
+            Some(unit) => {
+                let start = self.sql_frame_bound()?;
+                self.expected(&skw!(And))?;
+                Some(SqlWindowFrame {
+                    unit,
+                    start,
+                    end: self.sql_frame_bound()?,
+                })
+            }
+            None => None,
+        };
+
+        self.expected(&sym!(RightParen))?;
+
+        Ok(SqlWindow {
+            partition_by,
+            order_by,
+            frame,
+        })
+    }
+
+    fn sql_frame_bound(&mut self) -> ParseResult<SqlFrameBound> {
+        if self.match_next(&skw!(Unbounded)) {
+            if self.match_next(&skw!(Preceding)) {
+                return Ok(SqlFrameBound::UnboundedPreceding);
+            }
+            self.expected(&skw!(Following))?;
+            return Ok(SqlFrameBound::UnboundedFollowing);
+        }
+        if self.match_next(&skw!(Current)) {
+            self.expected(&skw!(Row))?;
+            return Ok(SqlFrameBound::CurrentRow);
+        }
+
+        let offset = self.primary()?;
+        if self.match_next(&skw!(Preceding)) {
+            return Ok(SqlFrameBound::Preceding { offset });
+        }
+        self.expected(&skw!(Following))?;
+        Ok(SqlFrameBound::Following { offset })
+    }
+
+    fn sql_returning(&mut self) -> ParseResult<Option<Vec<SqlProjection>>> {
+        if !self.match_next(&skw!(Returning)) {
+            return Ok(None);
+        }
+        // The projection refers to the fields of the written documents
+        self.in_select_depth += 1;
+        let projection = self.sql_select_projection();
+        self.in_select_depth -= 1;
+        Ok(Some(projection?))
+    }
+
     fn sql_collection_identifier(&mut self) -> ParseResult<Option<SqlCollectionIdentifier>> {
+        // The system namespace is spelled with a keyword, so it never
+        // scans as an identifier
+        if self.match_next_all_of(&[skw!(System), sym!(Dot), Identifier { dollar: false }]) {
+            let system_tok = self.peek_bw(3);
+            return Ok(Some(SqlCollectionIdentifier {
+                namespace: Some(super::ast::Identifier {
+                    name: "system".to_string(),
+                    dollar: false,
+                    span: system_tok.span,
+                }),
+                name: self.peek_bw(1).extract_identifier().unwrap(),
+                alias: optional_with_expected!(self, skw!(As), Identifier { dollar: false })
+                    .map(|t| t.extract_identifier().unwrap()),
+            }));
+        }
         if self.cmp_tok(&Identifier { dollar: false }) {
             if self.match_next_all_of(&[
                 Identifier { dollar: false },
@@ -1050,11 +1450,87 @@ impl Parser<'_> {
         Ok(None)
     }
 
+    fn sql_field_path(&mut self) -> ParseResult<SqlFieldPath> {
+        let head = self
+            .expected(&Identifier { dollar: false })?
+            .extract_identifier()
+            .unwrap();
+        let mut tail = vec![];
+        while self.match_next(&sym!(Dot)) {
+            tail.push(
+                self.expected(&Identifier { dollar: false })?
+                    .extract_identifier()
+                    .unwrap(),
+            );
+        }
+        Ok(SqlFieldPath { head, tail })
+    }
+
+    fn sql_field_definition(&mut self) -> ParseResult<SqlFieldDefinition> {
+        let path = self.sql_field_path()?;
+        let mut constraints = vec![];
+        loop {
+            if self.match_next(&skw!(Primary)) {
+                self.expected(&skw!(Key))?;
+                constraints.push(SqlFieldConstraint::PrimaryKey);
+            } else if self.match_next(&skw!(Unique)) {
+                constraints.push(SqlFieldConstraint::Unique);
+            } else if self.match_next(&skw!(Default)) {
+                constraints.push(SqlFieldConstraint::Default {
+                    expr: self.expression()?,
+                });
+            } else if self.match_next(&skw!(References)) {
+                let collection = Box::new(self.sql_collection_name()?);
+                self.expected(&sym!(LeftParen))?;
+                let field = self.sql_field_path()?;
+                self.expected(&sym!(RightParen))?;
+
+                let mut on_delete = SqlReferentialAction::Restrict;
+                if self.match_next(&skw!(On)) {
+                    self.expected(&skw!(Delete))?;
+                    on_delete = if self.match_next(&skw!(Cascade)) {
+                        SqlReferentialAction::Cascade
+                    } else if self.match_next(&skw!(Set)) {
+                        if !self.match_next(&TokenType::Null) {
+                            self.expected(&skw!(SqlKeyword::Null))?;
+                        }
+                        SqlReferentialAction::SetNull
+                    } else {
+                        self.expected(&skw!(Restrict))?;
+                        SqlReferentialAction::Restrict
+                    };
+                }
+                constraints.push(SqlFieldConstraint::References {
+                    collection,
+                    field,
+                    on_delete,
+                });
+            } else {
+                break;
+            }
+        }
+        Ok(SqlFieldDefinition { path, constraints })
+    }
+
+    /// A collection identifier that may not be aliased, as in DDL statements.
+    fn sql_collection_name(&mut self) -> ParseResult<SqlCollectionIdentifier> {
+        match self.sql_collection_identifier()? {
+            Some(collection) if collection.alias.is_none() => Ok(collection),
+            Some(_) => Err(ParseError::UnexpectedToken {
+                token: self.peek_bw(1).clone(),
+            }),
+            None => Err(ParseError::UnexpectedToken {
+                token: self.peek_bw(0).clone(),
+            }),
+        }
+    }
+
     fn sql_select(&mut self) -> ParseResult<Box<Expr>> {
-        if !self.cmp_tok(&skw!(Select)) {
+        if !self.cmp_select_start() {
             return self.call();
         }
 
+        let start = self.peek_bw(0).span;
         let query: ParseResult<SqlSelect> = {
             let select_inner = self.sql_select_inner();
 
@@ -1066,37 +1542,23 @@ impl Parser<'_> {
         };
 
         Ok(Box::new(Expr::Select {
-            span: Span::default(),
+            span: self.get_merged_span(&start, &self.peek_bw(1).span),
             query: query.unwrap(),
             id: self.get_expr_id(),
         }))
     }
 
+    fn cmp_select_start(&self) -> bool {
+        self.cmp_tok(&skw!(Select)) || self.cmp_tok(&skw!(With))
+    }
+
     fn sql_select_inner(&mut self) -> ParseResult<SqlSelect> {
         self.in_select_depth += 1;
+        let with = self.sql_with()?;
         let core: SqlSelectCore = self.sql_select_core()?;
         let order_by = if self.match_next(&skw!(Order)) {
             self.expected(&skw!(By))?;
-            let mut ordering: Vec<SqlOrderByClause> = vec![];
-
-            loop {
-                let order_expr = self.expression()?;
-                let order = if self.match_next(&skw!(Desc)) {
-                    Some(SqlOrdering::Desc)
-                } else {
-                    self.match_next(&skw!(Asc));
-                    Some(SqlOrdering::Asc)
-                };
-                ordering.push(SqlOrderByClause {
-                    expr: order_expr,
-                    ordering: order.unwrap(),
-                });
-                if !self.match_next(&sym!(Comma)) {
-                    break;
-                }
-            }
-
-            Some(ordering)
+            Some(self.sql_order_by_keys()?)
         } else {
             None
         };
@@ -1129,12 +1591,36 @@ impl Parser<'_> {
         self.in_select_depth -= 1;
 
         Ok(SqlSelect {
+            with,
             core,
             order_by,
             limit,
         })
     }
 
+    fn sql_with(&mut self) -> ParseResult<Option<SqlWith>> {
+        if !self.match_next(&skw!(With)) {
+            return Ok(None);
+        }
+        let recursive = self.match_next(&skw!(Recursive));
+        let mut ctes: Vec<SqlCommonTableExpression> = vec![];
+        loop {
+            let name = self
+                .expected(&Identifier { dollar: false })?
+                .extract_identifier()
+                .unwrap();
+            self.expected(&skw!(As))?;
+            self.expected(&sym!(LeftParen))?;
+            let query = Box::new(self.sql_select_inner()?);
+            self.expected(&sym!(RightParen))?;
+            ctes.push(SqlCommonTableExpression { name, query });
+            if !self.match_next(&sym!(Comma)) {
+                break;
+            }
+        }
+        Ok(Some(SqlWith { recursive, ctes }))
+    }
+
     fn sql_select_core(&mut self) -> ParseResult<SqlSelectCore> {
         self.expected(&skw!(Select))?;
         let distinct = if self.match_next(&skw!(Distinct)) {
@@ -1303,7 +1789,7 @@ impl Parser<'_> {
 
     fn sql_select_from_source(&mut self) -> ParseResult<SqlFrom> {
         if self.match_next(&sym!(LeftParen)) {
-            if self.cmp_tok(&skw!(Select)) {
+            if self.cmp_select_start() {
                 let subquery = Box::new(self.sql_select_inner()?);
                 self.expected(&sym!(RightParen))?;
                 let alias: Option<Token> =
@@ -1321,7 +1807,8 @@ impl Parser<'_> {
             return Ok(SqlFrom::Source(SqlSource::Collection(collection)));
         } else {
             let expr = self.expression()?;
-            self.expected(&skw!(As))?;
+            // Unlike collections, expressions always need an alias
+            self.match_next(&skw!(As));
             let identifier = self.expected(&Identifier { dollar: false })?.clone();
             return Ok(SqlFrom::Source(SqlSource::Expr(SqlExpressionSource {
                 expr,
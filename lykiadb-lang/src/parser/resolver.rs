@@ -133,12 +133,22 @@ impl VisitorMut<(), ResolveError> for Resolver<'_> {
                 self.resolve_expr(left);
                 self.resolve_expr(right);
             }
-            Expr::Call { callee, args, .. } => {
+            Expr::Call {
+                callee, args, over, ..
+            } => {
                 self.resolve_expr(callee);
 
                 for argument in args {
                     self.resolve_expr(argument);
                 }
+                if let Some(window) = over {
+                    for key in window.partition_by.iter() {
+                        self.resolve_expr(key);
+                    }
+                    for key in window.order_by.iter() {
+                        self.resolve_expr(&key.expr);
+                    }
+                }
             }
             Expr::Function {
                 name,
@@ -194,8 +204,18 @@ impl VisitorMut<(), ResolveError> for Resolver<'_> {
                 self.resolve_stmts(stmts);
                 self.end_scope();
             }
-            Stmt::Break { .. } | Stmt::Continue { .. } => (),
-            Stmt::Expression { expr, .. } => {
+            Stmt::Break { .. }
+            | Stmt::Continue { .. }
+            | Stmt::Begin { .. }
+            | Stmt::Commit { .. }
+            | Stmt::Rollback { .. }
+            | Stmt::Vacuum { .. }
+            | Stmt::CreateCollection { .. }
+            | Stmt::DropCollection { .. }
+            | Stmt::CreateIndex { .. } => (),
+            Stmt::Expression { expr, .. }
+            | Stmt::Explain { expr, .. }
+            | Stmt::Set { value: expr, .. } => {
                 self.resolve_expr(expr);
             }
             Stmt::Declaration { dst, expr, .. } => {
@@ -87,6 +87,7 @@ pub enum SqlKeyword {
     Transaction,
     Rollback,
     Commit,
+    Vacuum,
     //
     Where,
     Having,
@@ -135,11 +136,29 @@ pub enum SqlKeyword {
     Only,
     Primary,
     References,
+    Cascade,
+    Restrict,
     Set,
     System,
     Unique,
     Read,
     Write,
+    Returning,
+    Conflict,
+    Do,
+    Nothing,
+    Over,
+    Partition,
+    Rows,
+    Range,
+    Unbounded,
+    Preceding,
+    Following,
+    Current,
+    Row,
+    With,
+    Recursive,
+    Exists,
     //
     Union,
     All,
@@ -217,6 +236,7 @@ pub static SQL_KEYWORDS: phf::Map<&'static str, TokenType> = phf_map! {
     "TRANSACTION" => skw!(SqlKeyword::Transaction),
     "ROLLBACK" => skw!(SqlKeyword::Rollback),
     "COMMIT" => skw!(SqlKeyword::Commit),
+    "VACUUM" => skw!(SqlKeyword::Vacuum),
     "WHERE" => skw!(SqlKeyword::Where),
     "HAVING" => skw!(SqlKeyword::Having),
     "ASC" => skw!(SqlKeyword::Asc),
@@ -258,7 +278,25 @@ pub static SQL_KEYWORDS: phf::Map<&'static str, TokenType> = phf_map! {
     "ONLY" => skw!(SqlKeyword::Only),
     "PRIMARY" => skw!(SqlKeyword::Primary),
     "REFERENCES" => skw!(SqlKeyword::References),
+    "CASCADE" => skw!(SqlKeyword::Cascade),
+    "RESTRICT" => skw!(SqlKeyword::Restrict),
     "SET" => skw!(SqlKeyword::Set),
+    "RETURNING" => skw!(SqlKeyword::Returning),
+    "CONFLICT" => skw!(SqlKeyword::Conflict),
+    "DO" => skw!(SqlKeyword::Do),
+    "NOTHING" => skw!(SqlKeyword::Nothing),
+    "OVER" => skw!(SqlKeyword::Over),
+    "PARTITION" => skw!(SqlKeyword::Partition),
+    "ROWS" => skw!(SqlKeyword::Rows),
+    "RANGE" => skw!(SqlKeyword::Range),
+    "UNBOUNDED" => skw!(SqlKeyword::Unbounded),
+    "PRECEDING" => skw!(SqlKeyword::Preceding),
+    "FOLLOWING" => skw!(SqlKeyword::Following),
+    "CURRENT" => skw!(SqlKeyword::Current),
+    "ROW" => skw!(SqlKeyword::Row),
+    "WITH" => skw!(SqlKeyword::With),
+    "RECURSIVE" => skw!(SqlKeyword::Recursive),
+    "EXISTS" => skw!(SqlKeyword::Exists),
     "SYSTEM" => skw!(SqlKeyword::System),
     "COLLECTION" => skw!(SqlKeyword::Collection),
     "UNIQUE" => skw!(SqlKeyword::Unique),
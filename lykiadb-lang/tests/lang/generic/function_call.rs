@@ -11,6 +11,7 @@ assert_parsing! {
                 "@type": "Stmt::Expression",
                 "expr": {
                   "@type": "Expr::Call",
+                  "over": null,
                   "args": [
                     {
                       "@type": "Expr::Literal",
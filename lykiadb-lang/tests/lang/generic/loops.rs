@@ -32,6 +32,7 @@ assert_parsing! {
                       "@type": "Stmt::Expression",
                       "expr": {
                         "@type": "Expr::Call",
+                        "over": null,
                         "args": [
                           {
                             "@type": "Expr::Literal",
@@ -177,6 +178,7 @@ assert_parsing! {
                           "@type": "Stmt::Expression",
                           "expr": {
                             "@type": "Expr::Call",
+                            "over": null,
                             "args": [
                               {
                                 "@type": "Expr::Variable",
@@ -0,0 +1,278 @@
+use crate::assert_parsing;
+use crate::lang::compare_parsed_to_expected;
+use serde_json::json;
+
+assert_parsing! {
+    create_collection: {
+        "CREATE COLLECTION books;" => {
+          "@type": "Stmt::Program",
+          "body": [
+            {
+              "@type": "Stmt::CreateCollection",
+              "command": {
+                "@type": "SqlCreateCollection",
+                "fields": [],
+                "collection": {
+                  "@type": "SqlCollectionIdentifier",
+                  "alias": null,
+                  "name": {
+                    "@type": "Identifier",
+                    "dollar": false,
+                    "name": "books"
+                  },
+                  "namespace": null
+                }
+              }
+            }
+          ]
+        }
+    },
+    create_collection_in_namespace: {
+        "create collection library.books;" => {
+          "@type": "Stmt::Program",
+          "body": [
+            {
+              "@type": "Stmt::CreateCollection",
+              "command": {
+                "@type": "SqlCreateCollection",
+                "fields": [],
+                "collection": {
+                  "@type": "SqlCollectionIdentifier",
+                  "alias": null,
+                  "name": {
+                    "@type": "Identifier",
+                    "dollar": false,
+                    "name": "books"
+                  },
+                  "namespace": {
+                    "@type": "Identifier",
+                    "dollar": false,
+                    "name": "library"
+                  }
+                }
+              }
+            }
+          ]
+        }
+    },
+    create_collection_with_constraints: {
+        "CREATE COLLECTION books (isbn.code PRIMARY KEY, title UNIQUE DEFAULT 'Untitled');" => {
+          "@type": "Stmt::Program",
+          "body": [
+            {
+              "@type": "Stmt::CreateCollection",
+              "command": {
+                "@type": "SqlCreateCollection",
+                "collection": {
+                  "@type": "SqlCollectionIdentifier",
+                  "alias": null,
+                  "name": {
+                    "@type": "Identifier",
+                    "dollar": false,
+                    "name": "books"
+                  },
+                  "namespace": null
+                },
+                "fields": [
+                  {
+                    "@type": "SqlFieldDefinition",
+                    "constraints": [
+                      {
+                        "@type": "SqlFieldConstraint::PrimaryKey"
+                      }
+                    ],
+                    "path": {
+                      "@type": "SqlFieldPath",
+                      "head": {
+                        "@type": "Identifier",
+                        "dollar": false,
+                        "name": "isbn"
+                      },
+                      "tail": [
+                        {
+                          "@type": "Identifier",
+                          "dollar": false,
+                          "name": "code"
+                        }
+                      ]
+                    }
+                  },
+                  {
+                    "@type": "SqlFieldDefinition",
+                    "constraints": [
+                      {
+                        "@type": "SqlFieldConstraint::Unique"
+                      },
+                      {
+                        "@type": "SqlFieldConstraint::Default",
+                        "expr": {
+                          "@type": "Expr::Literal",
+                          "raw": "Untitled",
+                          "value": {
+                            "Str": "Untitled"
+                          }
+                        }
+                      }
+                    ],
+                    "path": {
+                      "@type": "SqlFieldPath",
+                      "head": {
+                        "@type": "Identifier",
+                        "dollar": false,
+                        "name": "title"
+                      },
+                      "tail": []
+                    }
+                  }
+                ]
+              }
+            }
+          ]
+        }
+    },
+    create_collection_with_reference: {
+        "CREATE COLLECTION books (category_id REFERENCES categories(id) ON DELETE SET NULL);" => {
+          "@type": "Stmt::Program",
+          "body": [
+            {
+              "@type": "Stmt::CreateCollection",
+              "command": {
+                "@type": "SqlCreateCollection",
+                "collection": {
+                  "@type": "SqlCollectionIdentifier",
+                  "alias": null,
+                  "name": {
+                    "@type": "Identifier",
+                    "dollar": false,
+                    "name": "books"
+                  },
+                  "namespace": null
+                },
+                "fields": [
+                  {
+                    "@type": "SqlFieldDefinition",
+                    "constraints": [
+                      {
+                        "@type": "SqlFieldConstraint::References",
+                        "collection": {
+                          "@type": "SqlCollectionIdentifier",
+                          "alias": null,
+                          "name": {
+                            "@type": "Identifier",
+                            "dollar": false,
+                            "name": "categories"
+                          },
+                          "namespace": null
+                        },
+                        "field": {
+                          "@type": "SqlFieldPath",
+                          "head": {
+                            "@type": "Identifier",
+                            "dollar": false,
+                            "name": "id"
+                          },
+                          "tail": []
+                        },
+                        "on_delete": {
+                          "@type": "SqlReferentialAction::SetNull"
+                        }
+                      }
+                    ],
+                    "path": {
+                      "@type": "SqlFieldPath",
+                      "head": {
+                        "@type": "Identifier",
+                        "dollar": false,
+                        "name": "category_id"
+                      },
+                      "tail": []
+                    }
+                  }
+                ]
+              }
+            }
+          ]
+        }
+    },
+    drop_collection: {
+        "DROP COLLECTION library.books;" => {
+          "@type": "Stmt::Program",
+          "body": [
+            {
+              "@type": "Stmt::DropCollection",
+              "command": {
+                "@type": "SqlDropCollection",
+                "collection": {
+                  "@type": "SqlCollectionIdentifier",
+                  "alias": null,
+                  "name": {
+                    "@type": "Identifier",
+                    "dollar": false,
+                    "name": "books"
+                  },
+                  "namespace": {
+                    "@type": "Identifier",
+                    "dollar": false,
+                    "name": "library"
+                  }
+                }
+              }
+            }
+          ]
+        }
+    },
+    select_from_system_namespace: {
+        "SELECT * FROM system.collections;" => {
+          "@type": "Stmt::Program",
+          "body": [
+            {
+              "@type": "Stmt::Expression",
+              "expr": {
+                "@type": "Expr::Select",
+                "query": {
+                  "@type": "SqlSelect",
+                  "with": null,
+                  "core": {
+                    "@type": "SqlSelectCore",
+                    "compound": null,
+                    "distinct": {
+                      "@type": "SqlDistinct::ImplicitAll"
+                    },
+                    "from": {
+                      "@type": "SqlFrom::Group",
+                      "values": [
+                        {
+                          "@type": "SqlCollectionIdentifier",
+                          "alias": null,
+                          "name": {
+                            "@type": "Identifier",
+                            "dollar": false,
+                            "name": "collections"
+                          },
+                          "namespace": {
+                            "@type": "Identifier",
+                            "dollar": false,
+                            "name": "system"
+                          }
+                        }
+                      ]
+                    },
+                    "group_by": null,
+                    "having": null,
+                    "projection": [
+                      {
+                        "@type": "SqlProjection::All",
+                        "collection": null
+                      }
+                    ],
+                    "where": null
+                  },
+                  "limit": null,
+                  "order_by": null
+                }
+              }
+            }
+          ]
+        }
+    }
+}
@@ -0,0 +1,111 @@
+use crate::assert_parsing;
+use crate::lang::compare_parsed_to_expected;
+use serde_json::json;
+
+assert_parsing! {
+    with_where: {
+        "DELETE FROM books WHERE author.name = 'Frank';" => {
+          "@type": "Stmt::Program",
+          "body": [
+            {
+              "@type": "Stmt::Expression",
+              "expr": {
+                "@type": "Expr::Delete",
+                "command": {
+                  "@type": "SqlDelete",
+                  "returning": null,
+                  "collection": {
+                    "@type": "SqlCollectionIdentifier",
+                    "alias": null,
+                    "name": {
+                      "@type": "Identifier",
+                      "dollar": false,
+                      "name": "books"
+                    },
+                    "namespace": null
+                  },
+                  "where": {
+                    "@type": "Expr::Binary",
+                    "left": {
+                      "@type": "Expr::FieldPath",
+                      "head": {
+                        "@type": "Identifier",
+                        "dollar": false,
+                        "name": "author"
+                      },
+                      "tail": [
+                        {
+                          "@type": "Identifier",
+                          "dollar": false,
+                          "name": "name"
+                        }
+                      ]
+                    },
+                    "operation": {
+                      "@type": "IsEqual"
+                    },
+                    "right": {
+                      "@type": "Expr::Literal",
+                      "raw": "Frank",
+                      "value": {
+                        "Str": "Frank"
+                      }
+                    }
+                  }
+                }
+              }
+            }
+          ]
+        }
+    },
+    without_where: {
+        "DELETE FROM books;" => {
+          "@type": "Stmt::Program",
+          "body": [
+            {
+              "@type": "Stmt::Expression",
+              "expr": {
+                "@type": "Expr::Delete",
+                "command": {
+                  "@type": "SqlDelete",
+                  "returning": null,
+                  "collection": {
+                    "@type": "SqlCollectionIdentifier",
+                    "alias": null,
+                    "name": {
+                      "@type": "Identifier",
+                      "dollar": false,
+                      "name": "books"
+                    },
+                    "namespace": null
+                  },
+                  "where": null
+                }
+              }
+            }
+          ]
+        }
+    },
+    set_statement: {
+        "SET allow_full_delete = true;" => {
+          "@type": "Stmt::Program",
+          "body": [
+            {
+              "@type": "Stmt::Set",
+              "name": {
+                "@type": "Identifier",
+                "dollar": false,
+                "name": "allow_full_delete"
+              },
+              "value": {
+                "@type": "Expr::Literal",
+                "raw": "true",
+                "value": {
+                  "Bool": true
+                }
+              }
+            }
+          ]
+        }
+    }
+}
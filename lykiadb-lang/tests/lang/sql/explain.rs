@@ -0,0 +1,104 @@
+use crate::assert_parsing;
+use crate::lang::compare_parsed_to_expected;
+use serde_json::json;
+
+assert_parsing! {
+    explain_select: {
+        "EXPLAIN SELECT * FROM books;" => {
+          "@type": "Stmt::Program",
+          "body": [
+            {
+              "@type": "Stmt::Explain",
+              "expr": {
+                "@type": "Expr::Select",
+                "query": {
+                  "@type": "SqlSelect",
+                  "with": null,
+                  "core": {
+                    "@type": "SqlSelectCore",
+                    "compound": null,
+                    "distinct": {
+                      "@type": "SqlDistinct::ImplicitAll"
+                    },
+                    "from": {
+                      "@type": "SqlFrom::Group",
+                      "values": [
+                        {
+                          "@type": "SqlCollectionIdentifier",
+                          "alias": null,
+                          "name": {
+                            "@type": "Identifier",
+                            "dollar": false,
+                            "name": "books"
+                          },
+                          "namespace": null
+                        }
+                      ]
+                    },
+                    "group_by": null,
+                    "having": null,
+                    "projection": [
+                      {
+                        "@type": "SqlProjection::All",
+                        "collection": null
+                      }
+                    ],
+                    "where": null
+                  },
+                  "limit": null,
+                  "order_by": null
+                }
+              }
+            }
+          ]
+        }
+    },
+    explain_insert: {
+        "EXPLAIN INSERT INTO books VALUES ({title: 'Dune'});" => {
+          "@type": "Stmt::Program",
+          "body": [
+            {
+              "@type": "Stmt::Explain",
+              "expr": {
+                "@type": "Expr::Insert",
+                "command": {
+                  "@type": "SqlInsert",
+                  "on_conflict": null,
+                  "returning": null,
+                  "collection": {
+                    "@type": "SqlCollectionIdentifier",
+                    "alias": null,
+                    "name": {
+                      "@type": "Identifier",
+                      "dollar": false,
+                      "name": "books"
+                    },
+                    "namespace": null
+                  },
+                  "values": {
+                    "@type": "SqlValues::Values",
+                    "values": [
+                      {
+                        "@type": "Expr::Literal",
+                        "raw": "",
+                        "value": {
+                          "Object": {
+                            "title": {
+                              "@type": "Expr::Literal",
+                              "raw": "Dune",
+                              "value": {
+                                "Str": "Dune"
+                              }
+                            }
+                          }
+                        }
+                      }
+                    ]
+                  }
+                }
+              }
+            }
+          ]
+        }
+    }
+}
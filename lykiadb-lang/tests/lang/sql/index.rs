@@ -0,0 +1,105 @@
+use crate::assert_parsing;
+use crate::lang::compare_parsed_to_expected;
+use serde_json::json;
+
+assert_parsing! {
+    create_index: {
+        "CREATE INDEX books_by_title ON books (title);" => {
+          "@type": "Stmt::Program",
+          "body": [
+            {
+              "@type": "Stmt::CreateIndex",
+              "command": {
+                "@type": "SqlCreateIndex",
+                "collection": {
+                  "@type": "SqlCollectionIdentifier",
+                  "alias": null,
+                  "name": {
+                    "@type": "Identifier",
+                    "dollar": false,
+                    "name": "books"
+                  },
+                  "namespace": null
+                },
+                "fields": [
+                  {
+                    "@type": "SqlFieldPath",
+                    "head": {
+                      "@type": "Identifier",
+                      "dollar": false,
+                      "name": "title"
+                    },
+                    "tail": []
+                  }
+                ],
+                "name": {
+                  "@type": "Identifier",
+                  "dollar": false,
+                  "name": "books_by_title"
+                },
+                "unique": false
+              }
+            }
+          ]
+        }
+    },
+    create_unique_index_on_paths: {
+        "create unique index books_by_author on library.books (author.name, year);" => {
+          "@type": "Stmt::Program",
+          "body": [
+            {
+              "@type": "Stmt::CreateIndex",
+              "command": {
+                "@type": "SqlCreateIndex",
+                "collection": {
+                  "@type": "SqlCollectionIdentifier",
+                  "alias": null,
+                  "name": {
+                    "@type": "Identifier",
+                    "dollar": false,
+                    "name": "books"
+                  },
+                  "namespace": {
+                    "@type": "Identifier",
+                    "dollar": false,
+                    "name": "library"
+                  }
+                },
+                "fields": [
+                  {
+                    "@type": "SqlFieldPath",
+                    "head": {
+                      "@type": "Identifier",
+                      "dollar": false,
+                      "name": "author"
+                    },
+                    "tail": [
+                      {
+                        "@type": "Identifier",
+                        "dollar": false,
+                        "name": "name"
+                      }
+                    ]
+                  },
+                  {
+                    "@type": "SqlFieldPath",
+                    "head": {
+                      "@type": "Identifier",
+                      "dollar": false,
+                      "name": "year"
+                    },
+                    "tail": []
+                  }
+                ],
+                "name": {
+                  "@type": "Identifier",
+                  "dollar": false,
+                  "name": "books_by_author"
+                },
+                "unique": true
+              }
+            }
+          ]
+        }
+    }
+}
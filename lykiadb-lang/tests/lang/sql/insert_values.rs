@@ -22,6 +22,8 @@ assert_parsing! {
                 "@type": "Expr::Insert",
                 "command": {
                   "@type": "SqlInsert",
+                  "on_conflict": null,
+                  "returning": null,
                   "collection": {
                     "@type": "SqlCollectionIdentifier",
                     "alias": null,
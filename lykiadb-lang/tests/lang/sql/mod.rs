@@ -1,6 +1,13 @@
+pub mod collection;
+pub mod delete;
+pub mod explain;
+pub mod index;
 pub mod insert_values;
+pub mod on_conflict;
+pub mod returning;
 pub mod select_compound;
 pub mod select_distinct;
+pub mod select_exists;
 pub mod select_from;
 pub mod select_group_by;
 pub mod select_join;
@@ -8,4 +15,9 @@ pub mod select_limit;
 pub mod select_order;
 pub mod select_projection;
 pub mod select_where;
+pub mod select_window;
+pub mod select_with;
 pub mod sql_expr;
+pub mod transaction;
+pub mod update;
+pub mod vacuum;
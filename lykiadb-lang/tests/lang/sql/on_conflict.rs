@@ -0,0 +1,155 @@
+use crate::assert_parsing;
+use crate::lang::compare_parsed_to_expected;
+use serde_json::json;
+
+assert_parsing! {
+    do_nothing: {
+        "INSERT INTO books VALUES ($book) ON CONFLICT (meta.isbn) DO NOTHING;" => {
+          "@type": "Stmt::Program",
+          "body": [
+            {
+              "@type": "Stmt::Expression",
+              "expr": {
+                "@type": "Expr::Insert",
+                "command": {
+                  "@type": "SqlInsert",
+                  "collection": {
+                    "@type": "SqlCollectionIdentifier",
+                    "alias": null,
+                    "name": {
+                      "@type": "Identifier",
+                      "dollar": false,
+                      "name": "books"
+                    },
+                    "namespace": null
+                  },
+                  "on_conflict": {
+                    "@type": "SqlOnConflict",
+                    "action": {
+                      "@type": "SqlConflictAction::Nothing"
+                    },
+                    "target": [
+                      {
+                        "@type": "SqlFieldPath",
+                        "head": {
+                          "@type": "Identifier",
+                          "dollar": false,
+                          "name": "meta"
+                        },
+                        "tail": [
+                          {
+                            "@type": "Identifier",
+                            "dollar": false,
+                            "name": "isbn"
+                          }
+                        ]
+                      }
+                    ]
+                  },
+                  "returning": null,
+                  "values": {
+                    "@type": "SqlValues::Values",
+                    "values": [
+                      {
+                        "@type": "Expr::Variable",
+                        "name": {
+                          "@type": "Identifier",
+                          "dollar": true,
+                          "name": "$book"
+                        }
+                      }
+                    ]
+                  }
+                }
+              }
+            }
+          ]
+        }
+    },
+    do_update: {
+        "INSERT INTO books VALUES ($book) ON CONFLICT (isbn) DO UPDATE SET title = excluded.title;" => {
+          "@type": "Stmt::Program",
+          "body": [
+            {
+              "@type": "Stmt::Expression",
+              "expr": {
+                "@type": "Expr::Insert",
+                "command": {
+                  "@type": "SqlInsert",
+                  "collection": {
+                    "@type": "SqlCollectionIdentifier",
+                    "alias": null,
+                    "name": {
+                      "@type": "Identifier",
+                      "dollar": false,
+                      "name": "books"
+                    },
+                    "namespace": null
+                  },
+                  "on_conflict": {
+                    "@type": "SqlOnConflict",
+                    "action": {
+                      "@type": "SqlConflictAction::Update",
+                      "assignments": [
+                        {
+                          "@type": "SqlAssignment",
+                          "path": {
+                            "@type": "SqlFieldPath",
+                            "head": {
+                              "@type": "Identifier",
+                              "dollar": false,
+                              "name": "title"
+                            },
+                            "tail": []
+                          },
+                          "expr": {
+                            "@type": "Expr::FieldPath",
+                            "head": {
+                              "@type": "Identifier",
+                              "dollar": false,
+                              "name": "excluded"
+                            },
+                            "tail": [
+                              {
+                                "@type": "Identifier",
+                                "dollar": false,
+                                "name": "title"
+                              }
+                            ]
+                          }
+                        }
+                      ]
+                    },
+                    "target": [
+                      {
+                        "@type": "SqlFieldPath",
+                        "head": {
+                          "@type": "Identifier",
+                          "dollar": false,
+                          "name": "isbn"
+                        },
+                        "tail": []
+                      }
+                    ]
+                  },
+                  "returning": null,
+                  "values": {
+                    "@type": "SqlValues::Values",
+                    "values": [
+                      {
+                        "@type": "Expr::Variable",
+                        "name": {
+                          "@type": "Identifier",
+                          "dollar": true,
+                          "name": "$book"
+                        }
+                      }
+                    ]
+                  }
+                }
+              }
+            }
+          ]
+        }
+    }
+}
@@ -0,0 +1,139 @@
+use crate::assert_parsing;
+use crate::lang::compare_parsed_to_expected;
+use serde_json::json;
+
+assert_parsing! {
+    insert: {
+        "INSERT INTO books VALUES ({title: 'Dune'}) RETURNING *;" => {
+          "@type": "Stmt::Program",
+          "body": [
+            {
+              "@type": "Stmt::Expression",
+              "expr": {
+                "@type": "Expr::Insert",
+                "command": {
+                  "@type": "SqlInsert",
+                  "on_conflict": null,
+                  "collection": {
+                    "@type": "SqlCollectionIdentifier",
+                    "alias": null,
+                    "name": {
+                      "@type": "Identifier",
+                      "dollar": false,
+                      "name": "books"
+                    },
+                    "namespace": null
+                  },
+                  "returning": [
+                    {
+                      "@type": "SqlProjection::All",
+                      "collection": null
+                    }
+                  ],
+                  "values": {
+                    "@type": "SqlValues::Values",
+                    "values": [
+                      {
+                        "@type": "Expr::Literal",
+                        "raw": "",
+                        "value": {
+                          "Object": {
+                            "title": {
+                              "@type": "Expr::Literal",
+                              "raw": "Dune",
+                              "value": {
+                                "Str": "Dune"
+                              }
+                            }
+                          }
+                        }
+                      }
+                    ]
+                  }
+                }
+              }
+            }
+          ]
+        }
+    },
+    delete: {
+        "DELETE FROM books WHERE year < 1970 RETURNING title, _id AS id;" => {
+          "@type": "Stmt::Program",
+          "body": [
+            {
+              "@type": "Stmt::Expression",
+              "expr": {
+                "@type": "Expr::Delete",
+                "command": {
+                  "@type": "SqlDelete",
+                  "collection": {
+                    "@type": "SqlCollectionIdentifier",
+                    "alias": null,
+                    "name": {
+                      "@type": "Identifier",
+                      "dollar": false,
+                      "name": "books"
+                    },
+                    "namespace": null
+                  },
+                  "returning": [
+                    {
+                      "@type": "SqlProjection::Expr",
+                      "alias": null,
+                      "expr": {
+                        "@type": "Expr::FieldPath",
+                        "head": {
+                          "@type": "Identifier",
+                          "dollar": false,
+                          "name": "title"
+                        },
+                        "tail": []
+                      }
+                    },
+                    {
+                      "@type": "SqlProjection::Expr",
+                      "alias": {
+                        "@type": "Identifier",
+                        "dollar": false,
+                        "name": "id"
+                      },
+                      "expr": {
+                        "@type": "Expr::FieldPath",
+                        "head": {
+                          "@type": "Identifier",
+                          "dollar": false,
+                          "name": "_id"
+                        },
+                        "tail": []
+                      }
+                    }
+                  ],
+                  "where": {
+                    "@type": "Expr::Binary",
+                    "left": {
+                      "@type": "Expr::FieldPath",
+                      "head": {
+                        "@type": "Identifier",
+                        "dollar": false,
+                        "name": "year"
+                      },
+                      "tail": []
+                    },
+                    "operation": {
+                      "@type": "Less"
+                    },
+                    "right": {
+                      "@type": "Expr::Literal",
+                      "raw": "1970",
+                      "value": {
+                        "Num": 1970.0
+                      }
+                    }
+                  }
+                }
+              }
+            }
+          ]
+        }
+    }
+}
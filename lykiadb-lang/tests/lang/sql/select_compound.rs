@@ -13,6 +13,7 @@ assert_parsing! {
                   "@type": "Expr::Select",
                   "query": {
                     "@type": "SqlSelect",
+                    "with": null,
                     "core": {
                       "@type": "SqlSelectCore",
                       "compound": {
@@ -98,6 +99,7 @@ assert_parsing! {
                   "@type": "Expr::Select",
                   "query": {
                     "@type": "SqlSelect",
+                    "with": null,
                     "core": {
                       "@type": "SqlSelectCore",
                       "compound": {
@@ -183,6 +185,7 @@ assert_parsing! {
                   "@type": "Expr::Select",
                   "query": {
                     "@type": "SqlSelect",
+                    "with": null,
                     "core": {
                       "@type": "SqlSelectCore",
                       "compound": {
@@ -268,6 +271,7 @@ assert_parsing! {
                   "@type": "Expr::Select",
                   "query": {
                     "@type": "SqlSelect",
+                    "with": null,
                     "core": {
                       "@type": "SqlSelectCore",
                       "compound": {
@@ -13,6 +13,7 @@ assert_parsing! {
                   "@type": "Expr::Select",
                   "query": {
                     "@type": "SqlSelect",
+                    "with": null,
                     "core": {
                       "@type": "SqlSelectCore",
                       "compound": null,
@@ -71,6 +72,7 @@ assert_parsing! {
                   "@type": "Expr::Select",
                   "query": {
                     "@type": "SqlSelect",
+                    "with": null,
                     "core": {
                       "@type": "SqlSelectCore",
                       "compound": null,
@@ -129,6 +131,7 @@ assert_parsing! {
                   "@type": "Expr::Select",
                   "query": {
                     "@type": "SqlSelect",
+                    "with": null,
                     "core": {
                       "@type": "SqlSelectCore",
                       "compound": null,
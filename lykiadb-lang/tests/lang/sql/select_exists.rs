@@ -0,0 +1,243 @@
+use crate::assert_parsing;
+use crate::lang::compare_parsed_to_expected;
+use serde_json::json;
+
+assert_parsing! {
+    exists: {
+        "SELECT * FROM books b WHERE EXISTS (SELECT * FROM authors a WHERE a.id = b.author_id);" => {
+          "@type": "Stmt::Program",
+          "body": [
+            {
+              "@type": "Stmt::Expression",
+              "expr": {
+                "@type": "Expr::Select",
+                "query": {
+                  "@type": "SqlSelect",
+                  "core": {
+                    "@type": "SqlSelectCore",
+                    "compound": null,
+                    "distinct": {
+                      "@type": "SqlDistinct::ImplicitAll"
+                    },
+                    "from": {
+                      "@type": "SqlFrom::Group",
+                      "values": [
+                        {
+                          "@type": "SqlCollectionIdentifier",
+                          "alias": {
+                            "@type": "Identifier",
+                            "dollar": false,
+                            "name": "b"
+                          },
+                          "name": {
+                            "@type": "Identifier",
+                            "dollar": false,
+                            "name": "books"
+                          },
+                          "namespace": null
+                        }
+                      ]
+                    },
+                    "group_by": null,
+                    "having": null,
+                    "projection": [
+                      {
+                        "@type": "SqlProjection::All",
+                        "collection": null
+                      }
+                    ],
+                    "where": {
+                      "@type": "Expr::Unary",
+                      "expr": {
+                        "@type": "Expr::Select",
+                        "query": {
+                          "@type": "SqlSelect",
+                          "core": {
+                            "@type": "SqlSelectCore",
+                            "compound": null,
+                            "distinct": {
+                              "@type": "SqlDistinct::ImplicitAll"
+                            },
+                            "from": {
+                              "@type": "SqlFrom::Group",
+                              "values": [
+                                {
+                                  "@type": "SqlCollectionIdentifier",
+                                  "alias": {
+                                    "@type": "Identifier",
+                                    "dollar": false,
+                                    "name": "a"
+                                  },
+                                  "name": {
+                                    "@type": "Identifier",
+                                    "dollar": false,
+                                    "name": "authors"
+                                  },
+                                  "namespace": null
+                                }
+                              ]
+                            },
+                            "group_by": null,
+                            "having": null,
+                            "projection": [
+                              {
+                                "@type": "SqlProjection::All",
+                                "collection": null
+                              }
+                            ],
+                            "where": {
+                              "@type": "Expr::Binary",
+                              "left": {
+                                "@type": "Expr::FieldPath",
+                                "head": {
+                                  "@type": "Identifier",
+                                  "dollar": false,
+                                  "name": "a"
+                                },
+                                "tail": [
+                                  {
+                                    "@type": "Identifier",
+                                    "dollar": false,
+                                    "name": "id"
+                                  }
+                                ]
+                              },
+                              "operation": {
+                                "@type": "IsEqual"
+                              },
+                              "right": {
+                                "@type": "Expr::FieldPath",
+                                "head": {
+                                  "@type": "Identifier",
+                                  "dollar": false,
+                                  "name": "b"
+                                },
+                                "tail": [
+                                  {
+                                    "@type": "Identifier",
+                                    "dollar": false,
+                                    "name": "author_id"
+                                  }
+                                ]
+                              }
+                            }
+                          },
+                          "limit": null,
+                          "order_by": null,
+                          "with": null
+                        }
+                      },
+                      "operation": {
+                        "@type": "Exists"
+                      }
+                    }
+                  },
+                  "limit": null,
+                  "order_by": null,
+                  "with": null
+                }
+              }
+            }
+          ]
+        }
+    },
+    not_exists: {
+        "SELECT * FROM books b WHERE NOT EXISTS (SELECT * FROM authors);" => {
+          "@type": "Stmt::Program",
+          "body": [
+            {
+              "@type": "Stmt::Expression",
+              "expr": {
+                "@type": "Expr::Select",
+                "query": {
+                  "@type": "SqlSelect",
+                  "core": {
+                    "@type": "SqlSelectCore",
+                    "compound": null,
+                    "distinct": {
+                      "@type": "SqlDistinct::ImplicitAll"
+                    },
+                    "from": {
+                      "@type": "SqlFrom::Group",
+                      "values": [
+                        {
+                          "@type": "SqlCollectionIdentifier",
+                          "alias": {
+                            "@type": "Identifier",
+                            "dollar": false,
+                            "name": "b"
+                          },
+                          "name": {
+                            "@type": "Identifier",
+                            "dollar": false,
+                            "name": "books"
+                          },
+                          "namespace": null
+                        }
+                      ]
+                    },
+                    "group_by": null,
+                    "having": null,
+                    "projection": [
+                      {
+                        "@type": "SqlProjection::All",
+                        "collection": null
+                      }
+                    ],
+                    "where": {
+                      "@type": "Expr::Unary",
+                      "expr": {
+                        "@type": "Expr::Select",
+                        "query": {
+                          "@type": "SqlSelect",
+                          "core": {
+                            "@type": "SqlSelectCore",
+                            "compound": null,
+                            "distinct": {
+                              "@type": "SqlDistinct::ImplicitAll"
+                            },
+                            "from": {
+                              "@type": "SqlFrom::Group",
+                              "values": [
+                                {
+                                  "@type": "SqlCollectionIdentifier",
+                                  "alias": null,
+                                  "name": {
+                                    "@type": "Identifier",
+                                    "dollar": false,
+                                    "name": "authors"
+                                  },
+                                  "namespace": null
+                                }
+                              ]
+                            },
+                            "group_by": null,
+                            "having": null,
+                            "projection": [
+                              {
+                                "@type": "SqlProjection::All",
+                                "collection": null
+                              }
+                            ],
+                            "where": null
+                          },
+                          "limit": null,
+                          "order_by": null,
+                          "with": null
+                        }
+                      },
+                      "operation": {
+                        "@type": "NotExists"
+                      }
+                    }
+                  },
+                  "limit": null,
+                  "order_by": null,
+                  "with": null
+                }
+              }
+            }
+          ]
+        }
+    }
+}
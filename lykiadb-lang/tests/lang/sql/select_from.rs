@@ -13,6 +13,7 @@ assert_parsing! {
                   "@type": "Expr::Select",
                   "query": {
                     "@type": "SqlSelect",
+                    "with": null,
                     "core": {
                       "@type": "SqlSelectCore",
                       "compound": null,
@@ -62,6 +63,7 @@ assert_parsing! {
                   "@type": "Expr::Select",
                   "query": {
                     "@type": "SqlSelect",
+                    "with": null,
                     "core": {
                       "@type": "SqlSelectCore",
                       "compound": null,
@@ -121,6 +123,7 @@ assert_parsing! {
                   "@type": "Expr::Select",
                   "query": {
                     "@type": "SqlSelect",
+                    "with": null,
                     "core": {
                       "@type": "SqlSelectCore",
                       "compound": null,
@@ -188,6 +191,7 @@ assert_parsing! {
                   "@type": "Expr::Select",
                   "query": {
                     "@type": "SqlSelect",
+                    "with": null,
                     "core": {
                       "@type": "SqlSelectCore",
                       "compound": null,
@@ -206,6 +210,7 @@ assert_parsing! {
                             },
                             "subquery": {
                                 "@type": "SqlSelect",
+                                "with": null,
                                 "core": {
                                   "@type": "SqlSelectCore",
                                   "compound": null,
@@ -271,6 +276,63 @@ assert_parsing! {
                 "@type": "Expr::Select",
                 "query": {
                   "@type": "SqlSelect",
+                  "with": null,
+                  "core": {
+                    "@type": "SqlSelectCore",
+                    "compound": null,
+                    "distinct": {
+                      "@type": "SqlDistinct::ImplicitAll"
+                    },
+                    "from": {
+                      "@type": "SqlFrom::Group",
+                      "values": [
+                        {
+                          "@type": "SqlExpressionSource",
+                          "alias": {
+                            "@type": "Identifier",
+                            "dollar": false,
+                            "name": "u"
+                          },
+                          "expr":{
+                              "@type": "Expr::Variable",
+                              "name": {
+                                  "@type": "Identifier",
+                                  "dollar": true,
+                                  "name": "$users"
+                              }
+                          }
+                        }
+                      ]
+                    },
+                    "group_by": null,
+                    "having": null,
+                    "projection": [
+                      {
+                        "@type": "SqlProjection::All",
+                        "collection": null
+                      }
+                    ],
+                    "where": null
+                  },
+                  "limit": null,
+                  "order_by": null
+                }
+              }
+            }
+          ]
+        }
+  },
+  expr_source_without_as: {
+      "SELECT * from $users u;" => {
+          "@type": "Stmt::Program",
+          "body": [
+            {
+              "@type": "Stmt::Expression",
+              "expr": {
+                "@type": "Expr::Select",
+                "query": {
+                  "@type": "SqlSelect",
+                  "with": null,
                   "core": {
                     "@type": "SqlSelectCore",
                     "compound": null,
@@ -326,6 +388,7 @@ assert_parsing! {
               "@type": "Expr::Select",
               "query": {
                 "@type": "SqlSelect",
+                "with": null,
                 "core": {
                   "@type": "SqlSelectCore",
                   "compound": null,
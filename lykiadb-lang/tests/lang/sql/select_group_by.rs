@@ -13,6 +13,7 @@ assert_parsing! {
                 "@type": "Expr::Select",
                 "query": {
                   "@type": "SqlSelect",
+                  "with": null,
                   "core": {
                     "@type": "SqlSelectCore",
                     "compound": null,
@@ -52,6 +53,7 @@ assert_parsing! {
                         "alias": null,
                         "expr": {
                           "@type": "Expr::Call",
+                          "over": null,
                           "args": [
                             {
                               "@type": "Expr::FieldPath",
@@ -94,6 +96,7 @@ assert_parsing! {
                 "@type": "Expr::Select",
                 "query": {
                   "@type": "SqlSelect",
+                  "with": null,
                   "core": {
                     "@type": "SqlSelectCore",
                     "compound": null,
@@ -130,6 +133,7 @@ assert_parsing! {
                       "@type": "Expr::Binary",
                       "left": {
                         "@type": "Expr::Call",
+                        "over": null,
                         "args": [
                           {
                             "@type": "Expr::FieldPath",
@@ -167,6 +171,7 @@ assert_parsing! {
                         "alias": null,
                         "expr": {
                           "@type": "Expr::Call",
+                          "over": null,
                           "args": [
                             {
                               "@type": "Expr::FieldPath",
@@ -209,6 +214,7 @@ assert_parsing! {
                 "@type": "Expr::Select",
                 "query": {
                   "@type": "SqlSelect",
+                  "with": null,
                   "core": {
                     "@type": "SqlSelectCore",
                     "compound": null,
@@ -254,6 +260,7 @@ assert_parsing! {
                       "@type": "Expr::Binary",
                       "left": {
                         "@type": "Expr::Call",
+                        "over": null,
                         "args": [
                           {
                             "@type": "Expr::FieldPath",
@@ -291,6 +298,7 @@ assert_parsing! {
                         "alias": null,
                         "expr": {
                           "@type": "Expr::Call",
+                          "over": null,
                           "args": [
                             {
                               "@type": "Expr::FieldPath",
@@ -322,5 +330,96 @@ assert_parsing! {
             }
           ]
         }
+    },
+    namespaced_function: {
+        "SELECT stats::median(salary) from employees group by department_id;" => {
+          "@type": "Stmt::Program",
+          "body": [
+            {
+              "@type": "Stmt::Expression",
+              "expr": {
+                "@type": "Expr::Select",
+                "query": {
+                  "@type": "SqlSelect",
+                  "with": null,
+                  "core": {
+                    "@type": "SqlSelectCore",
+                    "compound": null,
+                    "distinct": {
+                      "@type": "SqlDistinct::ImplicitAll"
+                    },
+                    "from": {
+                      "@type": "SqlFrom::Group",
+                      "values": [
+                        {
+                          "@type": "SqlCollectionIdentifier",
+                          "alias": null,
+                          "name": {
+                            "@type": "Identifier",
+                            "dollar": false,
+                            "name": "employees"
+                          },
+                          "namespace": null
+                        }
+                      ]
+                    },
+                    "group_by": [
+                      {
+                        "@type": "Expr::FieldPath",
+                        "head": {
+                          "@type": "Identifier",
+                          "dollar": false,
+                          "name": "department_id"
+                        },
+                        "tail": []
+                      }
+                    ],
+                    "having": null,
+                    "projection": [
+                      {
+                        "@type": "SqlProjection::Expr",
+                        "alias": null,
+                        "expr": {
+                          "@type": "Expr::Call",
+                          "over": null,
+                          "args": [
+                            {
+                              "@type": "Expr::FieldPath",
+                              "head": {
+                                "@type": "Identifier",
+                                "dollar": false,
+                                "name": "salary"
+                              },
+                              "tail": []
+                            }
+                          ],
+                          "callee": {
+                            "@type": "Expr::Get",
+                            "name": {
+                              "@type": "Identifier",
+                              "dollar": false,
+                              "name": "median"
+                            },
+                            "object": {
+                              "@type": "Expr::Variable",
+                              "name": {
+                                "@type": "Identifier",
+                                "dollar": false,
+                                "name": "stats"
+                              }
+                            }
+                          }
+                        }
+                      }
+                    ],
+                    "where": null
+                  },
+                  "limit": null,
+                  "order_by": null
+                }
+              }
+            }
+          ]
+        }
     }
 }
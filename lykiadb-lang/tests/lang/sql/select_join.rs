@@ -13,6 +13,7 @@ assert_parsing! {
                 "@type": "Expr::Select",
                 "query": {
                   "@type": "SqlSelect",
+                  "with": null,
                   "core": {
                     "@type": "SqlSelectCore",
                     "compound": null,
@@ -79,6 +80,7 @@ assert_parsing! {
                 "@type": "Expr::Select",
                 "query": {
                   "@type": "SqlSelect",
+                  "with": null,
                   "core": {
                     "@type": "SqlSelectCore",
                     "compound": null,
@@ -224,6 +226,7 @@ assert_parsing! {
                 "@type": "Expr::Select",
                 "query": {
                   "@type": "SqlSelect",
+                  "with": null,
                   "core": {
                     "@type": "SqlSelectCore",
                     "compound": null,
@@ -353,6 +356,7 @@ assert_parsing! {
                 "@type": "Expr::Select",
                 "query": {
                   "@type": "SqlSelect",
+                  "with": null,
                   "core": {
                     "@type": "SqlSelectCore",
                     "compound": null,
@@ -450,6 +454,7 @@ assert_parsing! {
                 "@type": "Expr::Select",
                 "query": {
                   "@type": "SqlSelect",
+                  "with": null,
                   "core": {
                     "@type": "SqlSelectCore",
                     "compound": null,
@@ -508,6 +513,7 @@ assert_parsing! {
                                 },
                                 "subquery": {
                                     "@type": "SqlSelect",
+                                    "with": null,
                                     "core": {
                                       "@type": "SqlSelectCore",
                                       "compound": null,
@@ -646,6 +652,7 @@ assert_parsing! {
                 "@type": "Expr::Select",
                 "query": {
                   "@type": "SqlSelect",
+                  "with": null,
                   "core": {
                     "@type": "SqlSelectCore",
                     "compound": null,
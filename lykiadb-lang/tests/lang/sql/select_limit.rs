@@ -13,6 +13,7 @@ assert_parsing! {
                 "@type": "Expr::Select",
                 "query": {
                   "@type": "SqlSelect",
+                  "with": null,
                   "core": {
                     "@type": "SqlSelectCore",
                     "compound": null,
@@ -72,6 +73,7 @@ assert_parsing! {
                 "@type": "Expr::Select",
                 "query": {
                   "@type": "SqlSelect",
+                  "with": null,
                   "core": {
                     "@type": "SqlSelectCore",
                     "compound": null,
@@ -137,6 +139,7 @@ assert_parsing! {
                 "@type": "Expr::Select",
                 "query": {
                   "@type": "SqlSelect",
+                  "with": null,
                   "core": {
                     "@type": "SqlSelectCore",
                     "compound": null,
@@ -13,6 +13,7 @@ assert_parsing! {
                 "@type": "Expr::Select",
                 "query": {
                   "@type": "SqlSelect",
+                  "with": null,
                   "core": {
                     "@type": "SqlSelectCore",
                     "compound": null,
@@ -62,6 +63,7 @@ assert_parsing! {
                 "@type": "Expr::Select",
                 "query": {
                   "@type": "SqlSelect",
+                  "with": null,
                   "core": {
                     "@type": "SqlSelectCore",
                     "compound": null,
@@ -127,6 +129,7 @@ assert_parsing! {
                 "@type": "Expr::Select",
                 "query": {
                   "@type": "SqlSelect",
+                  "with": null,
                   "core": {
                     "@type": "SqlSelectCore",
                     "compound": null,
@@ -207,6 +210,7 @@ assert_parsing! {
                 "@type": "Expr::Select",
                 "query": {
                   "@type": "SqlSelect",
+                  "with": null,
                   "core": {
                     "@type": "SqlSelectCore",
                     "compound": null,
@@ -287,6 +291,7 @@ assert_parsing! {
                 "@type": "Expr::Select",
                 "query": {
                   "@type": "SqlSelect",
+                  "with": null,
                   "core": {
                     "@type": "SqlSelectCore",
                     "compound": null,
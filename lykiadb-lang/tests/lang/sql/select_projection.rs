@@ -13,6 +13,7 @@ assert_parsing! {
                   "@type": "Expr::Select",
                   "query": {
                     "@type": "SqlSelect",
+                    "with": null,
                     "core": {
                       "@type": "SqlSelectCore",
                       "compound": null,
@@ -63,6 +64,7 @@ assert_parsing! {
                   "@type": "Expr::Select",
                   "query": {
                     "@type": "SqlSelect",
+                    "with": null,
                     "core": {
                       "@type": "SqlSelectCore",
                       "compound": null,
@@ -116,6 +118,7 @@ assert_parsing! {
                   "@type": "Expr::Select",
                   "query": {
                     "@type": "SqlSelect",
+                    "with": null,
                     "core": {
                       "@type": "SqlSelectCore",
                       "compound": null,
@@ -195,6 +198,7 @@ assert_parsing! {
                   "@type": "Expr::Select",
                   "query": {
                     "@type": "SqlSelect",
+                    "with": null,
                     "core": {
                       "@type": "SqlSelectCore",
                       "compound": null,
@@ -270,6 +274,7 @@ assert_parsing! {
                   "@type": "Expr::Select",
                   "query": {
                     "@type": "SqlSelect",
+                    "with": null,
                     "core": {
                       "@type": "SqlSelectCore",
                       "compound": null,
@@ -371,6 +376,7 @@ assert_parsing! {
                   "@type": "Expr::Select",
                   "query": {
                     "@type": "SqlSelect",
+                    "with": null,
                     "core": {
                       "@type": "SqlSelectCore",
                       "compound": null,
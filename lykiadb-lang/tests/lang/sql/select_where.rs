@@ -13,6 +13,7 @@ assert_parsing! {
                   "@type": "Expr::Select",
                   "query": {
                     "@type": "SqlSelect",
+                    "with": null,
                     "core": {
                       "@type": "SqlSelectCore",
                       "compound": null,
@@ -83,6 +84,7 @@ assert_parsing! {
                   "@type": "Expr::Select",
                   "query": {
                     "@type": "SqlSelect",
+                    "with": null,
                     "core": {
                       "@type": "SqlSelectCore",
                       "compound": null,
@@ -181,6 +183,7 @@ assert_parsing! {
                   "@type": "Expr::Select",
                   "query": {
                     "@type": "SqlSelect",
+                    "with": null,
                     "core": {
                       "@type": "SqlSelectCore",
                       "compound": null,
@@ -0,0 +1,290 @@
+use crate::assert_parsing;
+use crate::lang::compare_parsed_to_expected;
+use serde_json::json;
+
+assert_parsing! {
+    partition_and_order: {
+        "SELECT rank() OVER (PARTITION BY department ORDER BY salary DESC) FROM employees;" => {
+          "@type": "Stmt::Program",
+          "body": [
+            {
+              "@type": "Stmt::Expression",
+              "expr": {
+                "@type": "Expr::Select",
+                "query": {
+                  "@type": "SqlSelect",
+                  "with": null,
+                  "core": {
+                    "@type": "SqlSelectCore",
+                    "compound": null,
+                    "distinct": {
+                      "@type": "SqlDistinct::ImplicitAll"
+                    },
+                    "from": {
+                      "@type": "SqlFrom::Group",
+                      "values": [
+                        {
+                          "@type": "SqlCollectionIdentifier",
+                          "alias": null,
+                          "name": {
+                            "@type": "Identifier",
+                            "dollar": false,
+                            "name": "employees"
+                          },
+                          "namespace": null
+                        }
+                      ]
+                    },
+                    "group_by": null,
+                    "having": null,
+                    "projection": [
+                      {
+                        "@type": "SqlProjection::Expr",
+                        "alias": null,
+                        "expr": {
+                          "@type": "Expr::Call",
+                          "args": [],
+                          "callee": {
+                            "@type": "Expr::Variable",
+                            "name": {
+                              "@type": "Identifier",
+                              "dollar": false,
+                              "name": "rank"
+                            }
+                          },
+                          "over": {
+                            "@type": "SqlWindow",
+                            "frame": null,
+                            "order_by": [
+                              {
+                                "@type": "SqlOrderByClause",
+                                "expr": {
+                                  "@type": "Expr::FieldPath",
+                                  "head": {
+                                    "@type": "Identifier",
+                                    "dollar": false,
+                                    "name": "salary"
+                                  },
+                                  "tail": []
+                                },
+                                "ordering": {
+                                  "@type": "SqlOrdering::Desc"
+                                }
+                              }
+                            ],
+                            "partition_by": [
+                              {
+                                "@type": "Expr::FieldPath",
+                                "head": {
+                                  "@type": "Identifier",
+                                  "dollar": false,
+                                  "name": "department"
+                                },
+                                "tail": []
+                              }
+                            ]
+                          }
+                        }
+                      }
+                    ],
+                    "where": null
+                  },
+                  "limit": null,
+                  "order_by": null
+                }
+              }
+            }
+          ]
+        }
+    },
+    rows_frame: {
+        "SELECT sum(salary) OVER (ORDER BY hired ROWS BETWEEN 2 PRECEDING AND CURRENT ROW) FROM employees;" => {
+          "@type": "Stmt::Program",
+          "body": [
+            {
+              "@type": "Stmt::Expression",
+              "expr": {
+                "@type": "Expr::Select",
+                "query": {
+                  "@type": "SqlSelect",
+                  "with": null,
+                  "core": {
+                    "@type": "SqlSelectCore",
+                    "compound": null,
+                    "distinct": {
+                      "@type": "SqlDistinct::ImplicitAll"
+                    },
+                    "from": {
+                      "@type": "SqlFrom::Group",
+                      "values": [
+                        {
+                          "@type": "SqlCollectionIdentifier",
+                          "alias": null,
+                          "name": {
+                            "@type": "Identifier",
+                            "dollar": false,
+                            "name": "employees"
+                          },
+                          "namespace": null
+                        }
+                      ]
+                    },
+                    "group_by": null,
+                    "having": null,
+                    "projection": [
+                      {
+                        "@type": "SqlProjection::Expr",
+                        "alias": null,
+                        "expr": {
+                          "@type": "Expr::Call",
+                          "args": [
+                            {
+                              "@type": "Expr::FieldPath",
+                              "head": {
+                                "@type": "Identifier",
+                                "dollar": false,
+                                "name": "salary"
+                              },
+                              "tail": []
+                            }
+                          ],
+                          "callee": {
+                            "@type": "Expr::Variable",
+                            "name": {
+                              "@type": "Identifier",
+                              "dollar": false,
+                              "name": "sum"
+                            }
+                          },
+                          "over": {
+                            "@type": "SqlWindow",
+                            "frame": {
+                              "@type": "SqlWindowFrame",
+                              "end": {
+                                "@type": "SqlFrameBound::CurrentRow"
+                              },
+                              "start": {
+                                "@type": "SqlFrameBound::Preceding",
+                                "offset": {
+                                  "@type": "Expr::Literal",
+                                  "raw": "2",
+                                  "value": {
+                                    "Num": 2.0
+                                  }
+                                }
+                              },
+                              "unit": {
+                                "@type": "SqlFrameUnit::Rows"
+                              }
+                            },
+                            "order_by": [
+                              {
+                                "@type": "SqlOrderByClause",
+                                "expr": {
+                                  "@type": "Expr::FieldPath",
+                                  "head": {
+                                    "@type": "Identifier",
+                                    "dollar": false,
+                                    "name": "hired"
+                                  },
+                                  "tail": []
+                                },
+                                "ordering": {
+                                  "@type": "SqlOrdering::Asc"
+                                }
+                              }
+                            ],
+                            "partition_by": []
+                          }
+                        }
+                      }
+                    ],
+                    "where": null
+                  },
+                  "limit": null,
+                  "order_by": null
+                }
+              }
+            }
+          ]
+        }
+    },
+    empty_window: {
+        "SELECT count(id) OVER () FROM employees;" => {
+          "@type": "Stmt::Program",
+          "body": [
+            {
+              "@type": "Stmt::Expression",
+              "expr": {
+                "@type": "Expr::Select",
+                "query": {
+                  "@type": "SqlSelect",
+                  "with": null,
+                  "core": {
+                    "@type": "SqlSelectCore",
+                    "compound": null,
+                    "distinct": {
+                      "@type": "SqlDistinct::ImplicitAll"
+                    },
+                    "from": {
+                      "@type": "SqlFrom::Group",
+                      "values": [
+                        {
+                          "@type": "SqlCollectionIdentifier",
+                          "alias": null,
+                          "name": {
+                            "@type": "Identifier",
+                            "dollar": false,
+                            "name": "employees"
+                          },
+                          "namespace": null
+                        }
+                      ]
+                    },
+                    "group_by": null,
+                    "having": null,
+                    "projection": [
+                      {
+                        "@type": "SqlProjection::Expr",
+                        "alias": null,
+                        "expr": {
+                          "@type": "Expr::Call",
+                          "args": [
+                            {
+                              "@type": "Expr::FieldPath",
+                              "head": {
+                                "@type": "Identifier",
+                                "dollar": false,
+                                "name": "id"
+                              },
+                              "tail": []
+                            }
+                          ],
+                          "callee": {
+                            "@type": "Expr::Variable",
+                            "name": {
+                              "@type": "Identifier",
+                              "dollar": false,
+                              "name": "count"
+                            }
+                          },
+                          "over": {
+                            "@type": "SqlWindow",
+                            "frame": null,
+                            "order_by": [],
+                            "partition_by": []
+                          }
+                        }
+                      }
+                    ],
+                    "where": null
+                  },
+                  "limit": null,
+                  "order_by": null
+                }
+              }
+            }
+          ]
+        }
+    }
+}
@@ -0,0 +1,400 @@
+use crate::assert_parsing;
+use crate::lang::compare_parsed_to_expected;
+use serde_json::json;
+
+assert_parsing! {
+    single_cte: {
+        "WITH cheap AS (SELECT * FROM books WHERE price < 10) SELECT title FROM cheap;" => {
+          "@type": "Stmt::Program",
+          "body": [
+            {
+              "@type": "Stmt::Expression",
+              "expr": {
+                "@type": "Expr::Select",
+                "query": {
+                  "@type": "SqlSelect",
+                  "core": {
+                    "@type": "SqlSelectCore",
+                    "compound": null,
+                    "distinct": {
+                      "@type": "SqlDistinct::ImplicitAll"
+                    },
+                    "from": {
+                      "@type": "SqlFrom::Group",
+                      "values": [
+                        {
+                          "@type": "SqlCollectionIdentifier",
+                          "alias": null,
+                          "name": {
+                            "@type": "Identifier",
+                            "dollar": false,
+                            "name": "cheap"
+                          },
+                          "namespace": null
+                        }
+                      ]
+                    },
+                    "group_by": null,
+                    "having": null,
+                    "projection": [
+                      {
+                        "@type": "SqlProjection::Expr",
+                        "alias": null,
+                        "expr": {
+                          "@type": "Expr::FieldPath",
+                          "head": {
+                            "@type": "Identifier",
+                            "dollar": false,
+                            "name": "title"
+                          },
+                          "tail": []
+                        }
+                      }
+                    ],
+                    "where": null
+                  },
+                  "limit": null,
+                  "order_by": null,
+                  "with": {
+                    "@type": "SqlWith",
+                    "ctes": [
+                      {
+                        "@type": "SqlCommonTableExpression",
+                        "name": {
+                          "@type": "Identifier",
+                          "dollar": false,
+                          "name": "cheap"
+                        },
+                        "query": {
+                          "@type": "SqlSelect",
+                          "core": {
+                            "@type": "SqlSelectCore",
+                            "compound": null,
+                            "distinct": {
+                              "@type": "SqlDistinct::ImplicitAll"
+                            },
+                            "from": {
+                              "@type": "SqlFrom::Group",
+                              "values": [
+                                {
+                                  "@type": "SqlCollectionIdentifier",
+                                  "alias": null,
+                                  "name": {
+                                    "@type": "Identifier",
+                                    "dollar": false,
+                                    "name": "books"
+                                  },
+                                  "namespace": null
+                                }
+                              ]
+                            },
+                            "group_by": null,
+                            "having": null,
+                            "projection": [
+                              {
+                                "@type": "SqlProjection::All",
+                                "collection": null
+                              }
+                            ],
+                            "where": {
+                              "@type": "Expr::Binary",
+                              "left": {
+                                "@type": "Expr::FieldPath",
+                                "head": {
+                                  "@type": "Identifier",
+                                  "dollar": false,
+                                  "name": "price"
+                                },
+                                "tail": []
+                              },
+                              "operation": {
+                                "@type": "Less"
+                              },
+                              "right": {
+                                "@type": "Expr::Literal",
+                                "raw": "10",
+                                "value": {
+                                  "Num": 10.0
+                                }
+                              }
+                            }
+                          },
+                          "limit": null,
+                          "order_by": null,
+                          "with": null
+                        }
+                      }
+                    ],
+                    "recursive": false
+                  }
+                }
+              }
+            }
+          ]
+        }
+    },
+    recursive_ctes: {
+        "WITH RECURSIVE nums AS (SELECT 1 as n UNION ALL SELECT n + 1 as n FROM nums WHERE n < 5), evens AS (SELECT n FROM nums WHERE n > 2) SELECT n FROM evens;" => {
+          "@type": "Stmt::Program",
+          "body": [
+            {
+              "@type": "Stmt::Expression",
+              "expr": {
+                "@type": "Expr::Select",
+                "query": {
+                  "@type": "SqlSelect",
+                  "core": {
+                    "@type": "SqlSelectCore",
+                    "compound": null,
+                    "distinct": {
+                      "@type": "SqlDistinct::ImplicitAll"
+                    },
+                    "from": {
+                      "@type": "SqlFrom::Group",
+                      "values": [
+                        {
+                          "@type": "SqlCollectionIdentifier",
+                          "alias": null,
+                          "name": {
+                            "@type": "Identifier",
+                            "dollar": false,
+                            "name": "evens"
+                          },
+                          "namespace": null
+                        }
+                      ]
+                    },
+                    "group_by": null,
+                    "having": null,
+                    "projection": [
+                      {
+                        "@type": "SqlProjection::Expr",
+                        "alias": null,
+                        "expr": {
+                          "@type": "Expr::FieldPath",
+                          "head": {
+                            "@type": "Identifier",
+                            "dollar": false,
+                            "name": "n"
+                          },
+                          "tail": []
+                        }
+                      }
+                    ],
+                    "where": null
+                  },
+                  "limit": null,
+                  "order_by": null,
+                  "with": {
+                    "@type": "SqlWith",
+                    "ctes": [
+                      {
+                        "@type": "SqlCommonTableExpression",
+                        "name": {
+                          "@type": "Identifier",
+                          "dollar": false,
+                          "name": "nums"
+                        },
+                        "query": {
+                          "@type": "SqlSelect",
+                          "core": {
+                            "@type": "SqlSelectCore",
+                            "compound": {
+                              "@type": "SqlSelectCompound",
+                              "core": {
+                                "@type": "SqlSelectCore",
+                                "compound": null,
+                                "distinct": {
+                                  "@type": "SqlDistinct::ImplicitAll"
+                                },
+                                "from": {
+                                  "@type": "SqlFrom::Group",
+                                  "values": [
+                                    {
+                                      "@type": "SqlCollectionIdentifier",
+                                      "alias": null,
+                                      "name": {
+                                        "@type": "Identifier",
+                                        "dollar": false,
+                                        "name": "nums"
+                                      },
+                                      "namespace": null
+                                    }
+                                  ]
+                                },
+                                "group_by": null,
+                                "having": null,
+                                "projection": [
+                                  {
+                                    "@type": "SqlProjection::Expr",
+                                    "alias": {
+                                      "@type": "Identifier",
+                                      "dollar": false,
+                                      "name": "n"
+                                    },
+                                    "expr": {
+                                      "@type": "Expr::Binary",
+                                      "left": {
+                                        "@type": "Expr::FieldPath",
+                                        "head": {
+                                          "@type": "Identifier",
+                                          "dollar": false,
+                                          "name": "n"
+                                        },
+                                        "tail": []
+                                      },
+                                      "operation": {
+                                        "@type": "Add"
+                                      },
+                                      "right": {
+                                        "@type": "Expr::Literal",
+                                        "raw": "1",
+                                        "value": {
+                                          "Num": 1.0
+                                        }
+                                      }
+                                    }
+                                  }
+                                ],
+                                "where": {
+                                  "@type": "Expr::Binary",
+                                  "left": {
+                                    "@type": "Expr::FieldPath",
+                                    "head": {
+                                      "@type": "Identifier",
+                                      "dollar": false,
+                                      "name": "n"
+                                    },
+                                    "tail": []
+                                  },
+                                  "operation": {
+                                    "@type": "Less"
+                                  },
+                                  "right": {
+                                    "@type": "Expr::Literal",
+                                    "raw": "5",
+                                    "value": {
+                                      "Num": 5.0
+                                    }
+                                  }
+                                }
+                              },
+                              "operator": {
+                                "@type": "SqlCompoundOperator::UnionAll"
+                              }
+                            },
+                            "distinct": {
+                              "@type": "SqlDistinct::ImplicitAll"
+                            },
+                            "from": null,
+                            "group_by": null,
+                            "having": null,
+                            "projection": [
+                              {
+                                "@type": "SqlProjection::Expr",
+                                "alias": {
+                                  "@type": "Identifier",
+                                  "dollar": false,
+                                  "name": "n"
+                                },
+                                "expr": {
+                                  "@type": "Expr::Literal",
+                                  "raw": "1",
+                                  "value": {
+                                    "Num": 1.0
+                                  }
+                                }
+                              }
+                            ],
+                            "where": null
+                          },
+                          "limit": null,
+                          "order_by": null,
+                          "with": null
+                        }
+                      },
+                      {
+                        "@type": "SqlCommonTableExpression",
+                        "name": {
+                          "@type": "Identifier",
+                          "dollar": false,
+                          "name": "evens"
+                        },
+                        "query": {
+                          "@type": "SqlSelect",
+                          "core": {
+                            "@type": "SqlSelectCore",
+                            "compound": null,
+                            "distinct": {
+                              "@type": "SqlDistinct::ImplicitAll"
+                            },
+                            "from": {
+                              "@type": "SqlFrom::Group",
+                              "values": [
+                                {
+                                  "@type": "SqlCollectionIdentifier",
+                                  "alias": null,
+                                  "name": {
+                                    "@type": "Identifier",
+                                    "dollar": false,
+                                    "name": "nums"
+                                  },
+                                  "namespace": null
+                                }
+                              ]
+                            },
+                            "group_by": null,
+                            "having": null,
+                            "projection": [
+                              {
+                                "@type": "SqlProjection::Expr",
+                                "alias": null,
+                                "expr": {
+                                  "@type": "Expr::FieldPath",
+                                  "head": {
+                                    "@type": "Identifier",
+                                    "dollar": false,
+                                    "name": "n"
+                                  },
+                                  "tail": []
+                                }
+                              }
+                            ],
+                            "where": {
+                              "@type": "Expr::Binary",
+                              "left": {
+                                "@type": "Expr::FieldPath",
+                                "head": {
+                                  "@type": "Identifier",
+                                  "dollar": false,
+                                  "name": "n"
+                                },
+                                "tail": []
+                              },
+                              "operation": {
+                                "@type": "Greater"
+                              },
+                              "right": {
+                                "@type": "Expr::Literal",
+                                "raw": "2",
+                                "value": {
+                                  "Num": 2.0
+                                }
+                              }
+                            }
+                          },
+                          "limit": null,
+                          "order_by": null,
+                          "with": null
+                        }
+                      }
+                    ],
+                    "recursive": true
+                  }
+                }
+              }
+            }
+          ]
+        }
+    }
+}
@@ -13,6 +13,7 @@ assert_parsing! {
               "@type": "Expr::Select",
               "query": {
                 "@type": "SqlSelect",
+                "with": null,
                 "core": {
                   "@type": "SqlSelectCore",
                   "distinct": {
@@ -59,6 +60,7 @@ assert_parsing! {
                         "@type": "Expr::Select",
                         "query": {
                           "@type": "SqlSelect",
+                          "with": null,
                           "core": {
                             "@type": "SqlSelectCore",
                             "distinct": {
@@ -127,6 +129,7 @@ assert_parsing! {
             "@type": "Expr::Select",
             "query": {
               "@type": "SqlSelect",
+              "with": null,
               "core": {
                 "@type": "SqlSelectCore",
                 "distinct": {
@@ -173,6 +176,7 @@ assert_parsing! {
                       "@type": "Expr::Select",
                       "query": {
                         "@type": "SqlSelect",
+                        "with": null,
                         "core": {
                           "@type": "SqlSelectCore",
                           "distinct": {
@@ -241,6 +245,7 @@ assert_parsing! {
             "@type": "Expr::Select",
             "query": {
               "@type": "SqlSelect",
+              "with": null,
               "core": {
                 "@type": "SqlSelectCore",
                 "distinct": {
@@ -319,6 +324,7 @@ assert_parsing! {
             "@type": "Expr::Select",
             "query": {
               "@type": "SqlSelect",
+              "with": null,
               "core": {
                 "@type": "SqlSelectCore",
                 "distinct": {
@@ -396,6 +402,7 @@ assert_parsing! {
             "@type": "Expr::Select",
             "query": {
               "@type": "SqlSelect",
+              "with": null,
               "core": {
                 "@type": "SqlSelectCore",
                 "distinct": {
@@ -466,6 +473,7 @@ assert_parsing! {
             "@type": "Expr::Select",
             "query": {
               "@type": "SqlSelect",
+              "with": null,
               "core": {
                 "@type": "SqlSelectCore",
                 "distinct": {
@@ -536,6 +544,7 @@ assert_parsing! {
             "@type": "Expr::Select",
             "query": {
               "@type": "SqlSelect",
+              "with": null,
               "core": {
                 "@type": "SqlSelectCore",
                 "distinct": {
@@ -606,6 +615,7 @@ assert_parsing! {
             "@type": "Expr::Select",
             "query": {
               "@type": "SqlSelect",
+              "with": null,
               "core": {
                 "@type": "SqlSelectCore",
                 "distinct": {
@@ -0,0 +1,64 @@
+use crate::assert_parsing;
+use crate::lang::compare_parsed_to_expected;
+use serde_json::json;
+
+assert_parsing! {
+    begin: {
+        "BEGIN;" => {
+          "@type": "Stmt::Program",
+          "body": [
+            {
+              "@type": "Stmt::Begin",
+              "mode": null
+            }
+          ]
+        }
+    },
+    begin_transaction_read_only: {
+        "BEGIN TRANSACTION READ ONLY;" => {
+          "@type": "Stmt::Program",
+          "body": [
+            {
+              "@type": "Stmt::Begin",
+              "mode": {
+                "@type": "SqlTransactionMode::ReadOnly"
+              }
+            }
+          ]
+        }
+    },
+    begin_read_write: {
+        "begin read write;" => {
+          "@type": "Stmt::Program",
+          "body": [
+            {
+              "@type": "Stmt::Begin",
+              "mode": {
+                "@type": "SqlTransactionMode::ReadWrite"
+              }
+            }
+          ]
+        }
+    },
+    commit_and_rollback: {
+        "BEGIN; COMMIT; BEGIN TRANSACTION; ROLLBACK TRANSACTION;" => {
+          "@type": "Stmt::Program",
+          "body": [
+            {
+              "@type": "Stmt::Begin",
+              "mode": null
+            },
+            {
+              "@type": "Stmt::Commit"
+            },
+            {
+              "@type": "Stmt::Begin",
+              "mode": null
+            },
+            {
+              "@type": "Stmt::Rollback"
+            }
+          ]
+        }
+    }
+}
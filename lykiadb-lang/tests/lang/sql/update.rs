@@ -0,0 +1,124 @@
+use crate::assert_parsing;
+use crate::lang::compare_parsed_to_expected;
+use serde_json::json;
+
+assert_parsing! {
+    nested_paths: {
+        "UPDATE books SET author.name = 'Frank', tags = tags + ['classic'] WHERE year < 1970;" => {
+          "@type": "Stmt::Program",
+          "body": [
+            {
+              "@type": "Stmt::Expression",
+              "expr": {
+                "@type": "Expr::Update",
+                "command": {
+                  "@type": "SqlUpdate",
+                  "returning": null,
+                  "collection": {
+                    "@type": "SqlCollectionIdentifier",
+                    "alias": null,
+                    "name": {
+                      "@type": "Identifier",
+                      "dollar": false,
+                      "name": "books"
+                    },
+                    "namespace": null
+                  },
+                  "assignments": [
+                    {
+                      "@type": "SqlAssignment",
+                      "path": {
+                        "@type": "SqlFieldPath",
+                        "head": {
+                          "@type": "Identifier",
+                          "dollar": false,
+                          "name": "author"
+                        },
+                        "tail": [
+                          {
+                            "@type": "Identifier",
+                            "dollar": false,
+                            "name": "name"
+                          }
+                        ]
+                      },
+                      "expr": {
+                        "@type": "Expr::Literal",
+                        "raw": "Frank",
+                        "value": {
+                          "Str": "Frank"
+                        }
+                      }
+                    },
+                    {
+                      "@type": "SqlAssignment",
+                      "path": {
+                        "@type": "SqlFieldPath",
+                        "head": {
+                          "@type": "Identifier",
+                          "dollar": false,
+                          "name": "tags"
+                        },
+                        "tail": []
+                      },
+                      "expr": {
+                        "@type": "Expr::Binary",
+                        "left": {
+                          "@type": "Expr::FieldPath",
+                          "head": {
+                            "@type": "Identifier",
+                            "dollar": false,
+                            "name": "tags"
+                          },
+                          "tail": []
+                        },
+                        "operation": {
+                          "@type": "Add"
+                        },
+                        "right": {
+                          "@type": "Expr::Literal",
+                          "raw": "",
+                          "value": {
+                            "Array": [
+                              {
+                                "@type": "Expr::Literal",
+                                "raw": "classic",
+                                "value": {
+                                  "Str": "classic"
+                                }
+                              }
+                            ]
+                          }
+                        }
+                      }
+                    }
+                  ],
+                  "where": {
+                    "@type": "Expr::Binary",
+                    "left": {
+                      "@type": "Expr::FieldPath",
+                      "head": {
+                        "@type": "Identifier",
+                        "dollar": false,
+                        "name": "year"
+                      },
+                      "tail": []
+                    },
+                    "operation": {
+                      "@type": "Less"
+                    },
+                    "right": {
+                      "@type": "Expr::Literal",
+                      "raw": "1970",
+                      "value": {
+                        "Num": 1970.0
+                      }
+                    }
+                  }
+                }
+              }
+            }
+          ]
+        }
+    }
+}
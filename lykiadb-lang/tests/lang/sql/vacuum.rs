@@ -0,0 +1,41 @@
+use crate::assert_parsing;
+use crate::lang::compare_parsed_to_expected;
+use serde_json::json;
+
+assert_parsing! {
+    vacuum_all: {
+        "VACUUM;" => {
+          "@type": "Stmt::Program",
+          "body": [
+            {
+              "@type": "Stmt::Vacuum",
+              "collection": null
+            }
+          ]
+        }
+    },
+    vacuum_collection: {
+        "vacuum db.users;" => {
+          "@type": "Stmt::Program",
+          "body": [
+            {
+              "@type": "Stmt::Vacuum",
+              "collection": {
+                "@type": "SqlCollectionIdentifier",
+                "alias": null,
+                "name": {
+                  "@type": "Identifier",
+                  "dollar": false,
+                  "name": "users"
+                },
+                "namespace": {
+                  "@type": "Identifier",
+                  "dollar": false,
+                  "name": "db"
+                }
+              }
+            }
+          ]
+        }
+    }
+}
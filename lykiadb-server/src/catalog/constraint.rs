@@ -0,0 +1,848 @@
+use std::ops::Bound;
+
+use lykiadb_lang::ast::{expr::Expr, sql::SqlReferentialAction, visitor::VisitorMut, Span};
+use rustc_hash::FxHashMap;
+use serde::{Deserialize, Serialize};
+
+use crate::engine::error::ExecutionError;
+use crate::engine::interpreter::{HaltReason, Interpreter};
+use crate::store::index::{is_unique_key, value_at, IndexDefinition, IndexValue};
+use crate::store::transaction::Transaction;
+use crate::store::DocumentId;
+use crate::util::alloc_shared;
+use crate::value::RV;
+
+use super::{collections, CollectionEntry};
+
+#[derive(PartialEq, Eq, Debug, Clone, Serialize, Deserialize)]
+pub enum ConstraintError {
+    PrimaryKeyMissing {
+        collection: String,
+        field: String,
+        span: Span,
+    },
+    PrimaryKeyViolation {
+        collection: String,
+        field: String,
+        span: Span,
+    },
+    UniqueViolation {
+        collection: String,
+        fields: Vec<String>,
+        span: Span,
+    },
+    ForeignKeyViolation {
+        collection: String,
+        field: String,
+        references: String,
+        span: Span,
+    },
+    DocumentReferenced {
+        collection: String,
+        referenced_by: String,
+        span: Span,
+    },
+    InvalidId {
+        collection: String,
+        span: Span,
+    },
+    DuplicateId {
+        collection: String,
+        id: DocumentId,
+        span: Span,
+    },
+}
+
+impl From<ConstraintError> for ExecutionError {
+    fn from(err: ConstraintError) -> Self {
+        ExecutionError::Constraint(err)
+    }
+}
+
+/// Field holding the id of a document. Documents inserted without one are
+/// given the id following the largest one in the collection.
+pub const ID_FIELD: &str = "_id";
+
+/// Value a field takes when a document is inserted without it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FieldDefault {
+    pub field: String,
+    pub expr: Expr,
+}
+
+/// A field holding the value of a unique field of some collection, as
+/// declared by REFERENCES.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ForeignKey {
+    pub field: String,
+    pub collection: String,
+    pub referenced_field: String,
+    pub on_delete: SqlReferentialAction,
+}
+
+/// Name of the unique index backing the primary key of the collection.
+pub fn primary_key_index(collection: &str) -> String {
+    format!("{}_pkey", collection)
+}
+
+/// Name of the unique index backing a UNIQUE field of the collection.
+pub fn unique_index(collection: &str, field: &str) -> String {
+    format!("{}_{}_key", collection, field.replace('.', "_"))
+}
+
+/// Name of the index used to find the documents referencing a document.
+pub fn foreign_key_index(collection: &str, field: &str) -> String {
+    format!("{}_{}_fkey", collection, field.replace('.', "_"))
+}
+
+/// Fills in the defaults of the fields the document lacks, checks it against
+/// the constraints of the collection and inserts it under its id, which is
+/// generated unless the document has one. The span is the one errors are
+/// reported at.
+pub fn insert_document(
+    interpreter: &mut Interpreter,
+    entry: &CollectionEntry,
+    document: &RV,
+    span: Span,
+) -> Result<DocumentId, HaltReason> {
+    let document = with_defaults(interpreter, entry, document)?;
+
+    let transaction = interpreter.transaction().unwrap();
+    let storage_name = entry.storage_name();
+    let id = match value_at(&document, ID_FIELD) {
+        RV::Undefined => {
+            let id = transaction
+                .next_id(&storage_name)
+                .map_err(|err| HaltReason::Error(err.into()))?;
+            set_value_at(&document, ID_FIELD, RV::Num(id as f64));
+            id
+        }
+        RV::Num(id) if id >= 0.0 && id.fract() == 0.0 => {
+            let id = id as DocumentId;
+            let existing = transaction
+                .get(&storage_name, id)
+                .map_err(|err| HaltReason::Error(err.into()))?;
+            if existing.is_some() {
+                return Err(HaltReason::Error(
+                    ConstraintError::DuplicateId {
+                        collection: storage_name,
+                        id,
+                        span,
+                    }
+                    .into(),
+                ));
+            }
+            id
+        }
+        _ => {
+            return Err(HaltReason::Error(
+                ConstraintError::InvalidId {
+                    collection: storage_name,
+                    span,
+                }
+                .into(),
+            ))
+        }
+    };
+
+    check_document(transaction, entry, None, &document, span).map_err(HaltReason::Error)?;
+    transaction
+        .insert(&storage_name, id, document)
+        .map_err(|err| HaltReason::Error(err.into()))?;
+    Ok(id)
+}
+
+/// A copy of the document with the defaults of the fields it lacks filled
+/// in.
+pub fn with_defaults(
+    interpreter: &mut Interpreter,
+    entry: &CollectionEntry,
+    document: &RV,
+) -> Result<RV, HaltReason> {
+    let document = document.deep_clone();
+    for default in entry.defaults.iter() {
+        if let RV::Undefined = value_at(&document, &default.field) {
+            let value = interpreter.visit_expr(&default.expr)?;
+            set_value_at(&document, &default.field, value);
+        }
+    }
+    Ok(document)
+}
+
+/// The document the transaction sees holding the key of the given document
+/// in the unique index, if there is one.
+pub fn find_conflict(
+    transaction: &Transaction,
+    entry: &CollectionEntry,
+    definition: &IndexDefinition,
+    document: &RV,
+) -> Result<Option<(DocumentId, RV)>, ExecutionError> {
+    let key = definition.key_of(document);
+    if !is_unique_key(&key) {
+        return Ok(None);
+    }
+    let range = (Bound::Included(key.clone()), Bound::Included(key));
+    Ok(transaction
+        .index_scan(&entry.storage_name(), definition, &range)?
+        .into_iter()
+        .next())
+}
+
+/// Replaces the document after checking the new version against the
+/// constraints of the collection. Values other documents reference cannot
+/// change.
+pub fn update_document(
+    transaction: &mut Transaction,
+    entry: &CollectionEntry,
+    id: DocumentId,
+    document: &RV,
+    span: Span,
+) -> Result<(), ExecutionError> {
+    check_document(transaction, entry, Some(id), document, span)?;
+
+    let storage_name = entry.storage_name();
+    if let Some(previous) = transaction.get(&storage_name, id)? {
+        for (referencing, reference) in referrers(transaction, entry)? {
+            let value = value_at(&previous, &reference.referenced_field);
+            if IndexValue::from(&value)
+                == IndexValue::from(&value_at(document, &reference.referenced_field))
+            {
+                continue;
+            }
+            let referenced = lookup(transaction, &referencing, &reference.field, &value)?
+                .into_iter()
+                .any(|(other, _)| referencing.storage_name() != storage_name || other != id);
+            if referenced {
+                return Err(ConstraintError::DocumentReferenced {
+                    collection: storage_name,
+                    referenced_by: format!("{}.{}", referencing.storage_name(), reference.field),
+                    span,
+                }
+                .into());
+            }
+        }
+    }
+
+    transaction.insert(&storage_name, id, document.clone())?;
+    Ok(())
+}
+
+/// Deletes the document, then applies the ON DELETE action of every
+/// reference to it: deletes the referencing documents for CASCADE and clears
+/// the referencing field for SET NULL. Fails without deleting anything if a
+/// RESTRICT reference points to the document.
+pub fn delete_document(
+    transaction: &mut Transaction,
+    entry: &CollectionEntry,
+    id: DocumentId,
+    span: Span,
+) -> Result<(), ExecutionError> {
+    let storage_name = entry.storage_name();
+    // Already deleted, as happens when cascades go around a cycle
+    let Some(document) = transaction.get(&storage_name, id)? else {
+        return Ok(());
+    };
+
+    let mut actions = vec![];
+    for (referencing, reference) in referrers(transaction, entry)? {
+        let value = value_at(&document, &reference.referenced_field);
+        let matches: Vec<DocumentId> = lookup(transaction, &referencing, &reference.field, &value)?
+            .into_iter()
+            .map(|(other, _)| other)
+            // A document referencing itself goes away along with the reference
+            .filter(|other| referencing.storage_name() != storage_name || *other != id)
+            .collect();
+        if !matches.is_empty() && reference.on_delete == SqlReferentialAction::Restrict {
+            return Err(ConstraintError::DocumentReferenced {
+                collection: storage_name,
+                referenced_by: format!("{}.{}", referencing.storage_name(), reference.field),
+                span,
+            }
+            .into());
+        }
+        actions.push((referencing, reference, matches));
+    }
+
+    transaction.delete(&storage_name, id)?;
+
+    for (referencing, reference, matches) in actions {
+        for other in matches {
+            match reference.on_delete {
+                SqlReferentialAction::Restrict => {}
+                SqlReferentialAction::Cascade => {
+                    delete_document(transaction, &referencing, other, span)?;
+                }
+                SqlReferentialAction::SetNull => {
+                    // Read again, as an earlier cascade may have changed it
+                    let Some(referencing_document) =
+                        transaction.get(&referencing.storage_name(), other)?
+                    else {
+                        continue;
+                    };
+                    let updated = referencing_document.deep_clone();
+                    set_value_at(&updated, &reference.field, RV::Null);
+                    transaction.insert(&referencing.storage_name(), other, updated)?;
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Collections with a reference to the given one, along with the reference.
+fn referrers(
+    transaction: &Transaction,
+    entry: &CollectionEntry,
+) -> Result<Vec<(CollectionEntry, ForeignKey)>, ExecutionError> {
+    let storage_name = entry.storage_name();
+    Ok(collections(transaction)?
+        .into_iter()
+        .flat_map(|(_, referencing)| {
+            referencing
+                .references
+                .iter()
+                .filter(|reference| reference.collection == storage_name)
+                .map(|reference| (referencing.clone(), reference.clone()))
+                .collect::<Vec<_>>()
+        })
+        .collect())
+}
+
+/// Documents of the collection with the value in the field, looked up
+/// through an index on the field when there is one. Nothing matches null.
+fn lookup(
+    transaction: &Transaction,
+    entry: &CollectionEntry,
+    field: &str,
+    value: &RV,
+) -> Result<Vec<(DocumentId, RV)>, ExecutionError> {
+    let key = vec![IndexValue::from(value)];
+    if !is_unique_key(&key) {
+        return Ok(vec![]);
+    }
+    let definition = entry
+        .index_on(field, false)
+        .cloned()
+        .unwrap_or(IndexDefinition {
+            name: String::new(),
+            fields: vec![field.to_string()],
+            unique: false,
+        });
+    let range = (Bound::Included(key.clone()), Bound::Included(key));
+    Ok(transaction.index_scan(&entry.storage_name(), &definition, &range)?)
+}
+
+/// Fails if the document lacks its primary key, shares the key of a unique
+/// index with another document the transaction sees, or references one that
+/// does not exist. Concurrent transactions are caught once they commit.
+pub fn check_document(
+    transaction: &mut Transaction,
+    entry: &CollectionEntry,
+    id: Option<DocumentId>,
+    document: &RV,
+    span: Span,
+) -> Result<(), ExecutionError> {
+    let collection = entry.storage_name();
+
+    if let Some(field) = &entry.primary_key {
+        if matches!(value_at(document, field), RV::Undefined | RV::Null) {
+            return Err(ConstraintError::PrimaryKeyMissing {
+                collection,
+                field: field.clone(),
+                span,
+            }
+            .into());
+        }
+    }
+
+    for definition in entry.indexes.iter().filter(|index| index.unique) {
+        let key = definition.key_of(document);
+        if !is_unique_key(&key) {
+            continue;
+        }
+        let range = (Bound::Included(key.clone()), Bound::Included(key));
+        let taken = transaction
+            .index_scan(&collection, definition, &range)?
+            .into_iter()
+            .any(|(other, _)| Some(other) != id);
+        if !taken {
+            continue;
+        }
+
+        return Err(match &entry.primary_key {
+            Some(field) if definition.name == primary_key_index(&entry.name) => {
+                ConstraintError::PrimaryKeyViolation {
+                    collection,
+                    field: field.clone(),
+                    span,
+                }
+            }
+            _ => ConstraintError::UniqueViolation {
+                collection,
+                fields: definition.fields.clone(),
+                span,
+            },
+        }
+        .into());
+    }
+
+    for reference in entry.references.iter() {
+        let value = value_at(document, &reference.field);
+        if matches!(value, RV::Undefined | RV::Null) {
+            continue;
+        }
+        // A document may reference itself
+        if reference.collection == collection
+            && IndexValue::from(&value)
+                == IndexValue::from(&value_at(document, &reference.referenced_field))
+        {
+            continue;
+        }
+
+        let target = match collections(transaction)?
+            .into_iter()
+            .find(|(_, target)| target.storage_name() == reference.collection)
+        {
+            Some((_, target)) => target,
+            None => entry.clone(),
+        };
+        let referenced = lookup(transaction, &target, &reference.referenced_field, &value)?;
+        if referenced.is_empty() {
+            return Err(ConstraintError::ForeignKeyViolation {
+                collection,
+                field: reference.field.clone(),
+                references: format!("{}.{}", reference.collection, reference.referenced_field),
+                span,
+            }
+            .into());
+        }
+        // The referenced document must outlive the snapshot it was found in
+        for (id, _) in referenced {
+            transaction.depend_on(&reference.collection, id);
+        }
+    }
+
+    Ok(())
+}
+
+/// Sets the value under a dotted path, creating the objects along the path
+/// that are missing.
+pub fn set_value_at(document: &RV, path: &str, value: RV) {
+    let (parent, field) = match path.rsplit_once('.') {
+        Some((parent, field)) => (Some(parent), field),
+        None => (None, path),
+    };
+
+    let parent = match parent {
+        Some(parent) => {
+            if !matches!(value_at(document, parent), RV::Object(_)) {
+                set_value_at(
+                    document,
+                    parent,
+                    RV::Object(alloc_shared(FxHashMap::default())),
+                );
+            }
+            value_at(document, parent)
+        }
+        None => document.clone(),
+    };
+
+    if let RV::Object(map) = parent {
+        map.write().unwrap().insert(field.to_string(), value);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use lykiadb_lang::ast::sql::SqlTransactionMode;
+
+    use super::*;
+    use crate::catalog::{self, CatalogError};
+    use crate::store::memory::MemoryEngine;
+    use crate::store::transaction::{TransactionError, TransactionManager};
+
+    fn interpreter() -> Interpreter {
+        let manager = Arc::new(TransactionManager::new(alloc_shared(MemoryEngine::new())));
+        catalog::bootstrap(&manager).unwrap();
+        Interpreter::with_transactions(None, false, manager)
+    }
+
+    fn book(json: &str) -> RV {
+        serde_json::from_str(json).unwrap()
+    }
+
+    fn insert(interpreter: &mut Interpreter, document: &RV) -> Result<DocumentId, HaltReason> {
+        insert_into(interpreter, "books", document)
+    }
+
+    fn insert_into(
+        interpreter: &mut Interpreter,
+        collection: &str,
+        document: &RV,
+    ) -> Result<DocumentId, HaltReason> {
+        interpreter.run_in_transaction(Span::default(), |interpreter| {
+            let entry = entry_of(interpreter, collection);
+            insert_document(interpreter, &entry, document, Span::default())
+        })
+    }
+
+    fn delete_from(
+        interpreter: &mut Interpreter,
+        collection: &str,
+        id: DocumentId,
+    ) -> Result<(), HaltReason> {
+        interpreter.run_in_transaction(Span::default(), |interpreter| {
+            let entry = entry_of(interpreter, collection);
+            delete_document(
+                interpreter.transaction().unwrap(),
+                &entry,
+                id,
+                Span::default(),
+            )
+            .map_err(HaltReason::Error)
+        })
+    }
+
+    fn documents_of(interpreter: &mut Interpreter, collection: &str) -> Vec<(DocumentId, RV)> {
+        interpreter
+            .run_in_transaction(Span::default(), |interpreter| {
+                let entry = entry_of(interpreter, collection);
+                let transaction = interpreter.transaction().unwrap();
+                Ok(transaction
+                    .range(&entry.storage_name(), (Bound::Unbounded, Bound::Unbounded))
+                    .unwrap())
+            })
+            .unwrap()
+    }
+
+    fn entry_of(interpreter: &mut Interpreter, collection: &str) -> CollectionEntry {
+        let transaction = interpreter.transaction().unwrap();
+        catalog::collections(transaction)
+            .unwrap()
+            .into_iter()
+            .map(|(_, entry)| entry)
+            .find(|entry| entry.name == collection)
+            .unwrap()
+    }
+
+    fn books(interpreter: &mut Interpreter) -> CollectionEntry {
+        entry_of(interpreter, "books")
+    }
+
+    fn error_of<T>(result: Result<T, HaltReason>) -> ExecutionError {
+        match result {
+            Err(HaltReason::Error(err)) => err,
+            _ => panic!("Expected an error"),
+        }
+    }
+
+    #[test]
+    fn test_defaults_fill_missing_fields() {
+        let mut interpreter = interpreter();
+        interpreter
+            .interpret(
+                "CREATE COLLECTION books (
+                    title DEFAULT 'Untitled',
+                    stock.count DEFAULT 1 + 1
+                );",
+            )
+            .unwrap();
+
+        let id = insert(&mut interpreter, &book(r#"{"title": "1984"}"#)).unwrap();
+        assert_eq!(id, 1);
+        let id = insert(&mut interpreter, &book(r#"{"stock": {"count": 5}}"#)).unwrap();
+        assert_eq!(id, 2);
+
+        let documents = interpreter
+            .run_in_transaction(Span::default(), |interpreter| {
+                let transaction = interpreter.transaction().unwrap();
+                Ok(transaction
+                    .range("public.books", (Bound::Unbounded, Bound::Unbounded))
+                    .unwrap())
+            })
+            .unwrap();
+        let fields: Vec<(RV, RV)> = documents
+            .iter()
+            .map(|(_, doc)| (value_at(doc, "title"), value_at(doc, "stock.count")))
+            .collect();
+        assert_eq!(
+            fields,
+            vec![
+                (RV::Str(Arc::new("1984".to_string())), RV::Num(2.0)),
+                (RV::Str(Arc::new("Untitled".to_string())), RV::Num(5.0)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_concurrent_inserts_generate_different_ids() {
+        let mut alice = interpreter();
+        alice.interpret("CREATE COLLECTION books;").unwrap();
+        let mut bob = Interpreter::with_transactions(None, false, alice.transactions());
+
+        alice.interpret("BEGIN;").unwrap();
+        bob.interpret("BEGIN;").unwrap();
+        let first = insert(&mut alice, &book(r#"{"title": "1984"}"#)).unwrap();
+        let second = insert(&mut bob, &book(r#"{"title": "Animal Farm"}"#)).unwrap();
+        assert_eq!((first, second), (1, 2));
+        alice.interpret("COMMIT;").unwrap();
+        bob.interpret("COMMIT;").unwrap();
+
+        let ids: Vec<DocumentId> = documents_of(&mut alice, "books")
+            .into_iter()
+            .map(|(id, _)| id)
+            .collect();
+        assert_eq!(ids, vec![1, 2]);
+    }
+
+    #[test]
+    fn test_primary_key_and_unique() {
+        let mut interpreter = interpreter();
+        interpreter
+            .interpret("CREATE COLLECTION books (isbn.code PRIMARY KEY, title UNIQUE);")
+            .unwrap();
+
+        insert(
+            &mut interpreter,
+            &book(r#"{"isbn": {"code": "0451524934"}, "title": "1984"}"#),
+        )
+        .unwrap();
+
+        assert!(matches!(
+            error_of(insert(&mut interpreter, &book(r#"{"title": "Animal Farm"}"#))),
+            ExecutionError::Constraint(ConstraintError::PrimaryKeyMissing { field, .. })
+                if field == "isbn.code"
+        ));
+        assert!(matches!(
+            error_of(insert(
+                &mut interpreter,
+                &book(r#"{"isbn": {"code": "0451524934"}, "title": "Animal Farm"}"#)
+            )),
+            ExecutionError::Constraint(ConstraintError::PrimaryKeyViolation { field, .. })
+                if field == "isbn.code"
+        ));
+        assert!(matches!(
+            error_of(insert(
+                &mut interpreter,
+                &book(r#"{"isbn": {"code": "0451526341"}, "title": "1984"}"#)
+            )),
+            ExecutionError::Constraint(ConstraintError::UniqueViolation { fields, .. })
+                if fields == vec!["title".to_string()]
+        ));
+
+        // A document keeps its own keys when updated
+        interpreter
+            .run_in_transaction(Span::default(), |interpreter| {
+                let entry = books(interpreter);
+                let document = book(r#"{"isbn": {"code": "0451524934"}, "title": "Nineteen"}"#);
+                update_document(
+                    interpreter.transaction().unwrap(),
+                    &entry,
+                    1,
+                    &document,
+                    Span::default(),
+                )
+                .map_err(HaltReason::Error)
+            })
+            .unwrap();
+    }
+
+    #[test]
+    fn test_multiple_primary_keys() {
+        let mut interpreter = interpreter();
+        assert!(matches!(
+            interpreter.interpret("CREATE COLLECTION books (isbn PRIMARY KEY, id PRIMARY KEY);"),
+            Err(ExecutionError::Catalog(
+                CatalogError::MultiplePrimaryKeys { .. }
+            ))
+        ));
+    }
+
+    fn library() -> Interpreter {
+        let mut interpreter = interpreter();
+        interpreter
+            .interpret(
+                "CREATE COLLECTION categories (id PRIMARY KEY, parent REFERENCES categories(id) ON DELETE CASCADE);
+                CREATE COLLECTION books (
+                    title PRIMARY KEY,
+                    category_id REFERENCES categories(id)
+                );
+                CREATE COLLECTION reviews (book REFERENCES books(title) ON DELETE SET NULL);",
+            )
+            .unwrap();
+        insert_into(&mut interpreter, "categories", &book(r#"{"id": 1}"#)).unwrap();
+        insert_into(
+            &mut interpreter,
+            "categories",
+            &book(r#"{"id": 2, "parent": 1}"#),
+        )
+        .unwrap();
+        insert_into(
+            &mut interpreter,
+            "categories",
+            &book(r#"{"id": 3, "parent": 3}"#),
+        )
+        .unwrap();
+        interpreter
+    }
+
+    #[test]
+    fn test_references_must_exist() {
+        let mut interpreter = library();
+
+        insert(
+            &mut interpreter,
+            &book(r#"{"title": "1984", "category_id": 2}"#),
+        )
+        .unwrap();
+        insert(&mut interpreter, &book(r#"{"title": "Untitled"}"#)).unwrap();
+        assert!(matches!(
+            error_of(insert(
+                &mut interpreter,
+                &book(r#"{"title": "Animal Farm", "category_id": 5}"#)
+            )),
+            ExecutionError::Constraint(ConstraintError::ForeignKeyViolation { field, references, .. })
+                if field == "category_id" && references == "public.categories.id"
+        ));
+
+        // Referenced values cannot change while referenced
+        let result = interpreter.run_in_transaction(Span::default(), |interpreter| {
+            let entry = entry_of(interpreter, "categories");
+            update_document(
+                interpreter.transaction().unwrap(),
+                &entry,
+                2,
+                &book(r#"{"id": 4, "parent": 1}"#),
+                Span::default(),
+            )
+            .map_err(HaltReason::Error)
+        });
+        assert!(matches!(
+            error_of(result),
+            ExecutionError::Constraint(ConstraintError::DocumentReferenced { referenced_by, .. })
+                if referenced_by == "public.books.category_id"
+        ));
+    }
+
+    #[test]
+    fn test_concurrent_delete_of_referenced_document() {
+        let mut alice = library();
+        let mut bob = Interpreter::with_transactions(None, false, alice.transactions());
+        let is_serialization_failure = |result: Result<RV, ExecutionError>| {
+            matches!(
+                result,
+                Err(ExecutionError::Transaction(
+                    TransactionError::SerializationFailure { .. }
+                ))
+            )
+        };
+
+        // Alice deletes a category Bob files a book under, and commits first
+        alice.interpret("BEGIN;").unwrap();
+        bob.interpret("BEGIN;").unwrap();
+        delete_from(&mut alice, "categories", 3).unwrap();
+        insert(&mut bob, &book(r#"{"title": "1984", "category_id": 3}"#)).unwrap();
+        alice.interpret("COMMIT;").unwrap();
+        assert!(is_serialization_failure(bob.interpret("COMMIT;")));
+
+        // Bob commits first this time
+        alice.interpret("BEGIN;").unwrap();
+        bob.interpret("BEGIN;").unwrap();
+        delete_from(&mut alice, "categories", 2).unwrap();
+        insert(&mut bob, &book(r#"{"title": "1984", "category_id": 2}"#)).unwrap();
+        bob.interpret("COMMIT;").unwrap();
+        assert!(is_serialization_failure(alice.interpret("COMMIT;")));
+
+        let categories: Vec<DocumentId> = documents_of(&mut alice, "categories")
+            .into_iter()
+            .map(|(id, _)| id)
+            .collect();
+        assert_eq!(categories, vec![1, 2]);
+        assert_eq!(documents_of(&mut alice, "books").len(), 1);
+    }
+
+    #[test]
+    fn test_on_delete_actions() {
+        let mut interpreter = library();
+        insert(
+            &mut interpreter,
+            &book(r#"{"title": "1984", "category_id": 2}"#),
+        )
+        .unwrap();
+        insert_into(&mut interpreter, "reviews", &book(r#"{"book": "1984"}"#)).unwrap();
+
+        // Deleting the root cascades to its child, which a book references
+        assert!(matches!(
+            error_of(delete_from(&mut interpreter, "categories", 1)),
+            ExecutionError::Constraint(ConstraintError::DocumentReferenced { referenced_by, .. })
+                if referenced_by == "public.books.category_id"
+        ));
+
+        delete_from(&mut interpreter, "books", 1).unwrap();
+        assert_eq!(
+            value_at(&documents_of(&mut interpreter, "reviews")[0].1, "book"),
+            RV::Null
+        );
+
+        delete_from(&mut interpreter, "categories", 1).unwrap();
+        delete_from(&mut interpreter, "categories", 3).unwrap();
+        assert!(documents_of(&mut interpreter, "categories").is_empty());
+    }
+
+    #[test]
+    fn test_restrict_deletes_nothing() {
+        let mut interpreter = library();
+        insert(
+            &mut interpreter,
+            &book(r#"{"title": "1984", "category_id": 2}"#),
+        )
+        .unwrap();
+
+        interpreter.interpret("BEGIN;").unwrap();
+        let entry = entry_of(&mut interpreter, "categories");
+        let transaction = interpreter.transaction().unwrap();
+        assert!(matches!(
+            delete_document(transaction, &entry, 2, Span::default()),
+            Err(ExecutionError::Constraint(
+                ConstraintError::DocumentReferenced { .. }
+            ))
+        ));
+        assert!(transaction.get(&entry.storage_name(), 2).unwrap().is_some());
+        interpreter.interpret("COMMIT;").unwrap();
+
+        assert_eq!(documents_of(&mut interpreter, "categories").len(), 3);
+    }
+
+    #[test]
+    fn test_reference_ddl() {
+        let mut interpreter = library();
+
+        assert!(matches!(
+            interpreter.interpret("CREATE COLLECTION shelves (book REFERENCES books(category_id));"),
+            Err(ExecutionError::Catalog(CatalogError::ReferencedFieldNotUnique { field, .. }))
+                if field == "category_id"
+        ));
+        assert!(matches!(
+            interpreter.interpret("DROP COLLECTION categories;"),
+            Err(ExecutionError::Catalog(CatalogError::CollectionReferenced { referenced_by, .. }))
+                if referenced_by == "public.books"
+        ));
+
+        let transaction = interpreter
+            .transactions()
+            .begin(SqlTransactionMode::ReadOnly);
+        let books = catalog::collections(&transaction).unwrap().remove(1).1;
+        assert_eq!(
+            books.references,
+            vec![ForeignKey {
+                field: "category_id".to_string(),
+                collection: "public.categories".to_string(),
+                referenced_field: "id".to_string(),
+                on_delete: SqlReferentialAction::Restrict,
+            }]
+        );
+    }
+}
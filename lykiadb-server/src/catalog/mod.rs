@@ -0,0 +1,555 @@
+use std::ops::Bound;
+
+use lykiadb_lang::ast::sql::{
+    SqlCollectionIdentifier, SqlCreateCollection, SqlCreateIndex, SqlFieldConstraint,
+};
+use lykiadb_lang::ast::{Span, Spanned};
+use serde::{Deserialize, Serialize};
+
+use crate::engine::error::ExecutionError;
+use crate::store::index::IndexDefinition;
+use crate::store::transaction::{Transaction, TransactionManager};
+use crate::store::{DocumentId, StorageError};
+use crate::value::RV;
+
+use self::constraint::{
+    foreign_key_index, primary_key_index, unique_index, FieldDefault, ForeignKey,
+};
+
+pub mod constraint;
+
+/// Namespace of the collections that are not qualified with one.
+pub const DEFAULT_NAMESPACE: &str = "public";
+
+/// Namespace of the collections the database maintains itself. They can be
+/// read like any other collection, but not created or dropped.
+pub const SYSTEM_NAMESPACE: &str = "system";
+
+const COLLECTIONS: &str = "collections";
+
+#[derive(PartialEq, Eq, Debug, Clone, Serialize, Deserialize)]
+pub enum CatalogError {
+    CollectionNotFound {
+        namespace: String,
+        name: String,
+        span: Span,
+    },
+    CollectionAlreadyExists {
+        namespace: String,
+        name: String,
+        span: Span,
+    },
+    SystemNamespace {
+        span: Span,
+    },
+    IndexAlreadyExists {
+        collection: String,
+        name: String,
+        span: Span,
+    },
+    MultiplePrimaryKeys {
+        span: Span,
+    },
+    ReferencedFieldNotUnique {
+        collection: String,
+        field: String,
+        span: Span,
+    },
+    CollectionReferenced {
+        namespace: String,
+        name: String,
+        referenced_by: String,
+        span: Span,
+    },
+}
+
+impl From<CatalogError> for ExecutionError {
+    fn from(err: CatalogError) -> Self {
+        ExecutionError::Catalog(err)
+    }
+}
+
+/// A collection as recorded in `system.collections`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CollectionEntry {
+    pub namespace: String,
+    pub name: String,
+    #[serde(default)]
+    pub indexes: Vec<IndexDefinition>,
+    #[serde(default)]
+    pub primary_key: Option<String>,
+    #[serde(default)]
+    pub defaults: Vec<FieldDefault>,
+    #[serde(default)]
+    pub references: Vec<ForeignKey>,
+}
+
+impl CollectionEntry {
+    pub fn new(namespace: &str, name: &str) -> CollectionEntry {
+        CollectionEntry {
+            namespace: namespace.to_string(),
+            name: name.to_string(),
+            indexes: vec![],
+            primary_key: None,
+            defaults: vec![],
+            references: vec![],
+        }
+    }
+
+    /// The index on exactly the given field, unique ones first.
+    pub fn index_on(&self, field: &str, unique: bool) -> Option<&IndexDefinition> {
+        let mut indexes: Vec<&IndexDefinition> = self
+            .indexes
+            .iter()
+            .filter(|index| index.fields.len() == 1 && index.fields[0] == field)
+            .filter(|index| index.unique || !unique)
+            .collect();
+        indexes.sort_by_key(|index| !index.unique);
+        indexes.first().copied()
+    }
+
+    /// Name of the storage collection that holds the documents.
+    pub fn storage_name(&self) -> String {
+        format!("{}.{}", self.namespace, self.name)
+    }
+
+    fn to_document(&self) -> RV {
+        serde_json::from_value(serde_json::to_value(self).unwrap()).unwrap()
+    }
+
+    fn from_document(document: &RV) -> Result<CollectionEntry, StorageError> {
+        serde_json::to_value(document)
+            .and_then(serde_json::from_value)
+            .map_err(|err| StorageError::Corrupted {
+                message: format!("Invalid catalog entry: {}", err),
+            })
+    }
+}
+
+fn catalog() -> CollectionEntry {
+    CollectionEntry::new(SYSTEM_NAMESPACE, COLLECTIONS)
+}
+
+pub fn namespace_of(collection: &SqlCollectionIdentifier) -> &str {
+    collection
+        .namespace
+        .as_ref()
+        .map(|namespace| namespace.name.as_str())
+        .unwrap_or(DEFAULT_NAMESPACE)
+}
+
+/// Span of the collection name, including its namespace if qualified.
+pub fn span_of(collection: &SqlCollectionIdentifier) -> Span {
+    match &collection.namespace {
+        Some(namespace) => namespace.span.merge(&collection.name.span),
+        None => collection.name.span,
+    }
+}
+
+/// Opens the catalog, along with every collection recorded in it, and builds
+/// their indexes. Has to run once the storage is recovered and before any
+/// transaction starts.
+pub fn bootstrap(manager: &TransactionManager) -> Result<(), StorageError> {
+    let mut entries = vec![];
+    {
+        let storage = manager.storage();
+        let mut storage = storage.write().unwrap();
+        let catalog = catalog().storage_name();
+        storage.open_collection(&catalog)?;
+        for (_, document) in storage.range(&catalog, (Bound::Unbounded, Bound::Unbounded))? {
+            let entry = CollectionEntry::from_document(&document)?;
+            storage.open_collection(&entry.storage_name())?;
+            entries.push(entry);
+        }
+    }
+    for entry in entries {
+        for definition in entry.indexes.iter() {
+            manager.create_index(&entry.storage_name(), definition.clone())?;
+        }
+    }
+    Ok(())
+}
+
+/// Every collection visible to the transaction, system ones excluded.
+pub fn collections(
+    transaction: &Transaction,
+) -> Result<Vec<(DocumentId, CollectionEntry)>, ExecutionError> {
+    let documents = transaction.range(
+        &catalog().storage_name(),
+        (Bound::Unbounded, Bound::Unbounded),
+    )?;
+    Ok(documents
+        .iter()
+        .map(|(id, document)| Ok((*id, CollectionEntry::from_document(document)?)))
+        .collect::<Result<_, StorageError>>()?)
+}
+
+pub fn find_collection(
+    transaction: &Transaction,
+    collection: &SqlCollectionIdentifier,
+) -> Result<Option<CollectionEntry>, ExecutionError> {
+    let namespace = namespace_of(collection);
+    let name = &collection.name.name;
+
+    if namespace == SYSTEM_NAMESPACE {
+        return Ok(Some(catalog()).filter(|entry| entry.name == *name));
+    }
+
+    Ok(collections(transaction)?
+        .into_iter()
+        .map(|(_, entry)| entry)
+        .find(|entry| entry.namespace == namespace && entry.name == *name))
+}
+
+/// Like [`find_collection`], but fails if the collection does not exist.
+pub fn resolve_collection(
+    transaction: &Transaction,
+    collection: &SqlCollectionIdentifier,
+) -> Result<CollectionEntry, ExecutionError> {
+    find_collection(transaction, collection)?.ok_or_else(|| {
+        CatalogError::CollectionNotFound {
+            namespace: namespace_of(collection).to_string(),
+            name: collection.name.name.clone(),
+            span: span_of(collection),
+        }
+        .into()
+    })
+}
+
+/// Records the collection along with its constraints. Primary keys and
+/// unique fields are backed by unique indexes, and references by plain ones.
+/// Referenced fields have to be unique in their collection, which may be the
+/// one being created.
+pub fn create_collection(
+    transaction: &mut Transaction,
+    command: &SqlCreateCollection,
+) -> Result<CollectionEntry, ExecutionError> {
+    let collection = &command.collection;
+    let namespace = namespace_of(collection);
+    if namespace == SYSTEM_NAMESPACE {
+        return Err(CatalogError::SystemNamespace {
+            span: span_of(collection),
+        }
+        .into());
+    }
+
+    let existing = collections(transaction)?;
+    if existing
+        .iter()
+        .any(|(_, entry)| entry.namespace == namespace && entry.name == collection.name.name)
+    {
+        return Err(CatalogError::CollectionAlreadyExists {
+            namespace: namespace.to_string(),
+            name: collection.name.name.clone(),
+            span: span_of(collection),
+        }
+        .into());
+    }
+
+    let mut entry = CollectionEntry::new(namespace, &collection.name.name);
+    let mut references = vec![];
+    for definition in command.fields.iter() {
+        let field = definition.path.to_string();
+        for constraint in definition.constraints.iter() {
+            match constraint {
+                SqlFieldConstraint::PrimaryKey => {
+                    if entry.primary_key.is_some() {
+                        return Err(CatalogError::MultiplePrimaryKeys {
+                            span: definition.path.get_span(),
+                        }
+                        .into());
+                    }
+                    entry.primary_key = Some(field.clone());
+                    entry.indexes.push(IndexDefinition {
+                        name: primary_key_index(&entry.name),
+                        fields: vec![field.clone()],
+                        unique: true,
+                    });
+                }
+                SqlFieldConstraint::Unique => entry.indexes.push(IndexDefinition {
+                    name: unique_index(&entry.name, &field),
+                    fields: vec![field.clone()],
+                    unique: true,
+                }),
+                SqlFieldConstraint::Default { expr } => entry.defaults.push(FieldDefault {
+                    field: field.clone(),
+                    expr: *expr.clone(),
+                }),
+                SqlFieldConstraint::References {
+                    collection,
+                    field: referenced_field,
+                    on_delete,
+                } => references.push((field.clone(), collection, referenced_field, on_delete)),
+            }
+        }
+    }
+
+    for (field, target, target_field, on_delete) in references {
+        let target = if namespace_of(target) == namespace && target.name.name == entry.name {
+            entry.clone()
+        } else {
+            resolve_collection(transaction, target)?
+        };
+        if target.index_on(&target_field.to_string(), true).is_none() {
+            return Err(CatalogError::ReferencedFieldNotUnique {
+                collection: target.storage_name(),
+                field: target_field.to_string(),
+                span: target_field.get_span(),
+            }
+            .into());
+        }
+
+        entry.indexes.push(IndexDefinition {
+            name: foreign_key_index(&entry.name, &field),
+            fields: vec![field.clone()],
+            unique: false,
+        });
+        entry.references.push(ForeignKey {
+            field,
+            collection: target.storage_name(),
+            referenced_field: target_field.to_string(),
+            on_delete: on_delete.clone(),
+        });
+    }
+    let id = existing.last().map(|(id, _)| id + 1).unwrap_or(1);
+
+    transaction.open_collection(&entry.storage_name())?;
+    for definition in entry.indexes.iter() {
+        transaction.create_index(&entry.storage_name(), definition.clone())?;
+    }
+    transaction.insert(&catalog().storage_name(), id, entry.to_document())?;
+    Ok(entry)
+}
+
+/// Records the index in the catalog. It is built once the transaction
+/// commits, which fails if a unique index finds duplicate keys.
+pub fn create_index(
+    transaction: &mut Transaction,
+    command: &SqlCreateIndex,
+) -> Result<IndexDefinition, ExecutionError> {
+    let collection = &command.collection;
+    let namespace = namespace_of(collection);
+    if namespace == SYSTEM_NAMESPACE {
+        return Err(CatalogError::SystemNamespace {
+            span: span_of(collection),
+        }
+        .into());
+    }
+
+    let Some((id, mut entry)) = collections(transaction)?
+        .into_iter()
+        .find(|(_, entry)| entry.namespace == namespace && entry.name == collection.name.name)
+    else {
+        return Err(CatalogError::CollectionNotFound {
+            namespace: namespace.to_string(),
+            name: collection.name.name.clone(),
+            span: span_of(collection),
+        }
+        .into());
+    };
+
+    if entry
+        .indexes
+        .iter()
+        .any(|index| index.name == command.name.name)
+    {
+        return Err(CatalogError::IndexAlreadyExists {
+            collection: entry.storage_name(),
+            name: command.name.name.clone(),
+            span: command.name.span,
+        }
+        .into());
+    }
+
+    let definition = IndexDefinition {
+        name: command.name.name.clone(),
+        fields: command
+            .fields
+            .iter()
+            .map(|field| field.to_string())
+            .collect(),
+        unique: command.unique,
+    };
+    entry.indexes.push(definition.clone());
+
+    transaction.create_index(&entry.storage_name(), definition.clone())?;
+    transaction.insert(&catalog().storage_name(), id, entry.to_document())?;
+    Ok(definition)
+}
+
+/// Removes the collection from the catalog and deletes its documents.
+pub fn drop_collection(
+    transaction: &mut Transaction,
+    collection: &SqlCollectionIdentifier,
+) -> Result<CollectionEntry, ExecutionError> {
+    let namespace = namespace_of(collection);
+    if namespace == SYSTEM_NAMESPACE {
+        return Err(CatalogError::SystemNamespace {
+            span: span_of(collection),
+        }
+        .into());
+    }
+
+    let existing = collections(transaction)?;
+    let Some((id, entry)) = existing
+        .iter()
+        .find(|(_, entry)| entry.namespace == namespace && entry.name == collection.name.name)
+        .cloned()
+    else {
+        return Err(CatalogError::CollectionNotFound {
+            namespace: namespace.to_string(),
+            name: collection.name.name.clone(),
+            span: span_of(collection),
+        }
+        .into());
+    };
+
+    let storage_name = entry.storage_name();
+    if let Some((_, referencing)) = existing.iter().find(|(_, other)| {
+        other.storage_name() != storage_name
+            && other
+                .references
+                .iter()
+                .any(|reference| reference.collection == storage_name)
+    }) {
+        return Err(CatalogError::CollectionReferenced {
+            namespace: namespace.to_string(),
+            name: collection.name.name.clone(),
+            referenced_by: referencing.storage_name(),
+            span: span_of(collection),
+        }
+        .into());
+    }
+
+    for (id, _) in transaction.range(&storage_name, (Bound::Unbounded, Bound::Unbounded))? {
+        transaction.delete(&storage_name, id)?;
+    }
+    transaction.drop_indexes(&storage_name)?;
+    transaction.delete(&catalog().storage_name(), id)?;
+    Ok(entry)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+    use std::sync::Arc;
+
+    use lykiadb_lang::ast::sql::SqlTransactionMode;
+
+    use super::*;
+    use crate::engine::interpreter::Interpreter;
+    use crate::store::disk::tests::TempDir;
+    use crate::store::disk::{DiskEngine, StorageConfig};
+    use crate::store::transaction::{SharedTransactionManager, TransactionManager};
+    use crate::store::wal::{WalConfig, WalEngine};
+    use crate::util::alloc_shared;
+
+    fn open(dir: &Path) -> SharedTransactionManager {
+        let disk = DiskEngine::open(StorageConfig {
+            data_dir: dir.to_path_buf(),
+            page_cache_size: 16,
+        })
+        .unwrap();
+        let wal = WalEngine::open(
+            Box::new(disk),
+            WalConfig {
+                path: dir.join("wal.log"),
+                checkpoint_size: WalConfig::default().checkpoint_size,
+            },
+        )
+        .unwrap();
+        let manager = Arc::new(TransactionManager::new(alloc_shared(wal)));
+        bootstrap(&manager).unwrap();
+        manager
+    }
+
+    fn names(manager: &SharedTransactionManager) -> Vec<String> {
+        let transaction = manager.begin(SqlTransactionMode::ReadOnly);
+        collections(&transaction)
+            .unwrap()
+            .into_iter()
+            .map(|(_, entry)| entry.storage_name())
+            .collect()
+    }
+
+    #[test]
+    fn test_catalog_survives_restart() {
+        let dir = TempDir::new("catalog_restart");
+
+        {
+            let mut interpreter = Interpreter::with_transactions(None, false, open(dir.path()));
+            interpreter
+                .interpret(
+                    "CREATE COLLECTION books;
+                    CREATE COLLECTION library.books;
+                    CREATE COLLECTION authors;
+                    DROP COLLECTION authors;",
+                )
+                .unwrap();
+        }
+
+        let manager = open(dir.path());
+        assert_eq!(names(&manager), vec!["public.books", "library.books"]);
+
+        // Collections recorded in the catalog are open for reads right away
+        let transaction = manager.begin(SqlTransactionMode::ReadOnly);
+        assert_eq!(
+            transaction.range("library.books", (Bound::Unbounded, Bound::Unbounded)),
+            Ok(vec![])
+        );
+    }
+
+    #[test]
+    fn test_indexes_survive_restart() {
+        let dir = TempDir::new("catalog_indexes");
+
+        {
+            let mut interpreter = Interpreter::with_transactions(None, false, open(dir.path()));
+            interpreter
+                .interpret(
+                    "CREATE COLLECTION books;
+                    CREATE UNIQUE INDEX by_isbn ON books (isbn.code);",
+                )
+                .unwrap();
+        }
+
+        let manager = open(dir.path());
+        let transaction = manager.begin(SqlTransactionMode::ReadOnly);
+        let entry = collections(&transaction).unwrap().remove(0).1;
+        assert_eq!(
+            entry.indexes,
+            vec![IndexDefinition {
+                name: "by_isbn".to_string(),
+                fields: vec!["isbn.code".to_string()],
+                unique: true,
+            }]
+        );
+
+        // The index is enforced right away
+        let book: RV = serde_json::from_str(r#"{"isbn": {"code": "0451524934"}}"#).unwrap();
+        let mut transaction = manager.begin(SqlTransactionMode::ReadWrite);
+        transaction.insert("public.books", 1, book.clone()).unwrap();
+        transaction.insert("public.books", 2, book).unwrap();
+        assert!(transaction.commit().is_err());
+    }
+
+    #[test]
+    fn test_ddl_is_transactional() {
+        let dir = TempDir::new("catalog_rollback");
+        let manager = open(dir.path());
+        let mut interpreter = Interpreter::with_transactions(None, false, manager.clone());
+
+        interpreter
+            .interpret("BEGIN; CREATE COLLECTION books;")
+            .unwrap();
+        assert!(names(&manager).is_empty());
+
+        interpreter.interpret("ROLLBACK;").unwrap();
+        assert!(names(&manager).is_empty());
+
+        interpreter
+            .interpret("BEGIN; CREATE COLLECTION books; COMMIT;")
+            .unwrap();
+        assert_eq!(names(&manager), vec!["public.books"]);
+    }
+}
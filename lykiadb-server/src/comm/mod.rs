@@ -1,5 +1,6 @@
 use crate::engine::interpreter::Interpreter;
 use crate::engine::{Runtime, RuntimeMode};
+use crate::store::transaction::SharedTransactionManager;
 use crate::value::RV;
 use ::std::time::Instant;
 use tcp::TcpConnection;
@@ -56,10 +57,13 @@ pub struct ServerSession {
 }
 
 impl ServerSession {
-    pub fn new(stream: TcpStream) -> Self {
+    pub fn new(stream: TcpStream, transactions: SharedTransactionManager) -> Self {
         ServerSession {
             conn: TcpConnection::new(stream),
-            runtime: Runtime::new(RuntimeMode::File, Interpreter::new(None, true)),
+            runtime: Runtime::new(
+                RuntimeMode::File,
+                Interpreter::with_transactions(None, true, transactions),
+            ),
         }
     }
 
@@ -85,6 +89,10 @@ impl ServerSession {
             let elapsed = start.elapsed();
             info!("{:?} (took {:?})", message, elapsed);
         }
+
+        if self.runtime.rollback_transaction() {
+            info!("Rolled back the transaction left open by the client");
+        }
     }
 
     pub async fn send(&mut self, msg: Message) -> Result<(), CommunicationError> {
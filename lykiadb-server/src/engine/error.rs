@@ -1,6 +1,11 @@
 use std::fmt::{Display, Formatter, Result};
 
-use crate::{plan::PlannerError, value::environment::EnvironmentError};
+use crate::{
+    catalog::{constraint::ConstraintError, CatalogError},
+    plan::PlannerError,
+    store::{transaction::TransactionError, StorageError},
+    value::environment::EnvironmentError,
+};
 
 use super::interpreter::InterpretError;
 use lykiadb_lang::{ast::Span, parser::ParseError, tokenizer::scanner::ScanError, LangError};
@@ -12,6 +17,16 @@ pub enum ExecutionError {
     Interpret(InterpretError),
     Environment(EnvironmentError),
     Plan(PlannerError),
+    Storage(StorageError),
+    Transaction(TransactionError),
+    Catalog(CatalogError),
+    Constraint(ConstraintError),
+}
+
+impl From<StorageError> for ExecutionError {
+    fn from(err: StorageError) -> Self {
+        ExecutionError::Storage(err)
+    }
 }
 
 impl Display for ExecutionError {
@@ -118,6 +133,13 @@ pub fn report_error(
                 span,
             );
         }
+        ExecutionError::Interpret(InterpretError::AggregateNotCallable { span }) => {
+            print(
+                "Aggregate called outside of a query",
+                "Aggregates can only be called in the projection, HAVING and ORDER BY clauses of a query.",
+                span,
+            );
+        }
         ExecutionError::Interpret(InterpretError::PropertyNotFound { property, span }) => {
             print(
                 &format!(
@@ -128,6 +150,70 @@ pub fn report_error(
                 span,
             );
         }
+        ExecutionError::Interpret(InterpretError::InvalidRowSource { span, value }) => {
+            print(
+                "Invalid row source",
+                &format!(
+                    "Only arrays of objects can be queried, while this evaluates to {}.",
+                    value
+                ),
+                span,
+            );
+        }
+        ExecutionError::Interpret(InterpretError::UnknownSetting { span, name }) => {
+            print(
+                "Unknown setting",
+                &format!("There is no setting named {}.", name),
+                span,
+            );
+        }
+        ExecutionError::Interpret(InterpretError::InvalidSettingValue { span, name, value }) => {
+            print(
+                "Invalid value for setting",
+                &format!(
+                    "{} must be a non-negative integer, while this evaluates to {}.",
+                    name, value
+                ),
+                span,
+            );
+        }
+        ExecutionError::Interpret(InterpretError::FullDeleteNotAllowed { span, collection }) => {
+            print(
+                "Delete without a condition",
+                &format!(
+                    "This would delete every document in {}. Add a WHERE clause, or run `SET allow_full_delete = true;` first.",
+                    collection
+                ),
+                span,
+            );
+        }
+        ExecutionError::Interpret(InterpretError::RecursionLimitExceeded { span, name, limit }) => {
+            print(
+                "Recursion limit exceeded",
+                &format!(
+                    "{} kept producing rows after {} iterations. Check the recursive part of the query, or raise the limit with `SET max_recursion = n;`.",
+                    name, limit
+                ),
+                span,
+            );
+        }
+        ExecutionError::Interpret(InterpretError::MultipleRowsInScalarSubquery { span }) => {
+            print(
+                "Subquery returned more than one row",
+                "A subquery used as a value must return one row at most.",
+                span,
+            );
+        }
+        ExecutionError::Interpret(InterpretError::InvalidWindowOffset { span, value }) => {
+            print(
+                "Invalid offset for lag or lead",
+                &format!(
+                    "Offsets must be non-negative integers, while this evaluates to {}.",
+                    value
+                ),
+                span,
+            );
+        }
         ExecutionError::Plan(PlannerError::DuplicateObjectInScope { previous, ident }) => {
             print(
                 "Duplicate object in scope",
@@ -142,11 +228,298 @@ pub fn report_error(
                 span,
             );
         }
+        ExecutionError::Plan(PlannerError::MultipleFieldsInSubquery(span)) => {
+            print(
+                "Subquery selects more than one field",
+                "Subqueries used as a value or with IN must select exactly one field.",
+                span,
+            );
+        }
+        ExecutionError::Plan(PlannerError::AggregateNotAllowed(span)) => {
+            print(
+                "Aggregate not allowed",
+                "Aggregates are only allowed in the projection, HAVING and ORDER BY clauses, and cannot be nested.",
+                span,
+            );
+        }
+        ExecutionError::Plan(PlannerError::UngroupedField { field, span }) => {
+            print(
+                &format!("{} is not grouped", field),
+                "Fields outside of aggregates must appear in the GROUP BY clause.",
+                span,
+            );
+        }
+        ExecutionError::Plan(PlannerError::WindowNotAllowed(span)) => {
+            print(
+                "Window function not allowed",
+                "Window functions are only allowed in the projection and ORDER BY clauses, and cannot be nested.",
+                span,
+            );
+        }
+        ExecutionError::Plan(PlannerError::UnknownWindowFunction { name, span }) => {
+            print(
+                &format!("Unknown window function {}", name),
+                "Use row_number, rank, dense_rank, lag, lead or an aggregate over a window.",
+                span,
+            );
+        }
+        ExecutionError::Plan(PlannerError::InvalidWindowFrame(span)) => {
+            print(
+                "Invalid window frame",
+                "Frame offsets must be non-negative numbers in ROWS frames, and frames cannot start after they end.",
+                span,
+            );
+        }
+        ExecutionError::Plan(PlannerError::ConflictTargetNotUnique {
+            collection,
+            fields,
+            span,
+        }) => {
+            print(
+                &format!(
+                    "No unique index on ({}) of {}",
+                    fields.join(", "),
+                    collection
+                ),
+                "ON CONFLICT needs the fields of a primary key or unique index.",
+                span,
+            );
+        }
+        ExecutionError::Plan(PlannerError::CollectionNotFound {
+            namespace,
+            name,
+            span,
+        })
+        | ExecutionError::Catalog(CatalogError::CollectionNotFound {
+            namespace,
+            name,
+            span,
+        }) => {
+            print(
+                &format!("Collection {}.{} not found", namespace, name),
+                "Create the collection with CREATE COLLECTION first.",
+                span,
+            );
+        }
+        ExecutionError::Catalog(CatalogError::CollectionAlreadyExists {
+            namespace,
+            name,
+            span,
+        }) => {
+            print(
+                &format!("Collection {}.{} already exists", namespace, name),
+                "Choose another name, or drop the existing collection first.",
+                span,
+            );
+        }
+        ExecutionError::Catalog(CatalogError::SystemNamespace { span }) => {
+            print(
+                "System collections cannot be created or dropped",
+                "Use a namespace other than system.",
+                span,
+            );
+        }
+        ExecutionError::Catalog(CatalogError::IndexAlreadyExists {
+            collection,
+            name,
+            span,
+        }) => {
+            print(
+                &format!("Index {} already exists on {}", name, collection),
+                "Choose another name for the index.",
+                span,
+            );
+        }
+        ExecutionError::Catalog(CatalogError::MultiplePrimaryKeys { span }) => {
+            print(
+                "A collection can only have one primary key",
+                "Remove all but one of the PRIMARY KEY constraints.",
+                span,
+            );
+        }
+        ExecutionError::Catalog(CatalogError::ReferencedFieldNotUnique {
+            collection,
+            field,
+            span,
+        }) => {
+            print(
+                &format!("Field {} of {} is not unique", field, collection),
+                "Only primary keys and unique fields can be referenced.",
+                span,
+            );
+        }
+        ExecutionError::Catalog(CatalogError::CollectionReferenced {
+            namespace,
+            name,
+            referenced_by,
+            span,
+        }) => {
+            print(
+                &format!(
+                    "Collection {}.{} is referenced by {}",
+                    namespace, name, referenced_by
+                ),
+                "Drop the referencing collection first.",
+                span,
+            );
+        }
+        ExecutionError::Constraint(ConstraintError::PrimaryKeyMissing {
+            collection,
+            field,
+            span,
+        }) => {
+            print(
+                &format!("Missing primary key {} in {}", field, collection),
+                "Documents must have a value for the primary key.",
+                span,
+            );
+        }
+        ExecutionError::Constraint(ConstraintError::PrimaryKeyViolation {
+            collection,
+            field,
+            span,
+        }) => {
+            print(
+                &format!("Duplicate primary key {} in {}", field, collection),
+                "Another document already has this primary key.",
+                span,
+            );
+        }
+        ExecutionError::Constraint(ConstraintError::UniqueViolation {
+            collection,
+            fields,
+            span,
+        }) => {
+            print(
+                &format!(
+                    "Duplicate value for unique {} in {}",
+                    fields.join(", "),
+                    collection
+                ),
+                "Another document already has this value.",
+                span,
+            );
+        }
+        ExecutionError::Constraint(ConstraintError::ForeignKeyViolation {
+            collection,
+            field,
+            references,
+            span,
+        }) => {
+            print(
+                &format!(
+                    "Field {} of {} references a missing {}",
+                    field, collection, references
+                ),
+                "Make sure the referenced document exists.",
+                span,
+            );
+        }
+        ExecutionError::Constraint(ConstraintError::DocumentReferenced {
+            collection,
+            referenced_by,
+            span,
+        }) => {
+            print(
+                &format!(
+                    "Document of {} is still referenced by {}",
+                    collection, referenced_by
+                ),
+                "Delete or update the referencing documents first.",
+                span,
+            );
+        }
+        ExecutionError::Constraint(ConstraintError::InvalidId { collection, span }) => {
+            print(
+                &format!("Invalid document id in {}", collection),
+                "Ids have to be non-negative integers.",
+                span,
+            );
+        }
+        ExecutionError::Constraint(ConstraintError::DuplicateId {
+            collection,
+            id,
+            span,
+        }) => {
+            print(
+                &format!("Duplicate document id {} in {}", id, collection),
+                "Another document already has this id.",
+                span,
+            );
+        }
+        ExecutionError::Transaction(TransactionError::AlreadyInTransaction { span }) => {
+            print(
+                "There is already a transaction in progress",
+                "Commit or roll back the current transaction first.",
+                span,
+            );
+        }
+        ExecutionError::Transaction(TransactionError::NoActiveTransaction { span }) => {
+            print(
+                "There is no transaction in progress",
+                "Start a transaction with BEGIN first.",
+                span,
+            );
+        }
+        ExecutionError::Transaction(TransactionError::ReadOnlyTransaction { collection, span }) => {
+            print(
+                &format!("Cannot write to {} in a read-only transaction", collection),
+                "The transaction was started as READ ONLY.",
+                span,
+            );
+        }
+        ExecutionError::Transaction(TransactionError::SerializationFailure {
+            collection,
+            id,
+            span,
+        }) => {
+            print(
+                &format!(
+                    "Could not serialize access to document {} in {} due to a concurrent update",
+                    id, collection
+                ),
+                "Retry the transaction.",
+                span,
+            );
+        }
+        ExecutionError::Transaction(TransactionError::UniqueViolation {
+            collection,
+            index,
+            span,
+        }) => {
+            print(
+                &format!("Duplicate key in unique index {} of {}", index, collection),
+                "Make sure no two documents have the same values for the indexed fields.",
+                span,
+            );
+        }
+        ExecutionError::Storage(err)
+        | ExecutionError::Transaction(TransactionError::Storage(err)) => {
+            let (message, hint) = match err {
+                StorageError::CollectionNotFound { name } => {
+                    (format!("Collection {} does not exist in storage", name), "")
+                }
+                StorageError::CollectionNotOpen { name } => {
+                    (format!("Collection {} is not open", name), "")
+                }
+                StorageError::InvalidDocument { message } => {
+                    (format!("Invalid document: {}", message), "")
+                }
+                StorageError::Io { message } => (format!("I/O error: {}", message), ""),
+                StorageError::Corrupted { message } => (
+                    format!("Storage is corrupted: {}", message),
+                    "Restore the data directory from a backup.",
+                ),
+                StorageError::Other { message } => (message, ""),
+            };
+            print(&message, hint, Span::default());
+        }
         ExecutionError::Environment(EnvironmentError::Other { message })
         | ExecutionError::Interpret(InterpretError::Other { message }) => {
             print(&message, "", Span::default());
         }
-        _ => {}
+        ExecutionError::Lang(LangError::Resolve(_))
+        | ExecutionError::Plan(PlannerError::ObjectNotFoundInScope(_)) => {}
     };
 }
 #[cfg(test)]
@@ -324,6 +697,21 @@ mod tests {
         assert!(output.contains("Subqueries are not allowed in this context"));
     }
 
+    #[test]
+    fn test_planner_multiple_fields_in_subquery() {
+        let source = "SELECT * FROM users WHERE id IN (SELECT id, name FROM users);";
+        let error = ExecutionError::Plan(PlannerError::MultipleFieldsInSubquery(Span {
+            start: 32,
+            end: 59,
+            line: 0,
+            line_end: 0,
+        }));
+
+        let output = capture_error_output("test.txt", source, error);
+        assert!(output.contains("Subquery selects more than one field"));
+        assert!(output.contains("must select exactly one field"));
+    }
+
     // Interpreter Error Tests
     #[test]
     fn test_interpreter_arity_mismatch() {
@@ -409,6 +797,150 @@ mod tests {
         assert!(output.contains("Check if that field is present"));
     }
 
+    #[test]
+    fn test_invalid_row_source_reporting() {
+        let source = "SELECT * FROM $n n;";
+        let error = ExecutionError::Interpret(InterpretError::InvalidRowSource {
+            span: Span {
+                start: 14,
+                end: 16,
+                line: 0,
+                line_end: 0,
+            },
+            value: "Num(1.0)".to_string(),
+        });
+
+        let output = capture_error_output("test.txt", source, error);
+        assert!(output.contains("Invalid row source"));
+        assert!(output.contains("Only arrays of objects can be queried"));
+    }
+
+    #[test]
+    fn test_multiple_rows_in_scalar_subquery_reporting() {
+        let source = "SELECT (SELECT b.title FROM books b) as title FROM authors;";
+        let error = ExecutionError::Interpret(InterpretError::MultipleRowsInScalarSubquery {
+            span: Span {
+                start: 7,
+                end: 36,
+                line: 0,
+                line_end: 0,
+            },
+        });
+
+        let output = capture_error_output("test.txt", source, error);
+        assert!(output.contains("Subquery returned more than one row"));
+    }
+
+    #[test]
+    fn test_invalid_window_offset_reporting() {
+        let source = "SELECT lead(x.v, 'a') OVER () as next FROM $rows as x;";
+        let error = ExecutionError::Interpret(InterpretError::InvalidWindowOffset {
+            span: Span {
+                start: 17,
+                end: 20,
+                line: 0,
+                line_end: 0,
+            },
+            value: "Str(\"a\")".to_string(),
+        });
+
+        let output = capture_error_output("test.txt", source, error);
+        assert!(output.contains("Invalid offset for lag or lead"));
+        assert!(output.contains("Offsets must be non-negative integers"));
+    }
+
+    #[test]
+    fn test_invalid_setting_value_reporting() {
+        let source = "SET max_recursion = -1;";
+        let error = ExecutionError::Interpret(InterpretError::InvalidSettingValue {
+            span: Span {
+                start: 20,
+                end: 22,
+                line: 0,
+                line_end: 0,
+            },
+            name: "max_recursion".to_string(),
+            value: "Num(-1.0)".to_string(),
+        });
+
+        let output = capture_error_output("test.txt", source, error);
+        assert!(output.contains("Invalid value for setting"));
+        assert!(output.contains("max_recursion must be a non-negative integer"));
+    }
+
+    #[test]
+    fn test_recursion_limit_exceeded_reporting() {
+        let source = "WITH RECURSIVE nums AS (SELECT 1 as n UNION SELECT n + 1 as n FROM nums) SELECT * FROM nums;";
+        let error = ExecutionError::Interpret(InterpretError::RecursionLimitExceeded {
+            span: Span {
+                start: 15,
+                end: 19,
+                line: 0,
+                line_end: 0,
+            },
+            name: "nums".to_string(),
+            limit: 100,
+        });
+
+        let output = capture_error_output("test.txt", source, error);
+        assert!(output.contains("Recursion limit exceeded"));
+        assert!(output.contains("after 100 iterations"));
+    }
+
+    #[test]
+    fn test_full_delete_not_allowed_reporting() {
+        let source = "DELETE FROM books;";
+        let error = ExecutionError::Interpret(InterpretError::FullDeleteNotAllowed {
+            span: Span {
+                start: 12,
+                end: 17,
+                line: 0,
+                line_end: 0,
+            },
+            collection: "books".to_string(),
+        });
+
+        let output = capture_error_output("test.txt", source, error);
+        assert!(output.contains("Delete without a condition"));
+        assert!(output.contains("SET allow_full_delete = true;"));
+    }
+
+    #[test]
+    fn test_ungrouped_field_reporting() {
+        let source = "SELECT title, count(title) FROM books GROUP BY author;";
+        let error = ExecutionError::Plan(PlannerError::UngroupedField {
+            field: "title".to_string(),
+            span: Span {
+                start: 7,
+                end: 12,
+                line: 0,
+                line_end: 0,
+            },
+        });
+
+        let output = capture_error_output("test.txt", source, error);
+        assert!(output.contains("title is not grouped"));
+        assert!(output.contains("GROUP BY clause"));
+    }
+
+    #[test]
+    fn test_unknown_window_function_reporting() {
+        let source = "SELECT ntile(4) OVER (ORDER BY pages) FROM books;";
+        let error = ExecutionError::Plan(PlannerError::UnknownWindowFunction {
+            name: "ntile".to_string(),
+            span: Span {
+                start: 7,
+                end: 12,
+                line: 0,
+                line_end: 0,
+            },
+        });
+
+        let output = capture_error_output("test.txt", source, error);
+        assert!(output.contains("Unknown window function ntile"));
+        assert!(output.contains("row_number, rank, dense_rank"));
+    }
+
     #[test]
     fn test_environment_error_reporting() {
         let source = "";
@@ -419,4 +951,230 @@ mod tests {
         let output = capture_error_output("test.txt", source, error);
         assert!(output.contains("Variable not found"));
     }
+
+    #[test]
+    fn test_transaction_error_reporting() {
+        let source = "COMMIT;";
+        let error = ExecutionError::Transaction(TransactionError::NoActiveTransaction {
+            span: Span {
+                start: 0,
+                end: 6,
+                line: 0,
+                line_end: 0,
+            },
+        });
+
+        let output = capture_error_output("test.txt", source, error);
+        assert!(output.contains("There is no transaction in progress"));
+        assert!(output.contains("Start a transaction with BEGIN first"));
+    }
+
+    #[test]
+    fn test_serialization_failure_reporting() {
+        let source = "COMMIT;";
+        let error = ExecutionError::Transaction(TransactionError::SerializationFailure {
+            collection: "books".to_string(),
+            id: 1,
+            span: Span {
+                start: 0,
+                end: 6,
+                line: 0,
+                line_end: 0,
+            },
+        });
+
+        let output = capture_error_output("test.txt", source, error);
+        assert!(output.contains("Could not serialize access to document 1 in books"));
+        assert!(output.contains("Retry the transaction"));
+    }
+
+    #[test]
+    fn test_unique_violation_reporting() {
+        let source = "COMMIT;";
+        let error = ExecutionError::Transaction(TransactionError::UniqueViolation {
+            collection: "public.books".to_string(),
+            index: "by_isbn".to_string(),
+            span: Span::default(),
+        });
+
+        let output = capture_error_output("test.txt", source, error);
+        assert!(output.contains("Duplicate key in unique index by_isbn of public.books"));
+    }
+
+    #[test]
+    fn test_read_only_transaction_reporting() {
+        let source = "INSERT INTO books VALUES {title: '1984'};";
+        let error = ExecutionError::Transaction(TransactionError::ReadOnlyTransaction {
+            collection: "public.books".to_string(),
+            span: Span {
+                start: 0,
+                end: 40,
+                line: 0,
+                line_end: 0,
+            },
+        });
+
+        let output = capture_error_output("test.txt", source, error);
+        assert!(output.contains("Cannot write to public.books in a read-only transaction"));
+        assert!(output.contains("The transaction was started as READ ONLY"));
+    }
+
+    #[test]
+    fn test_storage_error_reporting() {
+        let source = "SELECT * FROM books;";
+        let error = ExecutionError::Storage(StorageError::Corrupted {
+            message: "Page 3 is truncated".to_string(),
+        });
+
+        let output = capture_error_output("test.txt", source, error);
+        assert!(output.contains("Storage is corrupted: Page 3 is truncated"));
+
+        let error = ExecutionError::Transaction(TransactionError::Storage(StorageError::Io {
+            message: "No space left on device".to_string(),
+        }));
+
+        let output = capture_error_output("test.txt", source, error);
+        assert!(output.contains("I/O error: No space left on device"));
+    }
+
+    #[test]
+    fn test_constraint_error_reporting() {
+        let source = "INSERT INTO books VALUES {title: '1984'};";
+        let span = Span {
+            start: 25,
+            end: 40,
+            line: 0,
+            line_end: 0,
+        };
+
+        let output = capture_error_output(
+            "test.txt",
+            source,
+            ExecutionError::Constraint(ConstraintError::PrimaryKeyMissing {
+                collection: "public.books".to_string(),
+                field: "isbn".to_string(),
+                span,
+            }),
+        );
+        assert!(output.contains("Missing primary key isbn in public.books"));
+
+        let output = capture_error_output(
+            "test.txt",
+            source,
+            ExecutionError::Constraint(ConstraintError::PrimaryKeyViolation {
+                collection: "public.books".to_string(),
+                field: "isbn".to_string(),
+                span,
+            }),
+        );
+        assert!(output.contains("Duplicate primary key isbn in public.books"));
+
+        let output = capture_error_output(
+            "test.txt",
+            source,
+            ExecutionError::Constraint(ConstraintError::UniqueViolation {
+                collection: "public.books".to_string(),
+                fields: vec!["title".to_string()],
+                span,
+            }),
+        );
+        assert!(output.contains("Duplicate value for unique title in public.books"));
+        assert!(output.contains("Another document already has this value"));
+
+        let output = capture_error_output(
+            "test.txt",
+            source,
+            ExecutionError::Constraint(ConstraintError::ForeignKeyViolation {
+                collection: "public.books".to_string(),
+                field: "category_id".to_string(),
+                references: "public.categories.id".to_string(),
+                span,
+            }),
+        );
+        assert!(output.contains(
+            "Field category_id of public.books references a missing public.categories.id"
+        ));
+
+        let output = capture_error_output(
+            "test.txt",
+            source,
+            ExecutionError::Constraint(ConstraintError::DocumentReferenced {
+                collection: "public.categories".to_string(),
+                referenced_by: "public.books.category_id".to_string(),
+                span,
+            }),
+        );
+        assert!(output.contains(
+            "Document of public.categories is still referenced by public.books.category_id"
+        ));
+
+        let output = capture_error_output(
+            "test.txt",
+            source,
+            ExecutionError::Constraint(ConstraintError::InvalidId {
+                collection: "public.books".to_string(),
+                span,
+            }),
+        );
+        assert!(output.contains("Invalid document id in public.books"));
+
+        let output = capture_error_output(
+            "test.txt",
+            source,
+            ExecutionError::Constraint(ConstraintError::DuplicateId {
+                collection: "public.books".to_string(),
+                id: 1,
+                span,
+            }),
+        );
+        assert!(output.contains("Duplicate document id 1 in public.books"));
+    }
+
+    #[test]
+    fn test_collection_not_found_reporting() {
+        let source = "SELECT * FROM books;";
+        let error = ExecutionError::Plan(PlannerError::CollectionNotFound {
+            namespace: "public".to_string(),
+            name: "books".to_string(),
+            span: Span {
+                start: 14,
+                end: 19,
+                line: 0,
+                line_end: 0,
+            },
+        });
+
+        let output = capture_error_output("test.txt", source, error);
+        assert!(output.contains("Collection public.books not found"));
+        assert!(output.contains("Create the collection with CREATE COLLECTION first"));
+    }
+
+    #[test]
+    fn test_catalog_error_reporting() {
+        let source = "CREATE COLLECTION books;";
+        let span = Span {
+            start: 18,
+            end: 23,
+            line: 0,
+            line_end: 0,
+        };
+
+        let output = capture_error_output(
+            "test.txt",
+            source,
+            ExecutionError::Catalog(CatalogError::CollectionAlreadyExists {
+                namespace: "public".to_string(),
+                name: "books".to_string(),
+                span,
+            }),
+        );
+        assert!(output.contains("Collection public.books already exists"));
+
+        let output = capture_error_output(
+            "test.txt",
+            source,
+            ExecutionError::Catalog(CatalogError::SystemNamespace { span }),
+        );
+        assert!(output.contains("System collections cannot be created or dropped"));
+    }
 }
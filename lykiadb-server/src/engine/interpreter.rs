@@ -1,4 +1,5 @@
 use lykiadb_lang::ast::expr::{Expr, Operation, RangeKind};
+use lykiadb_lang::ast::sql::SqlTransactionMode;
 use lykiadb_lang::ast::stmt::Stmt;
 use lykiadb_lang::ast::visitor::VisitorMut;
 use lykiadb_lang::ast::{Literal, Span, Spanned};
@@ -14,7 +15,14 @@ use string_interner::StringInterner;
 use super::error::ExecutionError;
 use super::stdlib::stdlib;
 
+use crate::catalog;
+use crate::plan::executor::{run_subquery, Executor, Row, Subqueries};
 use crate::plan::planner::Planner;
+use crate::store::memory::MemoryEngine;
+use crate::store::transaction::{
+    SharedTransactionManager, Transaction, TransactionError, TransactionManager,
+};
+use crate::store::SharedStorage;
 use crate::util::{alloc_shared, Shared};
 use crate::value::callable::{Callable, CallableKind, Function, Stateful};
 use crate::value::environment::EnvironmentFrame;
@@ -28,6 +36,9 @@ pub enum InterpretError {
     NotCallable {
         span: Span,
     },
+    AggregateNotCallable {
+        span: Span,
+    },
     ArityMismatch {
         span: Span,
         expected: usize,
@@ -40,6 +51,35 @@ pub enum InterpretError {
         span: Span,
         property: String,
     },
+    InvalidRowSource {
+        span: Span,
+        value: String,
+    },
+    UnknownSetting {
+        span: Span,
+        name: String,
+    },
+    InvalidSettingValue {
+        span: Span,
+        name: String,
+        value: String,
+    },
+    FullDeleteNotAllowed {
+        span: Span,
+        collection: String,
+    },
+    RecursionLimitExceeded {
+        span: Span,
+        name: String,
+        limit: usize,
+    },
+    MultipleRowsInScalarSubquery {
+        span: Span,
+    },
+    InvalidWindowOffset {
+        span: Span,
+        value: String,
+    },
     Other {
         message: String,
     }, // TODO(vck): Refactor this
@@ -142,6 +182,26 @@ impl LoopStack {
     }
 }
 
+/// Session-level switches, changed with `SET name = value;`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Settings {
+    /// Lets DELETE run without a WHERE clause, removing every document of
+    /// the collection.
+    pub allow_full_delete: bool,
+    /// Number of iterations a recursive common table expression may run
+    /// for before it is aborted.
+    pub max_recursion: usize,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            allow_full_delete: false,
+            max_recursion: 100,
+        }
+    }
+}
+
 pub struct Interpreter {
     env: Arc<EnvironmentFrame>,
     root_env: Arc<EnvironmentFrame>,
@@ -149,17 +209,33 @@ pub struct Interpreter {
     //
     loop_stack: LoopStack,
     source_processor: SourceProcessor,
-    output: Option<Shared<Output>>,
+    transactions: SharedTransactionManager,
+    transaction: Option<Transaction>,
+    rows: Vec<Row>,
+    tables: Vec<(String, Arc<Vec<RV>>)>,
+    subqueries: Vec<Subqueries>,
+    settings: Settings,
     //
     interner: StringInterner<StringBackend<SymbolU32>>,
 }
 
 impl Interpreter {
     pub fn new(out: Option<Shared<Output>>, with_stdlib: bool) -> Interpreter {
+        let storage: SharedStorage = alloc_shared(MemoryEngine::new());
+        let transactions = Arc::new(TransactionManager::new(storage));
+        catalog::bootstrap(&transactions).unwrap();
+        Interpreter::with_transactions(out, with_stdlib, transactions)
+    }
+
+    pub fn with_transactions(
+        out: Option<Shared<Output>>,
+        with_stdlib: bool,
+        transactions: SharedTransactionManager,
+    ) -> Interpreter {
         let root_env = Arc::new(EnvironmentFrame::new(None));
         let mut interner = StringInterner::<StringBackend<SymbolU32>>::new();
         if with_stdlib {
-            let native_fns = stdlib(out.clone());
+            let native_fns = stdlib(out);
 
             for (name, value) in native_fns {
                 root_env.define(interner.get_or_intern(name), value);
@@ -171,11 +247,135 @@ impl Interpreter {
             loop_stack: LoopStack::new(),
             source_processor: SourceProcessor::new(),
             current_program: None,
-            output: out,
+            transactions,
+            transaction: None,
+            rows: vec![],
+            tables: vec![],
+            subqueries: vec![],
+            settings: Settings::default(),
             interner,
         }
     }
 
+    pub fn transactions(&self) -> SharedTransactionManager {
+        self.transactions.clone()
+    }
+
+    pub fn in_transaction(&self) -> bool {
+        self.transaction.is_some()
+    }
+
+    pub fn transaction(&mut self) -> Option<&mut Transaction> {
+        self.transaction.as_mut()
+    }
+
+    pub fn settings(&self) -> &Settings {
+        &self.settings
+    }
+
+    /// Runs `f`, the statement at `span`, within the current transaction.
+    /// Outside of a BEGIN ... COMMIT block, a transaction is started for `f`
+    /// alone and committed once it succeeds, or rolled back if it fails.
+    /// Within one, the writes of `f` are undone if it fails, and the
+    /// transaction carries on as it was before the statement.
+    pub fn run_in_transaction<T>(
+        &mut self,
+        span: Span,
+        f: impl FnOnce(&mut Interpreter) -> Result<T, HaltReason>,
+    ) -> Result<T, HaltReason> {
+        if let Some(transaction) = &self.transaction {
+            let savepoint = transaction.savepoint();
+            let result = f(self);
+            if let (Err(HaltReason::Error(_)), Some(transaction)) =
+                (&result, self.transaction.as_mut())
+            {
+                transaction.rollback_to(savepoint);
+            }
+            return result.map_err(|err| locate(err, span));
+        }
+
+        self.transaction = Some(self.transactions.begin(SqlTransactionMode::ReadWrite));
+        let result = f(self);
+        let transaction = self.transaction.take();
+
+        match (result, transaction) {
+            (Ok(value), Some(transaction)) => {
+                transaction
+                    .commit()
+                    .map_err(|err| HaltReason::Error(err.at(span).into()))?;
+                Ok(value)
+            }
+            (result, _) => result.map_err(|err| locate(err, span)),
+        }
+    }
+
+    /// Evaluates `f` with the fields of the row in scope, as the executor
+    /// does for every row a query expression is evaluated against.
+    pub fn with_row<T>(
+        &mut self,
+        row: Row,
+        f: impl FnOnce(&mut Interpreter) -> Result<T, HaltReason>,
+    ) -> Result<T, HaltReason> {
+        self.rows.push(row);
+        let result = f(self);
+        self.rows.pop();
+        result
+    }
+
+    /// Runs `f` with the rows of a common table expression bound to its
+    /// name. Bindings made later shadow the earlier ones of the same name.
+    pub fn with_table<T>(
+        &mut self,
+        name: &str,
+        rows: Arc<Vec<RV>>,
+        f: impl FnOnce(&mut Interpreter) -> Result<T, HaltReason>,
+    ) -> Result<T, HaltReason> {
+        self.tables.push((name.to_string(), rows));
+        let result = f(self);
+        self.tables.pop();
+        result
+    }
+
+    /// Runs `f` with the subqueries a cursor has prepared, which the
+    /// subqueries of the expressions `f` evaluates are then run with.
+    pub fn with_subqueries<T>(
+        &mut self,
+        subqueries: &mut Subqueries,
+        f: impl FnOnce(&mut Interpreter) -> Result<T, HaltReason>,
+    ) -> Result<T, HaltReason> {
+        if subqueries.is_empty() {
+            return f(self);
+        }
+        self.subqueries.push(std::mem::take(subqueries));
+        let result = f(self);
+        *subqueries = self.subqueries.pop().unwrap();
+        result
+    }
+
+    pub fn subqueries(&mut self) -> &mut [Subqueries] {
+        &mut self.subqueries
+    }
+
+    pub fn table(&self, name: &str) -> Option<Arc<Vec<RV>>> {
+        self.tables
+            .iter()
+            .rev()
+            .find(|(table, _)| table == name)
+            .map(|(_, rows)| rows.clone())
+    }
+
+    /// Rolls back the transaction in progress, if any. Returns whether there
+    /// was one.
+    pub fn rollback_transaction(&mut self) -> bool {
+        match self.transaction.take() {
+            Some(transaction) => {
+                transaction.rollback();
+                true
+            }
+            None => false,
+        }
+    }
+
     pub fn eval(&mut self, e: &Expr) -> Result<RV, HaltReason> {
         self.visit_expr(e)
     }
@@ -196,6 +396,13 @@ impl Interpreter {
     }
 
     fn eval_unary(&mut self, operation: &Operation, expr: &Expr) -> Result<RV, HaltReason> {
+        if matches!(operation, Operation::Exists | Operation::NotExists) {
+            let exists = match self.visit_expr(expr)? {
+                RV::Array(rows) => !rows.read().unwrap().is_empty(),
+                other => other.as_bool(),
+            };
+            return Ok(RV::Bool(exists == (*operation == Operation::Exists)));
+        }
         if *operation == Operation::Subtract {
             if let Some(num) = self.visit_expr(expr)?.as_number() {
                 return Ok(RV::Num(-num));
@@ -232,6 +439,22 @@ impl Interpreter {
         }
     }
 
+    /// Calls the callable with evaluated arguments, as a call expression
+    /// does.
+    pub fn call(&mut self, callable: &Callable, arguments: &[RV]) -> Result<RV, HaltReason> {
+        self.loop_stack.push_fn();
+
+        let val = callable.call(self, arguments);
+
+        self.loop_stack.pop_fn();
+
+        match val {
+            Err(HaltReason::Return(ret_val)) => Ok(ret_val),
+            Ok(unpacked_val) => Ok(unpacked_val),
+            other_err @ Err(_) => other_err,
+        }
+    }
+
     pub fn user_fn_call(
         &mut self,
         statements: &Vec<Stmt>,
@@ -246,7 +469,12 @@ impl Interpreter {
             fn_env.define(*param, arguments.get(i).unwrap().clone());
         }
 
-        self.execute_block(statements, Arc::new(fn_env))
+        // The body is planned on its own, so the subqueries of a query
+        // calling the function are not the ones its expressions refer to
+        let subqueries = std::mem::take(&mut self.subqueries);
+        let result = self.execute_block(statements, Arc::new(fn_env));
+        self.subqueries = subqueries;
+        result
     }
 
     fn execute_block(
@@ -293,6 +521,17 @@ impl Interpreter {
     }
 }
 
+/// Places the transaction errors raised by the storage layer at the
+/// statement at `span`.
+fn locate(err: HaltReason, span: Span) -> HaltReason {
+    match err {
+        HaltReason::Error(ExecutionError::Transaction(err)) => {
+            HaltReason::Error(ExecutionError::Transaction(err.at(span)))
+        }
+        other => other,
+    }
+}
+
 impl VisitorMut<RV, HaltReason> for Interpreter {
     fn visit_expr(&mut self, e: &Expr) -> Result<RV, HaltReason> {
         match e {
@@ -353,6 +592,11 @@ impl VisitorMut<RV, HaltReason> for Interpreter {
                 let eval = self.visit_expr(callee)?;
 
                 if let RV::Callable(callable) = eval {
+                    if callable.as_aggregator().is_some() {
+                        return Err(HaltReason::Error(
+                            InterpretError::AggregateNotCallable { span: *span }.into(),
+                        ));
+                    }
                     if callable.arity.is_some() && callable.arity.unwrap() != args.len() {
                         return Err(HaltReason::Error(
                             InterpretError::ArityMismatch {
@@ -369,17 +613,7 @@ impl VisitorMut<RV, HaltReason> for Interpreter {
                     for arg in args.iter() {
                         args_evaluated.push(self.visit_expr(arg)?);
                     }
-                    self.loop_stack.push_fn();
-
-                    let val = callable.call(self, args_evaluated.as_slice());
-
-                    self.loop_stack.pop_fn();
-
-                    match val {
-                        Err(HaltReason::Return(ret_val)) => Ok(ret_val),
-                        Ok(unpacked_val) => Ok(unpacked_val),
-                        other_err @ Err(_) => other_err,
-                    }
+                    self.call(&callable, args_evaluated.as_slice())
                 } else {
                     Err(HaltReason::Error(
                         InterpretError::NotCallable {
@@ -464,6 +698,17 @@ impl VisitorMut<RV, HaltReason> for Interpreter {
                     ))
                 }
             }
+            Expr::FieldPath { head, tail, .. } if !self.rows.is_empty() => {
+                // Paths a subquery does not resolve are looked up in the rows
+                // of the queries enclosing it
+                let tail: Vec<&str> = tail.iter().map(|ident| ident.name.as_str()).collect();
+                Ok(self
+                    .rows
+                    .iter()
+                    .rev()
+                    .find_map(|row| row.lookup(&head.name, &tail))
+                    .unwrap_or(RV::Undefined))
+            }
             Expr::FieldPath { .. } => Err(HaltReason::Error(
                 InterpretError::Other {
                     message: "Unexpected field path expression".to_string(),
@@ -525,18 +770,21 @@ impl VisitorMut<RV, HaltReason> for Interpreter {
                     ))
                 }
             }
-            Expr::Select { .. }
-            | Expr::Insert { .. }
-            | Expr::Update { .. }
-            | Expr::Delete { .. } => {
-                let mut planner = Planner::new(self);
-                let plan = planner.build(e)?;
-                if let Some(out) = &self.output {
-                    out.write()
-                        .unwrap()
-                        .push(RV::Str(Arc::new(plan.to_string().trim().to_string())));
+            Expr::Select { id, span, .. } => {
+                // Subqueries the executor has planned along with their query
+                if let Some(result) = run_subquery(self, *id, *span) {
+                    return result;
                 }
-                Ok(RV::Undefined)
+                self.run_in_transaction(*span, |interpreter| {
+                    let plan = Planner::new(interpreter).build(e)?;
+                    Executor::new(interpreter).execute(&plan)
+                })
+            }
+            Expr::Insert { span, .. } | Expr::Update { span, .. } | Expr::Delete { span, .. } => {
+                self.run_in_transaction(*span, |interpreter| {
+                    let plan = Planner::new(interpreter).build(e)?;
+                    Executor::new(interpreter).execute(&plan)
+                })
             }
         }
     }
@@ -612,6 +860,112 @@ impl VisitorMut<RV, HaltReason> for Interpreter {
                     ));
                 }
             }
+            Stmt::Begin { mode, span } => {
+                if self.transaction.is_some() {
+                    return Err(HaltReason::Error(
+                        TransactionError::AlreadyInTransaction { span: *span }.into(),
+                    ));
+                }
+                self.transaction = Some(
+                    self.transactions
+                        .begin(mode.unwrap_or(SqlTransactionMode::ReadWrite)),
+                );
+            }
+            Stmt::Commit { span } => match self.transaction.take() {
+                Some(transaction) => transaction
+                    .commit()
+                    .map_err(|err| HaltReason::Error(err.at(*span).into()))?,
+                None => {
+                    return Err(HaltReason::Error(
+                        TransactionError::NoActiveTransaction { span: *span }.into(),
+                    ));
+                }
+            },
+            Stmt::Rollback { span } => {
+                if !self.rollback_transaction() {
+                    return Err(HaltReason::Error(
+                        TransactionError::NoActiveTransaction { span: *span }.into(),
+                    ));
+                }
+            }
+            Stmt::Vacuum { collection, span } => {
+                let storage_name = match collection {
+                    Some(collection) => Some(self.run_in_transaction(*span, |interpreter| {
+                        catalog::resolve_collection(interpreter.transaction().unwrap(), collection)
+                            .map(|entry| entry.storage_name())
+                            .map_err(HaltReason::Error)
+                    })?),
+                    None => None,
+                };
+                let reclaimed = self
+                    .transactions
+                    .vacuum(storage_name.as_deref())
+                    .map_err(|err| HaltReason::Error(err.into()))?;
+                let counts = reclaimed
+                    .into_iter()
+                    .map(|(name, count)| (name, RV::Num(count as f64)))
+                    .collect();
+                return Ok(RV::Object(alloc_shared(counts)));
+            }
+            Stmt::CreateCollection { command, span } => {
+                self.run_in_transaction(*span, |interpreter| {
+                    catalog::create_collection(interpreter.transaction().unwrap(), command)
+                        .map_err(HaltReason::Error)
+                })?;
+            }
+            Stmt::DropCollection { command, span } => {
+                self.run_in_transaction(*span, |interpreter| {
+                    catalog::drop_collection(
+                        interpreter.transaction().unwrap(),
+                        &command.collection,
+                    )
+                    .map_err(HaltReason::Error)
+                })?;
+            }
+            Stmt::CreateIndex { command, span } => {
+                self.run_in_transaction(*span, |interpreter| {
+                    catalog::create_index(interpreter.transaction().unwrap(), command)
+                        .map_err(HaltReason::Error)
+                })?;
+            }
+            Stmt::Explain { expr, span } => {
+                return self.run_in_transaction(*span, |interpreter| {
+                    let mut planner = Planner::new(interpreter);
+                    let plan = planner.build(expr)?;
+                    Ok(RV::Str(Arc::new(plan.to_string().trim().to_string())))
+                });
+            }
+            Stmt::Set { name, value, span } => {
+                let value_span = value.get_span();
+                let value = self.visit_expr(value)?;
+                match name.name.as_str() {
+                    "allow_full_delete" => self.settings.allow_full_delete = value.as_bool(),
+                    "max_recursion" => match value {
+                        RV::Num(limit) if limit >= 0.0 && limit.fract() == 0.0 => {
+                            self.settings.max_recursion = limit as usize
+                        }
+                        value => {
+                            return Err(HaltReason::Error(
+                                InterpretError::InvalidSettingValue {
+                                    span: value_span,
+                                    name: name.name.clone(),
+                                    value: format!("{:?}", value),
+                                }
+                                .into(),
+                            ))
+                        }
+                    },
+                    _ => {
+                        return Err(HaltReason::Error(
+                            InterpretError::UnknownSetting {
+                                span: *span,
+                                name: name.name.clone(),
+                            }
+                            .into(),
+                        ));
+                    }
+                }
+            }
             Stmt::Return { expr, .. } => {
                 if expr.is_some() {
                     let ret = self.visit_expr(expr.as_ref().unwrap())?;
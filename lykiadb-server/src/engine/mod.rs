@@ -32,6 +32,10 @@ impl Runtime {
 
         out
     }
+
+    pub fn rollback_transaction(&mut self) -> bool {
+        self.interpreter.rollback_transaction()
+    }
 }
 
 pub mod test_helpers {
@@ -112,11 +116,11 @@ pub mod test_helpers {
             );
 
             let mut errors: Vec<ExecutionError> = vec![];
+            let mut last = RV::Undefined;
 
-            let result = self.runtime.interpret(&case_parts[0]);
-
-            if let Err(err) = result {
-                errors.push(err);
+            match self.runtime.interpret(&case_parts[0]) {
+                Ok(value) => last = value,
+                Err(err) => errors.push(err),
             }
 
             for part in &case_parts[1..] {
@@ -130,17 +134,13 @@ pub mod test_helpers {
                         part[3..].trim()
                     );
                 } else if part.starts_with('>') {
-                    let result = self.runtime.interpret(part[1..].trim());
-
-                    if let Err(err) = result {
-                        errors.push(err);
+                    match self.runtime.interpret(part[1..].trim()) {
+                        Ok(value) => last = value,
+                        Err(err) => errors.push(err),
                     }
                 } else if flags.get("run") == Some(&"plan") {
-                    // TODO(vck): Remove this
-                    self.out
-                        .write()
-                        .unwrap()
-                        .expect(vec![RV::Str(Arc::new(part.to_string()))]);
+                    // Plans are compared with what the last EXPLAIN returned
+                    assert_eq!(last, RV::Str(Arc::new(part.to_string())));
                 } else {
                     self.out.write().unwrap().expect_str(
                         part.to_string()
@@ -0,0 +1,138 @@
+use crate::{
+    engine::interpreter::{HaltReason, InterpretError, Interpreter},
+    util::alloc_shared,
+    value::{
+        callable::{Callable, CallableKind, Function},
+        RV,
+    },
+};
+
+fn unexpected(function: &str, message: String) -> HaltReason {
+    HaltReason::Error(
+        InterpretError::Other {
+            message: format!("{}: {}", function, message),
+        }
+        .into(),
+    )
+}
+
+/// Defines an aggregate from an object of its `init`, `step` and `finalize`
+/// functions.
+pub fn nt_aggregate_define(_interpreter: &mut Interpreter, args: &[RV]) -> Result<RV, HaltReason> {
+    let RV::Object(parts) = &args[0] else {
+        return Err(unexpected(
+            "aggregate.define",
+            format!("Unexpected argument '{:?}'", args[0]),
+        ));
+    };
+    let parts = parts.read().unwrap();
+    let part = |name: &str, takes: fn(usize) -> bool| match parts.get(name) {
+        Some(RV::Callable(callable)) if callable.arity.is_none_or(takes) => Ok(callable.clone()),
+        Some(RV::Callable(_)) => Err(unexpected(
+            "aggregate.define",
+            format!("Unexpected number of parameters of '{}'", name),
+        )),
+        _ => Err(unexpected(
+            "aggregate.define",
+            format!("Missing function '{}'", name),
+        )),
+    };
+    Ok(RV::Callable(Callable::aggregator(
+        part("init", |arity| arity == 0)?,
+        part("step", |arity| arity >= 1)?,
+        part("finalize", |arity| arity == 1)?,
+    )))
+}
+
+fn nt_median_init(_interpreter: &mut Interpreter, _args: &[RV]) -> Result<RV, HaltReason> {
+    Ok(RV::Array(alloc_shared(vec![])))
+}
+
+fn nt_median_step(_interpreter: &mut Interpreter, args: &[RV]) -> Result<RV, HaltReason> {
+    if let (RV::Array(values), RV::Num(_)) = (&args[0], &args[1]) {
+        values.write().unwrap().push(args[1].clone());
+    }
+    Ok(args[0].clone())
+}
+
+fn nt_median_finalize(_interpreter: &mut Interpreter, args: &[RV]) -> Result<RV, HaltReason> {
+    let RV::Array(values) = &args[0] else {
+        return Ok(RV::Null);
+    };
+    let mut numbers: Vec<f64> = values
+        .read()
+        .unwrap()
+        .iter()
+        .filter_map(|value| value.as_number())
+        .collect();
+    if numbers.is_empty() {
+        return Ok(RV::Null);
+    }
+    numbers.sort_by(|a, b| a.total_cmp(b));
+    let middle = numbers.len() / 2;
+    Ok(RV::Num(if numbers.len().is_multiple_of(2) {
+        (numbers[middle - 1] + numbers[middle]) / 2.0
+    } else {
+        numbers[middle]
+    }))
+}
+
+/// The median of the numbers of a group, ignoring values of other types.
+pub fn median() -> Callable {
+    Callable::aggregator(
+        Callable::new(
+            Some(0),
+            CallableKind::Generic,
+            Function::Lambda {
+                function: nt_median_init,
+            },
+        ),
+        Callable::new(
+            Some(2),
+            CallableKind::Generic,
+            Function::Lambda {
+                function: nt_median_step,
+            },
+        ),
+        Callable::new(
+            Some(1),
+            CallableKind::Generic,
+            Function::Lambda {
+                function: nt_median_finalize,
+            },
+        ),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::interpreter::Output;
+
+    fn setup() -> Interpreter {
+        Interpreter::new(Some(alloc_shared(Output::new())), true)
+    }
+
+    #[test]
+    fn test_median() {
+        let mut interpreter = setup();
+        let callable = median();
+        let aggregator = callable.as_aggregator().unwrap();
+
+        let mut state = aggregator.init(&mut interpreter).unwrap();
+        for value in [RV::Num(3.0), RV::Null, RV::Num(1.0), RV::Num(2.0)] {
+            state = aggregator.step(&mut interpreter, state, &[value]).unwrap();
+        }
+        assert_eq!(
+            aggregator.finalize(&mut interpreter, state).unwrap(),
+            RV::Num(2.0)
+        );
+
+        let empty = aggregator.init(&mut interpreter).unwrap();
+        assert_eq!(
+            aggregator.finalize(&mut interpreter, empty).unwrap(),
+            RV::Null
+        );
+        assert_eq!(callable.arity, Some(1));
+    }
+}
@@ -0,0 +1,60 @@
+use crate::{
+    engine::interpreter::{HaltReason, InterpretError, Interpreter},
+    value::RV,
+};
+
+fn expect_array(function: &str, value: &RV) -> Result<Vec<RV>, HaltReason> {
+    match value {
+        RV::Array(values) => Ok(values.read().unwrap().clone()),
+        _ => Err(HaltReason::Error(
+            InterpretError::Other {
+                message: format!("{}: Unexpected argument '{:?}'", function, value),
+            }
+            .into(),
+        )),
+    }
+}
+
+pub fn nt_arr_len(_interpreter: &mut Interpreter, args: &[RV]) -> Result<RV, HaltReason> {
+    Ok(RV::Num(expect_array("arr.len", &args[0])?.len() as f64))
+}
+
+pub fn nt_arr_get(_interpreter: &mut Interpreter, args: &[RV]) -> Result<RV, HaltReason> {
+    let values = expect_array("arr.get", &args[0])?;
+    Ok(args[1]
+        .as_number()
+        .filter(|index| *index >= 0.0)
+        .and_then(|index| values.get(index as usize).cloned())
+        .unwrap_or(RV::Undefined))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::interpreter::Output;
+    use crate::util::alloc_shared;
+
+    fn setup() -> Interpreter {
+        Interpreter::new(Some(alloc_shared(Output::new())), true)
+    }
+
+    #[test]
+    fn test_arr_len_and_get() {
+        let mut interpreter = setup();
+        let array = RV::Array(alloc_shared(vec![RV::Num(1.0), RV::Num(2.0)]));
+
+        assert_eq!(
+            nt_arr_len(&mut interpreter, std::slice::from_ref(&array)).unwrap(),
+            RV::Num(2.0)
+        );
+        assert_eq!(
+            nt_arr_get(&mut interpreter, &[array.clone(), RV::Num(1.0)]).unwrap(),
+            RV::Num(2.0)
+        );
+        assert_eq!(
+            nt_arr_get(&mut interpreter, &[array, RV::Num(2.0)]).unwrap(),
+            RV::Undefined
+        );
+        assert!(nt_arr_len(&mut interpreter, &[RV::Num(1.0)]).is_err());
+    }
+}
@@ -9,6 +9,8 @@ use crate::{
 };
 
 use self::{
+    aggregate::{median, nt_aggregate_define},
+    arr::{nt_arr_get, nt_arr_len},
     fib::nt_fib,
     json::{nt_json_decode, nt_json_encode},
     out::nt_print,
@@ -17,6 +19,8 @@ use self::{
 
 use super::interpreter::Output;
 
+pub mod aggregate;
+pub mod arr;
 pub mod fib;
 pub mod json;
 pub mod out;
@@ -29,36 +33,47 @@ pub fn stdlib(out: Option<Shared<Output>>) -> FxHashMap<String, RV> {
     let mut json_namespace = FxHashMap::default();
     let mut time_namespace = FxHashMap::default();
     let mut io_namespace = FxHashMap::default();
+    let mut arr_namespace = FxHashMap::default();
+    let mut aggregate_namespace = FxHashMap::default();
 
     benchmark_namespace.insert(
         "fib".to_owned(),
-        RV::Callable(Callable::new(
-            Some(1),
-            CallableKind::Generic,
-            Function::Lambda { function: nt_fib },
-        )),
+        RV::Callable(
+            Callable::new(
+                Some(1),
+                CallableKind::Generic,
+                Function::Lambda { function: nt_fib },
+            )
+            .pure(),
+        ),
     );
 
     json_namespace.insert(
         "stringify".to_owned(),
-        RV::Callable(Callable::new(
-            Some(1),
-            CallableKind::Generic,
-            Function::Lambda {
-                function: nt_json_encode,
-            },
-        )),
+        RV::Callable(
+            Callable::new(
+                Some(1),
+                CallableKind::Generic,
+                Function::Lambda {
+                    function: nt_json_encode,
+                },
+            )
+            .pure(),
+        ),
     );
 
     json_namespace.insert(
         "parse".to_owned(),
-        RV::Callable(Callable::new(
-            Some(1),
-            CallableKind::Generic,
-            Function::Lambda {
-                function: nt_json_decode,
-            },
-        )),
+        RV::Callable(
+            Callable::new(
+                Some(1),
+                CallableKind::Generic,
+                Function::Lambda {
+                    function: nt_json_decode,
+                },
+            )
+            .pure(),
+        ),
     );
 
     time_namespace.insert(
@@ -79,6 +94,47 @@ pub fn stdlib(out: Option<Shared<Output>>) -> FxHashMap<String, RV> {
         )),
     );
 
+    arr_namespace.insert(
+        "len".to_owned(),
+        RV::Callable(
+            Callable::new(
+                Some(1),
+                CallableKind::Generic,
+                Function::Lambda {
+                    function: nt_arr_len,
+                },
+            )
+            .pure(),
+        ),
+    );
+
+    arr_namespace.insert(
+        "get".to_owned(),
+        RV::Callable(
+            Callable::new(
+                Some(2),
+                CallableKind::Generic,
+                Function::Lambda {
+                    function: nt_arr_get,
+                },
+            )
+            .pure(),
+        ),
+    );
+
+    aggregate_namespace.insert(
+        "define".to_owned(),
+        RV::Callable(Callable::new(
+            Some(1),
+            CallableKind::Generic,
+            Function::Lambda {
+                function: nt_aggregate_define,
+            },
+        )),
+    );
+
+    aggregate_namespace.insert("median".to_owned(), RV::Callable(median()));
+
     if out.is_some() {
         let mut test_namespace = FxHashMap::default();
 
@@ -104,6 +160,11 @@ pub fn stdlib(out: Option<Shared<Output>>) -> FxHashMap<String, RV> {
     std.insert("json".to_owned(), RV::Object(alloc_shared(json_namespace)));
     std.insert("time".to_owned(), RV::Object(alloc_shared(time_namespace)));
     std.insert("io".to_owned(), RV::Object(alloc_shared(io_namespace)));
+    std.insert("arr".to_owned(), RV::Object(alloc_shared(arr_namespace)));
+    std.insert(
+        "aggregate".to_owned(),
+        RV::Object(alloc_shared(aggregate_namespace)),
+    );
 
     std
 }
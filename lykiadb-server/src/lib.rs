@@ -1,6 +1,8 @@
+pub mod catalog;
 pub mod comm;
 pub mod engine;
 pub mod plan;
+pub mod store;
 pub mod util;
 pub mod value;
 
@@ -1,5 +1,18 @@
+use clap::Parser;
+use lykiadb_server::catalog;
 use lykiadb_server::comm::ServerSession;
+use lykiadb_server::store::disk::{DiskEngine, StorageConfig};
+use lykiadb_server::store::memory::MemoryEngine;
+use lykiadb_server::store::transaction::{
+    spawn_vacuum, SharedTransactionManager, TransactionManager,
+};
+use lykiadb_server::store::wal::{WalConfig, WalEngine};
+use lykiadb_server::store::SharedStorage;
+use lykiadb_server::util::alloc_shared;
 use std::io::Error;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
 use tokio::net::TcpListener;
 use tokio_stream::wrappers::TcpListenerStream;
 use tokio_stream::StreamExt as _;
@@ -19,13 +32,64 @@ $$$$$$$$\ \$$$$$$$ |$$ | \$$\ $$ |\$$$$$$$ |$$$$$$$  |$$$$$$$  |
            \______/
 ";
 
+#[derive(Parser, Debug)]
+#[command(author, version, about, long_about = None)]
+struct Args {
+    /// Directory to persist collections in. Data is kept in memory if omitted
+    #[arg(long)]
+    data_dir: Option<PathBuf>,
+
+    /// Number of pages cached in memory for each open collection
+    #[arg(long, default_value_t = StorageConfig::default().page_cache_size)]
+    page_cache_size: usize,
+
+    /// Size of the write-ahead log, in bytes, that triggers a checkpoint
+    #[arg(long, default_value_t = WalConfig::default().checkpoint_size)]
+    checkpoint_size: u64,
+
+    /// Seconds between two runs of the background vacuum, which 0 turns off
+    #[arg(long, default_value_t = 60)]
+    vacuum_interval: u64,
+}
+
 struct Server {
     listener: Option<TcpListener>,
+    transactions: SharedTransactionManager,
+    vacuum_interval: Option<Duration>,
 }
 
 impl Server {
-    pub fn new() -> Result<Self, Error> {
-        Ok(Server { listener: None })
+    pub fn new(args: Args) -> Result<Self, Error> {
+        let storage: SharedStorage = match args.data_dir {
+            Some(data_dir) => {
+                let wal = WalConfig {
+                    path: data_dir.join("wal.log"),
+                    checkpoint_size: args.checkpoint_size,
+                };
+                let config = StorageConfig {
+                    data_dir,
+                    page_cache_size: args.page_cache_size,
+                };
+                info!("Storing data in {}", config.data_dir.display());
+                let disk =
+                    DiskEngine::open(config).map_err(|err| Error::other(format!("{:?}", err)))?;
+                alloc_shared(
+                    WalEngine::open(Box::new(disk), wal)
+                        .map_err(|err| Error::other(format!("{:?}", err)))?,
+                )
+            }
+            None => alloc_shared(MemoryEngine::new()),
+        };
+        let transactions = Arc::new(TransactionManager::new(storage));
+        catalog::bootstrap(&transactions).map_err(|err| Error::other(format!("{:?}", err)))?;
+        Ok(Server {
+            listener: None,
+            transactions,
+            vacuum_interval: match args.vacuum_interval {
+                0 => None,
+                secs => Some(Duration::from_secs(secs)),
+            },
+        })
     }
 
     pub async fn listen(mut self, addr: &str) -> Result<Self, Error> {
@@ -38,11 +102,18 @@ impl Server {
 
     pub async fn serve(self) -> Result<(), Error> {
         if let Some(listener) = self.listener {
+            match self.vacuum_interval {
+                Some(every) => {
+                    spawn_vacuum(self.transactions.clone(), every);
+                }
+                None => info!("Background vacuum is turned off"),
+            }
             let mut stream = TcpListenerStream::new(listener);
             while let Some(socket) = stream.try_next().await? {
                 let peer = socket.peer_addr()?;
+                let transactions = self.transactions.clone();
                 tokio::spawn(async move {
-                    let mut session = ServerSession::new(socket);
+                    let mut session = ServerSession::new(socket, transactions);
                     info!("Client {} connected", peer);
                     session.handle().await;
                     info!("Client {} disconnected", peer);
@@ -55,5 +126,10 @@ impl Server {
 #[tokio::main]
 async fn main() -> Result<(), Error> {
     tracing_subscriber::fmt::init();
-    Server::new()?.listen("0.0.0.0:19191").await?.serve().await
+    let args = Args::parse();
+    Server::new(args)?
+        .listen("0.0.0.0:19191")
+        .await?
+        .serve()
+        .await
 }
@@ -0,0 +1,294 @@
+use lykiadb_lang::ast::{
+    expr::{Expr, Operation},
+    sql::SqlProjection,
+};
+
+use super::{IntermediateExpr, Node, SemiJoinType, Subquery, SubqueryKind};
+
+type Keys = Vec<(IntermediateExpr, IntermediateExpr)>;
+
+/// Rewrites the correlated EXISTS and IN subqueries in the conjuncts of a
+/// filter into semi and anti joins of its source, so that each of them is
+/// read once rather than once per row. Only subqueries that reference the
+/// enclosing rows through equalities in their WHERE clause are rewritten,
+/// and the filter keeps the rest of its predicate.
+pub fn decorrelate(node: Node) -> Node {
+    let Node::Filter {
+        mut source,
+        predicate: IntermediateExpr::Expr { expr: predicate },
+        mut subqueries,
+    } = node
+    else {
+        return node;
+    };
+
+    let mut rewritten: Vec<usize> = vec![];
+    for conjunct in conjuncts(&predicate) {
+        let Some((id, join_type, operand)) = subquery_test(conjunct) else {
+            continue;
+        };
+        let Some(position) = subqueries.iter().position(|subquery| subquery.id == id) else {
+            continue;
+        };
+        let Some((right, keys)) = semi_join_side(&subqueries[position], operand) else {
+            continue;
+        };
+        subqueries.remove(position);
+        rewritten.push(id);
+        source = Box::new(Node::SemiJoin {
+            left: source,
+            join_type,
+            right: Box::new(right),
+            keys,
+        });
+    }
+
+    let rest = prune(&predicate, &|conjunct: &Expr| {
+        subquery_test(conjunct).is_some_and(|(id, _, _)| rewritten.contains(&id))
+    });
+    match rest {
+        Some(expr) => Node::Filter {
+            source,
+            predicate: IntermediateExpr::Expr { expr },
+            subqueries,
+        },
+        None => *source,
+    }
+}
+
+/// The subquery a conjunct tests for rows or for a value, along with the
+/// join that does the same and the operand of an IN.
+fn subquery_test(conjunct: &Expr) -> Option<(usize, SemiJoinType, Option<&Expr>)> {
+    match conjunct {
+        Expr::Grouping { expr, .. } => subquery_test(expr),
+        Expr::Unary {
+            operation, expr, ..
+        } => {
+            let join_type = match operation {
+                Operation::Exists => SemiJoinType::Semi,
+                Operation::NotExists => SemiJoinType::Anti,
+                _ => return None,
+            };
+            Some((select_id(expr)?, join_type, None))
+        }
+        Expr::Binary {
+            left,
+            operation,
+            right,
+            ..
+        } => {
+            let join_type = match operation {
+                Operation::In => SemiJoinType::Semi,
+                Operation::NotIn => SemiJoinType::Anti,
+                _ => return None,
+            };
+            if has_select(left) {
+                return None;
+            }
+            Some((select_id(right)?, join_type, Some(left.as_ref())))
+        }
+        _ => None,
+    }
+}
+
+fn select_id(expr: &Expr) -> Option<usize> {
+    match expr {
+        Expr::Select { id, .. } => Some(*id),
+        Expr::Grouping { expr, .. } => select_id(expr),
+        _ => None,
+    }
+}
+
+/// The right side of the join a subquery turns into, and its keys. The
+/// subquery has to filter a single source, and every conjunct of the filter
+/// that reads the enclosing rows has to equate them to its own fields.
+fn semi_join_side(subquery: &Subquery, operand: Option<&Expr>) -> Option<(Node, Keys)> {
+    let outer = &subquery.outer;
+    if outer.is_empty() {
+        return None;
+    }
+
+    let (fields, filter) = match &subquery.node {
+        Node::Projection {
+            source,
+            fields,
+            subqueries,
+        } if subqueries.is_empty() => (Some(fields), source.as_ref()),
+        node => (None, node),
+    };
+    let Node::Filter {
+        source,
+        predicate: IntermediateExpr::Expr { expr: predicate },
+        subqueries,
+    } = filter
+    else {
+        return None;
+    };
+    let single_source = match source.as_ref() {
+        Node::Scan { .. } | Node::IndexScan { .. } | Node::CteScan { .. } => true,
+        Node::EvalScan { source, .. } => !has_field_path(&source.expr),
+        _ => false,
+    };
+    if !subqueries.is_empty() || !single_source {
+        return None;
+    }
+
+    let mut keys: Keys = vec![];
+    for conjunct in conjuncts(predicate) {
+        if reads_any(conjunct, outer) {
+            let (left, right) = correlation(conjunct, outer)?;
+            keys.push((
+                IntermediateExpr::Expr { expr: left.clone() },
+                IntermediateExpr::Expr {
+                    expr: right.clone(),
+                },
+            ));
+        }
+    }
+    if let Some(operand) = operand {
+        if subquery.kind != SubqueryKind::In {
+            return None;
+        }
+        let [SqlProjection::Expr { expr, .. }] = fields?.as_slice() else {
+            return None;
+        };
+        if reads_any(expr, outer) || has_select(expr) {
+            return None;
+        }
+        keys.push((
+            IntermediateExpr::Expr {
+                expr: operand.clone(),
+            },
+            IntermediateExpr::Expr {
+                expr: expr.as_ref().clone(),
+            },
+        ));
+    }
+
+    let right = match prune(predicate, &|conjunct: &Expr| reads_any(conjunct, outer)) {
+        Some(expr) => Node::Filter {
+            source: source.clone(),
+            predicate: IntermediateExpr::Expr { expr },
+            subqueries: vec![],
+        },
+        None => source.as_ref().clone(),
+    };
+    Some((right, keys))
+}
+
+/// Sides of an equality between an expression of the enclosing rows and
+/// one of the rows of the subquery, in that order.
+fn correlation<'e>(conjunct: &'e Expr, outer: &[Expr]) -> Option<(&'e Expr, &'e Expr)> {
+    match conjunct {
+        Expr::Grouping { expr, .. } => correlation(expr, outer),
+        Expr::Binary {
+            left,
+            operation: Operation::IsEqual,
+            right,
+            ..
+        } => {
+            let enclosing = |expr: &Expr| {
+                has_field_path(expr) && !has_select(expr) && !reads_other(expr, outer)
+            };
+            let own = |expr: &Expr| !has_select(expr) && !reads_any(expr, outer);
+            if enclosing(left) && own(right) {
+                Some((left, right))
+            } else if enclosing(right) && own(left) {
+                Some((right, left))
+            } else {
+                None
+            }
+        }
+        _ => None,
+    }
+}
+
+/// The conjuncts of an AND chain, or the expression itself.
+pub(super) fn conjuncts(expr: &Expr) -> Vec<&Expr> {
+    match expr {
+        Expr::Grouping { expr, .. } => conjuncts(expr),
+        Expr::Logical {
+            left,
+            operation: Operation::And,
+            right,
+            ..
+        } => {
+            let mut all = conjuncts(left);
+            all.extend(conjuncts(right));
+            all
+        }
+        other => vec![other],
+    }
+}
+
+/// The predicate without the conjuncts `remove` picks, or nothing if they
+/// were all removed.
+fn prune(expr: &Expr, remove: &impl Fn(&Expr) -> bool) -> Option<Expr> {
+    match expr {
+        Expr::Grouping { expr: inner, .. } if is_conjunction(inner) => prune(inner, remove),
+        Expr::Logical {
+            left,
+            operation: Operation::And,
+            right,
+            span,
+            id,
+        } => match (prune(left, remove), prune(right, remove)) {
+            (Some(left), Some(right)) => Some(Expr::Logical {
+                left: Box::new(left),
+                operation: Operation::And,
+                right: Box::new(right),
+                span: *span,
+                id: *id,
+            }),
+            (Some(expr), None) | (None, Some(expr)) => Some(expr),
+            (None, None) => None,
+        },
+        other if remove(other) => None,
+        other => Some(other.clone()),
+    }
+}
+
+fn is_conjunction(expr: &Expr) -> bool {
+    match expr {
+        Expr::Grouping { expr, .. } => is_conjunction(expr),
+        Expr::Logical {
+            operation: Operation::And,
+            ..
+        } => true,
+        _ => false,
+    }
+}
+
+/// Whether the expression reads any of the paths.
+fn reads_any(expr: &Expr, paths: &[Expr]) -> bool {
+    any(expr, &|e| {
+        matches!(e, Expr::FieldPath { .. }) && paths.contains(e)
+    })
+}
+
+/// Whether the expression reads a path other than the ones given.
+fn reads_other(expr: &Expr, paths: &[Expr]) -> bool {
+    any(expr, &|e| {
+        matches!(e, Expr::FieldPath { .. }) && !paths.contains(e)
+    })
+}
+
+fn has_field_path(expr: &Expr) -> bool {
+    any(expr, &|e| matches!(e, Expr::FieldPath { .. }))
+}
+
+pub(super) fn has_select(expr: &Expr) -> bool {
+    any(expr, &|e| matches!(e, Expr::Select { .. }))
+}
+
+fn any(expr: &Expr, test: &impl Fn(&Expr) -> bool) -> bool {
+    let mut found = false;
+    expr.walk::<(), ()>(&mut |e: &Expr| {
+        if test(e) {
+            found = true;
+            return None;
+        }
+        Some(Ok(()))
+    });
+    found
+}
@@ -0,0 +1,2033 @@
+use std::cmp::Ordering;
+use std::collections::BTreeSet;
+use std::ops::Bound;
+use std::sync::Arc;
+use std::vec;
+
+use lykiadb_lang::ast::{
+    expr::Expr,
+    sql::{
+        SqlAssignment, SqlCollectionIdentifier, SqlCompoundOperator, SqlConflictAction,
+        SqlExpressionSource, SqlFieldPath, SqlFrameUnit, SqlJoinType, SqlOrdering, SqlProjection,
+    },
+    visitor::VisitorMut,
+    Span, Spanned,
+};
+use rustc_hash::{FxHashMap, FxHashSet};
+
+use crate::{
+    catalog::{
+        self,
+        constraint::{
+            delete_document, find_conflict, insert_document, set_value_at, update_document,
+            with_defaults, ConstraintError, ID_FIELD,
+        },
+        CollectionEntry,
+    },
+    engine::interpreter::{HaltReason, InterpretError, Interpreter},
+    store::{
+        index::{IndexKey, IndexValue},
+        transaction::DocumentCursor,
+        DocumentId,
+    },
+    util::alloc_shared,
+    value::{callable::Aggregator, RV},
+};
+
+use super::{
+    name_of, Aggregate, CommonTable, Frame, FrameBound, IntermediateExpr, Node, OnConflict, Plan,
+    SemiJoinType, SubqueryKind, Window, WindowFunction,
+};
+
+/// Alias of the incoming document in the assignments of ON CONFLICT DO
+/// UPDATE.
+const EXCLUDED: &str = "excluded";
+
+/// A row flowing through the executor. It holds the document of every source
+/// in scope by its alias and, once projected, the projected object. Rows read
+/// from a single collection also know the id of their document, and rows
+/// carry the results of the aggregates of their group and of window
+/// functions by name.
+#[derive(Clone, Debug)]
+pub struct Row {
+    sources: Vec<(String, RV)>,
+    projected: Option<RV>,
+    aggregates: Vec<(String, RV)>,
+    id: Option<DocumentId>,
+}
+
+impl Row {
+    pub fn new(alias: &str, document: RV) -> Row {
+        Row {
+            sources: vec![(alias.to_string(), document)],
+            projected: None,
+            aggregates: vec![],
+            id: None,
+        }
+    }
+
+    fn stored(alias: &str, id: DocumentId, document: RV) -> Row {
+        Row {
+            id: Some(id),
+            ..Row::new(alias, document)
+        }
+    }
+
+    /// The row with one more source in scope.
+    fn with(mut self, alias: &str, document: RV) -> Row {
+        self.sources.push((alias.to_string(), document));
+        self
+    }
+
+    fn empty() -> Row {
+        Row {
+            sources: vec![],
+            projected: None,
+            aggregates: vec![],
+            id: None,
+        }
+    }
+
+    fn projected(value: RV) -> Row {
+        Row {
+            sources: vec![],
+            projected: Some(value),
+            aggregates: vec![],
+            id: None,
+        }
+    }
+
+    fn join(&self, other: &Row) -> Row {
+        Row {
+            sources: self
+                .sources
+                .iter()
+                .chain(other.sources.iter())
+                .cloned()
+                .collect(),
+            projected: None,
+            aggregates: vec![],
+            id: None,
+        }
+    }
+
+    /// Resolves a field path against the row. The head is looked up in the
+    /// projected object first, then among the aggregates and the aliases of
+    /// the sources, and last among the fields of the documents.
+    pub fn resolve(&self, head: &str, tail: &[&str]) -> RV {
+        self.lookup(head, tail).unwrap_or(RV::Undefined)
+    }
+
+    /// Resolves a field path against the row, if the row has its head.
+    pub fn lookup(&self, head: &str, tail: &[&str]) -> Option<RV> {
+        let mut value = self
+            .projected
+            .iter()
+            .find_map(|projected| field_of(projected, head))
+            .or_else(|| {
+                self.aggregates
+                    .iter()
+                    .find(|(name, _)| name == head)
+                    .map(|(_, value)| value.clone())
+            })
+            .or_else(|| {
+                self.sources
+                    .iter()
+                    .find(|(alias, _)| alias == head)
+                    .map(|(_, document)| document.clone())
+            })
+            .or_else(|| {
+                self.sources
+                    .iter()
+                    .find_map(|(_, document)| field_of(document, head))
+            })?;
+
+        for field in tail {
+            value = field_of(&value, field).unwrap_or(RV::Undefined);
+        }
+        Some(value)
+    }
+
+    /// The value the row stands for in a result set.
+    pub fn output(&self) -> RV {
+        if let Some(projected) = &self.projected {
+            return projected.clone();
+        }
+        match self.sources.as_slice() {
+            [(_, document)] => document.clone(),
+            sources => RV::Object(alloc_shared(sources.iter().cloned().collect())),
+        }
+    }
+}
+
+fn field_of(value: &RV, field: &str) -> Option<RV> {
+    match value {
+        RV::Object(map) => map.read().unwrap().get(field).cloned(),
+        _ => None,
+    }
+}
+
+/// An iterator over the rows of a plan node. Rows are pulled from node to
+/// node one at a time, and scans read documents from storage as they are
+/// pulled, so a query that stops early, like one with a LIMIT, leaves the
+/// rest of the collection unread. Sorts, joins, aggregates, windows and
+/// compound queries read their sources whole on the first pull.
+trait Cursor {
+    fn next(&mut self, interpreter: &mut Interpreter) -> Result<Option<Row>, HaltReason>;
+}
+
+type BoxedCursor = Box<dyn Cursor>;
+
+/// Runs plans built by the planner, reading rows from the root node until
+/// it is exhausted.
+pub struct Executor<'a> {
+    interpreter: &'a mut Interpreter,
+}
+
+impl<'a> Executor<'a> {
+    pub fn new(interpreter: &'a mut Interpreter) -> Executor<'a> {
+        Executor { interpreter }
+    }
+
+    pub fn execute(&mut self, plan: &Plan) -> Result<RV, HaltReason> {
+        match plan {
+            Plan::Select(node) => {
+                let rows = collect(self.interpreter, &mut open(node)?)?;
+                Ok(RV::Array(alloc_shared(
+                    rows.iter().map(|row| row.output()).collect(),
+                )))
+            }
+            Plan::Insert {
+                collection,
+                source,
+                on_conflict,
+                returning,
+            } => self.insert(collection, source, on_conflict, returning),
+            Plan::Update {
+                collection,
+                assignments,
+                source,
+                returning,
+            } => self.update(collection, assignments, source, returning),
+            Plan::Delete {
+                collection,
+                source,
+                returning,
+                full,
+            } => self.delete(collection, source, returning, *full),
+        }
+    }
+
+    /// Inserts the rows of the source as documents. The source is read whole
+    /// first, so a query never sees the documents it inserts. A document
+    /// conflicting with a stored one is skipped or turned into an update of
+    /// the stored one, as ON CONFLICT says. Reports the number of documents
+    /// inserted along with their ids, or the RETURNING projection of each
+    /// inserted or updated document.
+    fn insert(
+        &mut self,
+        collection: &SqlCollectionIdentifier,
+        source: &Node,
+        on_conflict: &Option<OnConflict>,
+        returning: &Option<Vec<SqlProjection>>,
+    ) -> Result<RV, HaltReason> {
+        let documents: Vec<RV> = collect(self.interpreter, &mut open(source)?)?
+            .iter()
+            .map(|row| row.output())
+            .collect();
+
+        let entry =
+            catalog::resolve_collection(self.interpreter.transaction().unwrap(), collection)
+                .map_err(HaltReason::Error)?;
+        let span = catalog::span_of(collection);
+        let alias = alias_of(collection);
+        let conflict_index = on_conflict.as_ref().and_then(|on_conflict| {
+            entry
+                .indexes
+                .iter()
+                .find(|index| index.name == on_conflict.index)
+                .cloned()
+        });
+
+        let mut ids = vec![];
+        let mut updated = 0;
+        let mut returned = vec![];
+        for document in documents.iter() {
+            if let (Some(on_conflict), Some(index)) = (on_conflict, &conflict_index) {
+                let incoming = with_defaults(self.interpreter, &entry, document)?;
+                let conflict = find_conflict(
+                    self.interpreter.transaction().unwrap(),
+                    &entry,
+                    index,
+                    &incoming,
+                )
+                .map_err(HaltReason::Error)?;
+                if let Some((id, existing)) = conflict {
+                    let SqlConflictAction::Update { assignments } = &on_conflict.action else {
+                        continue;
+                    };
+                    let row = Row::stored(alias, id, existing.clone()).with(EXCLUDED, incoming);
+                    let document = self.assign(&entry, alias, &row, &existing, assignments)?;
+                    update_document(
+                        self.interpreter.transaction().unwrap(),
+                        &entry,
+                        id,
+                        &document,
+                        span,
+                    )
+                    .map_err(HaltReason::Error)?;
+                    updated += 1;
+                    let row = Row::stored(alias, id, document);
+                    project_returning(self.interpreter, returning, row, &mut returned)?;
+                    continue;
+                }
+            }
+
+            let id = insert_document(self.interpreter, &entry, document, span)?;
+            ids.push(RV::Num(id as f64));
+            if returning.is_some() {
+                // Defaults and the id are only set on the stored copy
+                let stored = self
+                    .interpreter
+                    .transaction()
+                    .unwrap()
+                    .get(&entry.storage_name(), id)
+                    .map_err(|err| HaltReason::Error(err.into()))?
+                    .unwrap_or(RV::Undefined);
+                let row = Row::stored(alias, id, stored);
+                project_returning(self.interpreter, returning, row, &mut returned)?;
+            }
+        }
+        if returning.is_some() {
+            return Ok(RV::Array(alloc_shared(returned)));
+        }
+
+        let mut result = FxHashMap::default();
+        result.insert("inserted".to_string(), RV::Num(ids.len() as f64));
+        result.insert("ids".to_string(), RV::Array(alloc_shared(ids)));
+        if on_conflict.is_some() {
+            result.insert("updated".to_string(), RV::Num(updated as f64));
+        }
+        Ok(RV::Object(alloc_shared(result)))
+    }
+
+    /// Rewrites the documents the source yields. Every assignment is
+    /// evaluated against the document as it was before the update. Reports
+    /// the number of documents updated, or the RETURNING projection of each
+    /// updated document.
+    fn update(
+        &mut self,
+        collection: &SqlCollectionIdentifier,
+        assignments: &[SqlAssignment],
+        source: &Node,
+        returning: &Option<Vec<SqlProjection>>,
+    ) -> Result<RV, HaltReason> {
+        let rows = collect(self.interpreter, &mut open(source)?)?;
+
+        let entry =
+            catalog::resolve_collection(self.interpreter.transaction().unwrap(), collection)
+                .map_err(HaltReason::Error)?;
+        let span = catalog::span_of(collection);
+        let alias = alias_of(collection);
+
+        let mut updated = 0;
+        let mut returned = vec![];
+        for row in rows.iter() {
+            let Some(id) = row.id else {
+                continue;
+            };
+            let document = self.assign(&entry, alias, row, &row.output(), assignments)?;
+            update_document(
+                self.interpreter.transaction().unwrap(),
+                &entry,
+                id,
+                &document,
+                span,
+            )
+            .map_err(HaltReason::Error)?;
+            updated += 1;
+            let row = Row::stored(alias, id, document);
+            project_returning(self.interpreter, returning, row, &mut returned)?;
+        }
+        if returning.is_some() {
+            return Ok(RV::Array(alloc_shared(returned)));
+        }
+
+        let mut result = FxHashMap::default();
+        result.insert("updated".to_string(), RV::Num(updated as f64));
+        Ok(RV::Object(alloc_shared(result)))
+    }
+
+    /// A copy of the document with the assignments applied, each evaluated
+    /// against the row. The id of a document cannot be assigned.
+    fn assign(
+        &mut self,
+        entry: &CollectionEntry,
+        alias: &str,
+        row: &Row,
+        document: &RV,
+        assignments: &[SqlAssignment],
+    ) -> Result<RV, HaltReason> {
+        let document = document.deep_clone();
+        for assignment in assignments.iter() {
+            let path = path_of(alias, &assignment.path);
+            if path == ID_FIELD {
+                return Err(HaltReason::Error(
+                    ConstraintError::InvalidId {
+                        collection: entry.storage_name(),
+                        span: assignment.path.get_span(),
+                    }
+                    .into(),
+                ));
+            }
+            let value = self.interpreter.with_row(row.clone(), |interpreter| {
+                interpreter.visit_expr(&assignment.expr)
+            })?;
+            set_value_at(&document, &path, value);
+        }
+        Ok(document)
+    }
+
+    /// Deletes the documents the source yields, applying the ON DELETE
+    /// action of every reference to them. A statement without a WHERE clause
+    /// empties the collection, which the session has to allow explicitly.
+    /// Reports the number of documents deleted from the collection, or the
+    /// RETURNING projection of each deleted document.
+    fn delete(
+        &mut self,
+        collection: &SqlCollectionIdentifier,
+        source: &Node,
+        returning: &Option<Vec<SqlProjection>>,
+        full: bool,
+    ) -> Result<RV, HaltReason> {
+        let span = catalog::span_of(collection);
+        if full && !self.interpreter.settings().allow_full_delete {
+            return Err(HaltReason::Error(
+                InterpretError::FullDeleteNotAllowed {
+                    span,
+                    collection: collection.name.name.clone(),
+                }
+                .into(),
+            ));
+        }
+
+        let rows = collect(self.interpreter, &mut open(source)?)?;
+
+        let entry =
+            catalog::resolve_collection(self.interpreter.transaction().unwrap(), collection)
+                .map_err(HaltReason::Error)?;
+
+        let mut deleted = 0;
+        let mut returned = vec![];
+        for row in rows.into_iter() {
+            let Some(id) = row.id else {
+                continue;
+            };
+            // Documents a cascade has already removed are skipped here, yet
+            // they still count, since the statement matched them
+            delete_document(self.interpreter.transaction().unwrap(), &entry, id, span)
+                .map_err(HaltReason::Error)?;
+            deleted += 1;
+            project_returning(self.interpreter, returning, row, &mut returned)?;
+        }
+        if returning.is_some() {
+            return Ok(RV::Array(alloc_shared(returned)));
+        }
+
+        let mut result = FxHashMap::default();
+        result.insert("deleted".to_string(), RV::Num(deleted as f64));
+        Ok(RV::Object(alloc_shared(result)))
+    }
+}
+
+/// Adds the RETURNING projection of a written document to `returned`,
+/// when the write asks for one.
+fn project_returning(
+    interpreter: &mut Interpreter,
+    returning: &Option<Vec<SqlProjection>>,
+    row: Row,
+    returned: &mut Vec<RV>,
+) -> Result<(), HaltReason> {
+    if let Some(fields) = returning {
+        returned.push(Projection::project(interpreter, row, fields)?.output());
+    }
+    Ok(())
+}
+
+fn collect(
+    interpreter: &mut Interpreter,
+    cursor: &mut BoxedCursor,
+) -> Result<Vec<Row>, HaltReason> {
+    let mut rows = vec![];
+    while let Some(row) = cursor.next(interpreter)? {
+        rows.push(row);
+    }
+    Ok(rows)
+}
+
+fn unsupported(what: &str) -> HaltReason {
+    HaltReason::Error(
+        InterpretError::Other {
+            message: format!("{} is not supported yet", what),
+        }
+        .into(),
+    )
+}
+
+fn open(node: &Node) -> Result<BoxedCursor, HaltReason> {
+    Ok(match node {
+        Node::Nothing => Box::new(Nothing { done: false }),
+        Node::Empty { .. } => Box::new(Empty),
+        Node::Scan { source, filter } => Box::new(Scan {
+            source: source.clone(),
+            index: None,
+            filter: filter.clone(),
+            documents: None,
+        }),
+        Node::IndexScan {
+            source,
+            index,
+            lower,
+            upper,
+        } => Box::new(Scan {
+            source: source.clone(),
+            index: Some((index.clone(), lower.clone(), upper.clone())),
+            filter: None,
+            documents: None,
+        }),
+        Node::EvalScan { source, filter } => Box::new(EvalScan {
+            source: source.clone(),
+            filter: filter.clone(),
+            rows: None,
+        }),
+        Node::Filter {
+            source,
+            predicate,
+            subqueries,
+        } => Box::new(Filter {
+            source: open(source)?,
+            predicate: predicate.clone(),
+            subqueries: Subqueries::new(subqueries),
+        }),
+        Node::Projection {
+            source,
+            fields,
+            subqueries,
+        } => Box::new(Projection {
+            source: open(source)?,
+            fields: fields.clone(),
+            subqueries: Subqueries::new(subqueries),
+        }),
+        Node::Order { source, key } => Box::new(Order {
+            source: open(source)?,
+            key: key.clone(),
+            rows: None,
+        }),
+        Node::Distinct { source } => Box::new(Distinct {
+            source: open(source)?,
+            seen: FxHashSet::default(),
+        }),
+        Node::Limit { source, limit } => Box::new(Limit {
+            source: open(source)?,
+            remaining: *limit,
+        }),
+        Node::Offset { source, offset } => Box::new(Offset {
+            source: open(source)?,
+            skip: *offset,
+        }),
+        Node::Join {
+            left,
+            join_type,
+            right,
+            constraint,
+        } => Box::new(Join {
+            left: open(left)?,
+            right: open(right)?,
+            left_aliases: left.aliases(),
+            right_aliases: right.aliases(),
+            join_type: join_type.clone(),
+            constraint: constraint.clone(),
+            built: None,
+            rows: vec![].into_iter(),
+        }),
+        Node::SemiJoin {
+            left,
+            join_type,
+            right,
+            keys,
+        } => Box::new(SemiJoin {
+            left: open(left)?,
+            right: open(right)?,
+            join_type: *join_type,
+            left_keys: keys.iter().map(|(left, _)| left.clone()).collect(),
+            right_keys: keys.iter().map(|(_, right)| right.clone()).collect(),
+            matches: None,
+        }),
+        Node::Compound {
+            source,
+            operator,
+            right,
+        } => Box::new(Compound {
+            left: open(source)?,
+            right: open(right)?,
+            operator: operator.clone(),
+            rows: None,
+        }),
+        Node::Subquery { source, alias } => Box::new(Subquery {
+            source: open(source)?,
+            alias: alias
+                .as_ref()
+                .map(|alias| alias.name.clone())
+                .unwrap_or_default(),
+        }),
+        Node::With { tables, source } => Box::new(With {
+            tables: tables.clone(),
+            source: source.as_ref().clone(),
+            rows: None,
+        }),
+        Node::CteScan { name, alias } => Box::new(CteScan {
+            name: name.name.clone(),
+            alias: alias.name.clone(),
+            rows: None,
+        }),
+        Node::Values { rows } => Box::new(Values {
+            rows: rows.clone().into_iter(),
+        }),
+        Node::Window { source, windows } => Box::new(Windowing {
+            source: open(source)?,
+            windows: windows.clone(),
+            rows: None,
+        }),
+        Node::Aggregate {
+            source,
+            group_by,
+            aggregates,
+        } => Box::new(Aggregation {
+            source: open(source)?,
+            group_by: group_by.clone(),
+            aggregates: aggregates.clone(),
+            rows: None,
+        }),
+    })
+}
+
+fn alias_of(source: &SqlCollectionIdentifier) -> &str {
+    &source.alias.as_ref().unwrap_or(&source.name).name
+}
+
+/// Dotted path of an assigned field, relative to the documents of the
+/// collection. The path may start with the alias of the collection.
+fn path_of(alias: &str, path: &SqlFieldPath) -> String {
+    let mut fields: Vec<&str> = path.tail.iter().map(|ident| ident.name.as_str()).collect();
+    if path.head.name != alias || path.tail.is_empty() {
+        fields.insert(0, &path.head.name);
+    }
+    fields.join(".")
+}
+
+fn evaluate(interpreter: &mut Interpreter, expr: &IntermediateExpr) -> Result<RV, HaltReason> {
+    match expr {
+        IntermediateExpr::Constant(value) => Ok(value.clone()),
+        IntermediateExpr::Expr { expr } => interpreter.visit_expr(expr),
+    }
+}
+
+fn evaluate_in(
+    interpreter: &mut Interpreter,
+    row: &Row,
+    expr: &IntermediateExpr,
+) -> Result<RV, HaltReason> {
+    interpreter.with_row(row.clone(), |interpreter| evaluate(interpreter, expr))
+}
+
+/// Whether the row satisfies the filter a scan applies, if there is one.
+fn passes(
+    interpreter: &mut Interpreter,
+    row: &Row,
+    filter: &Option<IntermediateExpr>,
+) -> Result<bool, HaltReason> {
+    match filter {
+        Some(filter) => Ok(evaluate_in(interpreter, row, filter)?.as_bool()),
+        None => Ok(true),
+    }
+}
+
+struct Nothing {
+    done: bool,
+}
+
+impl Cursor for Nothing {
+    fn next(&mut self, _interpreter: &mut Interpreter) -> Result<Option<Row>, HaltReason> {
+        if self.done {
+            return Ok(None);
+        }
+        self.done = true;
+        Ok(Some(Row::empty()))
+    }
+}
+
+struct Empty;
+
+impl Cursor for Empty {
+    fn next(&mut self, _interpreter: &mut Interpreter) -> Result<Option<Row>, HaltReason> {
+        Ok(None)
+    }
+}
+
+type IndexLookup = (
+    String,
+    Bound<Vec<IntermediateExpr>>,
+    Bound<Vec<IntermediateExpr>>,
+);
+
+/// Reads a collection, either whole or through one of its indexes. The
+/// documents are pulled from storage as rows are asked for, and those the
+/// filter rejects are skipped.
+struct Scan {
+    source: SqlCollectionIdentifier,
+    index: Option<IndexLookup>,
+    filter: Option<IntermediateExpr>,
+    documents: Option<DocumentCursor>,
+}
+
+impl Scan {
+    fn open(&self, interpreter: &mut Interpreter) -> Result<DocumentCursor, HaltReason> {
+        let range = match &self.index {
+            Some((_, lower, upper)) => Some(key_range(interpreter, lower, upper)?),
+            None => None,
+        };
+
+        let transaction = interpreter.transaction().unwrap();
+        let entry =
+            catalog::resolve_collection(transaction, &self.source).map_err(HaltReason::Error)?;
+        let storage_name = entry.storage_name();
+
+        let everything = (Bound::Unbounded, Bound::Unbounded);
+        Ok(match (&self.index, range) {
+            (Some((name, _, _)), Some(range)) => {
+                let definition = entry
+                    .indexes
+                    .iter()
+                    .find(|index| index.name == *name)
+                    .ok_or_else(|| unsupported(&format!("Dropped index {}", name)))?;
+                // Otherwise the index misses documents the filter on top of
+                // the scan would keep
+                if transaction.index_compares_strictly(&storage_name, definition, &range) {
+                    transaction.index_cursor(&storage_name, definition, &range)
+                } else {
+                    transaction.cursor(&storage_name, everything)
+                }
+            }
+            _ => transaction.cursor(&storage_name, everything),
+        })
+    }
+}
+
+impl Cursor for Scan {
+    fn next(&mut self, interpreter: &mut Interpreter) -> Result<Option<Row>, HaltReason> {
+        if self.documents.is_none() {
+            self.documents = Some(self.open(interpreter)?);
+        }
+        let alias = alias_of(&self.source);
+        loop {
+            let transaction = interpreter.transaction().unwrap();
+            let Some((id, document)) = self
+                .documents
+                .as_mut()
+                .unwrap()
+                .next(transaction)
+                .map_err(|err| HaltReason::Error(err.into()))?
+            else {
+                return Ok(None);
+            };
+            let row = Row::stored(alias, id, document);
+            if passes(interpreter, &row, &self.filter)? {
+                return Ok(Some(row));
+            }
+        }
+    }
+}
+
+fn key_range(
+    interpreter: &mut Interpreter,
+    lower: &Bound<Vec<IntermediateExpr>>,
+    upper: &Bound<Vec<IntermediateExpr>>,
+) -> Result<(Bound<IndexKey>, Bound<IndexKey>), HaltReason> {
+    let mut key = |bound: &Bound<Vec<IntermediateExpr>>| -> Result<Bound<IndexKey>, HaltReason> {
+        Ok(match bound {
+            Bound::Included(exprs) => Bound::Included(key_of(interpreter, exprs)?),
+            Bound::Excluded(exprs) => Bound::Excluded(key_of(interpreter, exprs)?),
+            Bound::Unbounded => Bound::Unbounded,
+        })
+    };
+    let (lower, upper) = (key(lower)?, key(upper)?);
+
+    // BETWEEN accepts its bounds in either order
+    match (&lower, &upper) {
+        (Bound::Included(l) | Bound::Excluded(l), Bound::Included(u) | Bound::Excluded(u))
+            if l > u =>
+        {
+            Ok((upper, lower))
+        }
+        _ => Ok((lower, upper)),
+    }
+}
+
+fn key_of(
+    interpreter: &mut Interpreter,
+    exprs: &[IntermediateExpr],
+) -> Result<IndexKey, HaltReason> {
+    exprs
+        .iter()
+        .map(|expr| Ok(IndexValue::from(&evaluate(interpreter, expr)?)))
+        .collect()
+}
+
+/// Yields a row for every object of the array the source expression
+/// evaluates to, such as a script variable or the output of `json::parse`.
+/// Values other than objects have no fields to select, so an array holding
+/// any of them is rejected.
+struct EvalScan {
+    source: SqlExpressionSource,
+    filter: Option<IntermediateExpr>,
+    rows: Option<vec::IntoIter<Row>>,
+}
+
+impl EvalScan {
+    fn fetch(&self, interpreter: &mut Interpreter) -> Result<Vec<Row>, HaltReason> {
+        let invalid = |value: &RV| {
+            HaltReason::Error(
+                InterpretError::InvalidRowSource {
+                    span: self.source.expr.get_span(),
+                    value: format!("{:?}", value),
+                }
+                .into(),
+            )
+        };
+
+        let value = interpreter.visit_expr(&self.source.expr)?;
+        let RV::Array(values) = &value else {
+            return Err(invalid(&value));
+        };
+
+        let alias = &self.source.alias.name;
+        let values = values.read().unwrap();
+        values
+            .iter()
+            .map(|value| match value {
+                RV::Object(_) => Ok(Row::new(alias, value.clone())),
+                other => Err(invalid(other)),
+            })
+            .collect()
+    }
+}
+
+impl Cursor for EvalScan {
+    fn next(&mut self, interpreter: &mut Interpreter) -> Result<Option<Row>, HaltReason> {
+        if self.rows.is_none() {
+            self.rows = Some(self.fetch(interpreter)?.into_iter());
+        }
+        while let Some(row) = self.rows.as_mut().unwrap().next() {
+            if passes(interpreter, &row, &self.filter)? {
+                return Ok(Some(row));
+            }
+        }
+        Ok(None)
+    }
+}
+
+/// Evaluates a row of expressions at a time. A row of a single expression
+/// stands for its value, as the documents of INSERT ... VALUES do.
+struct Values {
+    rows: vec::IntoIter<Vec<IntermediateExpr>>,
+}
+
+impl Cursor for Values {
+    fn next(&mut self, interpreter: &mut Interpreter) -> Result<Option<Row>, HaltReason> {
+        let Some(row) = self.rows.next() else {
+            return Ok(None);
+        };
+        let mut values = vec![];
+        for expr in row.iter() {
+            values.push(evaluate(interpreter, expr)?);
+        }
+        Ok(Some(Row::projected(match values.len() {
+            1 => values.pop().unwrap(),
+            _ => RV::Array(alloc_shared(values)),
+        })))
+    }
+}
+
+struct Filter {
+    source: BoxedCursor,
+    predicate: IntermediateExpr,
+    subqueries: Subqueries,
+}
+
+impl Cursor for Filter {
+    fn next(&mut self, interpreter: &mut Interpreter) -> Result<Option<Row>, HaltReason> {
+        while let Some(row) = self.source.next(interpreter)? {
+            let matches = interpreter.with_subqueries(&mut self.subqueries, |interpreter| {
+                evaluate_in(interpreter, &row, &self.predicate)
+            })?;
+            if matches.as_bool() {
+                return Ok(Some(row));
+            }
+        }
+        Ok(None)
+    }
+}
+
+/// The subqueries of the expressions a cursor evaluates, along with the
+/// results they gave for the values of their outer references seen so far.
+#[derive(Default)]
+pub struct Subqueries {
+    plans: Arc<Vec<super::Subquery>>,
+    results: FxHashMap<(usize, String), RV>,
+}
+
+impl Subqueries {
+    fn new(plans: &[super::Subquery]) -> Subqueries {
+        Subqueries {
+            plans: Arc::new(plans.to_vec()),
+            results: FxHashMap::default(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.plans.is_empty()
+    }
+}
+
+/// The value a row of a subquery stands for: the field it selects.
+pub fn value_of(row: &RV) -> RV {
+    match row {
+        RV::Object(fields) => fields
+            .read()
+            .unwrap()
+            .values()
+            .next()
+            .cloned()
+            .unwrap_or(RV::Undefined),
+        other => other.clone(),
+    }
+}
+
+/// Runs the subquery with the given id if a cursor being pulled prepared
+/// it. A subquery is only run again when the values of its outer
+/// references change, and is read as far as the expression it is in needs.
+pub fn run_subquery(
+    interpreter: &mut Interpreter,
+    id: usize,
+    span: Span,
+) -> Option<Result<RV, HaltReason>> {
+    let (level, plans, index) =
+        interpreter
+            .subqueries()
+            .iter()
+            .enumerate()
+            .rev()
+            .find_map(|(level, subqueries)| {
+                let index = subqueries.plans.iter().position(|plan| plan.id == id)?;
+                Some((level, subqueries.plans.clone(), index))
+            })?;
+    let subquery = &plans[index];
+
+    let result = (|| {
+        let mut values = vec![];
+        for path in subquery.outer.iter() {
+            values.push(Compound::key_of(&interpreter.visit_expr(path)?));
+        }
+        let key = (id, values.join(","));
+        if let Some(result) = interpreter.subqueries()[level].results.get(&key) {
+            return Ok(result.clone());
+        }
+
+        let mut cursor = open(&subquery.node)?;
+        let result = match subquery.kind {
+            SubqueryKind::Exists => {
+                let row = cursor.next(interpreter)?;
+                RV::Array(alloc_shared(row.iter().map(|row| row.output()).collect()))
+            }
+            SubqueryKind::In => RV::Array(alloc_shared(
+                collect(interpreter, &mut cursor)?
+                    .iter()
+                    .map(|row| value_of(&row.output()))
+                    .collect(),
+            )),
+            SubqueryKind::Scalar => {
+                let Some(row) = cursor.next(interpreter)? else {
+                    return Ok(RV::Null);
+                };
+                if cursor.next(interpreter)?.is_some() {
+                    return Err(HaltReason::Error(
+                        InterpretError::MultipleRowsInScalarSubquery { span }.into(),
+                    ));
+                }
+                value_of(&row.output())
+            }
+        };
+        interpreter.subqueries()[level]
+            .results
+            .insert(key, result.clone());
+        Ok(result)
+    })();
+    Some(result)
+}
+
+struct Projection {
+    source: BoxedCursor,
+    fields: Vec<SqlProjection>,
+    subqueries: Subqueries,
+}
+
+impl Projection {
+    fn spread(projected: &mut FxHashMap<String, RV>, value: &RV) {
+        if let RV::Object(map) = value {
+            for (key, value) in map.read().unwrap().iter() {
+                projected.insert(key.clone(), value.clone());
+            }
+        }
+    }
+
+    fn project(
+        interpreter: &mut Interpreter,
+        row: Row,
+        fields: &[SqlProjection],
+    ) -> Result<Row, HaltReason> {
+        let mut projected = FxHashMap::default();
+        for field in fields.iter() {
+            match field {
+                SqlProjection::All { collection: None } => {
+                    Self::spread(&mut projected, &row.output());
+                }
+                SqlProjection::All {
+                    collection: Some(collection),
+                } => {
+                    Self::spread(&mut projected, &row.resolve(&collection.name, &[]));
+                }
+                SqlProjection::Expr { expr, alias } => {
+                    let value = interpreter
+                        .with_row(row.clone(), |interpreter| interpreter.visit_expr(expr))?;
+                    let name = match alias {
+                        Some(alias) => alias.name.clone(),
+                        None => name_of(expr),
+                    };
+                    projected.insert(name, value);
+                }
+            }
+        }
+
+        Ok(Row {
+            projected: Some(RV::Object(alloc_shared(projected))),
+            ..row
+        })
+    }
+}
+
+impl Cursor for Projection {
+    fn next(&mut self, interpreter: &mut Interpreter) -> Result<Option<Row>, HaltReason> {
+        let Some(row) = self.source.next(interpreter)? else {
+            return Ok(None);
+        };
+        let fields = &self.fields;
+        interpreter
+            .with_subqueries(&mut self.subqueries, |interpreter| {
+                Self::project(interpreter, row, fields)
+            })
+            .map(Some)
+    }
+}
+
+/// Sorts the rows of its source, which it reads whole on the first pull.
+/// Values are compared the way indexes order them.
+struct Order {
+    source: BoxedCursor,
+    key: Vec<(IntermediateExpr, SqlOrdering)>,
+    rows: Option<vec::IntoIter<Row>>,
+}
+
+impl Cursor for Order {
+    fn next(&mut self, interpreter: &mut Interpreter) -> Result<Option<Row>, HaltReason> {
+        if self.rows.is_none() {
+            let mut keyed = vec![];
+            for row in collect(interpreter, &mut self.source)? {
+                let mut key = vec![];
+                for (expr, _) in self.key.iter() {
+                    key.push(IndexValue::from(&evaluate_in(interpreter, &row, expr)?));
+                }
+                keyed.push((key, row));
+            }
+
+            keyed.sort_by(|(a, _), (b, _)| compare_keys(a, b, &self.key));
+            self.rows = Some(
+                keyed
+                    .into_iter()
+                    .map(|(_, row)| row)
+                    .collect::<Vec<Row>>()
+                    .into_iter(),
+            );
+        }
+        Ok(self.rows.as_mut().unwrap().next())
+    }
+}
+
+fn compare_keys(
+    a: &[IndexValue],
+    b: &[IndexValue],
+    key: &[(IntermediateExpr, SqlOrdering)],
+) -> Ordering {
+    a.iter()
+        .zip(b.iter())
+        .zip(key.iter())
+        .map(|((a, b), (_, ordering))| match ordering {
+            SqlOrdering::Asc => a.cmp(b),
+            SqlOrdering::Desc => b.cmp(a),
+        })
+        .find(|ordering| *ordering != Ordering::Equal)
+        .unwrap_or(Ordering::Equal)
+}
+
+/// Computes window functions over the rows of its source, which it reads
+/// whole on the first pull. Rows keep the order of the source.
+struct Windowing {
+    source: BoxedCursor,
+    windows: Vec<Window>,
+    rows: Option<vec::IntoIter<Row>>,
+}
+
+impl Windowing {
+    fn compute(&mut self, interpreter: &mut Interpreter) -> Result<Vec<Row>, HaltReason> {
+        let mut rows = collect(interpreter, &mut self.source)?;
+        for window in self.windows.iter() {
+            let values = Self::evaluate(interpreter, window, &rows)?;
+            let name = window.to_string();
+            for (row, value) in rows.iter_mut().zip(values) {
+                row.aggregates.push((name.clone(), value));
+            }
+        }
+        Ok(rows)
+    }
+
+    /// The value of the window function at every row.
+    fn evaluate(
+        interpreter: &mut Interpreter,
+        window: &Window,
+        rows: &[Row],
+    ) -> Result<Vec<RV>, HaltReason> {
+        // Partitions in the order they are first seen, by the indexes of rows
+        let mut partitions: Vec<Vec<usize>> = vec![];
+        let mut positions: FxHashMap<String, usize> = FxHashMap::default();
+        let mut keys: Vec<Vec<IndexValue>> = vec![];
+        for (index, row) in rows.iter().enumerate() {
+            let mut partition = vec![];
+            for expr in window.partition_by.iter() {
+                partition.push(evaluate_in(interpreter, row, expr)?);
+            }
+            let partition = Compound::key_of(&RV::Array(alloc_shared(partition)));
+            match positions.get(&partition) {
+                Some(position) => partitions[*position].push(index),
+                None => {
+                    positions.insert(partition, partitions.len());
+                    partitions.push(vec![index]);
+                }
+            }
+
+            let mut key = vec![];
+            for (expr, _) in window.order_by.iter() {
+                key.push(IndexValue::from(&evaluate_in(interpreter, row, expr)?));
+            }
+            keys.push(key);
+        }
+        let compare = |a: usize, b: usize| compare_keys(&keys[a], &keys[b], &window.order_by);
+
+        let mut values = vec![RV::Null; rows.len()];
+        for mut partition in partitions {
+            partition.sort_by(|a, b| compare(*a, *b));
+
+            // First and last position of the peers of every row
+            let mut peers = vec![(0, 0); partition.len()];
+            let mut first = 0;
+            for position in 1..=partition.len() {
+                if position == partition.len()
+                    || compare(partition[first], partition[position]) != Ordering::Equal
+                {
+                    peers[first..position].fill((first, position - 1));
+                    first = position;
+                }
+            }
+
+            let arguments = |interpreter: &mut Interpreter,
+                             position: usize,
+                             args: &[Expr]|
+             -> Result<Vec<RV>, HaltReason> {
+                interpreter.with_row(rows[partition[position]].clone(), |interpreter| {
+                    args.iter().map(|arg| interpreter.visit_expr(arg)).collect()
+                })
+            };
+
+            match &window.function {
+                WindowFunction::RowNumber => {
+                    for (position, index) in partition.iter().enumerate() {
+                        values[*index] = RV::Num((position + 1) as f64);
+                    }
+                }
+                WindowFunction::Rank => {
+                    for (position, index) in partition.iter().enumerate() {
+                        values[*index] = RV::Num((peers[position].0 + 1) as f64);
+                    }
+                }
+                WindowFunction::DenseRank => {
+                    let mut rank = 0;
+                    for (position, index) in partition.iter().enumerate() {
+                        if peers[position].0 == position {
+                            rank += 1;
+                        }
+                        values[*index] = RV::Num(rank as f64);
+                    }
+                }
+                WindowFunction::Lag(args) | WindowFunction::Lead(args) => {
+                    let backwards = matches!(window.function, WindowFunction::Lag(_));
+                    for position in 0..partition.len() {
+                        let current = arguments(interpreter, position, &args[1..])?;
+                        let offset = match current.first() {
+                            Some(RV::Num(offset)) if *offset >= 0.0 && offset.fract() == 0.0 => {
+                                *offset as i64
+                            }
+                            Some(offset) => {
+                                return Err(HaltReason::Error(
+                                    InterpretError::InvalidWindowOffset {
+                                        span: args[1].get_span(),
+                                        value: format!("{:?}", offset),
+                                    }
+                                    .into(),
+                                ))
+                            }
+                            None => 1,
+                        };
+                        let target = match backwards {
+                            true => position as i64 - offset,
+                            false => position as i64 + offset,
+                        };
+                        values[partition[position]] =
+                            if target >= 0 && (target as usize) < partition.len() {
+                                arguments(interpreter, target as usize, &args[..1])?.remove(0)
+                            } else {
+                                current.get(1).cloned().unwrap_or(RV::Null)
+                            };
+                    }
+                }
+                WindowFunction::Aggregate(aggregate) => {
+                    let mut frame_values = vec![];
+                    for position in 0..partition.len() {
+                        frame_values.push(arguments(interpreter, position, aggregate.args())?);
+                    }
+                    // Frames that start at the first row only grow, so a
+                    // single accumulator takes the rows as they join the frame
+                    if window.frame.start == FrameBound::UnboundedPreceding {
+                        let mut accumulator = Accumulator::new(interpreter, aggregate)?;
+                        let mut stepped = 0;
+                        for position in 0..partition.len() {
+                            if let Some((_, end)) =
+                                frame_of(&window.frame, position, partition.len(), peers[position])
+                            {
+                                for values in frame_values[stepped..=end].iter_mut() {
+                                    accumulator.step(interpreter, std::mem::take(values))?;
+                                }
+                                stepped = end + 1;
+                            }
+                            values[partition[position]] =
+                                accumulator.clone().finish(interpreter)?;
+                        }
+                    } else {
+                        for position in 0..partition.len() {
+                            let mut accumulator = Accumulator::new(interpreter, aggregate)?;
+                            if let Some((start, end)) =
+                                frame_of(&window.frame, position, partition.len(), peers[position])
+                            {
+                                for values in frame_values[start..=end].iter() {
+                                    accumulator.step(interpreter, values.clone())?;
+                                }
+                            }
+                            values[partition[position]] = accumulator.finish(interpreter)?;
+                        }
+                    }
+                }
+            }
+        }
+        Ok(values)
+    }
+}
+
+impl Cursor for Windowing {
+    fn next(&mut self, interpreter: &mut Interpreter) -> Result<Option<Row>, HaltReason> {
+        if self.rows.is_none() {
+            self.rows = Some(self.compute(interpreter)?.into_iter());
+        }
+        Ok(self.rows.as_mut().unwrap().next())
+    }
+}
+
+/// First and last position of the frame of the row at the position in a
+/// partition of the length, given the positions of its peers. Frames may be
+/// empty.
+fn frame_of(
+    frame: &Frame,
+    position: usize,
+    length: usize,
+    peers: (usize, usize),
+) -> Option<(usize, usize)> {
+    let range = frame.unit == SqlFrameUnit::Range;
+    let start = match frame.start {
+        FrameBound::UnboundedPreceding => 0,
+        FrameBound::Preceding(offset) => position.saturating_sub(offset),
+        FrameBound::CurrentRow if range => peers.0,
+        FrameBound::CurrentRow => position,
+        FrameBound::Following(offset) => position + offset,
+        FrameBound::UnboundedFollowing => length,
+    };
+    let end = match frame.end {
+        FrameBound::UnboundedPreceding => return None,
+        FrameBound::Preceding(offset) => position.checked_sub(offset)?,
+        FrameBound::CurrentRow if range => peers.1,
+        FrameBound::CurrentRow => position,
+        FrameBound::Following(offset) => (position + offset).min(length - 1),
+        FrameBound::UnboundedFollowing => length - 1,
+    };
+    (start <= end && start < length).then_some((start, end))
+}
+
+/// Groups the rows of its source by the values of the grouping expressions,
+/// which it reads whole on the first pull, and computes the aggregates of
+/// every group. Without grouping expressions all rows form a single group,
+/// even when there are none.
+struct Aggregation {
+    source: BoxedCursor,
+    group_by: Vec<IntermediateExpr>,
+    aggregates: Vec<Aggregate>,
+    rows: Option<vec::IntoIter<Row>>,
+}
+
+impl Aggregation {
+    fn accumulators(&self, interpreter: &mut Interpreter) -> Result<Vec<Accumulator>, HaltReason> {
+        self.aggregates
+            .iter()
+            .map(|aggregate| Accumulator::new(interpreter, aggregate))
+            .collect()
+    }
+
+    fn group(&mut self, interpreter: &mut Interpreter) -> Result<Vec<Row>, HaltReason> {
+        // Groups in the order they are first seen, each with its first row
+        let mut groups: Vec<(Row, Vec<Accumulator>)> = vec![];
+        let mut positions: FxHashMap<String, usize> = FxHashMap::default();
+        while let Some(row) = self.source.next(interpreter)? {
+            let mut key = vec![];
+            for expr in self.group_by.iter() {
+                key.push(evaluate_in(interpreter, &row, expr)?);
+            }
+            let key = Compound::key_of(&RV::Array(alloc_shared(key)));
+            let position = match positions.get(&key) {
+                Some(position) => *position,
+                None => {
+                    groups.push((row.clone(), self.accumulators(interpreter)?));
+                    positions.insert(key, groups.len() - 1);
+                    groups.len() - 1
+                }
+            };
+
+            for (aggregate, accumulator) in
+                self.aggregates.iter().zip(groups[position].1.iter_mut())
+            {
+                let values = interpreter.with_row(row.clone(), |interpreter| {
+                    aggregate
+                        .args()
+                        .iter()
+                        .map(|arg| interpreter.visit_expr(arg))
+                        .collect::<Result<Vec<RV>, HaltReason>>()
+                })?;
+                accumulator.step(interpreter, values)?;
+            }
+        }
+
+        if groups.is_empty() && self.group_by.is_empty() {
+            groups.push((Row::empty(), self.accumulators(interpreter)?));
+        }
+
+        let mut rows = vec![];
+        for (row, accumulators) in groups {
+            let mut aggregates = vec![];
+            for (aggregate, accumulator) in self.aggregates.iter().zip(accumulators) {
+                aggregates.push((aggregate.to_string(), accumulator.finish(interpreter)?));
+            }
+            rows.push(Row {
+                aggregates,
+                id: None,
+                ..row
+            });
+        }
+        Ok(rows)
+    }
+}
+
+impl Cursor for Aggregation {
+    fn next(&mut self, interpreter: &mut Interpreter) -> Result<Option<Row>, HaltReason> {
+        if self.rows.is_none() {
+            self.rows = Some(self.group(interpreter)?.into_iter());
+        }
+        Ok(self.rows.as_mut().unwrap().next())
+    }
+}
+
+/// Running state of an aggregate over the rows of a group. The built-in
+/// aggregates skip null and undefined values, and values other than numbers
+/// when summing or averaging. They are null over no values, apart from
+/// count. User-defined aggregates see every value.
+#[derive(Clone)]
+enum Accumulator {
+    Count(usize),
+    Sum(Option<f64>),
+    Average(f64, usize),
+    Extreme(Option<RV>, Ordering),
+    Custom(Aggregator, RV),
+}
+
+impl Accumulator {
+    fn new(
+        interpreter: &mut Interpreter,
+        aggregate: &Aggregate,
+    ) -> Result<Accumulator, HaltReason> {
+        Ok(match aggregate {
+            Aggregate::Count(_) => Accumulator::Count(0),
+            Aggregate::Sum(_) => Accumulator::Sum(None),
+            Aggregate::Average(_) => Accumulator::Average(0.0, 0),
+            Aggregate::Min(_) => Accumulator::Extreme(None, Ordering::Less),
+            Aggregate::Max(_) => Accumulator::Extreme(None, Ordering::Greater),
+            Aggregate::Custom { callee, .. } => {
+                let aggregator = match interpreter.visit_expr(callee)? {
+                    RV::Callable(callable) => callable.as_aggregator().cloned(),
+                    _ => None,
+                };
+                let Some(aggregator) = aggregator else {
+                    return Err(HaltReason::Error(
+                        InterpretError::NotCallable {
+                            span: callee.get_span(),
+                        }
+                        .into(),
+                    ));
+                };
+                let state = aggregator.init(interpreter)?;
+                Accumulator::Custom(aggregator, state)
+            }
+        })
+    }
+
+    fn step(
+        &mut self,
+        interpreter: &mut Interpreter,
+        mut values: Vec<RV>,
+    ) -> Result<(), HaltReason> {
+        if let Accumulator::Custom(aggregator, state) = self {
+            let previous = std::mem::replace(state, RV::Undefined);
+            *state = aggregator.step(interpreter, previous, &values)?;
+            return Ok(());
+        }
+
+        let value = values.swap_remove(0);
+        if matches!(value, RV::Null | RV::Undefined) {
+            return Ok(());
+        }
+        match (self, &value) {
+            (Accumulator::Count(count), _) => *count += 1,
+            (Accumulator::Sum(sum), RV::Num(n)) => *sum = Some(sum.unwrap_or(0.0) + n),
+            (Accumulator::Average(sum, count), RV::Num(n)) => {
+                *sum += n;
+                *count += 1;
+            }
+            (Accumulator::Extreme(current, ordering), _) => {
+                let replaces = match current {
+                    Some(current) => {
+                        IndexValue::from(&value).cmp(&IndexValue::from(&*current)) == *ordering
+                    }
+                    None => true,
+                };
+                if replaces {
+                    *current = Some(value);
+                }
+            }
+            _ => (),
+        }
+        Ok(())
+    }
+
+    fn finish(self, interpreter: &mut Interpreter) -> Result<RV, HaltReason> {
+        Ok(match self {
+            Accumulator::Count(count) => RV::Num(count as f64),
+            Accumulator::Sum(sum) => sum.map(RV::Num).unwrap_or(RV::Null),
+            Accumulator::Average(_, 0) => RV::Null,
+            Accumulator::Average(sum, count) => RV::Num(sum / count as f64),
+            Accumulator::Extreme(value, _) => value.unwrap_or(RV::Null),
+            Accumulator::Custom(aggregator, state) => aggregator.finalize(interpreter, state)?,
+        })
+    }
+}
+
+/// Passes on the rows whose output it has not seen yet, comparing values the
+/// way compound queries do.
+struct Distinct {
+    source: BoxedCursor,
+    seen: FxHashSet<String>,
+}
+
+impl Cursor for Distinct {
+    fn next(&mut self, interpreter: &mut Interpreter) -> Result<Option<Row>, HaltReason> {
+        while let Some(row) = self.source.next(interpreter)? {
+            if self.seen.insert(Compound::key_of(&row.output())) {
+                return Ok(Some(row));
+            }
+        }
+        Ok(None)
+    }
+}
+
+struct Limit {
+    source: BoxedCursor,
+    remaining: usize,
+}
+
+impl Cursor for Limit {
+    fn next(&mut self, interpreter: &mut Interpreter) -> Result<Option<Row>, HaltReason> {
+        if self.remaining == 0 {
+            return Ok(None);
+        }
+        self.remaining -= 1;
+        self.source.next(interpreter)
+    }
+}
+
+struct Offset {
+    source: BoxedCursor,
+    skip: usize,
+}
+
+impl Cursor for Offset {
+    fn next(&mut self, interpreter: &mut Interpreter) -> Result<Option<Row>, HaltReason> {
+        while self.skip > 0 {
+            self.skip -= 1;
+            if self.source.next(interpreter)?.is_none() {
+                return Ok(None);
+            }
+        }
+        self.source.next(interpreter)
+    }
+}
+
+/// Nested loop join. The right side, or the left one for a right join, is
+/// read on the first pull, and the rows of the other side are then pulled
+/// one at a time and matched against it. Rows of an outer join without a
+/// match are completed with nulls for the other side.
+struct Join {
+    left: BoxedCursor,
+    right: BoxedCursor,
+    left_aliases: Vec<String>,
+    right_aliases: Vec<String>,
+    join_type: SqlJoinType,
+    constraint: Option<IntermediateExpr>,
+    built: Option<Vec<Row>>,
+    // Joined rows of the row pulled last
+    rows: vec::IntoIter<Row>,
+}
+
+impl Join {
+    fn nulls(aliases: &[String]) -> Row {
+        Row {
+            sources: aliases
+                .iter()
+                .map(|alias| (alias.clone(), RV::Null))
+                .collect(),
+            projected: None,
+            aggregates: vec![],
+            id: None,
+        }
+    }
+
+    fn matches(&self, interpreter: &mut Interpreter, row: &Row) -> Result<bool, HaltReason> {
+        match &self.constraint {
+            Some(constraint) => Ok(evaluate_in(interpreter, row, constraint)?.as_bool()),
+            None => Ok(true),
+        }
+    }
+
+    /// Rows the pulled row makes with those of the side read whole.
+    fn join(&self, interpreter: &mut Interpreter, pulled: &Row) -> Result<Vec<Row>, HaltReason> {
+        let right_join = self.join_type == SqlJoinType::Right;
+        let mut rows = vec![];
+        for built in self.built.iter().flatten() {
+            let row = if right_join {
+                built.join(pulled)
+            } else {
+                pulled.join(built)
+            };
+            if self.matches(interpreter, &row)? {
+                rows.push(row);
+            }
+        }
+        if rows.is_empty() {
+            match self.join_type {
+                SqlJoinType::Right => rows.push(Self::nulls(&self.left_aliases).join(pulled)),
+                SqlJoinType::Left => rows.push(pulled.join(&Self::nulls(&self.right_aliases))),
+                _ => (),
+            }
+        }
+        Ok(rows)
+    }
+}
+
+impl Cursor for Join {
+    fn next(&mut self, interpreter: &mut Interpreter) -> Result<Option<Row>, HaltReason> {
+        let right_join = self.join_type == SqlJoinType::Right;
+        if self.built.is_none() {
+            let side = if right_join {
+                &mut self.left
+            } else {
+                &mut self.right
+            };
+            self.built = Some(collect(interpreter, side)?);
+        }
+        loop {
+            if let Some(row) = self.rows.next() {
+                return Ok(Some(row));
+            }
+            let pulled = if right_join {
+                self.right.next(interpreter)?
+            } else {
+                self.left.next(interpreter)?
+            };
+            let Some(pulled) = pulled else {
+                return Ok(None);
+            };
+            self.rows = self.join(interpreter, &pulled)?.into_iter();
+        }
+    }
+}
+
+/// Keeps the rows of the left side whose keys are found among those of the
+/// right side, or for an anti join the rows whose keys are not. The right
+/// side is read into a set of keys on the first pull. Keys holding objects
+/// or arrays match nothing, as such values are never equal.
+struct SemiJoin {
+    left: BoxedCursor,
+    right: BoxedCursor,
+    join_type: SemiJoinType,
+    left_keys: Vec<IntermediateExpr>,
+    right_keys: Vec<IntermediateExpr>,
+    matches: Option<Matches>,
+}
+
+/// Keys of the rows of the right side of a semi join.
+#[derive(Default)]
+struct Matches {
+    keys: BTreeSet<IndexKey>,
+    values: Vec<Vec<RV>>,
+    // A value of each type found at each position of the keys
+    types: Vec<Vec<IndexValue>>,
+}
+
+impl Matches {
+    fn insert(&mut self, key: IndexKey, values: Vec<RV>) {
+        self.types.resize(key.len(), vec![]);
+        for (value, types) in key.iter().zip(self.types.iter_mut()) {
+            if !types.iter().any(|other| other.same_type(value)) {
+                types.push(value.clone());
+            }
+        }
+        if self.keys.insert(key) {
+            self.values.push(values);
+        }
+    }
+
+    /// Whether a key equals the given one, as the interpreter compares
+    /// values. Keys are looked up by type first, and compared one by one
+    /// only if some of them have a type the interpreter would convert to.
+    fn contains(&self, key: &IndexKey, values: &[RV]) -> bool {
+        let strict = key
+            .iter()
+            .zip(&self.types)
+            .all(|(value, types)| !types.iter().any(|other| value.coerces(other)));
+        if strict {
+            return self.keys.contains(key);
+        }
+        self.values.iter().any(|other| {
+            values
+                .iter()
+                .zip(other)
+                .all(|(value, other)| value == other)
+        })
+    }
+}
+
+impl SemiJoin {
+    fn key_in(
+        interpreter: &mut Interpreter,
+        row: &Row,
+        exprs: &[IntermediateExpr],
+    ) -> Result<Option<(IndexKey, Vec<RV>)>, HaltReason> {
+        let values = interpreter.with_row(row.clone(), |interpreter| {
+            exprs
+                .iter()
+                .map(|expr| evaluate(interpreter, expr))
+                .collect::<Result<Vec<RV>, HaltReason>>()
+        })?;
+        let key: IndexKey = values.iter().map(IndexValue::from).collect();
+        if key.iter().any(|value| matches!(value, IndexValue::Other)) {
+            return Ok(None);
+        }
+        Ok(Some((key, values)))
+    }
+
+    fn fetch(&mut self, interpreter: &mut Interpreter) -> Result<Matches, HaltReason> {
+        let mut matches = Matches::default();
+        for row in collect(interpreter, &mut self.right)?.iter() {
+            if let Some((key, values)) = Self::key_in(interpreter, row, &self.right_keys)? {
+                matches.insert(key, values);
+            }
+        }
+        Ok(matches)
+    }
+}
+
+impl Cursor for SemiJoin {
+    fn next(&mut self, interpreter: &mut Interpreter) -> Result<Option<Row>, HaltReason> {
+        if self.matches.is_none() {
+            self.matches = Some(self.fetch(interpreter)?);
+        }
+        while let Some(row) = self.left.next(interpreter)? {
+            let matched =
+                Self::key_in(interpreter, &row, &self.left_keys)?.is_some_and(|(key, values)| {
+                    self.matches.as_ref().unwrap().contains(&key, &values)
+                });
+            if matched == (self.join_type == SemiJoinType::Semi) {
+                return Ok(Some(row));
+            }
+        }
+        Ok(None)
+    }
+}
+
+/// Combines the results of two queries, which it reads whole on the first
+/// pull. Apart from UNION ALL, duplicates are removed, comparing values by
+/// their JSON form.
+struct Compound {
+    left: BoxedCursor,
+    right: BoxedCursor,
+    operator: SqlCompoundOperator,
+    rows: Option<vec::IntoIter<Row>>,
+}
+
+impl Compound {
+    /// JSON form of the value with the fields of objects sorted, so that
+    /// equal values have equal keys.
+    fn key_of(value: &RV) -> String {
+        match value {
+            RV::Object(map) => {
+                let mut fields: Vec<String> = map
+                    .read()
+                    .unwrap()
+                    .iter()
+                    .map(|(key, value)| format!("{:?}:{}", key, Self::key_of(value)))
+                    .collect();
+                fields.sort();
+                format!("{{{}}}", fields.join(","))
+            }
+            RV::Array(values) => format!(
+                "[{}]",
+                values
+                    .read()
+                    .unwrap()
+                    .iter()
+                    .map(Self::key_of)
+                    .collect::<Vec<String>>()
+                    .join(",")
+            ),
+            other => serde_json::to_string(other).unwrap_or_default(),
+        }
+    }
+
+    fn fetch(&mut self, interpreter: &mut Interpreter) -> Result<Vec<Row>, HaltReason> {
+        let left: Vec<RV> = collect(interpreter, &mut self.left)?
+            .iter()
+            .map(|row| row.output())
+            .collect();
+        let right: Vec<RV> = collect(interpreter, &mut self.right)?
+            .iter()
+            .map(|row| row.output())
+            .collect();
+
+        if self.operator == SqlCompoundOperator::UnionAll {
+            return Ok(left.into_iter().chain(right).map(Row::projected).collect());
+        }
+
+        let right_keys: FxHashSet<String> = right.iter().map(Self::key_of).collect();
+        let candidates: Vec<RV> = match self.operator {
+            SqlCompoundOperator::Union => left.into_iter().chain(right).collect(),
+            SqlCompoundOperator::Intersect => left
+                .into_iter()
+                .filter(|value| right_keys.contains(&Self::key_of(value)))
+                .collect(),
+            _ => left
+                .into_iter()
+                .filter(|value| !right_keys.contains(&Self::key_of(value)))
+                .collect(),
+        };
+
+        let mut seen = FxHashSet::default();
+        Ok(candidates
+            .into_iter()
+            .filter(|value| seen.insert(Self::key_of(value)))
+            .map(Row::projected)
+            .collect())
+    }
+}
+
+impl Cursor for Compound {
+    fn next(&mut self, interpreter: &mut Interpreter) -> Result<Option<Row>, HaltReason> {
+        if self.rows.is_none() {
+            self.rows = Some(self.fetch(interpreter)?.into_iter());
+        }
+        Ok(self.rows.as_mut().unwrap().next())
+    }
+}
+
+/// Exposes the results of a subquery as the documents of a source named
+/// after its alias.
+struct Subquery {
+    source: BoxedCursor,
+    alias: String,
+}
+
+impl Cursor for Subquery {
+    fn next(&mut self, interpreter: &mut Interpreter) -> Result<Option<Row>, HaltReason> {
+        Ok(self
+            .source
+            .next(interpreter)?
+            .map(|row| Row::new(&self.alias, row.output())))
+    }
+}
+
+/// Materializes the tables of a WITH clause in order, and reads the query
+/// while they are bound.
+struct With {
+    tables: Vec<CommonTable>,
+    source: Node,
+    rows: Option<vec::IntoIter<Row>>,
+}
+
+impl With {
+    fn fetch(
+        interpreter: &mut Interpreter,
+        tables: &[CommonTable],
+        source: &Node,
+    ) -> Result<Vec<Row>, HaltReason> {
+        let Some((table, rest)) = tables.split_first() else {
+            return collect(interpreter, &mut open(source)?);
+        };
+        let rows = Arc::new(Self::materialize(interpreter, table)?);
+        interpreter.with_table(&table.name.name, rows, |interpreter| {
+            Self::fetch(interpreter, rest, source)
+        })
+    }
+
+    /// Rows of a table. The recursive part of a recursive table is run
+    /// against the rows of the previous iteration only, and UNION drops the
+    /// rows produced before, until an iteration adds nothing.
+    fn materialize(
+        interpreter: &mut Interpreter,
+        table: &CommonTable,
+    ) -> Result<Vec<RV>, HaltReason> {
+        let outputs = |rows: Vec<Row>| rows.iter().map(|row| row.output()).collect::<Vec<RV>>();
+
+        let mut results = outputs(collect(interpreter, &mut open(&table.source)?)?);
+        let Some((operator, recursive)) = &table.recursive else {
+            return Ok(results);
+        };
+
+        let distinct = *operator == SqlCompoundOperator::Union;
+        let mut seen = FxHashSet::default();
+        if distinct {
+            results.retain(|value| seen.insert(Compound::key_of(value)));
+        }
+
+        let limit = interpreter.settings().max_recursion;
+        let mut working = results.clone();
+        let mut iterations = 0;
+        while !working.is_empty() {
+            if iterations == limit {
+                return Err(HaltReason::Error(
+                    InterpretError::RecursionLimitExceeded {
+                        span: table.name.span,
+                        name: table.name.name.clone(),
+                        limit,
+                    }
+                    .into(),
+                ));
+            }
+            iterations += 1;
+
+            let rows =
+                interpreter.with_table(&table.name.name, Arc::new(working), |interpreter| {
+                    collect(interpreter, &mut open(recursive)?)
+                })?;
+            working = outputs(rows)
+                .into_iter()
+                .filter(|value| !distinct || seen.insert(Compound::key_of(value)))
+                .collect();
+            results.extend(working.iter().cloned());
+        }
+        Ok(results)
+    }
+}
+
+impl Cursor for With {
+    fn next(&mut self, interpreter: &mut Interpreter) -> Result<Option<Row>, HaltReason> {
+        if self.rows.is_none() {
+            self.rows = Some(Self::fetch(interpreter, &self.tables, &self.source)?.into_iter());
+        }
+        Ok(self.rows.as_mut().unwrap().next())
+    }
+}
+
+/// Reads the rows of a table bound by an enclosing WITH clause, as the
+/// documents of a source named after its alias.
+struct CteScan {
+    name: String,
+    alias: String,
+    rows: Option<vec::IntoIter<Row>>,
+}
+
+impl Cursor for CteScan {
+    fn next(&mut self, interpreter: &mut Interpreter) -> Result<Option<Row>, HaltReason> {
+        if self.rows.is_none() {
+            let Some(table) = interpreter.table(&self.name) else {
+                return Err(HaltReason::Error(
+                    InterpretError::Other {
+                        message: format!("Table {} is not bound", self.name),
+                    }
+                    .into(),
+                ));
+            };
+            let rows: Vec<Row> = table
+                .iter()
+                .map(|value| Row::new(&self.alias, value.clone()))
+                .collect();
+            self.rows = Some(rows.into_iter());
+        }
+        Ok(self.rows.as_mut().unwrap().next())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use lykiadb_lang::ast::{Identifier, Span};
+
+    use super::*;
+    use crate::catalog::constraint::insert_document;
+
+    fn interpreter() -> Interpreter {
+        let mut interpreter = Interpreter::new(None, false);
+        interpreter
+            .interpret(
+                "CREATE COLLECTION books (isbn UNIQUE); CREATE INDEX by_year ON books (year);",
+            )
+            .unwrap();
+        for json in [
+            r#"{"isbn": "a", "title": "Dune", "year": 1965}"#,
+            r#"{"isbn": "b", "title": "Neuromancer", "year": 1984}"#,
+            r#"{"isbn": "c", "title": "1984", "year": 1949}"#,
+        ] {
+            let document: RV = serde_json::from_str(json).unwrap();
+            interpreter
+                .run_in_transaction(Span::default(), |interpreter| {
+                    let entry = catalog::resolve_collection(
+                        interpreter.transaction().unwrap(),
+                        &SqlCollectionIdentifier {
+                            namespace: None,
+                            name: Identifier::new("books", false),
+                            alias: None,
+                        },
+                    )
+                    .map_err(HaltReason::Error)?;
+                    insert_document(interpreter, &entry, &document, Span::default())
+                })
+                .unwrap();
+        }
+        interpreter
+    }
+
+    fn titles(result: RV) -> Vec<String> {
+        let RV::Array(rows) = result else {
+            panic!("Expected an array, got {:?}", result);
+        };
+        let titles = rows
+            .read()
+            .unwrap()
+            .iter()
+            .map(
+                |row| match Row::new("", row.clone()).resolve("title", &[]) {
+                    RV::Str(title) => title.to_string(),
+                    other => panic!("Unexpected title {:?}", other),
+                },
+            )
+            .collect();
+        titles
+    }
+
+    #[test]
+    fn test_collection_scan() {
+        let mut interpreter = interpreter();
+
+        assert_eq!(
+            titles(
+                interpreter
+                    .interpret("SELECT * FROM books WHERE year > 1950 ORDER BY year;")
+                    .unwrap()
+            ),
+            vec!["Dune", "Neuromancer"]
+        );
+        assert_eq!(
+            titles(
+                interpreter
+                    .interpret("SELECT b.title FROM books b ORDER BY b.title DESC LIMIT 2;")
+                    .unwrap()
+            ),
+            vec!["Neuromancer", "Dune"]
+        );
+    }
+
+    #[test]
+    fn test_index_scan() {
+        let mut interpreter = interpreter();
+
+        let plan = interpreter
+            .interpret("EXPLAIN SELECT * FROM books WHERE isbn = 'b';")
+            .unwrap();
+        assert!(matches!(plan, RV::Str(plan) if plan.contains("index_scan")));
+
+        assert_eq!(
+            titles(
+                interpreter
+                    .interpret("SELECT * FROM books WHERE isbn = 'b';")
+                    .unwrap()
+            ),
+            vec!["Neuromancer"]
+        );
+        assert_eq!(
+            titles(
+                interpreter
+                    .interpret(
+                        "SELECT * FROM books WHERE year BETWEEN 1990 AND 1960 ORDER BY year;"
+                    )
+                    .unwrap()
+            ),
+            vec!["Dune", "Neuromancer"]
+        );
+    }
+}
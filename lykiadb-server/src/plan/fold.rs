@@ -0,0 +1,121 @@
+use std::sync::Arc;
+
+use lykiadb_lang::ast::{expr::Expr, visitor::VisitorMut, AstNode, Literal, Spanned};
+
+use crate::{engine::interpreter::Interpreter, value::RV};
+
+use super::{Aggregate, IntermediateExpr};
+
+/// Evaluates the parts of an expression that read no rows: literals, script
+/// variables, and calls to pure functions with such arguments. An expression
+/// that is constant as a whole becomes its value, and the constant parts of
+/// the others become literals where their values can be written as one.
+/// Parts failing to evaluate are left for the executor to report.
+pub fn fold(interpreter: &mut Interpreter, expr: &Expr) -> IntermediateExpr {
+    if is_constant(interpreter, expr) {
+        if let Ok(value) = interpreter.visit_expr(expr) {
+            return IntermediateExpr::Constant(value);
+        }
+    }
+    let folded = expr.rewrite::<()>(&mut |e: &Expr| {
+        if matches!(e, Expr::Literal { .. }) || !is_constant(interpreter, e) {
+            return Ok(None);
+        }
+        let Ok(value) = interpreter.visit_expr(e) else {
+            return Ok(None);
+        };
+        let Some(literal) = literal(&value) else {
+            return Ok(None);
+        };
+        Ok(Some(Expr::Literal {
+            value: literal,
+            raw: value.to_string(),
+            span: e.get_span(),
+            id: e.get_id(),
+        }))
+    });
+    IntermediateExpr::Expr {
+        expr: folded.unwrap_or_else(|_| expr.clone()),
+    }
+}
+
+/// Value of a literal that holds no expressions.
+pub fn value_of(literal: &Literal) -> Option<RV> {
+    match literal {
+        Literal::Str(s) => Some(RV::Str(Arc::clone(s))),
+        Literal::Num(n) => Some(RV::Num(*n)),
+        Literal::Bool(b) => Some(RV::Bool(*b)),
+        Literal::Undefined => Some(RV::Undefined),
+        Literal::NaN => Some(RV::NaN),
+        Literal::Null => Some(RV::Null),
+        Literal::Object(_) | Literal::Array(_) => None,
+    }
+}
+
+/// Literal of a scalar value. Objects and arrays are shared, so they are not
+/// copied into literals.
+fn literal(value: &RV) -> Option<Literal> {
+    match value {
+        RV::Str(s) => Some(Literal::Str(Arc::clone(s))),
+        RV::Num(n) => Some(Literal::Num(*n)),
+        RV::Bool(b) => Some(Literal::Bool(*b)),
+        RV::Undefined => Some(Literal::Undefined),
+        RV::NaN => Some(Literal::NaN),
+        RV::Null => Some(Literal::Null),
+        RV::Object(_) | RV::Array(_) | RV::Callable(_) => None,
+    }
+}
+
+/// Whether the expression has the same value for every row, and evaluating
+/// it has no side effects.
+fn is_constant(interpreter: &mut Interpreter, expr: &Expr) -> bool {
+    match expr {
+        Expr::Literal { value, .. } => match value {
+            Literal::Object(fields) => fields.values().all(|field| is_constant(interpreter, field)),
+            Literal::Array(items) => items.iter().all(|item| is_constant(interpreter, item)),
+            _ => true,
+        },
+        Expr::Variable { .. } => true,
+        Expr::Grouping { expr, .. } | Expr::Unary { expr, .. } => is_constant(interpreter, expr),
+        Expr::Get { object, .. } => is_constant(interpreter, object),
+        Expr::Binary { left, right, .. } | Expr::Logical { left, right, .. } => {
+            is_constant(interpreter, left) && is_constant(interpreter, right)
+        }
+        Expr::Between {
+            lower,
+            upper,
+            subject,
+            ..
+        } => [lower, upper, subject]
+            .iter()
+            .all(|expr| is_constant(interpreter, expr)),
+        Expr::Call {
+            callee,
+            args,
+            over: None,
+            ..
+        } => {
+            Aggregate::function_of(expr).is_none()
+                && is_pure(interpreter, callee)
+                && args.iter().all(|arg| is_constant(interpreter, arg))
+        }
+        _ => false,
+    }
+}
+
+/// Whether the callee names a pure function. Only variables and the
+/// properties of their objects are looked up, as evaluating them has no
+/// side effects.
+fn is_pure(interpreter: &mut Interpreter, callee: &Expr) -> bool {
+    fn is_path(expr: &Expr) -> bool {
+        match expr {
+            Expr::Variable { .. } => true,
+            Expr::Get { object, .. } => is_path(object),
+            _ => false,
+        }
+    }
+    if !is_path(callee) {
+        return false;
+    }
+    matches!(interpreter.visit_expr(callee), Ok(RV::Callable(callable)) if callable.pure)
+}
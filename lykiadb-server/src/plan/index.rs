@@ -0,0 +1,204 @@
+use std::ops::Bound;
+
+use lykiadb_lang::ast::{
+    expr::{Expr, Operation, RangeKind},
+    sql::SqlCollectionIdentifier,
+};
+use rustc_hash::FxHashMap;
+
+use crate::store::index::IndexDefinition;
+
+use super::{IntermediateExpr, Node};
+
+struct Range {
+    lower: Bound<Expr>,
+    upper: Bound<Expr>,
+}
+
+/// Equality and range predicates on the fields of a single collection, as
+/// found in the conjuncts of a WHERE clause.
+#[derive(Default)]
+struct Predicates {
+    equalities: FxHashMap<String, Expr>,
+    ranges: FxHashMap<String, Range>,
+}
+
+/// Picks the index that best serves the predicate, if any. Lookups on every
+/// field of an index are preferred over ranges, and unique indexes over the
+/// others. The predicate is still applied on top of the index scan, so the
+/// index only has to narrow the documents down. The executor reads the whole
+/// collection instead if the index may miss some documents, as happens when
+/// the predicate compares values of different types.
+pub fn choose_index(
+    source: &SqlCollectionIdentifier,
+    indexes: &[IndexDefinition],
+    predicate: &Expr,
+) -> Option<Node> {
+    let alias = &source.alias.as_ref().unwrap_or(&source.name).name;
+    let mut predicates = Predicates::default();
+    collect(alias, predicate, &mut predicates);
+
+    let mut best: Option<(usize, Node)> = None;
+    for index in indexes {
+        let (score, lower, upper) = if let Some(key) = index
+            .fields
+            .iter()
+            .map(|field| predicates.equalities.get(field).cloned())
+            .collect::<Option<Vec<Expr>>>()
+        {
+            let score = if index.unique { 3 } else { 2 };
+            (score, Bound::Included(key.clone()), Bound::Included(key))
+        } else if let [field] = index.fields.as_slice() {
+            let Some(range) = predicates.ranges.get(field) else {
+                continue;
+            };
+            let wrap = |bound: &Bound<Expr>| bound.as_ref().map(|expr| vec![expr.clone()]);
+            (1, wrap(&range.lower), wrap(&range.upper))
+        } else {
+            continue;
+        };
+
+        if best.as_ref().is_some_and(|(best, _)| *best >= score) {
+            continue;
+        }
+
+        let to_intermediate = |bound: Bound<Vec<Expr>>| {
+            bound.map(|key| {
+                key.into_iter()
+                    .map(|expr| IntermediateExpr::Expr { expr })
+                    .collect()
+            })
+        };
+        best = Some((
+            score,
+            Node::IndexScan {
+                source: source.clone(),
+                index: index.name.clone(),
+                lower: to_intermediate(lower),
+                upper: to_intermediate(upper),
+            },
+        ));
+    }
+
+    best.map(|(_, node)| node)
+}
+
+fn collect(alias: &str, predicate: &Expr, predicates: &mut Predicates) {
+    match predicate {
+        Expr::Grouping { expr, .. } => collect(alias, expr, predicates),
+        Expr::Logical {
+            left,
+            operation: Operation::And,
+            right,
+            ..
+        } => {
+            collect(alias, left, predicates);
+            collect(alias, right, predicates);
+        }
+        Expr::Binary {
+            left,
+            operation,
+            right,
+            ..
+        } => {
+            let (field, operation, value) = match (field_of(alias, left), field_of(alias, right)) {
+                (Some(field), None) if is_constant(right) => (field, *operation, right),
+                (None, Some(field)) if is_constant(left) => (field, flip(*operation), left),
+                _ => return,
+            };
+            let value = value.as_ref().clone();
+            match operation {
+                Operation::IsEqual => {
+                    predicates.equalities.entry(field).or_insert(value);
+                }
+                Operation::Greater => narrow(predicates, field, Bound::Excluded(value), true),
+                Operation::GreaterEqual => narrow(predicates, field, Bound::Included(value), true),
+                Operation::Less => narrow(predicates, field, Bound::Excluded(value), false),
+                Operation::LessEqual => narrow(predicates, field, Bound::Included(value), false),
+                _ => {}
+            }
+        }
+        Expr::Between {
+            lower,
+            upper,
+            subject,
+            kind: RangeKind::Between,
+            ..
+        } => {
+            if let Some(field) = field_of(alias, subject) {
+                if is_constant(lower) && is_constant(upper) {
+                    narrow(
+                        predicates,
+                        field.clone(),
+                        Bound::Included(lower.as_ref().clone()),
+                        true,
+                    );
+                    narrow(
+                        predicates,
+                        field,
+                        Bound::Included(upper.as_ref().clone()),
+                        false,
+                    );
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Sets a side of the range on the field, unless an earlier conjunct did.
+fn narrow(predicates: &mut Predicates, field: String, bound: Bound<Expr>, lower: bool) {
+    let range = predicates.ranges.entry(field).or_insert(Range {
+        lower: Bound::Unbounded,
+        upper: Bound::Unbounded,
+    });
+    let side = if lower {
+        &mut range.lower
+    } else {
+        &mut range.upper
+    };
+    if let Bound::Unbounded = side {
+        *side = bound;
+    }
+}
+
+fn flip(operation: Operation) -> Operation {
+    match operation {
+        Operation::Greater => Operation::Less,
+        Operation::GreaterEqual => Operation::LessEqual,
+        Operation::Less => Operation::Greater,
+        Operation::LessEqual => Operation::GreaterEqual,
+        other => other,
+    }
+}
+
+/// Dotted path of the field, relative to the documents of the collection.
+fn field_of(alias: &str, expr: &Expr) -> Option<String> {
+    let Expr::FieldPath { head, tail, .. } = expr else {
+        return None;
+    };
+    let mut path: Vec<&str> = tail.iter().map(|ident| ident.name.as_str()).collect();
+    if head.name != alias || tail.is_empty() {
+        path.insert(0, &head.name);
+    }
+    Some(path.join("."))
+}
+
+/// Whether the expression has the same value for every document.
+fn is_constant(expr: &Expr) -> bool {
+    let mut constant = true;
+    expr.walk::<(), ()>(&mut |e: &Expr| {
+        if matches!(
+            e,
+            Expr::FieldPath { .. }
+                | Expr::Select { .. }
+                | Expr::Insert { .. }
+                | Expr::Update { .. }
+                | Expr::Delete { .. }
+        ) {
+            constant = false;
+        }
+        Some(Ok(()))
+    });
+    constant
+}
@@ -1,10 +1,11 @@
 use std::fmt::Display;
+use std::ops::Bound;
 
 use lykiadb_lang::ast::{
     expr::Expr,
     sql::{
-        SqlCollectionIdentifier, SqlCompoundOperator, SqlExpressionSource, SqlJoinType,
-        SqlOrdering, SqlProjection,
+        SqlAssignment, SqlCollectionIdentifier, SqlCompoundOperator, SqlConflictAction,
+        SqlExpressionSource, SqlFrameUnit, SqlJoinType, SqlOrdering, SqlProjection,
     },
     Identifier, Span,
 };
@@ -12,17 +13,44 @@ use serde::{Deserialize, Serialize};
 
 use crate::value::RV;
 
+mod decorrelate;
+pub mod executor;
+mod fold;
+mod index;
+mod optimizer;
 pub mod planner;
 mod scope;
 
 #[derive(PartialEq, Eq, Debug, Clone, Serialize, Deserialize)]
 pub enum PlannerError {
     SubqueryNotAllowed(Span),
+    MultipleFieldsInSubquery(Span),
+    CollectionNotFound {
+        namespace: String,
+        name: String,
+        span: Span,
+    },
     ObjectNotFoundInScope(Identifier),
     DuplicateObjectInScope {
         previous: Identifier,
         ident: Identifier,
     },
+    ConflictTargetNotUnique {
+        collection: String,
+        fields: Vec<String>,
+        span: Span,
+    },
+    AggregateNotAllowed(Span),
+    UngroupedField {
+        field: String,
+        span: Span,
+    },
+    WindowNotAllowed(Span),
+    UnknownWindowFunction {
+        name: String,
+        span: Span,
+    },
+    InvalidWindowFrame(Span),
 }
 
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
@@ -32,6 +60,223 @@ pub enum Aggregate {
     Max(Expr),
     Min(Expr),
     Sum(Expr),
+    /// A user-defined aggregate, called through the callee it is named by.
+    Custom {
+        callee: Box<Expr>,
+        args: Vec<Expr>,
+    },
+}
+
+impl Aggregate {
+    /// Name of the function an aggregate call is made with, if the call is
+    /// to one of the built-in aggregates.
+    pub fn function_of(expr: &Expr) -> Option<String> {
+        let Expr::Call { callee, .. } = expr else {
+            return None;
+        };
+        let Expr::Variable { name, .. } = callee.as_ref() else {
+            return None;
+        };
+        let function = name.name.to_lowercase();
+        match function.as_str() {
+            "avg" | "count" | "max" | "min" | "sum" => Some(function),
+            _ => None,
+        }
+    }
+
+    pub fn args(&self) -> &[Expr] {
+        match self {
+            Aggregate::Average(arg)
+            | Aggregate::Count(arg)
+            | Aggregate::Max(arg)
+            | Aggregate::Min(arg)
+            | Aggregate::Sum(arg) => std::slice::from_ref(arg),
+            Aggregate::Custom { args, .. } => args,
+        }
+    }
+
+    pub fn new(function: &str, arg: Expr) -> Aggregate {
+        match function {
+            "avg" => Aggregate::Average(arg),
+            "count" => Aggregate::Count(arg),
+            "max" => Aggregate::Max(arg),
+            "min" => Aggregate::Min(arg),
+            _ => Aggregate::Sum(arg),
+        }
+    }
+}
+
+impl Display for Aggregate {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Aggregate::Average(arg) => write!(f, "avg({})", arg),
+            Aggregate::Count(arg) => write!(f, "count({})", arg),
+            Aggregate::Max(arg) => write!(f, "max({})", arg),
+            Aggregate::Min(arg) => write!(f, "min({})", arg),
+            Aggregate::Sum(arg) => write!(f, "sum({})", arg),
+            Aggregate::Custom { callee, args } => write!(
+                f,
+                "{}({})",
+                callee,
+                args.iter()
+                    .map(|arg| arg.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum WindowFunction {
+    RowNumber,
+    Rank,
+    DenseRank,
+    /// The value of the first argument at the row as many rows before as the
+    /// second one says, one by default, or else the third argument.
+    Lag(Vec<Expr>),
+    /// Like `Lag`, but looking at the rows after.
+    Lead(Vec<Expr>),
+    Aggregate(Box<Aggregate>),
+}
+
+impl Display for WindowFunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let describe_args = |args: &[Expr]| {
+            args.iter()
+                .map(|arg| arg.to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        match self {
+            WindowFunction::RowNumber => write!(f, "row_number()"),
+            WindowFunction::Rank => write!(f, "rank()"),
+            WindowFunction::DenseRank => write!(f, "dense_rank()"),
+            WindowFunction::Lag(args) => write!(f, "lag({})", describe_args(args)),
+            WindowFunction::Lead(args) => write!(f, "lead({})", describe_args(args)),
+            WindowFunction::Aggregate(aggregate) => write!(f, "{}", aggregate),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum FrameBound {
+    UnboundedPreceding,
+    Preceding(usize),
+    CurrentRow,
+    Following(usize),
+    UnboundedFollowing,
+}
+
+impl Display for FrameBound {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FrameBound::UnboundedPreceding => write!(f, "unbounded preceding"),
+            FrameBound::Preceding(offset) => write!(f, "{} preceding", offset),
+            FrameBound::CurrentRow => write!(f, "current row"),
+            FrameBound::Following(offset) => write!(f, "{} following", offset),
+            FrameBound::UnboundedFollowing => write!(f, "unbounded following"),
+        }
+    }
+}
+
+/// The rows of the partition an aggregate is computed over, relative to the
+/// current row. Frames in rows count rows, while frames in ranges extend the
+/// current row to all of its peers, the rows of equal order.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Frame {
+    pub unit: SqlFrameUnit,
+    pub start: FrameBound,
+    pub end: FrameBound,
+}
+
+impl Display for Frame {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let unit = match self.unit {
+            SqlFrameUnit::Rows => "rows",
+            SqlFrameUnit::Range => "range",
+        };
+        write!(f, "{} between {} and {}", unit, self.start, self.end)
+    }
+}
+
+/// A window function along with the partitions, order and frame of the rows
+/// it is computed over.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Window {
+    pub function: WindowFunction,
+    pub partition_by: Vec<IntermediateExpr>,
+    pub order_by: Vec<(IntermediateExpr, SqlOrdering)>,
+    pub frame: Frame,
+}
+
+impl Display for Window {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut clauses = vec![];
+        if !self.partition_by.is_empty() {
+            clauses.push(format!(
+                "partition by {}",
+                self.partition_by
+                    .iter()
+                    .map(|expr| expr.to_string())
+                    .collect::<Vec<String>>()
+                    .join(", ")
+            ));
+        }
+        if !self.order_by.is_empty() {
+            clauses.push(format!(
+                "order by {}",
+                self.order_by
+                    .iter()
+                    .map(|(expr, ordering)| format!("({}, {:?})", expr, ordering))
+                    .collect::<Vec<String>>()
+                    .join(", ")
+            ));
+        }
+        clauses.push(self.frame.to_string());
+        write!(f, "{} over ({})", self.function, clauses.join(", "))
+    }
+}
+
+/// How the expression a subquery is in reads its rows.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SubqueryKind {
+    /// `EXISTS (...)`, which only asks whether there are any rows.
+    Exists,
+    /// `x IN (...)`, which reads the values of the field selected.
+    In,
+    /// Anywhere else, the subquery stands for the value of its only row.
+    Scalar,
+}
+
+/// A subquery in an expression of the enclosing query, by the id of the
+/// expression. Outer holds the fields of the enclosing rows it references,
+/// and it is run again whenever their values change.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Subquery {
+    pub id: usize,
+    pub kind: SubqueryKind,
+    pub node: Node,
+    pub outer: Vec<Expr>,
+}
+
+/// Joins the planner turns subqueries into, which only filter the rows of
+/// their left side. A semi join keeps the rows with a match on the right,
+/// and an anti join the rows without one.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SemiJoinType {
+    Semi,
+    Anti,
+}
+
+/// A table named by a WITH clause. The rows of a recursive table start out
+/// as those of its source, and its recursive part is then run against the
+/// rows the previous iteration produced until no new rows come out.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct CommonTable {
+    pub name: Identifier,
+    pub source: Node,
+    pub recursive: Option<(SqlCompoundOperator, Node)>,
 }
 
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
@@ -51,9 +296,50 @@ impl Display for IntermediateExpr {
     }
 }
 
+/// How an insert resolves conflicts: stored documents holding the key of an
+/// incoming one in the unique index are left alone or updated.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct OnConflict {
+    pub index: String,
+    pub action: SqlConflictAction,
+}
+
+impl Display for OnConflict {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.action {
+            SqlConflictAction::Nothing => write!(f, "{}, do nothing", self.index),
+            SqlConflictAction::Update { assignments } => write!(
+                f,
+                "{}, do update {}",
+                self.index,
+                describe_assignments(assignments)
+            ),
+        }
+    }
+}
+
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub enum Plan {
     Select(Node),
+    Insert {
+        collection: SqlCollectionIdentifier,
+        source: Node,
+        on_conflict: Option<OnConflict>,
+        returning: Option<Vec<SqlProjection>>,
+    },
+    Update {
+        collection: SqlCollectionIdentifier,
+        assignments: Vec<SqlAssignment>,
+        source: Node,
+        returning: Option<Vec<SqlProjection>>,
+    },
+    Delete {
+        collection: SqlCollectionIdentifier,
+        source: Node,
+        returning: Option<Vec<SqlProjection>>,
+        // Set for statements without a WHERE clause
+        full: bool,
+    },
 }
 
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
@@ -70,15 +356,26 @@ pub enum Node {
         aggregates: Vec<Aggregate>,
     },
 
+    Window {
+        source: Box<Node>,
+        windows: Vec<Window>,
+    },
+
     Filter {
         source: Box<Node>,
         predicate: IntermediateExpr,
-        subqueries: Vec<Node>,
+        subqueries: Vec<Subquery>,
     },
 
     Projection {
         source: Box<Node>,
         fields: Vec<SqlProjection>,
+        subqueries: Vec<Subquery>,
+    },
+
+    /// Drops the rows whose output equals that of an earlier row.
+    Distinct {
+        source: Box<Node>,
     },
 
     Limit {
@@ -105,6 +402,13 @@ pub enum Node {
         filter: Option<IntermediateExpr>,
     },
 
+    IndexScan {
+        source: SqlCollectionIdentifier,
+        index: String,
+        lower: Bound<Vec<IntermediateExpr>>,
+        upper: Bound<Vec<IntermediateExpr>>,
+    },
+
     EvalScan {
         source: SqlExpressionSource,
         filter: Option<IntermediateExpr>,
@@ -117,30 +421,227 @@ pub enum Node {
         constraint: Option<IntermediateExpr>,
     },
 
+    /// Rows of the left side match those of the right when each pair of
+    /// keys, the first read from the left row and the second from the right
+    /// one, is equal.
+    SemiJoin {
+        left: Box<Node>,
+        join_type: SemiJoinType,
+        right: Box<Node>,
+        keys: Vec<(IntermediateExpr, IntermediateExpr)>,
+    },
+
     Subquery {
         source: Box<Node>,
         alias: Option<Identifier>,
     },
 
+    With {
+        tables: Vec<CommonTable>,
+        source: Box<Node>,
+    },
+
+    CteScan {
+        name: Identifier,
+        alias: Identifier,
+    },
+
     Nothing,
+
+    /// No rows, which is all a filter that never holds leaves of its
+    /// source. The aliases of the source are kept for outer joins to
+    /// complete rows with.
+    Empty {
+        aliases: Vec<String>,
+    },
 }
 
 impl Display for Plan {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Plan::Select(node) => write!(f, "{}", node),
+            Plan::Insert {
+                collection,
+                source,
+                on_conflict,
+                returning,
+            } => {
+                let on_conflict_description = on_conflict
+                    .as_ref()
+                    .map(|on_conflict| format!(", on_conflict=({})", on_conflict))
+                    .unwrap_or_default();
+                write!(
+                    f,
+                    "- insert [{}{}{}]{}",
+                    collection.name,
+                    on_conflict_description,
+                    describe_returning(returning),
+                    Node::NEWLINE
+                )?;
+                source._fmt_recursive(f, 1)
+            }
+            Plan::Update {
+                collection,
+                assignments,
+                source,
+                returning,
+            } => {
+                write!(
+                    f,
+                    "- update [{}, {}{}]{}",
+                    collection.name,
+                    describe_assignments(assignments),
+                    describe_returning(returning),
+                    Node::NEWLINE
+                )?;
+                source._fmt_recursive(f, 1)
+            }
+            Plan::Delete {
+                collection,
+                source,
+                returning,
+                ..
+            } => {
+                write!(
+                    f,
+                    "- delete [{}{}]{}",
+                    collection.name,
+                    describe_returning(returning),
+                    Node::NEWLINE
+                )?;
+                source._fmt_recursive(f, 1)
+            }
         }
     }
 }
 
+/// Name of a projected expression without an alias: the last field of a
+/// field path, or the expression itself.
+fn name_of(expr: &Expr) -> String {
+    match expr {
+        Expr::FieldPath { head, tail, .. } => tail.last().unwrap_or(head).name.clone(),
+        other => other.to_string(),
+    }
+}
+
+fn describe_assignments(assignments: &[SqlAssignment]) -> String {
+    assignments
+        .iter()
+        .map(|assignment| format!("{} = {}", assignment.path, assignment.expr))
+        .collect::<Vec<String>>()
+        .join(", ")
+}
+
+fn describe_projection(fields: &[SqlProjection]) -> String {
+    fields
+        .iter()
+        .map(|field| match field {
+            SqlProjection::All { collection } => {
+                if let Some(c) = collection.as_ref() {
+                    return format!("* in {}", c.name);
+                }
+                "*".to_string()
+            }
+            SqlProjection::Expr { expr, alias } => {
+                if let Some(alias) = alias {
+                    return format!("{} as {}", expr, alias.name);
+                }
+                format!("{} as {}", expr, expr)
+            }
+        })
+        .collect::<Vec<String>>()
+        .join(", ")
+}
+
+fn describe_returning(returning: &Option<Vec<SqlProjection>>) -> String {
+    match returning {
+        Some(fields) => format!(", returning=({})", describe_projection(fields)),
+        None => String::new(),
+    }
+}
+
 impl Node {
     const TAB: &'static str = "  ";
     const NEWLINE: &'static str = "\n";
 
+    /// Aliases of the sources a node reads from, as they appear in its rows.
+    pub fn aliases(&self) -> Vec<String> {
+        match self {
+            Node::Scan { source, .. } | Node::IndexScan { source, .. } => {
+                vec![source.alias.as_ref().unwrap_or(&source.name).name.clone()]
+            }
+            Node::EvalScan { source, .. } => vec![source.alias.name.clone()],
+            Node::Empty { aliases } => aliases.clone(),
+            Node::CteScan { alias, .. } => vec![alias.name.clone()],
+            Node::Subquery { alias, .. } => vec![alias
+                .as_ref()
+                .map(|alias| alias.name.clone())
+                .unwrap_or_default()],
+            Node::Join { left, right, .. } => {
+                let mut aliases = left.aliases();
+                aliases.extend(right.aliases());
+                aliases
+            }
+            Node::SemiJoin { left, .. } => left.aliases(),
+            Node::Filter { source, .. }
+            | Node::Order { source, .. }
+            | Node::Window { source, .. }
+            | Node::Distinct { source }
+            | Node::Limit { source, .. }
+            | Node::Offset { source, .. }
+            | Node::With { source, .. } => source.aliases(),
+            _ => vec![],
+        }
+    }
+
+    fn describe_filter(filter: &Option<IntermediateExpr>) -> String {
+        match filter {
+            Some(filter) => format!(", filter={}", filter),
+            None => String::new(),
+        }
+    }
+
+    fn _fmt_subqueries(
+        f: &mut std::fmt::Formatter<'_>,
+        subqueries: &[Subquery],
+        indent: usize,
+    ) -> std::fmt::Result {
+        if subqueries.is_empty() {
+            return Ok(());
+        }
+        let indent_str = Self::TAB.repeat(indent);
+        write!(f, "{}  > subqueries{}", indent_str, Self::NEWLINE)?;
+        for subquery in subqueries {
+            if !subquery.outer.is_empty() {
+                write!(
+                    f,
+                    "{}    > correlated [{}]{}",
+                    indent_str,
+                    subquery
+                        .outer
+                        .iter()
+                        .map(|expr| expr.to_string())
+                        .collect::<Vec<String>>()
+                        .join(", "),
+                    Self::NEWLINE
+                )?;
+            }
+            subquery.node._fmt_recursive(f, indent + 2)?;
+        }
+        Ok(())
+    }
+
     fn _fmt_recursive(&self, f: &mut std::fmt::Formatter<'_>, indent: usize) -> std::fmt::Result {
         let indent_str = Self::TAB.repeat(indent);
         match self {
             Node::Nothing => write!(f, "{}- nothing{}", indent_str, Self::NEWLINE),
+            Node::Empty { aliases } => write!(
+                f,
+                "{}- empty [{}]{}",
+                indent_str,
+                aliases.join(", "),
+                Self::NEWLINE
+            ),
             Node::Order { source, key } => {
                 let key_description = key
                     .iter()
@@ -156,33 +657,56 @@ impl Node {
                 )?;
                 source._fmt_recursive(f, indent + 1)
             }
-            Node::Projection { source, fields } => {
-                let fields_description = fields
-                    .iter()
-                    .map(|field| match field {
-                        SqlProjection::All { collection } => {
-                            if let Some(c) = collection.as_ref() {
-                                return format!("* in {}", c.name);
-                            }
-                            "*".to_string()
-                        }
-                        SqlProjection::Expr { expr, alias } => {
-                            if let Some(alias) = alias {
-                                return format!("{} as {}", expr, alias.name);
-                            }
-                            format!("{} as {}", expr, expr)
-                        }
-                    })
-                    .collect::<Vec<String>>()
-                    .join(", ");
+            Node::Window { source, windows } => {
+                write!(
+                    f,
+                    "{}- window [{}]{}",
+                    indent_str,
+                    windows
+                        .iter()
+                        .map(|window| window.to_string())
+                        .collect::<Vec<String>>()
+                        .join(", "),
+                    Self::NEWLINE
+                )?;
+                source._fmt_recursive(f, indent + 1)
+            }
+            Node::Aggregate {
+                source,
+                group_by,
+                aggregates,
+            } => {
+                write!(
+                    f,
+                    "{}- aggregate [group_by=({}), aggregates=({})]{}",
+                    indent_str,
+                    group_by
+                        .iter()
+                        .map(|expr| expr.to_string())
+                        .collect::<Vec<String>>()
+                        .join(", "),
+                    aggregates
+                        .iter()
+                        .map(|aggregate| aggregate.to_string())
+                        .collect::<Vec<String>>()
+                        .join(", "),
+                    Self::NEWLINE
+                )?;
+                source._fmt_recursive(f, indent + 1)
+            }
+            Node::Projection {
+                source,
+                fields,
+                subqueries,
+            } => {
                 write!(
                     f,
                     "{}- project [{}]{}",
                     indent_str,
-                    fields_description,
+                    describe_projection(fields),
                     Self::NEWLINE
                 )?;
-
+                Self::_fmt_subqueries(f, subqueries, indent)?;
                 source._fmt_recursive(f, indent + 1)
             }
             Node::Filter {
@@ -191,14 +715,53 @@ impl Node {
                 subqueries,
             } => {
                 write!(f, "{}- filter [{}]{}", indent_str, predicate, Self::NEWLINE)?;
-                if !subqueries.is_empty() {
-                    write!(f, "{}  > subqueries{}", indent_str, Self::NEWLINE)?;
-                    subqueries
+                Self::_fmt_subqueries(f, subqueries, indent)?;
+                source._fmt_recursive(f, indent + 1)
+            }
+            Node::With { tables, source } => {
+                write!(
+                    f,
+                    "{}- with [{}]{}",
+                    indent_str,
+                    tables
                         .iter()
-                        .try_for_each(|subquery| subquery._fmt_recursive(f, indent + 2))?;
+                        .map(|table| table.name.name.clone())
+                        .collect::<Vec<String>>()
+                        .join(", "),
+                    Self::NEWLINE
+                )?;
+                for table in tables {
+                    match &table.recursive {
+                        Some((operator, recursive)) => {
+                            write!(
+                                f,
+                                "{}  > {} [recursive, type={:?}]{}",
+                                indent_str,
+                                table.name.name,
+                                operator,
+                                Self::NEWLINE
+                            )?;
+                            table.source._fmt_recursive(f, indent + 2)?;
+                            recursive._fmt_recursive(f, indent + 2)?;
+                        }
+                        None => {
+                            write!(f, "{}  > {}{}", indent_str, table.name.name, Self::NEWLINE)?;
+                            table.source._fmt_recursive(f, indent + 2)?;
+                        }
+                    }
                 }
                 source._fmt_recursive(f, indent + 1)
             }
+            Node::CteScan { name, alias } => {
+                write!(
+                    f,
+                    "{}- cte_scan [{} as {}]{}",
+                    indent_str,
+                    name.name,
+                    alias.name,
+                    Self::NEWLINE
+                )
+            }
             Node::Subquery { source, alias } => {
                 write!(
                     f,
@@ -215,10 +778,52 @@ impl Node {
             Node::Scan { source, filter } => {
                 write!(
                     f,
-                    "{}- scan [{} as {}]{}",
+                    "{}- scan [{} as {}{}]{}",
+                    indent_str,
+                    source.name,
+                    source.alias.as_ref().unwrap_or(&source.name),
+                    Self::describe_filter(filter),
+                    Self::NEWLINE
+                )
+            }
+            Node::IndexScan {
+                source,
+                index,
+                lower,
+                upper,
+            } => {
+                let key = |key: &Vec<IntermediateExpr>| {
+                    key.iter()
+                        .map(|expr| expr.to_string())
+                        .collect::<Vec<String>>()
+                        .join(", ")
+                };
+                let lookup = match (lower, upper) {
+                    (Bound::Included(lower), Bound::Included(upper)) if lower == upper => {
+                        format!("key=({})", key(lower))
+                    }
+                    _ => {
+                        let (open, lower) = match lower {
+                            Bound::Included(lower) => ("[", key(lower)),
+                            Bound::Excluded(lower) => ("(", key(lower)),
+                            Bound::Unbounded => ("(", "-inf".to_string()),
+                        };
+                        let (upper, close) = match upper {
+                            Bound::Included(upper) => (key(upper), "]"),
+                            Bound::Excluded(upper) => (key(upper), ")"),
+                            Bound::Unbounded => ("+inf".to_string(), ")"),
+                        };
+                        format!("range={}{}, {}{}", open, lower, upper, close)
+                    }
+                };
+                write!(
+                    f,
+                    "{}- index_scan [{} as {}, index={}, {}]{}",
                     indent_str,
                     source.name,
                     source.alias.as_ref().unwrap_or(&source.name),
+                    index,
+                    lookup,
                     Self::NEWLINE
                 )
             }
@@ -237,6 +842,10 @@ impl Node {
                 source._fmt_recursive(f, indent + 1)?;
                 right._fmt_recursive(f, indent + 1)
             }
+            Node::Distinct { source } => {
+                write!(f, "{}- distinct{}", indent_str, Self::NEWLINE)?;
+                source._fmt_recursive(f, indent + 1)
+            }
             Node::Limit { source, limit } => {
                 write!(
                     f,
@@ -277,16 +886,58 @@ impl Node {
                 left._fmt_recursive(f, indent + 1)?;
                 right._fmt_recursive(f, indent + 1)
             }
+            Node::SemiJoin {
+                left,
+                join_type,
+                right,
+                keys,
+            } => {
+                write!(
+                    f,
+                    "{}- semi_join [type={:?}, {}]{}",
+                    indent_str,
+                    join_type,
+                    keys.iter()
+                        .map(|(left, right)| format!("({} IsEqual {})", left, right))
+                        .collect::<Vec<String>>()
+                        .join(", "),
+                    Self::NEWLINE
+                )?;
+                left._fmt_recursive(f, indent + 1)?;
+                right._fmt_recursive(f, indent + 1)
+            }
             Node::EvalScan { source, filter } => {
                 write!(
                     f,
-                    "{}- eval_scan [{}]{}",
+                    "{}- eval_scan [{}{}]{}",
                     indent_str,
                     source.expr,
+                    Self::describe_filter(filter),
+                    Self::NEWLINE
+                )
+            }
+            Node::Values { rows } => {
+                let rows_description = rows
+                    .iter()
+                    .map(|row| {
+                        format!(
+                            "({})",
+                            row.iter()
+                                .map(|expr| expr.to_string())
+                                .collect::<Vec<String>>()
+                                .join(", ")
+                        )
+                    })
+                    .collect::<Vec<String>>()
+                    .join(", ");
+                write!(
+                    f,
+                    "{}- values [{}]{}",
+                    indent_str,
+                    rows_description,
                     Self::NEWLINE
                 )
             }
-            _ => "<NotImplementedYet>".fmt(f),
         }
     }
 }
@@ -0,0 +1,790 @@
+use lykiadb_lang::ast::{
+    expr::{Expr, Operation},
+    sql::{SqlJoinType, SqlProjection},
+    AstNode, Spanned,
+};
+
+use super::{
+    decorrelate::{conjuncts, has_select},
+    fold::value_of,
+    name_of, CommonTable, IntermediateExpr, Node, Plan, Subquery, SubqueryKind,
+};
+
+/// Rules are applied over the whole plan again until it stops changing, or
+/// this many times at most.
+const MAX_PASSES: usize = 16;
+
+/// A rewrite of plan nodes that leaves their results as they are.
+pub trait Rule {
+    /// Rewrites the node, whose children have been rewritten already, or
+    /// gives it back unchanged. `top` tells whether the node is the top of
+    /// a query, which nothing but the query it is a subquery of reads from.
+    fn apply(&self, node: Node, top: bool) -> Node;
+}
+
+/// Rewrites the plans the planner builds with a list of rules.
+pub struct Optimizer {
+    rules: Vec<Box<dyn Rule>>,
+}
+
+impl Default for Optimizer {
+    fn default() -> Self {
+        Optimizer::new(vec![
+            Box::new(MergeFilters),
+            Box::new(RemoveConstantFilters),
+            Box::new(PushDownPredicates),
+            Box::new(PruneProjections),
+            Box::new(RemoveUnboundedLimits),
+        ])
+    }
+}
+
+impl Optimizer {
+    pub fn new(rules: Vec<Box<dyn Rule>>) -> Optimizer {
+        Optimizer { rules }
+    }
+
+    pub fn optimize(&self, plan: Plan) -> Plan {
+        match plan {
+            Plan::Select(node) => Plan::Select(self.optimize_node(node)),
+            Plan::Insert {
+                collection,
+                source,
+                on_conflict,
+                returning,
+            } => Plan::Insert {
+                collection,
+                source: self.optimize_node(source),
+                on_conflict,
+                returning,
+            },
+            Plan::Update {
+                collection,
+                assignments,
+                source,
+                returning,
+            } => Plan::Update {
+                collection,
+                assignments,
+                source: self.optimize_node(source),
+                returning,
+            },
+            Plan::Delete {
+                collection,
+                source,
+                returning,
+                full,
+            } => Plan::Delete {
+                collection,
+                source: self.optimize_node(source),
+                returning,
+                full,
+            },
+        }
+    }
+
+    fn optimize_node(&self, mut node: Node) -> Node {
+        for _ in 0..MAX_PASSES {
+            let previous = node.clone();
+            for rule in self.rules.iter() {
+                node = rewrite(node, true, &|node, top| rule.apply(node, top));
+            }
+            if node == previous {
+                break;
+            }
+        }
+        node
+    }
+}
+
+/// Rebuilds the node bottom up, handing every node to `f` once its children
+/// and the plans of its subqueries and common tables are rewritten, along
+/// with whether it is the top of a query.
+pub fn rewrite(node: Node, top: bool, f: &impl Fn(Node, bool) -> Node) -> Node {
+    let child = |node: Box<Node>| Box::new(rewrite(*node, false, f));
+    let query = |node: Box<Node>| Box::new(rewrite(*node, true, f));
+    let subqueries = |subqueries: Vec<Subquery>| {
+        subqueries
+            .into_iter()
+            .map(|subquery| Subquery {
+                node: rewrite(subquery.node, true, f),
+                ..subquery
+            })
+            .collect()
+    };
+
+    let node = match node {
+        Node::Compound {
+            source,
+            operator,
+            right,
+        } => Node::Compound {
+            source: query(source),
+            operator,
+            right: query(right),
+        },
+        Node::Aggregate {
+            source,
+            group_by,
+            aggregates,
+        } => Node::Aggregate {
+            source: child(source),
+            group_by,
+            aggregates,
+        },
+        Node::Window { source, windows } => Node::Window {
+            source: child(source),
+            windows,
+        },
+        Node::Filter {
+            source,
+            predicate,
+            subqueries: filter_subqueries,
+        } => Node::Filter {
+            source: child(source),
+            predicate,
+            subqueries: subqueries(filter_subqueries),
+        },
+        Node::Projection {
+            source,
+            fields,
+            subqueries: projection_subqueries,
+        } => Node::Projection {
+            source: child(source),
+            fields,
+            subqueries: subqueries(projection_subqueries),
+        },
+        Node::Distinct { source } => Node::Distinct {
+            source: child(source),
+        },
+        Node::Limit { source, limit } => Node::Limit {
+            source: child(source),
+            limit,
+        },
+        Node::Offset { source, offset } => Node::Offset {
+            source: child(source),
+            offset,
+        },
+        Node::Order { source, key } => Node::Order {
+            source: child(source),
+            key,
+        },
+        Node::Join {
+            left,
+            join_type,
+            right,
+            constraint,
+        } => Node::Join {
+            left: child(left),
+            join_type,
+            right: child(right),
+            constraint,
+        },
+        Node::SemiJoin {
+            left,
+            join_type,
+            right,
+            keys,
+        } => Node::SemiJoin {
+            left: child(left),
+            join_type,
+            right: query(right),
+            keys,
+        },
+        Node::Subquery { source, alias } => Node::Subquery {
+            source: query(source),
+            alias,
+        },
+        Node::With { tables, source } => Node::With {
+            tables: tables
+                .into_iter()
+                .map(|table| CommonTable {
+                    name: table.name,
+                    source: rewrite(table.source, true, f),
+                    recursive: table
+                        .recursive
+                        .map(|(operator, node)| (operator, rewrite(node, true, f))),
+                })
+                .collect(),
+            source: query(source),
+        },
+        leaf @ (Node::Values { .. }
+        | Node::Scan { .. }
+        | Node::IndexScan { .. }
+        | Node::EvalScan { .. }
+        | Node::CteScan { .. }
+        | Node::Nothing
+        | Node::Empty { .. }) => leaf,
+    };
+    f(node, top)
+}
+
+/// The field paths the expression reads.
+fn paths_of(expr: &Expr) -> Vec<Expr> {
+    let mut paths = vec![];
+    expr.walk::<(), ()>(&mut |e: &Expr| {
+        if let Expr::FieldPath { .. } = e {
+            paths.push(e.clone());
+        }
+        Some(Ok(()))
+    });
+    paths
+}
+
+/// Joins the conjuncts into a single predicate, if there are any.
+fn conjoin(exprs: Vec<Expr>) -> Option<Expr> {
+    exprs.into_iter().reduce(|left, right| Expr::Logical {
+        span: left.get_span().merge(&right.get_span()),
+        id: left.get_id(),
+        left: Box::new(left),
+        operation: Operation::And,
+        right: Box::new(right),
+    })
+}
+
+/// Filters the node with the conjuncts, unless there are none.
+fn filtered(node: Node, exprs: Vec<Expr>) -> Node {
+    match conjoin(exprs) {
+        Some(expr) => Node::Filter {
+            source: Box::new(node),
+            predicate: IntermediateExpr::Expr { expr },
+            subqueries: vec![],
+        },
+        None => node,
+    }
+}
+
+/// Adds the conjuncts to the filter of a scan.
+fn narrowed(filter: Option<IntermediateExpr>, mut exprs: Vec<Expr>) -> Option<IntermediateExpr> {
+    if let Some(IntermediateExpr::Expr { expr }) = filter {
+        exprs.insert(0, expr);
+    }
+    conjoin(exprs).map(|expr| IntermediateExpr::Expr { expr })
+}
+
+/// Combines a filter with the one right below it.
+struct MergeFilters;
+
+impl Rule for MergeFilters {
+    fn apply(&self, node: Node, _top: bool) -> Node {
+        match node {
+            Node::Filter {
+                source,
+                predicate: IntermediateExpr::Expr { expr: upper },
+                subqueries,
+            } if matches!(
+                source.as_ref(),
+                Node::Filter {
+                    predicate: IntermediateExpr::Expr { .. },
+                    ..
+                }
+            ) =>
+            {
+                let Node::Filter {
+                    source,
+                    predicate: IntermediateExpr::Expr { expr: lower },
+                    subqueries: mut lower_subqueries,
+                } = *source
+                else {
+                    unreachable!()
+                };
+                lower_subqueries.extend(subqueries);
+                Node::Filter {
+                    source,
+                    predicate: IntermediateExpr::Expr {
+                        expr: conjoin(vec![lower, upper]).unwrap(),
+                    },
+                    subqueries: lower_subqueries,
+                }
+            }
+            other => other,
+        }
+    }
+}
+
+/// Drops the conjuncts of a filter the planner folded into values that
+/// hold, and the filter if none are left. A filter with a conjunct that
+/// does not hold is replaced by no rows, and its source is never read.
+struct RemoveConstantFilters;
+
+impl RemoveConstantFilters {
+    /// Whether the conjunct holds, if it is a literal.
+    fn truth(conjunct: &Expr) -> Option<bool> {
+        match conjunct {
+            Expr::Literal { value, .. } => value_of(value).map(|value| value.as_bool()),
+            _ => None,
+        }
+    }
+}
+
+impl Rule for RemoveConstantFilters {
+    fn apply(&self, node: Node, _top: bool) -> Node {
+        let Node::Filter {
+            source,
+            predicate,
+            subqueries,
+        } = node
+        else {
+            return node;
+        };
+
+        let (holds, rest) = match &predicate {
+            IntermediateExpr::Constant(value) => (value.as_bool(), vec![]),
+            IntermediateExpr::Expr { expr } => {
+                let all = conjuncts(expr);
+                if all.iter().all(|conjunct| Self::truth(conjunct).is_none()) {
+                    return Node::Filter {
+                        source,
+                        predicate,
+                        subqueries,
+                    };
+                }
+                let holds = all
+                    .iter()
+                    .all(|conjunct| Self::truth(conjunct) != Some(false));
+                let rest = all
+                    .into_iter()
+                    .filter(|conjunct| Self::truth(conjunct).is_none())
+                    .cloned()
+                    .collect();
+                (holds, rest)
+            }
+        };
+        if !holds {
+            return Node::Empty {
+                aliases: source.aliases(),
+            };
+        }
+        match conjoin(rest) {
+            Some(expr) => Node::Filter {
+                source,
+                predicate: IntermediateExpr::Expr { expr },
+                subqueries,
+            },
+            None => *source,
+        }
+    }
+}
+
+/// Where a conjunct of a filter is evaluated once pushed down.
+#[derive(Clone, Copy, PartialEq)]
+enum Target {
+    Above,
+    Scan,
+    Left,
+    Right,
+}
+
+/// Moves the conjuncts of a filter into the scan below it, or to the side
+/// of the join below it whose rows they read. Conjuncts holding subqueries
+/// stay where they are, as do those an outer join would complete with
+/// nulls.
+struct PushDownPredicates;
+
+impl PushDownPredicates {
+    fn target(source: &Node, conjunct: &Expr) -> Target {
+        if has_select(conjunct) {
+            return Target::Above;
+        }
+        let paths = paths_of(conjunct);
+        let reads_only = |node: &Node| {
+            let aliases = node.aliases();
+            !paths.is_empty()
+                && paths.iter().all(|path| match path {
+                    Expr::FieldPath { head, .. } => aliases.contains(&head.name),
+                    _ => false,
+                })
+        };
+        match source {
+            Node::Scan { filter, .. } | Node::EvalScan { filter, .. }
+                if !matches!(filter, Some(IntermediateExpr::Constant(_))) =>
+            {
+                Target::Scan
+            }
+            Node::Join {
+                left,
+                join_type,
+                right,
+                ..
+            } => {
+                if *join_type != SqlJoinType::Right && reads_only(left) {
+                    Target::Left
+                } else if *join_type != SqlJoinType::Left && reads_only(right) {
+                    Target::Right
+                } else {
+                    Target::Above
+                }
+            }
+            // A semi join only filters the rows of its left side
+            Node::SemiJoin { .. } => Target::Left,
+            _ => Target::Above,
+        }
+    }
+}
+
+impl Rule for PushDownPredicates {
+    fn apply(&self, node: Node, _top: bool) -> Node {
+        let Node::Filter {
+            source,
+            predicate: IntermediateExpr::Expr { expr },
+            subqueries,
+        } = node
+        else {
+            return node;
+        };
+
+        let targets: Vec<(Target, Expr)> = conjuncts(&expr)
+            .into_iter()
+            .map(|conjunct| (Self::target(&source, conjunct), conjunct.clone()))
+            .collect();
+        if targets.iter().all(|(target, _)| *target == Target::Above) {
+            return Node::Filter {
+                source,
+                predicate: IntermediateExpr::Expr { expr },
+                subqueries,
+            };
+        }
+        let take = |target: Target| -> Vec<Expr> {
+            targets
+                .iter()
+                .filter(|(t, _)| *t == target)
+                .map(|(_, expr)| expr.clone())
+                .collect()
+        };
+
+        let source = match *source {
+            Node::Scan { source, filter } => Node::Scan {
+                source,
+                filter: narrowed(filter, take(Target::Scan)),
+            },
+            Node::EvalScan { source, filter } => Node::EvalScan {
+                source,
+                filter: narrowed(filter, take(Target::Scan)),
+            },
+            Node::Join {
+                left,
+                join_type,
+                right,
+                constraint,
+            } => Node::Join {
+                left: Box::new(filtered(*left, take(Target::Left))),
+                join_type,
+                right: Box::new(filtered(*right, take(Target::Right))),
+                constraint,
+            },
+            Node::SemiJoin {
+                left,
+                join_type,
+                right,
+                keys,
+            } => Node::SemiJoin {
+                left: Box::new(filtered(*left, take(Target::Left))),
+                join_type,
+                right,
+                keys,
+            },
+            other => other,
+        };
+        match conjoin(take(Target::Above)) {
+            Some(expr) => Node::Filter {
+                source: Box::new(source),
+                predicate: IntermediateExpr::Expr { expr },
+                subqueries,
+            },
+            None => source,
+        }
+    }
+}
+
+/// Drops projected fields nothing reads: those of an EXISTS subquery, which
+/// only counts rows, and those of a FROM subquery the enclosing query does
+/// not reference.
+struct PruneProjections;
+
+impl PruneProjections {
+    fn prune_exists(subqueries: Vec<Subquery>) -> Vec<Subquery> {
+        subqueries
+            .into_iter()
+            .map(|subquery| match subquery.node {
+                Node::Projection { source, .. } if subquery.kind == SubqueryKind::Exists => {
+                    Subquery {
+                        node: *source,
+                        ..subquery
+                    }
+                }
+                _ => subquery,
+            })
+            .collect()
+    }
+
+    /// Follows the filters, sorts, limits and projections from the top of a
+    /// query down to a FROM subquery, gathering the field paths read on the
+    /// way.
+    fn prune_source(node: Node, paths: &mut Vec<Expr>) -> Node {
+        match node {
+            Node::Projection {
+                source,
+                fields,
+                subqueries,
+            } if subqueries.is_empty()
+                && fields
+                    .iter()
+                    .all(|field| matches!(field, SqlProjection::Expr { .. })) =>
+            {
+                for field in fields.iter() {
+                    if let SqlProjection::Expr { expr, .. } = field {
+                        paths.extend(paths_of(expr));
+                    }
+                }
+                Node::Projection {
+                    source: Box::new(Self::prune_source(*source, paths)),
+                    fields,
+                    subqueries,
+                }
+            }
+            Node::Filter {
+                source,
+                predicate,
+                subqueries,
+            } if subqueries.is_empty() => {
+                Self::gather(&predicate, paths);
+                Node::Filter {
+                    source: Box::new(Self::prune_source(*source, paths)),
+                    predicate,
+                    subqueries,
+                }
+            }
+            Node::Order { source, key } => {
+                key.iter().for_each(|(expr, _)| Self::gather(expr, paths));
+                Node::Order {
+                    source: Box::new(Self::prune_source(*source, paths)),
+                    key,
+                }
+            }
+            Node::Limit { source, limit } => Node::Limit {
+                source: Box::new(Self::prune_source(*source, paths)),
+                limit,
+            },
+            Node::Offset { source, offset } => Node::Offset {
+                source: Box::new(Self::prune_source(*source, paths)),
+                offset,
+            },
+            Node::Subquery {
+                source,
+                alias: Some(alias),
+            } => {
+                let source = match *source {
+                    Node::Projection {
+                        source,
+                        fields,
+                        subqueries,
+                    } if Self::prunable(&fields, &alias.name, paths) => Node::Projection {
+                        source,
+                        fields: fields
+                            .into_iter()
+                            .filter(|field| Self::is_read(field, &alias.name, paths))
+                            .collect(),
+                        subqueries,
+                    },
+                    other => other,
+                };
+                Node::Subquery {
+                    source: Box::new(source),
+                    alias: Some(alias),
+                }
+            }
+            other => other,
+        }
+    }
+
+    fn gather(expr: &IntermediateExpr, paths: &mut Vec<Expr>) {
+        if let IntermediateExpr::Expr { expr } = expr {
+            paths.extend(paths_of(expr));
+        }
+    }
+
+    /// Fields can be told apart when they are all expressions, and the rows
+    /// are not read whole.
+    fn prunable(fields: &[SqlProjection], alias: &str, paths: &[Expr]) -> bool {
+        fields
+            .iter()
+            .all(|field| matches!(field, SqlProjection::Expr { .. }))
+            && !paths.iter().any(|path| {
+                matches!(path, Expr::FieldPath { head, tail, .. } if head.name == alias && tail.is_empty())
+            })
+    }
+
+    fn is_read(field: &SqlProjection, alias: &str, paths: &[Expr]) -> bool {
+        let SqlProjection::Expr { expr, alias: name } = field else {
+            return true;
+        };
+        let name = name
+            .as_ref()
+            .map(|name| name.name.clone())
+            .unwrap_or_else(|| name_of(expr));
+        paths.iter().any(|path| match path {
+            Expr::FieldPath { head, tail, .. } => {
+                head.name == name
+                    || (head.name == alias && tail.first().is_some_and(|field| field.name == name))
+            }
+            _ => false,
+        })
+    }
+}
+
+impl Rule for PruneProjections {
+    fn apply(&self, node: Node, top: bool) -> Node {
+        let node = match node {
+            Node::Filter {
+                source,
+                predicate,
+                subqueries,
+            } => Node::Filter {
+                source,
+                predicate,
+                subqueries: Self::prune_exists(subqueries),
+            },
+            Node::Projection {
+                source,
+                fields,
+                subqueries,
+            } => Node::Projection {
+                source,
+                fields,
+                subqueries: Self::prune_exists(subqueries),
+            },
+            other => other,
+        };
+        // Below the top, what the rest of the query reads is not known
+        if top {
+            return Self::prune_source(node, &mut vec![]);
+        }
+        node
+    }
+}
+
+/// A limit that is too large to ever be reached reads its source whole.
+struct RemoveUnboundedLimits;
+
+impl Rule for RemoveUnboundedLimits {
+    fn apply(&self, node: Node, _top: bool) -> Node {
+        match node {
+            Node::Limit { source, limit } if limit == usize::MAX => *source,
+            other => other,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use lykiadb_lang::ast::{sql::SqlCollectionIdentifier, Identifier, Literal, Span};
+
+    use super::*;
+    use crate::value::RV;
+
+    fn greater(field: &str, value: f64) -> Expr {
+        Expr::Binary {
+            left: Box::new(Expr::FieldPath {
+                head: Identifier::new(field, false),
+                tail: vec![],
+                span: Span::default(),
+                id: 0,
+            }),
+            operation: Operation::Greater,
+            right: Box::new(Expr::Literal {
+                value: Literal::Num(value),
+                raw: value.to_string(),
+                span: Span::default(),
+                id: 0,
+            }),
+            span: Span::default(),
+            id: 0,
+        }
+    }
+
+    fn filter(source: Node, expr: Expr) -> Node {
+        Node::Filter {
+            source: Box::new(source),
+            predicate: IntermediateExpr::Expr { expr },
+            subqueries: vec![],
+        }
+    }
+
+    fn scan() -> Node {
+        Node::Scan {
+            source: SqlCollectionIdentifier {
+                namespace: None,
+                name: Identifier::new("books", false),
+                alias: None,
+            },
+            filter: None,
+        }
+    }
+
+    #[test]
+    fn test_merge_filters() {
+        let optimizer = Optimizer::new(vec![Box::new(MergeFilters)]);
+        let node = filter(
+            filter(scan(), greater("year", 1950.0)),
+            greater("pages", 100.0),
+        );
+        assert_eq!(
+            optimizer.optimize_node(node),
+            filter(
+                scan(),
+                conjoin(vec![greater("year", 1950.0), greater("pages", 100.0)]).unwrap()
+            )
+        );
+    }
+
+    #[test]
+    fn test_remove_constant_filters() {
+        let optimizer = Optimizer::new(vec![Box::new(RemoveConstantFilters)]);
+        let holds = Node::Filter {
+            source: Box::new(scan()),
+            predicate: IntermediateExpr::Constant(RV::Bool(true)),
+            subqueries: vec![],
+        };
+        assert_eq!(optimizer.optimize_node(holds), scan());
+
+        let never = Expr::Literal {
+            value: Literal::Bool(false),
+            raw: "false".to_owned(),
+            span: Span::default(),
+            id: 0,
+        };
+        let node = filter(
+            scan(),
+            conjoin(vec![greater("year", 1950.0), never]).unwrap(),
+        );
+        assert_eq!(
+            optimizer.optimize_node(node),
+            Node::Empty {
+                aliases: vec!["books".to_owned()]
+            }
+        );
+    }
+
+    #[test]
+    fn test_default_rules() {
+        let optimizer = Optimizer::default();
+        let node = Node::Limit {
+            source: Box::new(filter(
+                filter(scan(), greater("year", 1950.0)),
+                greater("pages", 100.0),
+            )),
+            limit: usize::MAX,
+        };
+        let Node::Scan {
+            filter: Some(filter),
+            ..
+        } = optimizer.optimize_node(node)
+        else {
+            panic!("Expected the filters to be pushed into the scan");
+        };
+        assert_eq!(
+            filter.to_string(),
+            "(year Greater Num(1950.0)) And (pages Greater Num(100.0))"
+        );
+    }
+}
@@ -1,40 +1,170 @@
 use crate::{
+    catalog::{self, CollectionEntry},
     engine::{
         error::ExecutionError,
-        interpreter::{HaltReason, Interpreter},
+        interpreter::{HaltReason, InterpretError, Interpreter},
     },
-    value::RV,
+    value::{callable::Callable, RV},
 };
 
 use lykiadb_lang::ast::{
-    expr::Expr,
-    sql::{SqlFrom, SqlJoinType, SqlProjection, SqlSelect, SqlSelectCore, SqlSource},
+    expr::{Expr, Operation},
+    sql::{
+        SqlCollectionIdentifier, SqlCompoundOperator, SqlConflictAction, SqlDistinct,
+        SqlFrameBound, SqlFrameUnit, SqlFrom, SqlJoinType, SqlOnConflict, SqlProjection, SqlSelect,
+        SqlSelectCore, SqlSource, SqlValues, SqlWindow, SqlWindowFrame, SqlWith,
+    },
     visitor::VisitorMut,
-    Spanned,
+    Identifier, Span, Spanned,
 };
 
-use super::{scope::Scope, IntermediateExpr, Node, Plan, PlannerError};
+use super::{
+    decorrelate::decorrelate, fold::fold, index::choose_index, optimizer::Optimizer, scope::Scope,
+    Aggregate, CommonTable, Frame, FrameBound, IntermediateExpr, Node, OnConflict, Plan,
+    PlannerError, Subquery, SubqueryKind, Window, WindowFunction,
+};
 
 pub struct Planner<'a> {
     interpreter: &'a mut Interpreter,
+    ctes: Scope,
+    /// Sources of the queries being planned, the innermost last.
+    scopes: Vec<Scope>,
+    /// Field paths read by the queries being planned, which are not yet
+    /// known to resolve against their own sources.
+    references: Vec<Vec<Expr>>,
 }
 
 impl<'a> Planner<'a> {
     pub fn new(interpreter: &'a mut Interpreter) -> Planner {
-        Planner { interpreter }
+        Planner {
+            interpreter,
+            ctes: Scope::new(),
+            scopes: vec![],
+            references: vec![],
+        }
     }
 
+    /// Plans the statement, and then optimizes the plan.
     pub fn build(&mut self, expr: &Expr) -> Result<Plan, HaltReason> {
+        let plan = self.build_plan(expr)?;
+        Ok(Optimizer::default().optimize(plan))
+    }
+
+    fn build_plan(&mut self, expr: &Expr) -> Result<Plan, HaltReason> {
         match expr {
             Expr::Select { query, .. } => {
                 let plan = Plan::Select(self.build_select(query)?);
                 Ok(plan)
             }
+            Expr::Insert { command, .. } => {
+                let entry = self.resolve_collection(&command.collection)?;
+                let source = match &command.values {
+                    SqlValues::Values { values } => Node::Values {
+                        rows: values
+                            .iter()
+                            .map(|value| {
+                                vec![IntermediateExpr::Expr {
+                                    expr: value.clone(),
+                                }]
+                            })
+                            .collect(),
+                    },
+                    SqlValues::Select(query) => self.build_select(query)?,
+                };
+                let on_conflict = command
+                    .on_conflict
+                    .as_ref()
+                    .map(|on_conflict| self.build_on_conflict(&entry, on_conflict))
+                    .transpose()?;
+                Ok(Plan::Insert {
+                    collection: command.collection.clone(),
+                    source,
+                    on_conflict,
+                    returning: self.build_returning(&command.returning)?,
+                })
+            }
+            Expr::Update { command, .. } => {
+                self.resolve_collection(&command.collection)?;
+                self.enter_collection(&command.collection);
+                let mut source = Node::Scan {
+                    source: command.collection.clone(),
+                    filter: None,
+                };
+                if let Some(predicate) = &command.r#where {
+                    source = self.build_where(source, predicate)?;
+                }
+                for assignment in command.assignments.iter() {
+                    self.build_expr(&assignment.expr, true, false)?;
+                }
+                Ok(Plan::Update {
+                    collection: command.collection.clone(),
+                    assignments: command.assignments.clone(),
+                    source,
+                    returning: self.build_returning(&command.returning)?,
+                })
+            }
+            Expr::Delete { command, .. } => {
+                self.resolve_collection(&command.collection)?;
+                self.enter_collection(&command.collection);
+                let mut source = Node::Scan {
+                    source: command.collection.clone(),
+                    filter: None,
+                };
+                if let Some(predicate) = &command.r#where {
+                    source = self.build_where(source, predicate)?;
+                }
+                Ok(Plan::Delete {
+                    collection: command.collection.clone(),
+                    source,
+                    returning: self.build_returning(&command.returning)?,
+                    full: command.r#where.is_none(),
+                })
+            }
             _ => panic!("Not implemented yet."),
         }
     }
 
-    fn build_select_core(&mut self, core: &SqlSelectCore) -> Result<Node, HaltReason> {
+    /// Builds the plan of a SELECT core. Aggregates in the ORDER BY keys of
+    /// the query are computed along with those of the core, so the keys are
+    /// rewritten in place to read their results.
+    fn build_select_core(
+        &mut self,
+        core: &SqlSelectCore,
+        order_by: &mut [Expr],
+    ) -> Result<Node, HaltReason> {
+        self.references.push(vec![]);
+        let depth = self.scopes.len();
+        let node = self.build_core(core, order_by);
+        let scope = self.scopes.drain(depth..).next();
+        let references = self.references.pop().unwrap_or_default();
+
+        // Paths that name none of the sources of the core are left to the
+        // queries enclosing it
+        if let (Some(scope), Some(enclosing)) = (scope, self.references.last_mut()) {
+            enclosing.extend(
+                references
+                    .into_iter()
+                    .filter(|path| !scope.contains(&head_of(path).name)),
+            );
+        }
+        let mut node = node?;
+
+        // COMPOUND
+        if let Some(compound) = &core.compound {
+            node = Node::Compound {
+                source: Box::new(node),
+                operator: compound.operator.clone(),
+                right: Box::new(self.build_select_core(&compound.core, &mut [])?),
+            }
+        }
+        Ok(node)
+    }
+
+    fn build_core(
+        &mut self,
+        core: &SqlSelectCore,
+        order_by: &mut [Expr],
+    ) -> Result<Node, HaltReason> {
         let mut node: Node = Node::Nothing;
 
         let mut parent_scope = Scope::new();
@@ -43,48 +173,385 @@ impl<'a> Planner<'a> {
         if let Some(from) = &core.from {
             node = self.build_from(from, &mut parent_scope)?;
         }
+        self.scopes.push(parent_scope.clone());
 
         // WHERE
         if let Some(predicate) = &core.r#where {
-            let (expr, subqueries): (IntermediateExpr, Vec<Node>) =
-                self.build_expr(predicate.as_ref(), true, false)?;
-            node = Node::Filter {
-                source: Box::new(node),
-                predicate: expr,
-                subqueries,
-            }
+            node = self.build_where(node, predicate)?;
         }
 
         // AGGREGATES
+        let mut aggregates: Vec<Aggregate> = vec![];
+        let mut projection = core.projection.clone();
+        for field in projection.iter_mut() {
+            if let SqlProjection::Expr { expr, .. } = field {
+                **expr = extract_aggregates(self.interpreter, expr, &mut aggregates)?;
+            }
+        }
+        let having = core
+            .having
+            .as_ref()
+            .map(|having| extract_aggregates(self.interpreter, having, &mut aggregates))
+            .transpose()?;
+        for key in order_by.iter_mut() {
+            *key = extract_aggregates(self.interpreter, key, &mut aggregates)?;
+        }
 
         // GROUP BY
+        if core.group_by.is_some() || !aggregates.is_empty() {
+            let group_by = core.group_by.clone().unwrap_or_default();
+            let mut keys = vec![];
+            for expr in group_by.iter() {
+                keys.push(self.build_expr(expr, false, false)?.0);
+            }
 
-        // PROJECTION
-        if core.projection.as_slice() != [SqlProjection::All { collection: None }] {
-            for projection in &core.projection {
-                if let SqlProjection::Expr { expr, .. } = projection {
-                    self.build_expr(expr, false, true)?;
+            let mut allowed: Vec<String> = aggregates.iter().map(|a| a.to_string()).collect();
+            for field in projection.iter() {
+                match field {
+                    SqlProjection::All { collection } => {
+                        return Err(ungrouped(
+                            "*",
+                            collection.as_ref().map(|c| c.span).unwrap_or_default(),
+                        ));
+                    }
+                    SqlProjection::Expr { expr, .. } => {
+                        check_grouped(expr, &group_by, &allowed, &parent_scope)?
+                    }
                 }
             }
-            node = Node::Projection {
+            if let Some(having) = &having {
+                check_grouped(having, &group_by, &allowed, &parent_scope)?;
+            }
+            // ORDER BY may also sort by the projected fields
+            allowed.extend(projection.iter().filter_map(|field| match field {
+                SqlProjection::Expr {
+                    alias: Some(alias), ..
+                } => Some(alias.name.clone()),
+                _ => None,
+            }));
+            for key in order_by.iter() {
+                check_grouped(key, &group_by, &allowed, &parent_scope)?;
+            }
+
+            for aggregate in aggregates.iter() {
+                aggregate
+                    .args()
+                    .iter()
+                    .for_each(|arg| self.note_references(arg));
+            }
+            node = Node::Aggregate {
                 source: Box::new(node),
-                fields: core.projection.clone(),
+                group_by: keys,
+                aggregates,
             };
         }
 
         // HAVING
+        if let Some(having) = having {
+            let (predicate, subqueries) = self.build_expr(&having, true, false)?;
+            node = Node::Filter {
+                source: Box::new(node),
+                predicate,
+                subqueries,
+            };
+        }
 
-        // COMPOUND
-        if let Some(compound) = &core.compound {
-            node = Node::Compound {
+        // WINDOWS
+        let mut windows: Vec<Window> = vec![];
+        for field in projection.iter_mut() {
+            if let SqlProjection::Expr { expr, .. } = field {
+                **expr = self.extract_windows(expr, &mut windows)?;
+            }
+        }
+        for key in order_by.iter_mut() {
+            *key = self.extract_windows(key, &mut windows)?;
+        }
+        if !windows.is_empty() {
+            node = Node::Window {
                 source: Box::new(node),
-                operator: compound.operator.clone(),
-                right: Box::new(self.build_select_core(&compound.core)?),
+                windows,
+            };
+        }
+
+        // PROJECTION
+        if projection.as_slice() != [SqlProjection::All { collection: None }] {
+            let mut subqueries = vec![];
+            for field in &projection {
+                if let SqlProjection::Expr { expr, .. } = field {
+                    subqueries.extend(self.build_expr(expr, true, true)?.1);
+                }
             }
+            node = Node::Projection {
+                source: Box::new(node),
+                fields: projection,
+                subqueries,
+            };
+        }
+
+        // DISTINCT
+        if core.distinct == SqlDistinct::Distinct {
+            node = Node::Distinct {
+                source: Box::new(node),
+            };
         }
         Ok(node)
     }
 
+    /// Finds the unique index conflicts on the target fields are detected
+    /// with.
+    fn build_on_conflict(
+        &mut self,
+        entry: &CollectionEntry,
+        on_conflict: &SqlOnConflict,
+    ) -> Result<OnConflict, HaltReason> {
+        let mut fields: Vec<String> = on_conflict
+            .target
+            .iter()
+            .map(|path| path.to_string())
+            .collect();
+        fields.sort();
+
+        let index = entry.indexes.iter().find(|index| {
+            let mut indexed = index.fields.clone();
+            indexed.sort();
+            index.unique && indexed == fields
+        });
+        let Some(index) = index else {
+            let first = on_conflict.target.first().unwrap().get_span();
+            let last = on_conflict.target.last().unwrap().get_span();
+            return Err(HaltReason::Error(ExecutionError::Plan(
+                PlannerError::ConflictTargetNotUnique {
+                    collection: entry.storage_name(),
+                    fields,
+                    span: first.merge(&last),
+                },
+            )));
+        };
+
+        if let SqlConflictAction::Update { assignments } = &on_conflict.action {
+            for assignment in assignments.iter() {
+                self.build_expr(&assignment.expr, true, false)?;
+            }
+        }
+        Ok(OnConflict {
+            index: index.name.clone(),
+            action: on_conflict.action.clone(),
+        })
+    }
+
+    /// Checks the RETURNING list of a write, which is projected from each
+    /// written document.
+    fn build_returning(
+        &mut self,
+        returning: &Option<Vec<SqlProjection>>,
+    ) -> Result<Option<Vec<SqlProjection>>, HaltReason> {
+        if let Some(fields) = returning {
+            for field in fields {
+                if let SqlProjection::Expr { expr, .. } = field {
+                    self.build_expr(expr, false, false)?;
+                }
+            }
+        }
+        Ok(returning.clone())
+    }
+
+    /// Filters the rows of the node, reading the collection through an index
+    /// when one serves the predicate. Correlated subqueries the predicate
+    /// tests are turned into joins where possible.
+    fn build_where(&mut self, mut node: Node, predicate: &Expr) -> Result<Node, HaltReason> {
+        let indexes = match &node {
+            Node::Scan { source, .. } => self.resolve_collection(source)?.indexes,
+            _ => vec![],
+        };
+
+        let (expr, subqueries): (IntermediateExpr, Vec<Subquery>) =
+            self.build_expr(predicate, true, false)?;
+        if let (Node::Scan { source, .. }, IntermediateExpr::Expr { expr }) = (&node, &expr) {
+            if let Some(index_scan) = choose_index(source, &indexes, expr) {
+                node = index_scan;
+            }
+        }
+        Ok(decorrelate(Node::Filter {
+            source: Box::new(node),
+            predicate: expr,
+            subqueries,
+        }))
+    }
+
+    /// Replaces the window function calls in the expression with references
+    /// to their results, which the window node names after them.
+    fn extract_windows(
+        &mut self,
+        expr: &Expr,
+        windows: &mut Vec<Window>,
+    ) -> Result<Expr, HaltReason> {
+        expr.rewrite(&mut |e| {
+            let Expr::Call {
+                over: Some(over),
+                span,
+                id,
+                ..
+            } = e
+            else {
+                return Ok(None);
+            };
+            let window = self.build_window(e, over)?;
+            let name = window.to_string();
+            if !windows.contains(&window) {
+                windows.push(window);
+            }
+            Ok(Some(Expr::FieldPath {
+                head: Identifier {
+                    name,
+                    dollar: false,
+                    span: *span,
+                },
+                tail: vec![],
+                span: *span,
+                id: *id,
+            }))
+        })
+    }
+
+    fn build_window(&mut self, call: &Expr, over: &SqlWindow) -> Result<Window, HaltReason> {
+        let Expr::Call {
+            callee, args, span, ..
+        } = call
+        else {
+            unreachable!("Windows are only computed for calls");
+        };
+        self.note_references(call);
+        for arg in args.iter() {
+            if let Some(nested) = find_window(arg) {
+                return Err(HaltReason::Error(ExecutionError::Plan(
+                    PlannerError::WindowNotAllowed(nested),
+                )));
+            }
+        }
+
+        let arity_mismatch = |expected: usize| {
+            HaltReason::Error(
+                InterpretError::ArityMismatch {
+                    span: *span,
+                    expected,
+                    found: args.len(),
+                }
+                .into(),
+            )
+        };
+        let function = match aggregate_of_call(self.interpreter, call)? {
+            Some(aggregate) => WindowFunction::Aggregate(Box::new(aggregate)),
+            None => {
+                let name = match callee.as_ref() {
+                    Expr::Variable { name, .. } if !name.dollar => name.name.to_lowercase(),
+                    _ => String::new(),
+                };
+                match name.as_str() {
+                    "row_number" | "rank" | "dense_rank" if !args.is_empty() => {
+                        return Err(arity_mismatch(0));
+                    }
+                    "row_number" => WindowFunction::RowNumber,
+                    "rank" => WindowFunction::Rank,
+                    "dense_rank" => WindowFunction::DenseRank,
+                    "lag" | "lead" if args.is_empty() => return Err(arity_mismatch(1)),
+                    "lag" | "lead" if args.len() > 3 => return Err(arity_mismatch(3)),
+                    "lag" => WindowFunction::Lag(args.clone()),
+                    "lead" => WindowFunction::Lead(args.clone()),
+                    _ => {
+                        return Err(HaltReason::Error(ExecutionError::Plan(
+                            PlannerError::UnknownWindowFunction {
+                                name: callee.to_string(),
+                                span: callee.get_span(),
+                            },
+                        )))
+                    }
+                }
+            }
+        };
+
+        let mut partition_by = vec![];
+        for key in over.partition_by.iter() {
+            partition_by.push(self.build_expr(key, false, true)?.0);
+        }
+        let mut order_by = vec![];
+        for key in over.order_by.iter() {
+            order_by.push((
+                self.build_expr(&key.expr, false, true)?.0,
+                key.ordering.clone(),
+            ));
+        }
+        let frame = self.build_frame(&over.frame, !order_by.is_empty(), *span)?;
+
+        Ok(Window {
+            function,
+            partition_by,
+            order_by,
+            frame,
+        })
+    }
+
+    /// Frames default to the whole partition, or with an order to the rows
+    /// up to the peers of the current one. Offsets are only allowed in
+    /// frames of rows, and frames cannot start after they end.
+    fn build_frame(
+        &mut self,
+        frame: &Option<SqlWindowFrame>,
+        ordered: bool,
+        span: Span,
+    ) -> Result<Frame, HaltReason> {
+        let Some(frame) = frame else {
+            return Ok(match ordered {
+                true => Frame {
+                    unit: SqlFrameUnit::Range,
+                    start: FrameBound::UnboundedPreceding,
+                    end: FrameBound::CurrentRow,
+                },
+                false => Frame {
+                    unit: SqlFrameUnit::Rows,
+                    start: FrameBound::UnboundedPreceding,
+                    end: FrameBound::UnboundedFollowing,
+                },
+            });
+        };
+
+        let invalid =
+            || HaltReason::Error(ExecutionError::Plan(PlannerError::InvalidWindowFrame(span)));
+        let mut bound = |bound: &SqlFrameBound| -> Result<(FrameBound, i64), HaltReason> {
+            let mut offset = |offset: &Expr| match self.eval_constant(offset)?.as_number() {
+                Some(offset) if frame.unit == SqlFrameUnit::Rows && offset >= 0.0 => {
+                    Ok(offset.floor() as usize)
+                }
+                _ => Err(invalid()),
+            };
+            Ok(match bound {
+                SqlFrameBound::UnboundedPreceding => (FrameBound::UnboundedPreceding, i64::MIN),
+                SqlFrameBound::Preceding { offset: expr } => {
+                    let offset = offset(expr)?;
+                    (FrameBound::Preceding(offset), -(offset as i64))
+                }
+                SqlFrameBound::CurrentRow => (FrameBound::CurrentRow, 0),
+                SqlFrameBound::Following { offset: expr } => {
+                    let offset = offset(expr)?;
+                    (FrameBound::Following(offset), offset as i64)
+                }
+                SqlFrameBound::UnboundedFollowing => (FrameBound::UnboundedFollowing, i64::MAX),
+            })
+        };
+        let (start, start_position) = bound(&frame.start)?;
+        let (end, end_position) = bound(&frame.end)?;
+        if start == FrameBound::UnboundedFollowing
+            || end == FrameBound::UnboundedPreceding
+            || start_position > end_position
+        {
+            return Err(invalid());
+        }
+        Ok(Frame {
+            unit: frame.unit,
+            start,
+            end,
+        })
+    }
+
     fn eval_constant(&mut self, expr: &Expr) -> Result<RV, HaltReason> {
         self.interpreter.visit_expr(expr)
     }
@@ -94,37 +561,56 @@ impl<'a> Planner<'a> {
         expr: &Expr,
         allow_subqueries: bool,
         allow_aggregates: bool,
-    ) -> Result<(IntermediateExpr, Vec<Node>), HaltReason> {
-        // TODO(vck): Implement this
+    ) -> Result<(IntermediateExpr, Vec<Subquery>), HaltReason> {
+        if let Some(span) = find_window(expr) {
+            return Err(HaltReason::Error(ExecutionError::Plan(
+                PlannerError::WindowNotAllowed(span),
+            )));
+        }
+        if !allow_aggregates {
+            if let Some(span) = find_aggregate(self.interpreter, expr) {
+                return Err(HaltReason::Error(ExecutionError::Plan(
+                    PlannerError::AggregateNotAllowed(span),
+                )));
+            }
+        }
 
-        let mut subqueries: Vec<Node> = vec![];
+        self.note_references(expr);
+        let kinds = subquery_kinds(expr);
+        let mut subqueries: Vec<Subquery> = vec![];
 
         let result = expr.walk::<(), HaltReason>(&mut |e: &Expr| match e {
-            Expr::Get { object, name, .. } => {
-                println!("Get {}.({})", object, name);
-                None
-            }
-            Expr::FieldPath { head, tail, .. } => {
-                println!(
-                    "FieldPath {} {}",
-                    head,
-                    tail.iter().map(|x| x.to_string()).collect::<String>()
-                );
-                None
-            }
-            Expr::Call { callee, args, .. } => {
-                println!("Call {}({:?})", callee, args);
-                None
-            }
-            Expr::Select { query, .. } => {
+            Expr::FieldPath { .. } => None,
+            Expr::Select { query, id, .. } => {
                 if !allow_subqueries {
                     return Some(Err(HaltReason::Error(ExecutionError::Plan(
                         PlannerError::SubqueryNotAllowed(expr.get_span()),
                     ))));
                 }
-                let subquery = self.build_select(query);
-                subqueries.push(subquery.unwrap());
-                None
+                let kind = kinds
+                    .iter()
+                    .find(|(select, _)| select == id)
+                    .map(|(_, kind)| *kind)
+                    .unwrap_or(SubqueryKind::Scalar);
+                // IN and scalar subqueries are unwrapped to the single field
+                // they select, so anything else has no value to unwrap to
+                if kind != SubqueryKind::Exists && !selects_single_field(query) {
+                    return Some(Err(HaltReason::Error(ExecutionError::Plan(
+                        PlannerError::MultipleFieldsInSubquery(e.get_span()),
+                    ))));
+                }
+                match self.build_subquery(query) {
+                    Ok((node, outer)) => {
+                        subqueries.push(Subquery {
+                            id: *id,
+                            kind,
+                            node,
+                            outer,
+                        });
+                        None
+                    }
+                    Err(err) => Some(Err(err)),
+                }
             }
             _ => Some(Ok(())),
         });
@@ -133,17 +619,138 @@ impl<'a> Planner<'a> {
             return Err(err);
         }
 
-        Ok((IntermediateExpr::Expr { expr: expr.clone() }, subqueries))
+        Ok((fold(self.interpreter, expr), subqueries))
+    }
+
+    /// Makes the collection a statement writes to a source the subqueries
+    /// of its WHERE clause can reference.
+    fn enter_collection(&mut self, ident: &SqlCollectionIdentifier) {
+        let mut scope = Scope::new();
+        scope
+            .add_source(SqlSource::Collection(ident.clone()))
+            .expect("A new scope holds no sources");
+        self.scopes.push(scope);
+    }
+
+    /// Plans a subquery along with the paths it reads from the sources of
+    /// the queries enclosing it.
+    fn build_subquery(&mut self, query: &SqlSelect) -> Result<(Node, Vec<Expr>), HaltReason> {
+        self.references.push(vec![]);
+        let node = self.build_select(query);
+        let references = self.references.pop().unwrap_or_default();
+        let node = node?;
+
+        let mut outer: Vec<Expr> = vec![];
+        for path in references {
+            let head = &head_of(&path).name;
+            if self.scopes.iter().any(|scope| scope.contains(head)) && !outer.contains(&path) {
+                outer.push(path);
+            }
+        }
+        if let Some(enclosing) = self.references.last_mut() {
+            enclosing.extend(outer.iter().cloned());
+        }
+        Ok((node, outer))
+    }
+
+    /// Records the field paths the expression reads, so that the ones
+    /// naming the sources of an enclosing query can be told apart.
+    fn note_references(&mut self, expr: &Expr) {
+        let Some(references) = self.references.last_mut() else {
+            return;
+        };
+        expr.walk::<(), ()>(&mut |e: &Expr| match e {
+            Expr::Select { .. } => None,
+            Expr::FieldPath { .. } => {
+                references.push(e.clone());
+                None
+            }
+            _ => Some(Ok(())),
+        });
     }
 
     fn build_select(&mut self, query: &SqlSelect) -> Result<Node, HaltReason> {
-        let mut node: Node = self.build_select_core(&query.core)?;
+        let Some(with) = &query.with else {
+            return self.build_query(query);
+        };
+        // The tables are only visible within the query that names them
+        let outer = self.ctes.clone();
+        let node = self.build_with(with).and_then(|tables| {
+            Ok(Node::With {
+                tables,
+                source: Box::new(self.build_query(query)?),
+            })
+        });
+        self.ctes = outer;
+        node
+    }
+
+    /// Plans the tables of a WITH clause. The name of a table is registered
+    /// once it is planned, so the tables that follow it and the query can
+    /// read it. Under WITH RECURSIVE, a table whose query is a UNION also
+    /// reads itself in the part after the UNION.
+    fn build_with(&mut self, with: &SqlWith) -> Result<Vec<CommonTable>, HaltReason> {
+        let mut tables = vec![];
+        for cte in with.ctes.iter() {
+            let compound = cte.query.core.compound.as_ref().filter(|compound| {
+                with.recursive
+                    && matches!(
+                        compound.operator,
+                        SqlCompoundOperator::Union | SqlCompoundOperator::UnionAll
+                    )
+            });
+            let table = match compound {
+                Some(compound) => {
+                    let anchor = SqlSelectCore {
+                        compound: None,
+                        ..cte.query.core.clone()
+                    };
+                    let source = self.build_select_core(&anchor, &mut [])?;
+                    self.register_cte(&cte.name)?;
+                    CommonTable {
+                        name: cte.name.clone(),
+                        source,
+                        recursive: Some((
+                            compound.operator.clone(),
+                            self.build_select_core(&compound.core, &mut [])?,
+                        )),
+                    }
+                }
+                None => {
+                    let source = self.build_select(&cte.query)?;
+                    self.register_cte(&cte.name)?;
+                    CommonTable {
+                        name: cte.name.clone(),
+                        source,
+                        recursive: None,
+                    }
+                }
+            };
+            tables.push(table);
+        }
+        Ok(tables)
+    }
+
+    fn register_cte(&mut self, name: &Identifier) -> Result<(), HaltReason> {
+        self.ctes
+            .add_cte(name.clone())
+            .map_err(|err| HaltReason::Error(ExecutionError::Plan(err)))
+    }
+
+    fn build_query(&mut self, query: &SqlSelect) -> Result<Node, HaltReason> {
+        let mut order_keys: Vec<Expr> = query
+            .order_by
+            .iter()
+            .flatten()
+            .map(|key| (*key.expr).clone())
+            .collect();
+        let mut node: Node = self.build_select_core(&query.core, &mut order_keys)?;
 
         if let Some(order_by) = &query.order_by {
             let mut order_key = vec![];
 
-            for key in order_by {
-                let (expr, _) = self.build_expr(&key.expr, false, true)?;
+            for (key, expr) in order_by.iter().zip(order_keys.iter()) {
+                let (expr, _) = self.build_expr(expr, false, true)?;
                 order_key.push((expr, key.ordering.clone()));
             }
 
@@ -177,16 +784,48 @@ impl<'a> Planner<'a> {
         Ok(node)
     }
 
+    fn resolve_collection(
+        &mut self,
+        ident: &SqlCollectionIdentifier,
+    ) -> Result<CollectionEntry, HaltReason> {
+        let transaction = self.interpreter.transaction().unwrap();
+        match catalog::find_collection(transaction, ident).map_err(HaltReason::Error)? {
+            Some(entry) => Ok(entry),
+            None => Err(HaltReason::Error(ExecutionError::Plan(
+                PlannerError::CollectionNotFound {
+                    namespace: catalog::namespace_of(ident).to_string(),
+                    name: ident.name.name.clone(),
+                    span: catalog::span_of(ident),
+                },
+            ))),
+        }
+    }
+
     fn build_from(&mut self, from: &SqlFrom, parent_scope: &mut Scope) -> Result<Node, HaltReason> {
         let mut scope = Scope::new();
 
         let node = match from {
             SqlFrom::Source(source) => {
                 let wrapped = match source {
-                    SqlSource::Collection(ident) => Node::Scan {
-                        source: ident.clone(),
-                        filter: None,
-                    },
+                    SqlSource::Collection(ident) => {
+                        let cte = self
+                            .ctes
+                            .cte(&ident.name.name)
+                            .filter(|_| ident.namespace.is_none());
+                        match cte {
+                            Some(name) => Node::CteScan {
+                                name: name.clone(),
+                                alias: ident.alias.clone().unwrap_or(ident.name.clone()),
+                            },
+                            None => {
+                                self.resolve_collection(ident)?;
+                                Node::Scan {
+                                    source: ident.clone(),
+                                    filter: None,
+                                }
+                            }
+                        }
+                    }
                     SqlSource::Expr(expr) => Node::EvalScan {
                         source: expr.clone(),
                         filter: None,
@@ -246,3 +885,273 @@ impl<'a> Planner<'a> {
         node
     }
 }
+
+/// Head of a field path expression.
+fn head_of(path: &Expr) -> &Identifier {
+    match path {
+        Expr::FieldPath { head, .. } => head,
+        _ => unreachable!("Only field paths are referenced"),
+    }
+}
+
+/// Whether every core of the query, compound ones included, selects exactly
+/// one field.
+fn selects_single_field(query: &SqlSelect) -> bool {
+    let mut core = Some(&query.core);
+    while let Some(current) = core {
+        if !matches!(current.projection.as_slice(), [SqlProjection::Expr { .. }]) {
+            return false;
+        }
+        core = current.compound.as_ref().map(|compound| &compound.core);
+    }
+    true
+}
+
+/// Kinds of the subqueries that are the operand of an IN or EXISTS in the
+/// expression, by the id of the subquery. The rest stand for a value.
+fn subquery_kinds(expr: &Expr) -> Vec<(usize, SubqueryKind)> {
+    fn select_id(expr: &Expr) -> Option<usize> {
+        match expr {
+            Expr::Select { id, .. } => Some(*id),
+            Expr::Grouping { expr, .. } => select_id(expr),
+            _ => None,
+        }
+    }
+
+    let mut kinds = vec![];
+    expr.walk::<(), ()>(&mut |e: &Expr| {
+        let kind = match e {
+            Expr::Binary {
+                operation: Operation::In | Operation::NotIn,
+                right,
+                ..
+            } => select_id(right).map(|id| (id, SubqueryKind::In)),
+            Expr::Unary {
+                operation: Operation::Exists | Operation::NotExists,
+                expr,
+                ..
+            } => select_id(expr).map(|id| (id, SubqueryKind::Exists)),
+            _ => None,
+        };
+        kinds.extend(kind);
+        Some(Ok(()))
+    });
+    kinds
+}
+
+/// The user-defined aggregate a callee names, if it does. Only variables and
+/// the properties of their objects are looked up, as evaluating them has no
+/// side effects.
+fn aggregator_of(interpreter: &mut Interpreter, callee: &Expr) -> Option<Callable> {
+    fn is_path(expr: &Expr) -> bool {
+        match expr {
+            Expr::Variable { .. } => true,
+            Expr::Get { object, .. } => is_path(object),
+            _ => false,
+        }
+    }
+    if !is_path(callee) {
+        return None;
+    }
+    match interpreter.visit_expr(callee) {
+        Ok(RV::Callable(callable)) if callable.as_aggregator().is_some() => Some(callable),
+        _ => None,
+    }
+}
+
+fn is_aggregate(interpreter: &mut Interpreter, expr: &Expr) -> bool {
+    match expr {
+        // Aggregates over windows are computed for each row instead
+        Expr::Call { callee, over, .. } if over.is_none() => {
+            Aggregate::function_of(expr).is_some() || aggregator_of(interpreter, callee).is_some()
+        }
+        _ => false,
+    }
+}
+
+/// Span of the first aggregate call in the expression, if there is one.
+fn find_aggregate(interpreter: &mut Interpreter, expr: &Expr) -> Option<Span> {
+    let mut found = None;
+    expr.walk::<(), HaltReason>(&mut |e| {
+        if found.is_some() {
+            return None;
+        }
+        if is_aggregate(interpreter, e) {
+            found = Some(e.get_span());
+            return None;
+        }
+        Some(Ok(()))
+    });
+    found
+}
+
+/// The aggregate a call is made to, if it is one, after checking its
+/// arguments. Whether it is computed over a window is left to the caller.
+fn aggregate_of_call(
+    interpreter: &mut Interpreter,
+    expr: &Expr,
+) -> Result<Option<Aggregate>, HaltReason> {
+    let Expr::Call {
+        callee, args, span, ..
+    } = expr
+    else {
+        return Ok(None);
+    };
+    let function = Aggregate::function_of(expr);
+    let arity = match &function {
+        Some(_) => Some(1),
+        None => match aggregator_of(interpreter, callee) {
+            Some(aggregator) => aggregator.arity,
+            None => return Ok(None),
+        },
+    };
+    if let Some(expected) = arity.filter(|expected| *expected != args.len()) {
+        return Err(HaltReason::Error(
+            InterpretError::ArityMismatch {
+                span: *span,
+                expected,
+                found: args.len(),
+            }
+            .into(),
+        ));
+    }
+    for arg in args.iter() {
+        if let Some(nested) = find_aggregate(interpreter, arg) {
+            return Err(HaltReason::Error(ExecutionError::Plan(
+                PlannerError::AggregateNotAllowed(nested),
+            )));
+        }
+        if let Some(nested) = find_window(arg) {
+            return Err(HaltReason::Error(ExecutionError::Plan(
+                PlannerError::WindowNotAllowed(nested),
+            )));
+        }
+    }
+
+    Ok(Some(match function {
+        Some(function) => Aggregate::new(&function, args[0].clone()),
+        None => Aggregate::Custom {
+            callee: callee.clone(),
+            args: args.clone(),
+        },
+    }))
+}
+
+/// Replaces the aggregate calls in the expression with references to their
+/// results, which the aggregate node names after them. Every distinct
+/// aggregate is collected once. Calls over windows are left in place, though
+/// the aggregates in their arguments are extracted.
+fn extract_aggregates(
+    interpreter: &mut Interpreter,
+    expr: &Expr,
+    aggregates: &mut Vec<Aggregate>,
+) -> Result<Expr, HaltReason> {
+    expr.rewrite(&mut |e| {
+        let Expr::Call {
+            over: None,
+            span,
+            id,
+            ..
+        } = e
+        else {
+            return Ok(None);
+        };
+        let Some(aggregate) = aggregate_of_call(interpreter, e)? else {
+            return Ok(None);
+        };
+        let name = aggregate.to_string();
+        if !aggregates.contains(&aggregate) {
+            aggregates.push(aggregate);
+        }
+        Ok(Some(Expr::FieldPath {
+            head: Identifier {
+                name,
+                dollar: false,
+                span: *span,
+            },
+            tail: vec![],
+            span: *span,
+            id: *id,
+        }))
+    })
+}
+
+/// Span of the first call over a window in the expression, if there is one.
+fn find_window(expr: &Expr) -> Option<Span> {
+    let mut found = None;
+    expr.walk::<(), HaltReason>(&mut |e| {
+        if found.is_some() {
+            return None;
+        }
+        if let Expr::Call {
+            over: Some(_),
+            span,
+            ..
+        } = e
+        {
+            found = Some(*span);
+            return None;
+        }
+        Some(Ok(()))
+    });
+    found
+}
+
+/// Whether two expressions read the same value of a grouped row, where a
+/// field path may or may not start with the alias of its source.
+fn same_value(grouped: &Expr, expr: &Expr, scope: &Scope) -> bool {
+    if grouped == expr {
+        return true;
+    }
+    let segments = |e: &Expr| match e {
+        Expr::FieldPath { head, tail, .. } => Some(
+            std::iter::once(head)
+                .chain(tail.iter())
+                .map(|ident| ident.name.clone())
+                .collect::<Vec<String>>(),
+        ),
+        _ => None,
+    };
+    let (Some(a), Some(b)) = (segments(grouped), segments(expr)) else {
+        return false;
+    };
+    let qualified = |long: &[String], short: &[String]| {
+        long.len() == short.len() + 1 && scope.contains(&long[0]) && long[1..] == *short
+    };
+    qualified(&a, &b) || qualified(&b, &a)
+}
+
+/// Fails on the first field the expression reads that is neither grouped
+/// on, nor one of the allowed names such as the results of aggregates.
+fn check_grouped(
+    expr: &Expr,
+    group_by: &[Expr],
+    allowed: &[String],
+    scope: &Scope,
+) -> Result<(), HaltReason> {
+    let mut offending = None;
+    expr.walk::<(), HaltReason>(&mut |e| {
+        if offending.is_some() || group_by.iter().any(|grouped| same_value(grouped, e, scope)) {
+            return None;
+        }
+        match e {
+            Expr::FieldPath { head, .. } if allowed.contains(&head.name) => None,
+            Expr::FieldPath { .. } => {
+                offending = Some(e.clone());
+                None
+            }
+            _ => Some(Ok(())),
+        }
+    });
+    match offending {
+        Some(field) => Err(ungrouped(&field.to_string(), field.get_span())),
+        None => Ok(()),
+    }
+}
+
+fn ungrouped(field: &str, span: Span) -> HaltReason {
+    HaltReason::Error(ExecutionError::Plan(PlannerError::UngroupedField {
+        field: field.to_string(),
+        span,
+    }))
+}
@@ -4,18 +4,39 @@ use lykiadb_lang::ast::{sql::SqlSource, Identifier};
 
 use super::PlannerError;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Scope {
     sources: HashMap<Identifier, SqlSource>,
+    ctes: Vec<Identifier>,
 }
 
 impl Scope {
     pub fn new() -> Scope {
         Scope {
             sources: HashMap::new(),
+            ctes: vec![],
         }
     }
 
+    /// Registers the name of a common table expression, which the
+    /// collections of the query are then looked up in first.
+    pub fn add_cte(&mut self, name: Identifier) -> Result<(), PlannerError> {
+        if let Some(previous) = self.ctes.iter().find(|cte| **cte == name) {
+            return Err(PlannerError::DuplicateObjectInScope {
+                previous: previous.clone(),
+                ident: name,
+            });
+        }
+
+        self.ctes.push(name);
+
+        Ok(())
+    }
+
+    pub fn cte(&self, name: &str) -> Option<&Identifier> {
+        self.ctes.iter().find(|cte| cte.name == name)
+    }
+
     pub fn add_source(&mut self, source: SqlSource) -> Result<(), PlannerError> {
         if self.sources.contains_key(source.alias()) {
             let previous = self.sources.get(source.alias()).unwrap();
@@ -30,6 +51,10 @@ impl Scope {
         Ok(())
     }
 
+    pub fn contains(&self, alias: &str) -> bool {
+        self.sources.keys().any(|ident| ident.name == alias)
+    }
+
     pub fn merge(&mut self, other: Scope) -> Result<(), PlannerError> {
         for (_, source) in other.sources {
             self.add_source(source.clone())?;
@@ -0,0 +1,62 @@
+use crate::value::RV;
+
+use super::{validate_document, StorageError};
+
+/// Documents are persisted as BSON, the same encoding the wire protocol uses.
+pub fn encode_document(document: &RV) -> Result<Vec<u8>, StorageError> {
+    validate_document(document)?;
+    bson::to_vec(document).map_err(|err| StorageError::InvalidDocument {
+        message: err.to_string(),
+    })
+}
+
+pub fn decode_document(bytes: &[u8]) -> Result<RV, StorageError> {
+    bson::from_slice(bytes).map_err(|err| StorageError::Corrupted {
+        message: err.to_string(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use rustc_hash::FxHashMap;
+
+    use super::*;
+    use crate::util::alloc_shared;
+
+    #[test]
+    fn test_roundtrip() {
+        let mut inner = FxHashMap::default();
+        inner.insert(
+            "city".to_string(),
+            RV::Str(Arc::new("Istanbul".to_string())),
+        );
+
+        let mut map = FxHashMap::default();
+        map.insert("name".to_string(), RV::Str(Arc::new("John".to_string())));
+        map.insert("age".to_string(), RV::Num(42.0));
+        map.insert("admin".to_string(), RV::Bool(false));
+        map.insert("address".to_string(), RV::Object(alloc_shared(inner)));
+        map.insert(
+            "tags".to_string(),
+            RV::Array(alloc_shared(vec![RV::Num(1.0), RV::Null])),
+        );
+        let document = RV::Object(alloc_shared(map));
+
+        let decoded = decode_document(&encode_document(&document).unwrap()).unwrap();
+
+        assert_eq!(
+            serde_json::to_value(&decoded).unwrap(),
+            serde_json::to_value(&document).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_garbage_is_reported() {
+        assert!(matches!(
+            decode_document(&[1, 2, 3]),
+            Err(StorageError::Corrupted { .. })
+        ));
+    }
+}
@@ -0,0 +1,768 @@
+use std::ops::Bound;
+use std::path::Path;
+
+use crate::store::{DocumentId, DocumentRange, StorageError};
+
+use super::pager::{PageId, Pager, PAGE_SIZE};
+
+const MAGIC: &[u8; 8] = b"LYKIADB\0";
+const VERSION: u32 = 1;
+
+const META_PAGE: PageId = 0;
+// Page 0 always holds the metadata, so it doubles as the null pointer
+const NULL_PAGE: PageId = 0;
+
+const KIND_LEAF: u8 = 1;
+const KIND_INTERNAL: u8 = 2;
+const KIND_OVERFLOW: u8 = 3;
+const KIND_FREE: u8 = 4;
+
+// kind (1) + entry count (2) + sibling or leftmost child (4)
+const NODE_HEADER: usize = 7;
+// key (8) + storage flag (1) + length (4)
+const LEAF_ENTRY_HEADER: usize = 13;
+// key (8) + child (4)
+const INTERNAL_ENTRY: usize = 12;
+// kind (1) + next (4) + length (4)
+const OVERFLOW_HEADER: usize = 9;
+const OVERFLOW_CAPACITY: usize = PAGE_SIZE - OVERFLOW_HEADER;
+
+// Values larger than this go to overflow pages, so that every leaf
+// can hold at least four entries and a split always produces two valid pages
+const MAX_INLINE: usize = (PAGE_SIZE - NODE_HEADER) / 4 - LEAF_ENTRY_HEADER;
+
+#[derive(Debug, Clone)]
+enum Value {
+    Inline(Vec<u8>),
+    Overflow { first: PageId, len: u32 },
+}
+
+#[derive(Debug, Clone)]
+enum Node {
+    Leaf {
+        entries: Vec<(DocumentId, Value)>,
+        next: PageId,
+    },
+    Internal {
+        first: PageId,
+        entries: Vec<(DocumentId, PageId)>,
+    },
+}
+
+impl Value {
+    /// Bytes the value takes in a leaf, along with its entry header.
+    fn entry_size(&self) -> usize {
+        LEAF_ENTRY_HEADER
+            + match self {
+                Value::Inline(bytes) => bytes.len(),
+                Value::Overflow { .. } => 4,
+            }
+    }
+}
+
+impl Node {
+    fn size(&self) -> usize {
+        match self {
+            Node::Leaf { entries, .. } => {
+                NODE_HEADER
+                    + entries
+                        .iter()
+                        .map(|(_, value)| value.entry_size())
+                        .sum::<usize>()
+            }
+            Node::Internal { entries, .. } => NODE_HEADER + entries.len() * INTERNAL_ENTRY,
+        }
+    }
+
+    fn encode(&self) -> Vec<u8> {
+        let mut page = Vec::with_capacity(PAGE_SIZE);
+        match self {
+            Node::Leaf { entries, next } => {
+                page.push(KIND_LEAF);
+                page.extend_from_slice(&(entries.len() as u16).to_le_bytes());
+                page.extend_from_slice(&next.to_le_bytes());
+                for (key, value) in entries {
+                    page.extend_from_slice(&key.to_le_bytes());
+                    match value {
+                        Value::Inline(bytes) => {
+                            page.push(0);
+                            page.extend_from_slice(&(bytes.len() as u32).to_le_bytes());
+                            page.extend_from_slice(bytes);
+                        }
+                        Value::Overflow { first, len } => {
+                            page.push(1);
+                            page.extend_from_slice(&len.to_le_bytes());
+                            page.extend_from_slice(&first.to_le_bytes());
+                        }
+                    }
+                }
+            }
+            Node::Internal { first, entries } => {
+                page.push(KIND_INTERNAL);
+                page.extend_from_slice(&(entries.len() as u16).to_le_bytes());
+                page.extend_from_slice(&first.to_le_bytes());
+                for (key, child) in entries {
+                    page.extend_from_slice(&key.to_le_bytes());
+                    page.extend_from_slice(&child.to_le_bytes());
+                }
+            }
+        }
+        page
+    }
+
+    fn decode(id: PageId, page: &[u8]) -> Result<Node, StorageError> {
+        let count = u16_at(page, 1)? as usize;
+        let link = u32_at(page, 3)?;
+        let mut offset = NODE_HEADER;
+
+        match bytes_at(page, 0, 1)?[0] {
+            KIND_LEAF => {
+                let mut entries = Vec::with_capacity(count);
+                for _ in 0..count {
+                    let key = u64_at(page, offset)?;
+                    let flag = bytes_at(page, offset + 8, 1)?[0];
+                    let len = u32_at(page, offset + 9)?;
+                    offset += LEAF_ENTRY_HEADER;
+                    let value = if flag == 0 {
+                        let bytes = bytes_at(page, offset, len as usize)?.to_vec();
+                        offset += len as usize;
+                        Value::Inline(bytes)
+                    } else {
+                        let first = u32_at(page, offset)?;
+                        offset += 4;
+                        Value::Overflow { first, len }
+                    };
+                    entries.push((key, value));
+                }
+                Ok(Node::Leaf {
+                    entries,
+                    next: link,
+                })
+            }
+            KIND_INTERNAL => {
+                let mut entries = Vec::with_capacity(count);
+                for _ in 0..count {
+                    entries.push((u64_at(page, offset)?, u32_at(page, offset + 8)?));
+                    offset += INTERNAL_ENTRY;
+                }
+                Ok(Node::Internal {
+                    first: link,
+                    entries,
+                })
+            }
+            kind => Err(StorageError::Corrupted {
+                message: format!("Page {} has unexpected kind {}", id, kind),
+            }),
+        }
+    }
+}
+
+/// A B+tree of documents keyed by document id, stored in a single file.
+///
+/// Leaves are chained from left to right for range scans. Deleting keys does
+/// not rebalance the tree; emptied leaves stay in place and are reused by
+/// later inserts into the same key range.
+pub struct BTree {
+    pager: Pager,
+    root: PageId,
+    free_head: PageId,
+}
+
+impl BTree {
+    pub fn open(path: &Path, cache_capacity: usize) -> Result<BTree, StorageError> {
+        let mut pager = Pager::open(path, cache_capacity)?;
+
+        if pager.page_count() == 0 {
+            pager.allocate()?;
+            let root = pager.allocate()?;
+            let mut tree = BTree {
+                pager,
+                root,
+                free_head: NULL_PAGE,
+            };
+            tree.write_node(
+                root,
+                &Node::Leaf {
+                    entries: vec![],
+                    next: NULL_PAGE,
+                },
+            )?;
+            tree.write_meta()?;
+            tree.flush()?;
+            return Ok(tree);
+        }
+
+        let meta = pager.read(META_PAGE)?;
+
+        if bytes_at(&meta, 0, 8)? != MAGIC {
+            return Err(StorageError::Corrupted {
+                message: format!("{} is not a database file", path.display()),
+            });
+        }
+
+        if u32_at(&meta, 8)? != VERSION || u32_at(&meta, 12)? as usize != PAGE_SIZE {
+            return Err(StorageError::Corrupted {
+                message: format!("{} has an unsupported format", path.display()),
+            });
+        }
+
+        Ok(BTree {
+            pager,
+            root: u32_at(&meta, 16)?,
+            free_head: u32_at(&meta, 20)?,
+        })
+    }
+
+    pub fn get(&mut self, key: DocumentId) -> Result<Option<Vec<u8>>, StorageError> {
+        let leaf = self.find_leaf(key)?;
+        if let Node::Leaf { entries, .. } = self.read_node(leaf)? {
+            if let Ok(idx) = entries.binary_search_by_key(&key, |(k, _)| *k) {
+                return Ok(Some(self.read_value(&entries[idx].1)?));
+            }
+        }
+        Ok(None)
+    }
+
+    pub fn insert(
+        &mut self,
+        key: DocumentId,
+        bytes: Vec<u8>,
+    ) -> Result<Option<Vec<u8>>, StorageError> {
+        let value = self.write_value(bytes)?;
+        let (previous, split) = self.insert_into(self.root, key, value)?;
+
+        if let Some((separator, right)) = split {
+            let root = self.allocate()?;
+            self.write_node(
+                root,
+                &Node::Internal {
+                    first: self.root,
+                    entries: vec![(separator, right)],
+                },
+            )?;
+            self.root = root;
+            self.write_meta()?;
+        }
+
+        self.pager.flush_if_full()?;
+        Ok(previous)
+    }
+
+    pub fn delete(&mut self, key: DocumentId) -> Result<Option<Vec<u8>>, StorageError> {
+        let leaf = self.find_leaf(key)?;
+        if let Node::Leaf { mut entries, next } = self.read_node(leaf)? {
+            if let Ok(idx) = entries.binary_search_by_key(&key, |(k, _)| *k) {
+                let (_, value) = entries.remove(idx);
+                self.write_node(leaf, &Node::Leaf { entries, next })?;
+                let bytes = self.read_value(&value)?;
+                self.free_value(&value)?;
+                self.pager.flush_if_full()?;
+                return Ok(Some(bytes));
+            }
+        }
+        Ok(None)
+    }
+
+    pub fn range(
+        &mut self,
+        range: DocumentRange,
+    ) -> Result<Vec<(DocumentId, Vec<u8>)>, StorageError> {
+        self.page(range, usize::MAX)
+    }
+
+    /// The first `limit` entries in the range. Leaves past the last one
+    /// returned are not read.
+    pub fn page(
+        &mut self,
+        range: DocumentRange,
+        limit: usize,
+    ) -> Result<Vec<(DocumentId, Vec<u8>)>, StorageError> {
+        let (start, end) = range;
+        let mut page = match start {
+            Bound::Included(key) | Bound::Excluded(key) => self.find_leaf(key)?,
+            Bound::Unbounded => self.find_leaf(DocumentId::MIN)?,
+        };
+
+        let mut result = vec![];
+
+        while page != NULL_PAGE {
+            let Node::Leaf { entries, next } = self.read_node(page)? else {
+                return Err(StorageError::Corrupted {
+                    message: format!("Page {} is not a leaf", page),
+                });
+            };
+
+            for (key, value) in entries {
+                let after_start = match start {
+                    Bound::Included(s) => key >= s,
+                    Bound::Excluded(s) => key > s,
+                    Bound::Unbounded => true,
+                };
+                let before_end = match end {
+                    Bound::Included(e) => key <= e,
+                    Bound::Excluded(e) => key < e,
+                    Bound::Unbounded => true,
+                };
+                if !before_end || result.len() == limit {
+                    return Ok(result);
+                }
+                if after_start {
+                    result.push((key, self.read_value(&value)?));
+                }
+            }
+
+            page = next;
+        }
+
+        Ok(result)
+    }
+
+    /// Largest key up to the bound, if any.
+    pub fn last_key(&mut self, end: Bound<DocumentId>) -> Result<Option<DocumentId>, StorageError> {
+        self.last_key_in(self.root, end)
+    }
+
+    pub fn flush(&mut self) -> Result<(), StorageError> {
+        self.pager.flush()
+    }
+
+    fn find_leaf(&mut self, key: DocumentId) -> Result<PageId, StorageError> {
+        let mut page = self.root;
+        loop {
+            match self.read_node(page)? {
+                Node::Leaf { .. } => return Ok(page),
+                Node::Internal { first, entries } => {
+                    page = child_for(first, &entries, key);
+                }
+            }
+        }
+    }
+
+    fn last_key_in(
+        &mut self,
+        page: PageId,
+        end: Bound<DocumentId>,
+    ) -> Result<Option<DocumentId>, StorageError> {
+        let within = |key: DocumentId| match end {
+            Bound::Included(e) => key <= e,
+            Bound::Excluded(e) => key < e,
+            Bound::Unbounded => true,
+        };
+        match self.read_node(page)? {
+            Node::Leaf { entries, .. } => Ok(entries
+                .iter()
+                .rev()
+                .map(|(key, _)| *key)
+                .find(|key| within(*key))),
+            Node::Internal { first, entries } => {
+                // Leaves emptied by deletes stay in place, so the children
+                // are tried from right to left until one has a key
+                for idx in (0..=entries.len()).rev() {
+                    let child = match idx {
+                        0 => first,
+                        _ if !within(entries[idx - 1].0) => continue,
+                        _ => entries[idx - 1].1,
+                    };
+                    if let Some(key) = self.last_key_in(child, end)? {
+                        return Ok(Some(key));
+                    }
+                }
+                Ok(None)
+            }
+        }
+    }
+
+    #[allow(clippy::type_complexity)]
+    fn insert_into(
+        &mut self,
+        page: PageId,
+        key: DocumentId,
+        value: Value,
+    ) -> Result<(Option<Vec<u8>>, Option<(DocumentId, PageId)>), StorageError> {
+        match self.read_node(page)? {
+            Node::Leaf { mut entries, next } => {
+                let previous = match entries.binary_search_by_key(&key, |(k, _)| *k) {
+                    Ok(idx) => {
+                        let old = std::mem::replace(&mut entries[idx].1, value);
+                        let bytes = self.read_value(&old)?;
+                        self.free_value(&old)?;
+                        Some(bytes)
+                    }
+                    Err(idx) => {
+                        entries.insert(idx, (key, value));
+                        None
+                    }
+                };
+
+                let node = Node::Leaf { entries, next };
+                if node.size() <= PAGE_SIZE {
+                    self.write_node(page, &node)?;
+                    return Ok((previous, None));
+                }
+
+                let Node::Leaf {
+                    mut entries, next, ..
+                } = node
+                else {
+                    unreachable!()
+                };
+                let right_entries = entries.split_off(leaf_split_point(&entries));
+                let separator = right_entries[0].0;
+                let right = self.allocate()?;
+
+                self.write_node(
+                    right,
+                    &Node::Leaf {
+                        entries: right_entries,
+                        next,
+                    },
+                )?;
+                self.write_node(
+                    page,
+                    &Node::Leaf {
+                        entries,
+                        next: right,
+                    },
+                )?;
+
+                Ok((previous, Some((separator, right))))
+            }
+            Node::Internal { first, mut entries } => {
+                let child = child_for(first, &entries, key);
+                let (previous, split) = self.insert_into(child, key, value)?;
+
+                let Some((separator, new_child)) = split else {
+                    return Ok((previous, None));
+                };
+
+                let idx = entries.partition_point(|(k, _)| *k <= separator);
+                entries.insert(idx, (separator, new_child));
+
+                let node = Node::Internal { first, entries };
+                if node.size() <= PAGE_SIZE {
+                    self.write_node(page, &node)?;
+                    return Ok((previous, None));
+                }
+
+                let Node::Internal { first, mut entries } = node else {
+                    unreachable!()
+                };
+                // Internal entries all take the same room, so halving them by
+                // count halves the bytes too
+                let mut right_entries = entries.split_off(entries.len() / 2);
+                let (promoted, right_first) = right_entries.remove(0);
+                let right = self.allocate()?;
+
+                self.write_node(
+                    right,
+                    &Node::Internal {
+                        first: right_first,
+                        entries: right_entries,
+                    },
+                )?;
+                self.write_node(page, &Node::Internal { first, entries })?;
+
+                Ok((previous, Some((promoted, right))))
+            }
+        }
+    }
+
+    fn read_node(&mut self, page: PageId) -> Result<Node, StorageError> {
+        Node::decode(page, &self.pager.read(page)?)
+    }
+
+    fn write_node(&mut self, page: PageId, node: &Node) -> Result<(), StorageError> {
+        let encoded = node.encode();
+        if encoded.len() > PAGE_SIZE {
+            return Err(StorageError::Corrupted {
+                message: format!(
+                    "Node of page {} takes {} bytes, more than a page",
+                    page,
+                    encoded.len()
+                ),
+            });
+        }
+        self.pager.write(page, encoded)
+    }
+
+    fn write_value(&mut self, bytes: Vec<u8>) -> Result<Value, StorageError> {
+        if bytes.len() <= MAX_INLINE {
+            return Ok(Value::Inline(bytes));
+        }
+
+        let len = bytes.len() as u32;
+        let chunks: Vec<&[u8]> = bytes.chunks(OVERFLOW_CAPACITY).collect();
+        let pages = chunks
+            .iter()
+            .map(|_| self.allocate())
+            .collect::<Result<Vec<PageId>, StorageError>>()?;
+
+        for (i, chunk) in chunks.iter().enumerate() {
+            let next = pages.get(i + 1).copied().unwrap_or(NULL_PAGE);
+            let mut page = Vec::with_capacity(PAGE_SIZE);
+            page.push(KIND_OVERFLOW);
+            page.extend_from_slice(&next.to_le_bytes());
+            page.extend_from_slice(&(chunk.len() as u32).to_le_bytes());
+            page.extend_from_slice(chunk);
+            self.pager.write(pages[i], page)?;
+        }
+
+        Ok(Value::Overflow {
+            first: pages[0],
+            len,
+        })
+    }
+
+    fn read_value(&mut self, value: &Value) -> Result<Vec<u8>, StorageError> {
+        match value {
+            Value::Inline(bytes) => Ok(bytes.clone()),
+            Value::Overflow { first, len } => {
+                let mut bytes = Vec::with_capacity(*len as usize);
+                let mut page_id = *first;
+                while page_id != NULL_PAGE {
+                    let page = self.pager.read(page_id)?;
+                    if bytes_at(&page, 0, 1)?[0] != KIND_OVERFLOW {
+                        return Err(StorageError::Corrupted {
+                            message: format!("Page {} is not an overflow page", page_id),
+                        });
+                    }
+                    let chunk_len = u32_at(&page, 5)? as usize;
+                    bytes.extend_from_slice(bytes_at(&page, OVERFLOW_HEADER, chunk_len)?);
+                    page_id = u32_at(&page, 1)?;
+                }
+                Ok(bytes)
+            }
+        }
+    }
+
+    fn free_value(&mut self, value: &Value) -> Result<(), StorageError> {
+        if let Value::Overflow { first, .. } = value {
+            let mut page_id = *first;
+            while page_id != NULL_PAGE {
+                let next = u32_at(&self.pager.read(page_id)?, 1)?;
+                let mut page = vec![KIND_FREE];
+                page.extend_from_slice(&self.free_head.to_le_bytes());
+                self.pager.write(page_id, page)?;
+                self.free_head = page_id;
+                page_id = next;
+            }
+            self.write_meta()?;
+        }
+        Ok(())
+    }
+
+    fn allocate(&mut self) -> Result<PageId, StorageError> {
+        if self.free_head == NULL_PAGE {
+            return self.pager.allocate();
+        }
+
+        let page = self.free_head;
+        self.free_head = u32_at(&self.pager.read(page)?, 1)?;
+        self.write_meta()?;
+        Ok(page)
+    }
+
+    fn write_meta(&mut self) -> Result<(), StorageError> {
+        let mut meta = Vec::with_capacity(PAGE_SIZE);
+        meta.extend_from_slice(MAGIC);
+        meta.extend_from_slice(&VERSION.to_le_bytes());
+        meta.extend_from_slice(&(PAGE_SIZE as u32).to_le_bytes());
+        meta.extend_from_slice(&self.root.to_le_bytes());
+        meta.extend_from_slice(&self.free_head.to_le_bytes());
+        self.pager.write(META_PAGE, meta)
+    }
+}
+
+fn child_for(first: PageId, entries: &[(DocumentId, PageId)], key: DocumentId) -> PageId {
+    let idx = entries.partition_point(|(k, _)| *k <= key);
+    if idx == 0 {
+        first
+    } else {
+        entries[idx - 1].1
+    }
+}
+
+/// Where to split the entries of a leaf that outgrew its page: the first
+/// entry past half of their bytes, so that both halves fit in a page
+/// whatever the sizes of the entries.
+fn leaf_split_point(entries: &[(DocumentId, Value)]) -> usize {
+    let total: usize = entries.iter().map(|(_, value)| value.entry_size()).sum();
+    let mut left = 0;
+    for (idx, (_, value)) in entries.iter().enumerate() {
+        if left + value.entry_size() / 2 >= total / 2 {
+            return idx.clamp(1, entries.len() - 1);
+        }
+        left += value.entry_size();
+    }
+    entries.len() - 1
+}
+
+fn bytes_at(page: &[u8], offset: usize, len: usize) -> Result<&[u8], StorageError> {
+    page.get(offset..offset + len)
+        .ok_or_else(|| StorageError::Corrupted {
+            message: format!(
+                "Reading {} bytes at offset {} runs past the end of a page",
+                len, offset
+            ),
+        })
+}
+
+fn u16_at(page: &[u8], offset: usize) -> Result<u16, StorageError> {
+    Ok(u16::from_le_bytes(
+        bytes_at(page, offset, 2)?.try_into().unwrap(),
+    ))
+}
+
+fn u32_at(page: &[u8], offset: usize) -> Result<u32, StorageError> {
+    Ok(u32::from_le_bytes(
+        bytes_at(page, offset, 4)?.try_into().unwrap(),
+    ))
+}
+
+fn u64_at(page: &[u8], offset: usize) -> Result<u64, StorageError> {
+    Ok(u64::from_le_bytes(
+        bytes_at(page, offset, 8)?.try_into().unwrap(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ops::Bound;
+
+    use super::*;
+    use crate::store::disk::tests::TempDir;
+
+    fn value_for(key: u64, len: usize) -> Vec<u8> {
+        (0..len).map(|i| ((key as usize + i) % 251) as u8).collect()
+    }
+
+    #[test]
+    fn test_many_inserts_split_pages() {
+        let dir = TempDir::new("btree_split");
+        let path = dir.path().join("tree.ldb");
+        let mut tree = BTree::open(&path, 8).unwrap();
+
+        // Insert in an interleaved order so that splits happen in the middle
+        let keys: Vec<u64> = (0..5000).map(|i| (i * 7919) % 5000).collect();
+        for key in &keys {
+            tree.insert(*key, value_for(*key, 40)).unwrap();
+        }
+
+        for key in 0..5000 {
+            assert_eq!(tree.get(key).unwrap(), Some(value_for(key, 40)));
+        }
+        assert_eq!(tree.get(5000).unwrap(), None);
+
+        let all = tree.range((Bound::Unbounded, Bound::Unbounded)).unwrap();
+        assert_eq!(
+            all.iter().map(|(k, _)| *k).collect::<Vec<_>>(),
+            (0..5000).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_overflow_values() {
+        let dir = TempDir::new("btree_overflow");
+        let path = dir.path().join("tree.ldb");
+        let mut tree = BTree::open(&path, 4).unwrap();
+
+        let big = value_for(1, PAGE_SIZE * 3 + 17);
+        tree.insert(1, big.clone()).unwrap();
+        tree.insert(2, value_for(2, 10)).unwrap();
+        assert_eq!(tree.get(1).unwrap(), Some(big.clone()));
+
+        // Overwriting and deleting releases the overflow chain for reuse
+        let pages_before = tree.pager.page_count();
+        assert_eq!(tree.insert(1, value_for(3, 20)).unwrap(), Some(big.clone()));
+        tree.insert(3, big.clone()).unwrap();
+        assert_eq!(tree.pager.page_count(), pages_before);
+
+        assert_eq!(tree.delete(3).unwrap(), Some(big));
+        assert_eq!(tree.get(3).unwrap(), None);
+    }
+
+    #[test]
+    fn test_split_mixed_value_sizes() {
+        let dir = TempDir::new("btree_mixed_sizes");
+        let path = dir.path().join("tree.ldb");
+        let mut tree = BTree::open(&path, 8).unwrap();
+
+        // Many small values followed by a few near the inline limit, so that
+        // halving the entries by count would put the large ones together in
+        // a side larger than a page
+        let len_of = |key: u64| if key < 56 { 5 } else { MAX_INLINE };
+        for key in 0..60 {
+            tree.insert(key, value_for(key, len_of(key))).unwrap();
+        }
+        tree.flush().unwrap();
+        drop(tree);
+
+        let mut tree = BTree::open(&path, 8).unwrap();
+        let all = tree.range((Bound::Unbounded, Bound::Unbounded)).unwrap();
+        assert_eq!(all.len(), 60);
+        for (key, value) in all {
+            assert_eq!(value, value_for(key, len_of(key)));
+        }
+    }
+
+    #[test]
+    fn test_decode_truncated_page() {
+        // A leaf claiming an entry its bytes do not hold
+        let page = [KIND_LEAF, 1, 0, 0, 0, 0, 0, 1, 2, 3];
+        assert!(matches!(
+            Node::decode(1, &page),
+            Err(StorageError::Corrupted { .. })
+        ));
+    }
+
+    #[test]
+    fn test_range_bounds_and_deletes() {
+        let dir = TempDir::new("btree_range");
+        let path = dir.path().join("tree.ldb");
+        let mut tree = BTree::open(&path, 16).unwrap();
+
+        for key in 0..1000 {
+            tree.insert(key, value_for(key, 100)).unwrap();
+        }
+        for key in (0..1000).filter(|k| k % 2 == 0) {
+            assert!(tree.delete(key).unwrap().is_some());
+        }
+
+        let keys: Vec<u64> = tree
+            .range((Bound::Excluded(101), Bound::Included(111)))
+            .unwrap()
+            .into_iter()
+            .map(|(k, _)| k)
+            .collect();
+        assert_eq!(keys, vec![103, 105, 107, 109, 111]);
+
+        let keys: Vec<u64> = tree
+            .page((Bound::Excluded(101), Bound::Unbounded), 3)
+            .unwrap()
+            .into_iter()
+            .map(|(k, _)| k)
+            .collect();
+        assert_eq!(keys, vec![103, 105, 107]);
+    }
+
+    #[test]
+    fn test_last_key() {
+        let dir = TempDir::new("btree_last");
+        let path = dir.path().join("tree.ldb");
+        let mut tree = BTree::open(&path, 16).unwrap();
+        assert_eq!(tree.last_key(Bound::Unbounded).unwrap(), None);
+
+        for key in 0..1000 {
+            tree.insert(key, value_for(key, 100)).unwrap();
+        }
+        // Empties the leaves at the end of the tree
+        for key in 500..1000 {
+            tree.delete(key).unwrap();
+        }
+
+        assert_eq!(tree.last_key(Bound::Unbounded).unwrap(), Some(499));
+        assert_eq!(tree.last_key(Bound::Excluded(300)).unwrap(), Some(299));
+        assert_eq!(tree.last_key(Bound::Included(300)).unwrap(), Some(300));
+        assert_eq!(tree.last_key(Bound::Excluded(0)).unwrap(), None);
+    }
+}
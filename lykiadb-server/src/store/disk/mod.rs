@@ -0,0 +1,313 @@
+use std::ops::Bound;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use rustc_hash::FxHashMap;
+
+use crate::value::RV;
+
+use self::btree::BTree;
+use super::codec::{decode_document, encode_document};
+use super::{DocumentId, DocumentRange, StorageEngine, StorageError};
+
+mod btree;
+mod pager;
+
+#[derive(Debug, Clone)]
+pub struct StorageConfig {
+    /// Directory holding one file per collection
+    pub data_dir: PathBuf,
+    /// Number of pages kept in memory for each open collection
+    pub page_cache_size: usize,
+}
+
+impl Default for StorageConfig {
+    fn default() -> Self {
+        StorageConfig {
+            data_dir: PathBuf::from("data"),
+            page_cache_size: 256,
+        }
+    }
+}
+
+/// Persists every collection as a B+tree in its own file under the data
+/// directory. Trees sit behind a mutex since even reads move pages in and
+/// out of the page cache.
+pub struct DiskEngine {
+    config: StorageConfig,
+    trees: FxHashMap<String, Mutex<BTree>>,
+}
+
+impl DiskEngine {
+    pub fn open(config: StorageConfig) -> Result<DiskEngine, StorageError> {
+        std::fs::create_dir_all(&config.data_dir)?;
+        Ok(DiskEngine {
+            config,
+            trees: FxHashMap::default(),
+        })
+    }
+
+    fn path_of(&self, name: &str) -> PathBuf {
+        self.config.data_dir.join(format!("{}.ldb", name))
+    }
+
+    fn with_tree<T>(
+        &self,
+        name: &str,
+        f: impl FnOnce(&mut BTree) -> Result<T, StorageError>,
+    ) -> Result<T, StorageError> {
+        match self.trees.get(name) {
+            Some(tree) => f(&mut tree.lock().unwrap()),
+            None if self.path_of(name).exists() => Err(StorageError::CollectionNotOpen {
+                name: name.to_string(),
+            }),
+            None => Err(StorageError::CollectionNotFound {
+                name: name.to_string(),
+            }),
+        }
+    }
+}
+
+impl StorageEngine for DiskEngine {
+    fn open_collection(&mut self, name: &str) -> Result<(), StorageError> {
+        if !self.trees.contains_key(name) {
+            let tree = BTree::open(&self.path_of(name), self.config.page_cache_size)?;
+            self.trees.insert(name.to_string(), Mutex::new(tree));
+        }
+        Ok(())
+    }
+
+    fn close_collection(&mut self, name: &str) -> Result<(), StorageError> {
+        self.with_tree(name, |tree| tree.flush())?;
+        self.trees.remove(name);
+        Ok(())
+    }
+
+    fn is_open(&self, name: &str) -> bool {
+        self.trees.contains_key(name)
+    }
+
+    fn insert(
+        &mut self,
+        collection: &str,
+        id: DocumentId,
+        document: RV,
+    ) -> Result<Option<RV>, StorageError> {
+        let bytes = encode_document(&document)?;
+        self.with_tree(collection, |tree| {
+            tree.insert(id, bytes)?
+                .map(|previous| decode_document(&previous))
+                .transpose()
+        })
+    }
+
+    fn get(&self, collection: &str, id: DocumentId) -> Result<Option<RV>, StorageError> {
+        self.with_tree(collection, |tree| {
+            tree.get(id)?
+                .map(|bytes| decode_document(&bytes))
+                .transpose()
+        })
+    }
+
+    fn delete(&mut self, collection: &str, id: DocumentId) -> Result<Option<RV>, StorageError> {
+        self.with_tree(collection, |tree| {
+            tree.delete(id)?
+                .map(|previous| decode_document(&previous))
+                .transpose()
+        })
+    }
+
+    fn range(
+        &self,
+        collection: &str,
+        range: DocumentRange,
+    ) -> Result<Vec<(DocumentId, RV)>, StorageError> {
+        self.with_tree(collection, |tree| {
+            tree.range(range)?
+                .into_iter()
+                .map(|(id, bytes)| Ok((id, decode_document(&bytes)?)))
+                .collect()
+        })
+    }
+
+    fn page(
+        &self,
+        collection: &str,
+        range: DocumentRange,
+        limit: usize,
+    ) -> Result<Vec<(DocumentId, RV)>, StorageError> {
+        self.with_tree(collection, |tree| {
+            tree.page(range, limit)?
+                .into_iter()
+                .map(|(id, bytes)| Ok((id, decode_document(&bytes)?)))
+                .collect()
+        })
+    }
+
+    fn last_id(
+        &self,
+        collection: &str,
+        end: Bound<DocumentId>,
+    ) -> Result<Option<DocumentId>, StorageError> {
+        self.with_tree(collection, |tree| tree.last_key(end))
+    }
+
+    fn flush(&mut self) -> Result<(), StorageError> {
+        for tree in self.trees.values() {
+            tree.lock().unwrap().flush()?;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for DiskEngine {
+    fn drop(&mut self) {
+        if let Err(err) = self.flush() {
+            tracing::error!("Failed to flush storage: {:?}", err);
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::ops::Bound;
+    use std::path::{Path, PathBuf};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    use rustc_hash::FxHashMap;
+
+    use super::*;
+    use crate::util::alloc_shared;
+
+    /// A directory under the system temp dir that is removed once dropped.
+    pub(crate) struct TempDir(PathBuf);
+
+    impl TempDir {
+        pub(crate) fn new(prefix: &str) -> TempDir {
+            static COUNTER: AtomicUsize = AtomicUsize::new(0);
+            let path = std::env::temp_dir().join(format!(
+                "lykiadb_{}_{}_{}",
+                prefix,
+                std::process::id(),
+                COUNTER.fetch_add(1, Ordering::SeqCst)
+            ));
+            let _ = std::fs::remove_dir_all(&path);
+            std::fs::create_dir_all(&path).unwrap();
+            TempDir(path)
+        }
+
+        pub(crate) fn path(&self) -> &Path {
+            &self.0
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn config(dir: &TempDir) -> StorageConfig {
+        StorageConfig {
+            data_dir: dir.path().to_path_buf(),
+            page_cache_size: 4,
+        }
+    }
+
+    fn doc(n: usize) -> RV {
+        let mut map = FxHashMap::default();
+        map.insert("n".to_string(), RV::Num(n as f64));
+        map.insert(
+            "text".to_string(),
+            RV::Str(Arc::new("lorem ipsum ".repeat(n % 20))),
+        );
+        RV::Object(alloc_shared(map))
+    }
+
+    fn n_of(doc: &RV) -> usize {
+        match doc {
+            RV::Object(map) => match map.read().unwrap().get("n") {
+                Some(RV::Num(n)) => *n as usize,
+                _ => panic!("Expected a number"),
+            },
+            _ => panic!("Expected an object"),
+        }
+    }
+
+    #[test]
+    fn test_documents_survive_reopen() {
+        let dir = TempDir::new("disk_reopen");
+
+        {
+            let mut engine = DiskEngine::open(config(&dir)).unwrap();
+            engine.open_collection("books").unwrap();
+            for n in 0..2000 {
+                engine.insert("books", n as u64, doc(n)).unwrap();
+            }
+            engine.delete("books", 7).unwrap();
+        }
+
+        let mut engine = DiskEngine::open(config(&dir)).unwrap();
+        engine.open_collection("books").unwrap();
+
+        assert_eq!(n_of(&engine.get("books", 1999).unwrap().unwrap()), 1999);
+        assert!(engine.get("books", 7).unwrap().is_none());
+
+        let ids: Vec<u64> = engine
+            .range("books", (Bound::Included(5), Bound::Excluded(10)))
+            .unwrap()
+            .into_iter()
+            .map(|(id, doc)| {
+                assert_eq!(n_of(&doc), id as usize);
+                id
+            })
+            .collect();
+        assert_eq!(ids, vec![5, 6, 8, 9]);
+    }
+
+    #[test]
+    fn test_collection_lifecycle() {
+        let dir = TempDir::new("disk_lifecycle");
+        let mut engine = DiskEngine::open(config(&dir)).unwrap();
+
+        assert_eq!(
+            engine.get("books", 1),
+            Err(StorageError::CollectionNotFound {
+                name: "books".to_string()
+            })
+        );
+
+        assert!(!engine.is_open("books"));
+        engine.open_collection("books").unwrap();
+        assert!(engine.is_open("books"));
+        engine.insert("books", 1, doc(1)).unwrap();
+        engine.close_collection("books").unwrap();
+        assert!(!engine.is_open("books"));
+
+        assert_eq!(
+            engine.get("books", 1),
+            Err(StorageError::CollectionNotOpen {
+                name: "books".to_string()
+            })
+        );
+
+        engine.open_collection("books").unwrap();
+        assert_eq!(
+            n_of(&engine.insert("books", 1, doc(2)).unwrap().unwrap()),
+            1
+        );
+    }
+
+    #[test]
+    fn test_corrupted_file_is_rejected() {
+        let dir = TempDir::new("disk_corrupted");
+        std::fs::write(dir.path().join("books.ldb"), [7u8; 4096]).unwrap();
+
+        let mut engine = DiskEngine::open(config(&dir)).unwrap();
+        assert!(matches!(
+            engine.open_collection("books"),
+            Err(StorageError::Corrupted { .. })
+        ));
+    }
+}
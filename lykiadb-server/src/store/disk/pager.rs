@@ -0,0 +1,349 @@
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+use rustc_hash::FxHashMap;
+
+use crate::store::{wal::checksum, StorageError};
+
+pub type PageId = u32;
+
+pub const PAGE_SIZE: usize = 4096;
+
+// page id (4) + page
+const JOURNAL_ENTRY: usize = 4 + PAGE_SIZE;
+// page count (4) + checksum (4)
+const JOURNAL_TRAILER: usize = 8;
+
+struct CachedPage {
+    data: Vec<u8>,
+    dirty: bool,
+    last_used: u64,
+}
+
+/// Reads and writes fixed size pages of a single file, keeping the most
+/// recently used ones in memory.
+///
+/// Dirty pages only reach the disk when the pager is flushed, never when
+/// they are evicted. The cache may grow past its capacity while a change
+/// is written, and [`Pager::flush_if_full`] brings it back once the change
+/// is done, so the file never holds part of one. A flush first copies the
+/// dirty pages to a journal, and a journal left behind by a crash in the
+/// middle of a flush is replayed when the file is opened again, so the
+/// file never holds part of a flush either.
+pub struct Pager {
+    file: File,
+    journal: PathBuf,
+    capacity: usize,
+    cache: FxHashMap<PageId, CachedPage>,
+    clock: u64,
+    page_count: u32,
+}
+
+impl Pager {
+    pub fn open(path: &Path, capacity: usize) -> Result<Pager, StorageError> {
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)?;
+
+        let mut journal = path.as_os_str().to_owned();
+        journal.push("-journal");
+        let journal = PathBuf::from(journal);
+        if journal.exists() {
+            replay_journal(&mut file, &journal)?;
+        }
+
+        let len = file.metadata()?.len();
+
+        if len % PAGE_SIZE as u64 != 0 {
+            return Err(StorageError::Corrupted {
+                message: format!(
+                    "{} is not aligned to the page size ({} bytes)",
+                    path.display(),
+                    len
+                ),
+            });
+        }
+
+        Ok(Pager {
+            file,
+            journal,
+            capacity: capacity.max(1),
+            cache: FxHashMap::default(),
+            clock: 0,
+            page_count: (len / PAGE_SIZE as u64) as u32,
+        })
+    }
+
+    pub fn page_count(&self) -> u32 {
+        self.page_count
+    }
+
+    pub fn allocate(&mut self) -> Result<PageId, StorageError> {
+        let id = self.page_count;
+        self.page_count += 1;
+        self.write(id, vec![0; PAGE_SIZE])?;
+        Ok(id)
+    }
+
+    pub fn read(&mut self, id: PageId) -> Result<Vec<u8>, StorageError> {
+        self.clock += 1;
+
+        if let Some(page) = self.cache.get_mut(&id) {
+            page.last_used = self.clock;
+            return Ok(page.data.clone());
+        }
+
+        if id >= self.page_count {
+            return Err(StorageError::Corrupted {
+                message: format!("Page {} is out of bounds", id),
+            });
+        }
+
+        let mut data = vec![0; PAGE_SIZE];
+        self.file
+            .seek(SeekFrom::Start(id as u64 * PAGE_SIZE as u64))?;
+        self.file.read_exact(&mut data)?;
+
+        self.cache_page(id, data.clone(), false)?;
+        Ok(data)
+    }
+
+    pub fn write(&mut self, id: PageId, mut data: Vec<u8>) -> Result<(), StorageError> {
+        if data.len() > PAGE_SIZE {
+            return Err(StorageError::Other {
+                message: format!(
+                    "Page {} would hold {} bytes, more than fit in a page",
+                    id,
+                    data.len()
+                ),
+            });
+        }
+        self.clock += 1;
+        data.resize(PAGE_SIZE, 0);
+        self.cache_page(id, data, true)
+    }
+
+    pub fn flush(&mut self) -> Result<(), StorageError> {
+        let mut dirty: Vec<PageId> = self
+            .cache
+            .iter()
+            .filter(|(_, page)| page.dirty)
+            .map(|(id, _)| *id)
+            .collect();
+        dirty.sort();
+        if dirty.is_empty() {
+            return Ok(());
+        }
+
+        self.write_journal(&dirty)?;
+        for id in dirty {
+            let page = self.cache.get_mut(&id).unwrap();
+            page.dirty = false;
+            let data = page.data.clone();
+            self.write_through(id, &data)?;
+        }
+        self.file.sync_all()?;
+        std::fs::remove_file(&self.journal)?;
+        sync_dir(&self.journal)?;
+
+        while self.cache.len() > self.capacity && self.evict() {}
+        Ok(())
+    }
+
+    /// Flushes once the cache holds more pages than it should, which only
+    /// happens when dirty pages fill it. Called between changes.
+    pub fn flush_if_full(&mut self) -> Result<(), StorageError> {
+        if self.cache.len() > self.capacity {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Writes the dirty pages to the journal and waits until they reach the
+    /// disk, after which the file can be written to.
+    fn write_journal(&mut self, dirty: &[PageId]) -> Result<(), StorageError> {
+        let mut buf = Vec::with_capacity(dirty.len() * JOURNAL_ENTRY + JOURNAL_TRAILER);
+        for id in dirty {
+            buf.extend_from_slice(&id.to_le_bytes());
+            buf.extend_from_slice(&self.cache[id].data);
+        }
+        let sum = checksum(&buf);
+        buf.extend_from_slice(&(dirty.len() as u32).to_le_bytes());
+        buf.extend_from_slice(&sum.to_le_bytes());
+
+        let mut journal = File::create(&self.journal)?;
+        journal.write_all(&buf)?;
+        journal.sync_all()?;
+        sync_dir(&self.journal)
+    }
+
+    fn cache_page(&mut self, id: PageId, data: Vec<u8>, dirty: bool) -> Result<(), StorageError> {
+        if let Some(page) = self.cache.get_mut(&id) {
+            page.data = data;
+            page.dirty |= dirty;
+            page.last_used = self.clock;
+            return Ok(());
+        }
+
+        if self.cache.len() >= self.capacity {
+            self.evict();
+        }
+
+        self.cache.insert(
+            id,
+            CachedPage {
+                data,
+                dirty,
+                last_used: self.clock,
+            },
+        );
+        Ok(())
+    }
+
+    /// Drops the least recently used clean page, if there is one.
+    fn evict(&mut self) -> bool {
+        let victim = self
+            .cache
+            .iter()
+            .filter(|(_, page)| !page.dirty)
+            .min_by_key(|(_, page)| page.last_used)
+            .map(|(id, _)| *id);
+
+        match victim {
+            Some(id) => self.cache.remove(&id).is_some(),
+            None => false,
+        }
+    }
+
+    fn write_through(&mut self, id: PageId, data: &[u8]) -> Result<(), StorageError> {
+        self.file
+            .seek(SeekFrom::Start(id as u64 * PAGE_SIZE as u64))?;
+        self.file.write_all(data)?;
+        Ok(())
+    }
+}
+
+/// Copies the pages of a complete journal into the file and removes it. A
+/// journal cut short by a crash is dropped, as the file was not written to
+/// before the journal was complete.
+fn replay_journal(file: &mut File, path: &Path) -> Result<(), StorageError> {
+    let bytes = std::fs::read(path)?;
+    let complete = bytes.len() >= JOURNAL_TRAILER && {
+        let body = &bytes[..bytes.len() - JOURNAL_TRAILER];
+        let trailer = &bytes[body.len()..];
+        let count = u32::from_le_bytes(trailer[0..4].try_into().unwrap()) as usize;
+        let sum = u32::from_le_bytes(trailer[4..8].try_into().unwrap());
+        body.len() == count * JOURNAL_ENTRY && checksum(body) == sum
+    };
+
+    if complete {
+        for entry in bytes[..bytes.len() - JOURNAL_TRAILER].chunks(JOURNAL_ENTRY) {
+            let id = u32::from_le_bytes(entry[0..4].try_into().unwrap());
+            file.seek(SeekFrom::Start(id as u64 * PAGE_SIZE as u64))?;
+            file.write_all(&entry[4..])?;
+        }
+        file.sync_all()?;
+    } else {
+        tracing::warn!("Discarding the incomplete journal {}", path.display());
+    }
+
+    std::fs::remove_file(path)?;
+    sync_dir(path)
+}
+
+/// Makes the creation or removal of the file durable.
+fn sync_dir(path: &Path) -> Result<(), StorageError> {
+    #[cfg(unix)]
+    {
+        let dir = match path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => Path::new("."),
+        };
+        File::open(dir)?.sync_all()?;
+    }
+    #[cfg(not(unix))]
+    let _ = path;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::disk::tests::TempDir;
+
+    fn page(byte: u8) -> Vec<u8> {
+        vec![byte; PAGE_SIZE]
+    }
+
+    #[test]
+    fn test_journal_is_replayed_after_crash() {
+        let dir = TempDir::new("pager_journal");
+        let path = dir.path().join("pages.ldb");
+
+        let mut pager = Pager::open(&path, 1).unwrap();
+        let first = pager.allocate().unwrap();
+        let second = pager.allocate().unwrap();
+        pager.write(first, page(1)).unwrap();
+        pager.write(second, page(2)).unwrap();
+
+        // Crash once the journal is written, before the file is
+        let dirty = vec![first, second];
+        pager.write_journal(&dirty).unwrap();
+        std::mem::forget(pager);
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), 0);
+
+        let mut pager = Pager::open(&path, 1).unwrap();
+        assert_eq!(pager.page_count(), 2);
+        assert_eq!(pager.read(first).unwrap(), page(1));
+        assert_eq!(pager.read(second).unwrap(), page(2));
+        assert!(!pager.journal.exists());
+    }
+
+    #[test]
+    fn test_full_cache_is_flushed() {
+        let dir = TempDir::new("pager_full_cache");
+        let path = dir.path().join("pages.ldb");
+
+        let mut pager = Pager::open(&path, 2).unwrap();
+        for byte in 0..8 {
+            let id = pager.allocate().unwrap();
+            pager.write(id, page(byte)).unwrap();
+        }
+        assert_eq!(pager.cache.len(), 8);
+
+        pager.flush_if_full().unwrap();
+        assert_eq!(pager.cache.len(), 2);
+        assert_eq!(
+            std::fs::metadata(&path).unwrap().len(),
+            8 * PAGE_SIZE as u64
+        );
+        assert_eq!(pager.read(0).unwrap(), page(0));
+    }
+
+    #[test]
+    fn test_torn_journal_is_discarded() {
+        let dir = TempDir::new("pager_torn_journal");
+        let path = dir.path().join("pages.ldb");
+
+        let mut pager = Pager::open(&path, 4).unwrap();
+        let id = pager.allocate().unwrap();
+        pager.write(id, page(1)).unwrap();
+        pager.flush().unwrap();
+
+        pager.write(id, page(2)).unwrap();
+        pager.write_journal(&[id]).unwrap();
+        let journal = pager.journal.clone();
+        std::mem::forget(pager);
+
+        let bytes = std::fs::read(&journal).unwrap();
+        std::fs::write(&journal, &bytes[..bytes.len() - 1]).unwrap();
+
+        let mut pager = Pager::open(&path, 4).unwrap();
+        assert_eq!(pager.read(id).unwrap(), page(1));
+        assert!(!journal.exists());
+    }
+}
@@ -0,0 +1,400 @@
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, BTreeSet};
+use std::ops::Bound;
+
+use serde::{Deserialize, Serialize};
+
+use crate::value::RV;
+
+use super::DocumentId;
+
+/// An index as declared by CREATE INDEX. Fields are dotted paths into the
+/// documents of the collection.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct IndexDefinition {
+    pub name: String,
+    pub fields: Vec<String>,
+    pub unique: bool,
+}
+
+impl IndexDefinition {
+    pub fn key_of(&self, document: &RV) -> IndexKey {
+        self.fields
+            .iter()
+            .map(|path| IndexValue::from(&value_at(document, path)))
+            .collect()
+    }
+}
+
+/// Reads the value under a dotted path, or undefined if any part of the path
+/// is missing.
+pub fn value_at(document: &RV, path: &str) -> RV {
+    let mut current = document.clone();
+    for field in path.split('.') {
+        current = match current {
+            RV::Object(map) => map
+                .read()
+                .unwrap()
+                .get(field)
+                .cloned()
+                .unwrap_or(RV::Undefined),
+            _ => return RV::Undefined,
+        };
+    }
+    current
+}
+
+/// A value as ordered by an index. Values of different types never compare
+/// equal, and sort by type first: null, booleans, numbers, strings, then
+/// everything that has no order of its own.
+#[derive(Debug, Clone)]
+pub enum IndexValue {
+    Null,
+    Bool(bool),
+    Num(f64),
+    Str(String),
+    Other,
+}
+
+impl IndexValue {
+    fn rank(&self) -> usize {
+        match self {
+            IndexValue::Null => 0,
+            IndexValue::Bool(_) => 1,
+            IndexValue::Num(_) => 2,
+            IndexValue::Str(_) => 3,
+            IndexValue::Other => 4,
+        }
+    }
+
+    pub fn same_type(&self, other: &IndexValue) -> bool {
+        self.rank() == other.rank()
+    }
+
+    /// Whether the interpreter may find the values equal, or ordered, by
+    /// converting one to the type of the other, as it does between booleans,
+    /// numbers and strings. The index never does.
+    pub fn coerces(&self, other: &IndexValue) -> bool {
+        COERCED.contains(&self.rank())
+            && COERCED.contains(&other.rank())
+            && self.rank() != other.rank()
+    }
+}
+
+/// Ranks of the types the interpreter converts between when comparing.
+const COERCED: [usize; 3] = [1, 2, 3];
+
+impl From<&RV> for IndexValue {
+    fn from(value: &RV) -> Self {
+        match value {
+            RV::Null | RV::Undefined => IndexValue::Null,
+            RV::Bool(b) => IndexValue::Bool(*b),
+            RV::Num(n) => IndexValue::Num(*n),
+            RV::NaN => IndexValue::Num(f64::NAN),
+            RV::Str(s) => IndexValue::Str(s.to_string()),
+            RV::Object(_) | RV::Array(_) | RV::Callable(_) => IndexValue::Other,
+        }
+    }
+}
+
+impl Ord for IndexValue {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self, other) {
+            (IndexValue::Bool(a), IndexValue::Bool(b)) => a.cmp(b),
+            (IndexValue::Num(a), IndexValue::Num(b)) => a.total_cmp(b),
+            (IndexValue::Str(a), IndexValue::Str(b)) => a.cmp(b),
+            (a, b) => a.rank().cmp(&b.rank()),
+        }
+    }
+}
+
+impl PartialOrd for IndexValue {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for IndexValue {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for IndexValue {}
+
+pub type IndexKey = Vec<IndexValue>;
+
+pub type KeyRange = (Bound<IndexKey>, Bound<IndexKey>);
+
+/// Keys with a null in them are exempt from uniqueness, as missing fields
+/// are indexed as null.
+pub fn is_unique_key(key: &IndexKey) -> bool {
+    !key.iter().any(|value| matches!(value, IndexValue::Null))
+}
+
+/// Values of the bounds of the range, field by field.
+fn bound_values(range: &KeyRange) -> impl Iterator<Item = &IndexKey> {
+    [&range.0, &range.1]
+        .into_iter()
+        .filter_map(|bound| match bound {
+            Bound::Included(key) | Bound::Excluded(key) => Some(key),
+            Bound::Unbounded => None,
+        })
+}
+
+/// Whether the key falls in the range as the interpreter compares values,
+/// exactly when [`in_range`] says it does.
+pub fn compares_strictly(key: &IndexKey, range: &KeyRange) -> bool {
+    bound_values(range).all(|bound| {
+        key.iter()
+            .zip(bound)
+            .all(|(value, bound)| !value.coerces(bound))
+    })
+}
+
+pub fn in_range(key: &IndexKey, range: &KeyRange) -> bool {
+    let above = match &range.0 {
+        Bound::Included(lower) => key >= lower,
+        Bound::Excluded(lower) => key > lower,
+        Bound::Unbounded => true,
+    };
+    let below = match &range.1 {
+        Bound::Included(upper) => key <= upper,
+        Bound::Excluded(upper) => key < upper,
+        Bound::Unbounded => true,
+    };
+    above && below
+}
+
+/// Maps the keys of an index to the documents holding them.
+///
+/// Like the storage, an index is shared by every transaction, so it keeps
+/// the keys of every version some snapshot may still see. Lookups return
+/// candidates, which readers check against the version they actually see.
+/// Keys no snapshot needs anymore are pruned by vacuum.
+pub struct Index {
+    definition: IndexDefinition,
+    entries: BTreeMap<IndexKey, BTreeSet<DocumentId>>,
+    keys: BTreeMap<DocumentId, BTreeSet<IndexKey>>,
+    // Documents that were deleted or got a new key since they were indexed
+    stale: BTreeSet<DocumentId>,
+    // Number of keys holding a value of each type, by field and type rank
+    types: Vec<[usize; 5]>,
+}
+
+impl Index {
+    pub fn new(definition: IndexDefinition) -> Index {
+        Index {
+            types: vec![[0; 5]; definition.fields.len()],
+            definition,
+            entries: BTreeMap::new(),
+            keys: BTreeMap::new(),
+            stale: BTreeSet::new(),
+        }
+    }
+
+    pub fn definition(&self) -> &IndexDefinition {
+        &self.definition
+    }
+
+    /// Records the key of a new version of the document, or its deletion.
+    pub fn add(&mut self, id: DocumentId, document: Option<&RV>) {
+        let Some(document) = document else {
+            if self.keys.contains_key(&id) {
+                self.stale.insert(id);
+            }
+            return;
+        };
+
+        let key = self.definition.key_of(document);
+        let keys = self.keys.entry(id).or_default();
+        if !keys.contains(&key) {
+            if !keys.is_empty() {
+                self.stale.insert(id);
+            }
+            keys.insert(key.clone());
+            if !self.entries.contains_key(&key) {
+                self.count_types(&key, true);
+            }
+            self.entries.entry(key).or_default().insert(id);
+        }
+    }
+
+    pub fn candidates(&self, range: &KeyRange) -> BTreeSet<DocumentId> {
+        self.entries
+            .range::<IndexKey, _>((range.0.as_ref(), range.1.as_ref()))
+            .flat_map(|(_, ids)| ids.iter().copied())
+            .collect()
+    }
+
+    /// Whether the keys of the range are all the interpreter would find in
+    /// it, which is the case unless the index holds values of a type the
+    /// bounds of the range convert to.
+    pub fn compares_strictly(&self, range: &KeyRange) -> bool {
+        bound_values(range).all(|bound| {
+            bound.iter().zip(&self.types).all(|(bound, counts)| {
+                !COERCED.contains(&bound.rank())
+                    || COERCED
+                        .iter()
+                        .all(|&rank| rank == bound.rank() || counts[rank] == 0)
+            })
+        })
+    }
+
+    fn count_types(&mut self, key: &IndexKey, added: bool) {
+        for (value, counts) in key.iter().zip(self.types.iter_mut()) {
+            if added {
+                counts[value.rank()] += 1;
+            } else {
+                counts[value.rank()] -= 1;
+            }
+        }
+    }
+
+    pub fn stale(&self) -> Vec<DocumentId> {
+        self.stale.iter().copied().collect()
+    }
+
+    /// Drops every key of the document but the one of its current version,
+    /// once no snapshot can see its older versions.
+    pub fn prune(&mut self, id: DocumentId, current: Option<&RV>) {
+        let current = current.map(|document| self.definition.key_of(document));
+        for key in self.keys.remove(&id).unwrap_or_default() {
+            if Some(&key) == current.as_ref() {
+                continue;
+            }
+            if let Some(ids) = self.entries.get_mut(&key) {
+                ids.remove(&id);
+                if ids.is_empty() {
+                    self.entries.remove(&key);
+                    self.count_types(&key, false);
+                }
+            }
+        }
+        if let Some(key) = current {
+            self.keys.insert(id, BTreeSet::from([key]));
+        }
+        self.stale.remove(&id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use rustc_hash::FxHashMap;
+
+    use super::*;
+    use crate::util::alloc_shared;
+
+    fn book(title: &str, year: f64) -> RV {
+        let mut author = FxHashMap::default();
+        author.insert("name".to_string(), RV::Str(Arc::new("Orwell".to_string())));
+
+        let mut map = FxHashMap::default();
+        map.insert("title".to_string(), RV::Str(Arc::new(title.to_string())));
+        map.insert("year".to_string(), RV::Num(year));
+        map.insert("author".to_string(), RV::Object(alloc_shared(author)));
+        RV::Object(alloc_shared(map))
+    }
+
+    fn by_year() -> IndexDefinition {
+        IndexDefinition {
+            name: "by_year".to_string(),
+            fields: vec!["year".to_string()],
+            unique: false,
+        }
+    }
+
+    #[test]
+    fn test_keys_of_nested_fields() {
+        let definition = IndexDefinition {
+            name: "by_author".to_string(),
+            fields: vec!["author.name".to_string(), "publisher.name".to_string()],
+            unique: false,
+        };
+        assert_eq!(
+            definition.key_of(&book("1984", 1949.0)),
+            vec![IndexValue::Str("Orwell".to_string()), IndexValue::Null]
+        );
+    }
+
+    #[test]
+    fn test_values_order_by_type_first() {
+        let mut values = vec![
+            IndexValue::Str("a".to_string()),
+            IndexValue::Num(10.0),
+            IndexValue::Other,
+            IndexValue::Null,
+            IndexValue::Num(-1.0),
+            IndexValue::Bool(true),
+        ];
+        values.sort();
+        assert_eq!(
+            values,
+            vec![
+                IndexValue::Null,
+                IndexValue::Bool(true),
+                IndexValue::Num(-1.0),
+                IndexValue::Num(10.0),
+                IndexValue::Str("a".to_string()),
+                IndexValue::Other,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_candidates_and_pruning() {
+        let mut index = Index::new(by_year());
+        index.add(1, Some(&book("1984", 1949.0)));
+        index.add(2, Some(&book("Animal Farm", 1945.0)));
+        // Document 1 is updated, older snapshots still need the old key
+        index.add(1, Some(&book("1984", 1950.0)));
+
+        let range = (
+            Bound::Included(vec![IndexValue::Num(1945.0)]),
+            Bound::Excluded(vec![IndexValue::Num(1950.0)]),
+        );
+        assert_eq!(index.candidates(&range), BTreeSet::from([1, 2]));
+        assert_eq!(index.stale(), vec![1]);
+
+        index.prune(1, Some(&book("1984", 1950.0)));
+        assert_eq!(index.candidates(&range), BTreeSet::from([2]));
+        assert!(index.stale().is_empty());
+
+        index.add(2, None);
+        index.prune(2, None);
+        assert!(index
+            .candidates(&(Bound::Unbounded, Bound::Unbounded))
+            .eq(&BTreeSet::from([1])));
+    }
+
+    #[test]
+    fn test_compares_strictly() {
+        let mut index = Index::new(by_year());
+        index.add(1, Some(&book("1984", 1949.0)));
+        index.add(2, Some(&book("Animal Farm", 1945.0)));
+
+        let key = |value: IndexValue| {
+            (
+                Bound::Included(vec![value.clone()]),
+                Bound::Included(vec![value]),
+            )
+        };
+        let number = key(IndexValue::Num(1949.0));
+        let string = key(IndexValue::Str("1949".to_string()));
+        assert!(index.compares_strictly(&number));
+        assert!(!index.compares_strictly(&string));
+        assert!(index.compares_strictly(&key(IndexValue::Null)));
+        assert!(!compares_strictly(&vec![IndexValue::Num(1949.0)], &string));
+
+        // Strings only matter while the index holds some
+        let mut map = FxHashMap::default();
+        map.insert("year".to_string(), RV::Str(Arc::new("1949".to_string())));
+        index.add(3, Some(&RV::Object(alloc_shared(map))));
+        assert!(!index.compares_strictly(&number));
+        index.add(3, None);
+        index.prune(3, None);
+        assert!(index.compares_strictly(&number));
+    }
+}
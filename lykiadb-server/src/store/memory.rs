@@ -0,0 +1,267 @@
+use std::collections::BTreeMap;
+use std::ops::Bound;
+
+use rustc_hash::FxHashMap;
+
+use crate::value::RV;
+
+use super::{validate_document, DocumentId, DocumentRange, StorageEngine, StorageError};
+
+struct MemoryCollection {
+    open: bool,
+    documents: BTreeMap<DocumentId, RV>,
+}
+
+/// Keeps every collection in memory. Documents are copied on the way in and
+/// out, so scripts never mutate stored values through shared references.
+#[derive(Default)]
+pub struct MemoryEngine {
+    collections: FxHashMap<String, MemoryCollection>,
+}
+
+impl MemoryEngine {
+    pub fn new() -> MemoryEngine {
+        MemoryEngine {
+            collections: FxHashMap::default(),
+        }
+    }
+
+    fn collection(&self, name: &str) -> Result<&MemoryCollection, StorageError> {
+        match self.collections.get(name) {
+            Some(collection) if collection.open => Ok(collection),
+            Some(_) => Err(StorageError::CollectionNotOpen {
+                name: name.to_string(),
+            }),
+            None => Err(StorageError::CollectionNotFound {
+                name: name.to_string(),
+            }),
+        }
+    }
+
+    fn collection_mut(&mut self, name: &str) -> Result<&mut MemoryCollection, StorageError> {
+        match self.collections.get_mut(name) {
+            Some(collection) if collection.open => Ok(collection),
+            Some(_) => Err(StorageError::CollectionNotOpen {
+                name: name.to_string(),
+            }),
+            None => Err(StorageError::CollectionNotFound {
+                name: name.to_string(),
+            }),
+        }
+    }
+}
+
+impl StorageEngine for MemoryEngine {
+    fn open_collection(&mut self, name: &str) -> Result<(), StorageError> {
+        self.collections
+            .entry(name.to_string())
+            .or_insert_with(|| MemoryCollection {
+                open: false,
+                documents: BTreeMap::new(),
+            })
+            .open = true;
+        Ok(())
+    }
+
+    fn close_collection(&mut self, name: &str) -> Result<(), StorageError> {
+        self.collection_mut(name)?.open = false;
+        Ok(())
+    }
+
+    fn is_open(&self, name: &str) -> bool {
+        self.collections
+            .get(name)
+            .is_some_and(|collection| collection.open)
+    }
+
+    fn insert(
+        &mut self,
+        collection: &str,
+        id: DocumentId,
+        document: RV,
+    ) -> Result<Option<RV>, StorageError> {
+        validate_document(&document)?;
+        Ok(self
+            .collection_mut(collection)?
+            .documents
+            .insert(id, document.deep_clone()))
+    }
+
+    fn get(&self, collection: &str, id: DocumentId) -> Result<Option<RV>, StorageError> {
+        Ok(self
+            .collection(collection)?
+            .documents
+            .get(&id)
+            .map(|doc| doc.deep_clone()))
+    }
+
+    fn delete(&mut self, collection: &str, id: DocumentId) -> Result<Option<RV>, StorageError> {
+        Ok(self.collection_mut(collection)?.documents.remove(&id))
+    }
+
+    fn range(
+        &self,
+        collection: &str,
+        range: DocumentRange,
+    ) -> Result<Vec<(DocumentId, RV)>, StorageError> {
+        Ok(self
+            .collection(collection)?
+            .documents
+            .range(range)
+            .map(|(id, doc)| (*id, doc.deep_clone()))
+            .collect())
+    }
+
+    fn page(
+        &self,
+        collection: &str,
+        range: DocumentRange,
+        limit: usize,
+    ) -> Result<Vec<(DocumentId, RV)>, StorageError> {
+        Ok(self
+            .collection(collection)?
+            .documents
+            .range(range)
+            .take(limit)
+            .map(|(id, doc)| (*id, doc.deep_clone()))
+            .collect())
+    }
+
+    fn last_id(
+        &self,
+        collection: &str,
+        end: Bound<DocumentId>,
+    ) -> Result<Option<DocumentId>, StorageError> {
+        Ok(self
+            .collection(collection)?
+            .documents
+            .range((Bound::Unbounded, end))
+            .next_back()
+            .map(|(id, _)| *id))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ops::Bound;
+    use std::sync::Arc;
+
+    use rustc_hash::FxHashMap;
+
+    use super::*;
+    use crate::util::alloc_shared;
+
+    fn doc(name: &str) -> RV {
+        let mut map = FxHashMap::default();
+        map.insert("name".to_string(), RV::Str(Arc::new(name.to_string())));
+        RV::Object(alloc_shared(map))
+    }
+
+    fn name_of(doc: &RV) -> String {
+        match doc {
+            RV::Object(map) => map.read().unwrap().get("name").unwrap().to_string(),
+            _ => panic!("Expected an object"),
+        }
+    }
+
+    #[test]
+    fn test_insert_get_delete() {
+        let mut engine = MemoryEngine::new();
+        engine.open_collection("books").unwrap();
+
+        assert!(engine.insert("books", 1, doc("Dune")).unwrap().is_none());
+        assert_eq!(name_of(&engine.get("books", 1).unwrap().unwrap()), "Dune");
+
+        let previous = engine.insert("books", 1, doc("Emma")).unwrap().unwrap();
+        assert_eq!(name_of(&previous), "Dune");
+
+        assert_eq!(
+            name_of(&engine.delete("books", 1).unwrap().unwrap()),
+            "Emma"
+        );
+        assert!(engine.get("books", 1).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_range() {
+        let mut engine = MemoryEngine::new();
+        engine.open_collection("books").unwrap();
+
+        for (id, name) in [(3, "c"), (1, "a"), (2, "b"), (5, "e")] {
+            engine.insert("books", id, doc(name)).unwrap();
+        }
+
+        let all = engine
+            .range("books", (Bound::Unbounded, Bound::Unbounded))
+            .unwrap();
+        assert_eq!(
+            all.iter().map(|(id, _)| *id).collect::<Vec<_>>(),
+            vec![1, 2, 3, 5]
+        );
+
+        let some = engine
+            .range("books", (Bound::Excluded(1), Bound::Included(3)))
+            .unwrap();
+        assert_eq!(
+            some.iter().map(|(_, d)| name_of(d)).collect::<Vec<_>>(),
+            vec!["b", "c"]
+        );
+    }
+
+    #[test]
+    fn test_documents_are_copied() {
+        let mut engine = MemoryEngine::new();
+        engine.open_collection("books").unwrap();
+
+        let original = doc("Dune");
+        engine.insert("books", 1, original.clone()).unwrap();
+
+        if let RV::Object(map) = &original {
+            map.write()
+                .unwrap()
+                .insert("name".to_string(), RV::Str(Arc::new("Emma".to_string())));
+        }
+
+        assert_eq!(name_of(&engine.get("books", 1).unwrap().unwrap()), "Dune");
+    }
+
+    #[test]
+    fn test_collection_lifecycle() {
+        let mut engine = MemoryEngine::new();
+
+        assert_eq!(
+            engine.get("books", 1),
+            Err(StorageError::CollectionNotFound {
+                name: "books".to_string()
+            })
+        );
+
+        assert!(!engine.is_open("books"));
+        engine.open_collection("books").unwrap();
+        assert!(engine.is_open("books"));
+        engine.insert("books", 1, doc("Dune")).unwrap();
+        engine.close_collection("books").unwrap();
+        assert!(!engine.is_open("books"));
+
+        assert_eq!(
+            engine.get("books", 1),
+            Err(StorageError::CollectionNotOpen {
+                name: "books".to_string()
+            })
+        );
+
+        engine.open_collection("books").unwrap();
+        assert!(engine.get("books", 1).unwrap().is_some());
+    }
+
+    #[test]
+    fn test_only_objects_are_stored() {
+        let mut engine = MemoryEngine::new();
+        engine.open_collection("books").unwrap();
+
+        assert!(matches!(
+            engine.insert("books", 1, RV::Num(1.0)),
+            Err(StorageError::InvalidDocument { .. })
+        ));
+    }
+}
@@ -0,0 +1,146 @@
+use std::ops::Bound;
+
+use serde::{Deserialize, Serialize};
+
+use crate::util::Shared;
+use crate::value::RV;
+
+pub mod codec;
+pub mod disk;
+pub mod index;
+pub mod memory;
+pub mod mvcc;
+pub mod transaction;
+pub mod wal;
+
+pub type DocumentId = u64;
+
+pub type DocumentRange = (Bound<DocumentId>, Bound<DocumentId>);
+
+pub type SharedStorage = Shared<dyn StorageEngine>;
+
+#[derive(PartialEq, Eq, Debug, Clone, Serialize, Deserialize)]
+pub enum StorageError {
+    CollectionNotFound { name: String },
+    CollectionNotOpen { name: String },
+    InvalidDocument { message: String },
+    Io { message: String },
+    Corrupted { message: String },
+    Other { message: String },
+}
+
+/// A single change to a collection, as applied by [`StorageEngine::write_batch`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum Mutation {
+    Insert {
+        collection: String,
+        id: DocumentId,
+        document: RV,
+    },
+    Delete {
+        collection: String,
+        id: DocumentId,
+    },
+}
+
+impl From<std::io::Error> for StorageError {
+    fn from(err: std::io::Error) -> Self {
+        StorageError::Io {
+            message: err.to_string(),
+        }
+    }
+}
+
+/// A storage engine keeps the documents of every collection, keyed by their
+/// document id. Engines are shared by all sessions of a server, so they have
+/// to be safe to send across the tokio worker threads.
+pub trait StorageEngine: Send + Sync {
+    /// Opens the collection, creating it if it does not exist yet.
+    fn open_collection(&mut self, name: &str) -> Result<(), StorageError>;
+
+    /// Releases the collection. Documents are kept and become reachable again
+    /// once the collection is reopened.
+    fn close_collection(&mut self, name: &str) -> Result<(), StorageError>;
+
+    /// Whether the collection is open, without touching its documents.
+    fn is_open(&self, name: &str) -> bool;
+
+    /// Stores the document under the given id, replacing the previous one.
+    fn insert(
+        &mut self,
+        collection: &str,
+        id: DocumentId,
+        document: RV,
+    ) -> Result<Option<RV>, StorageError>;
+
+    fn get(&self, collection: &str, id: DocumentId) -> Result<Option<RV>, StorageError>;
+
+    fn delete(&mut self, collection: &str, id: DocumentId) -> Result<Option<RV>, StorageError>;
+
+    /// Returns the documents whose ids fall in the range, in ascending id order.
+    fn range(
+        &self,
+        collection: &str,
+        range: DocumentRange,
+    ) -> Result<Vec<(DocumentId, RV)>, StorageError>;
+
+    /// Returns the first `limit` documents whose ids fall in the range, in
+    /// ascending id order.
+    fn page(
+        &self,
+        collection: &str,
+        range: DocumentRange,
+        limit: usize,
+    ) -> Result<Vec<(DocumentId, RV)>, StorageError> {
+        let mut documents = self.range(collection, range)?;
+        documents.truncate(limit);
+        Ok(documents)
+    }
+
+    /// Returns the largest id in the collection up to the bound.
+    fn last_id(
+        &self,
+        collection: &str,
+        end: Bound<DocumentId>,
+    ) -> Result<Option<DocumentId>, StorageError> {
+        Ok(self
+            .range(collection, (Bound::Unbounded, end))?
+            .last()
+            .map(|(id, _)| *id))
+    }
+
+    /// Applies the mutations in order. Engines with a log make the whole
+    /// batch durable at once, so it is either recovered entirely or not at all.
+    fn write_batch(&mut self, batch: Vec<Mutation>) -> Result<(), StorageError> {
+        for mutation in batch {
+            match mutation {
+                Mutation::Insert {
+                    collection,
+                    id,
+                    document,
+                } => {
+                    self.insert(&collection, id, document)?;
+                }
+                Mutation::Delete { collection, id } => {
+                    self.delete(&collection, id)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Makes every change so far durable. Engines that do not persist
+    /// anything have nothing to do here.
+    fn flush(&mut self) -> Result<(), StorageError> {
+        Ok(())
+    }
+}
+
+pub fn validate_document(document: &RV) -> Result<(), StorageError> {
+    match document {
+        RV::Object(_) => Ok(()),
+        other => Err(StorageError::InvalidDocument {
+            message: format!("Only objects can be stored as documents, found {:?}", other),
+        }),
+    }
+}
@@ -0,0 +1,268 @@
+use std::collections::BTreeMap;
+use std::ops::Bound;
+
+use rustc_hash::FxHashMap;
+
+use crate::value::RV;
+
+use super::{DocumentId, DocumentRange};
+
+/// Logical clock value. Every commit advances the clock by one, and every
+/// transaction reads the state as of the clock value it started at.
+pub type Timestamp = u64;
+
+struct Version {
+    committed_at: Timestamp,
+    // None marks a deleted document
+    document: Option<RV>,
+}
+
+/// Keeps the older versions of recently written documents, so that
+/// transactions started before a commit keep seeing what was there when they
+/// started. The storage itself only ever holds the latest committed version.
+///
+/// A chain starts with the version the document had before it was first
+/// tracked, stamped with timestamp zero, followed by one version per commit.
+#[derive(Default)]
+pub struct VersionStore {
+    chains: FxHashMap<String, BTreeMap<DocumentId, Vec<Version>>>,
+}
+
+impl VersionStore {
+    pub fn new() -> VersionStore {
+        VersionStore::default()
+    }
+
+    /// The version visible at the snapshot, or None if the document is not
+    /// tracked and the storage holds the visible version.
+    pub fn visible(
+        &self,
+        collection: &str,
+        id: DocumentId,
+        snapshot: Timestamp,
+    ) -> Option<Option<RV>> {
+        let chain = self.chains.get(collection)?.get(&id)?;
+        Some(visible_in(chain, snapshot))
+    }
+
+    /// Replaces the latest versions in `documents` with the ones visible at
+    /// the snapshot.
+    pub fn overlay(
+        &self,
+        collection: &str,
+        range: DocumentRange,
+        snapshot: Timestamp,
+        documents: &mut BTreeMap<DocumentId, RV>,
+    ) {
+        let Some(chains) = self.chains.get(collection) else {
+            return;
+        };
+        for (id, chain) in chains.range(range) {
+            match visible_in(chain, snapshot) {
+                Some(document) => documents.insert(*id, document),
+                None => documents.remove(id),
+            };
+        }
+    }
+
+    /// Largest id up to the bound of the tracked documents visible at the
+    /// snapshot.
+    pub fn last_visible(
+        &self,
+        collection: &str,
+        end: Bound<DocumentId>,
+        snapshot: Timestamp,
+    ) -> Option<DocumentId> {
+        self.chains
+            .get(collection)?
+            .range((Bound::Unbounded, end))
+            .rev()
+            .find(|(_, chain)| visible_in(chain, snapshot).is_some())
+            .map(|(id, _)| *id)
+    }
+
+    /// Timestamp of the latest commit that wrote the document, if tracked.
+    pub fn last_commit(&self, collection: &str, id: DocumentId) -> Option<Timestamp> {
+        self.chains
+            .get(collection)?
+            .get(&id)?
+            .last()
+            .map(|version| version.committed_at)
+    }
+
+    pub fn record(
+        &mut self,
+        collection: &str,
+        id: DocumentId,
+        previous: Option<RV>,
+        document: Option<RV>,
+        committed_at: Timestamp,
+    ) {
+        let chain = self
+            .chains
+            .entry(collection.to_string())
+            .or_default()
+            .entry(id)
+            .or_insert_with(|| {
+                vec![Version {
+                    committed_at: 0,
+                    document: previous,
+                }]
+            });
+        chain.push(Version {
+            committed_at,
+            document,
+        });
+    }
+
+    /// Drops the versions no snapshot at or after the horizon can see, and
+    /// returns how many were dropped from each collection. Chains whose only
+    /// remaining version is the one in storage are dropped entirely.
+    pub fn vacuum(
+        &mut self,
+        collection: Option<&str>,
+        horizon: Timestamp,
+    ) -> FxHashMap<String, usize> {
+        let mut reclaimed: FxHashMap<String, usize> = FxHashMap::default();
+
+        for (name, chains) in self.chains.iter_mut() {
+            if collection.is_some_and(|c| c != name) {
+                continue;
+            }
+
+            let mut count = 0;
+            chains.retain(|_, chain| {
+                let oldest_needed = chain
+                    .iter()
+                    .rposition(|version| version.committed_at <= horizon)
+                    .unwrap_or(0);
+                count += oldest_needed;
+                chain.drain(..oldest_needed);
+
+                if chain.len() == 1 {
+                    count += 1;
+                    return false;
+                }
+                true
+            });
+
+            reclaimed.insert(name.clone(), count);
+        }
+
+        self.chains.retain(|_, chains| !chains.is_empty());
+        reclaimed
+    }
+
+    pub fn is_tracked(&self, collection: &str, id: DocumentId) -> bool {
+        self.chains
+            .get(collection)
+            .is_some_and(|chains| chains.contains_key(&id))
+    }
+
+    /// Every version of the collection some snapshot may still see, deleted
+    /// ones aside.
+    pub fn documents<'a>(
+        &'a self,
+        collection: &str,
+    ) -> impl Iterator<Item = (DocumentId, &'a RV)> + 'a {
+        self.chains
+            .get(collection)
+            .into_iter()
+            .flat_map(|chains| chains.iter())
+            .flat_map(|(id, chain)| {
+                chain
+                    .iter()
+                    .filter_map(move |version| version.document.as_ref().map(|doc| (*id, doc)))
+            })
+    }
+
+    pub fn version_count(&self) -> usize {
+        self.chains
+            .values()
+            .flat_map(|chains| chains.values())
+            .map(|chain| chain.len())
+            .sum()
+    }
+}
+
+fn visible_in(chain: &[Version], snapshot: Timestamp) -> Option<RV> {
+    chain
+        .iter()
+        .rev()
+        .find(|version| version.committed_at <= snapshot)
+        .and_then(|version| version.document.as_ref())
+        .map(|document| document.deep_clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ops::Bound;
+
+    use super::*;
+
+    fn num(doc: &Option<RV>) -> Option<f64> {
+        doc.as_ref().map(|doc| match doc {
+            RV::Num(n) => *n,
+            _ => panic!("Expected a number"),
+        })
+    }
+
+    #[test]
+    fn test_snapshots_see_their_versions() {
+        let mut versions = VersionStore::new();
+        assert!(versions.visible("books", 1, 5).is_none());
+
+        versions.record("books", 1, Some(RV::Num(1.0)), Some(RV::Num(2.0)), 3);
+        versions.record("books", 1, Some(RV::Num(2.0)), None, 5);
+
+        assert_eq!(num(&versions.visible("books", 1, 2).unwrap()), Some(1.0));
+        assert_eq!(num(&versions.visible("books", 1, 3).unwrap()), Some(2.0));
+        assert_eq!(num(&versions.visible("books", 1, 4).unwrap()), Some(2.0));
+        assert_eq!(num(&versions.visible("books", 1, 5).unwrap()), None);
+        assert_eq!(versions.last_commit("books", 1), Some(5));
+    }
+
+    #[test]
+    fn test_overlay() {
+        let mut versions = VersionStore::new();
+        versions.record("books", 1, Some(RV::Num(1.0)), None, 2);
+        versions.record("books", 2, None, Some(RV::Num(2.0)), 2);
+
+        // The storage holds the latest state
+        let mut documents = BTreeMap::new();
+        documents.insert(2, RV::Num(2.0));
+        documents.insert(3, RV::Num(3.0));
+
+        versions.overlay(
+            "books",
+            (Bound::Unbounded, Bound::Unbounded),
+            1,
+            &mut documents,
+        );
+
+        assert_eq!(documents.keys().copied().collect::<Vec<_>>(), vec![1, 3]);
+    }
+
+    #[test]
+    fn test_vacuum_keeps_what_snapshots_need() {
+        let mut versions = VersionStore::new();
+        versions.record("books", 1, Some(RV::Num(1.0)), Some(RV::Num(2.0)), 2);
+        versions.record("books", 1, Some(RV::Num(2.0)), Some(RV::Num(3.0)), 4);
+        versions.record("authors", 1, None, Some(RV::Num(1.0)), 3);
+        assert_eq!(versions.version_count(), 5);
+
+        // A snapshot taken at 3 still needs the version committed at 2
+        let reclaimed = versions.vacuum(Some("books"), 3);
+        assert_eq!(reclaimed.get("books"), Some(&1));
+        assert_eq!(reclaimed.get("authors"), None);
+        assert_eq!(num(&versions.visible("books", 1, 3).unwrap()), Some(2.0));
+        assert_eq!(versions.version_count(), 4);
+
+        // Once every snapshot is past the last commit, storage has it all
+        let reclaimed = versions.vacuum(None, 4);
+        assert_eq!(reclaimed.get("books"), Some(&2));
+        assert_eq!(reclaimed.get("authors"), Some(&2));
+        assert!(versions.visible("books", 1, 4).is_none());
+        assert_eq!(versions.version_count(), 0);
+    }
+}
@@ -0,0 +1,1569 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::ops::Bound;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use lykiadb_lang::ast::sql::SqlTransactionMode;
+use lykiadb_lang::ast::Span;
+use rustc_hash::FxHashMap;
+use serde::{Deserialize, Serialize};
+use tokio::task::JoinHandle;
+use tracing::{error, info};
+
+use crate::engine::error::ExecutionError;
+use crate::value::RV;
+
+use super::index::{
+    compares_strictly, in_range, is_unique_key, Index, IndexDefinition, IndexKey, KeyRange,
+};
+use super::mvcc::{Timestamp, VersionStore};
+use super::{
+    validate_document, DocumentId, DocumentRange, Mutation, SharedStorage, StorageEngine,
+    StorageError,
+};
+
+pub type TransactionId = u64;
+
+pub type SharedTransactionManager = Arc<TransactionManager>;
+
+/// Documents read at once, along with the rest of the range they were read
+/// from, if any.
+type Page = (Vec<(DocumentId, RV)>, Option<DocumentRange>);
+
+#[derive(PartialEq, Eq, Debug, Clone, Serialize, Deserialize)]
+pub enum TransactionError {
+    AlreadyInTransaction {
+        span: Span,
+    },
+    NoActiveTransaction {
+        span: Span,
+    },
+    ReadOnlyTransaction {
+        collection: String,
+        span: Span,
+    },
+    SerializationFailure {
+        collection: String,
+        id: DocumentId,
+        span: Span,
+    },
+    UniqueViolation {
+        collection: String,
+        index: String,
+        span: Span,
+    },
+    Storage(StorageError),
+}
+
+impl TransactionError {
+    /// Places an error raised by the storage layer, which knows nothing of
+    /// the statements, at the statement it was raised for. Errors already
+    /// placed are left where they are.
+    pub fn at(mut self, at: Span) -> Self {
+        if let TransactionError::ReadOnlyTransaction { span, .. }
+        | TransactionError::SerializationFailure { span, .. }
+        | TransactionError::UniqueViolation { span, .. } = &mut self
+        {
+            if *span == Span::default() {
+                *span = at;
+            }
+        }
+        self
+    }
+}
+
+impl From<StorageError> for TransactionError {
+    fn from(err: StorageError) -> Self {
+        TransactionError::Storage(err)
+    }
+}
+
+impl From<TransactionError> for ExecutionError {
+    fn from(err: TransactionError) -> Self {
+        match err {
+            TransactionError::Storage(err) => ExecutionError::Storage(err),
+            other => ExecutionError::Transaction(other),
+        }
+    }
+}
+
+struct MvccState {
+    next_id: TransactionId,
+    // Timestamp of the latest commit
+    clock: Timestamp,
+    // Snapshots of the transactions in progress
+    active: BTreeMap<TransactionId, Timestamp>,
+    versions: VersionStore,
+    // Indexes of every collection, by storage collection name
+    indexes: FxHashMap<String, Vec<Index>>,
+    // Largest document id handed out for each collection
+    ids: FxHashMap<String, DocumentId>,
+    // Latest commit of a transaction that depended on each document
+    dependents: FxHashMap<String, BTreeMap<DocumentId, Timestamp>>,
+}
+
+/// Changes to the indexes of the database, applied once the transaction
+/// making them commits.
+enum SchemaChange {
+    CreateIndex {
+        collection: String,
+        definition: IndexDefinition,
+    },
+    DropIndexes {
+        collection: String,
+    },
+}
+
+type Writes = BTreeMap<String, BTreeMap<DocumentId, Option<RV>>>;
+
+type Dependencies = BTreeMap<String, BTreeSet<DocumentId>>;
+
+/// The writes a transaction had buffered at some point, which it can go
+/// back to. Buffered documents are copied on their way in and out, so the
+/// ones kept here are never changed in place.
+pub struct Savepoint {
+    transaction: TransactionId,
+    writes: Writes,
+    dependencies: Dependencies,
+    schema_changes: usize,
+}
+
+/// Hands out transactions over the storage shared by all sessions.
+///
+/// Transactions run under snapshot isolation: each one reads the documents as
+/// they were committed when it started, along with its own writes. When two
+/// transactions write the same document, or one writes a document the other
+/// depends on, the first one to commit wins and the other fails with a
+/// serialization failure.
+pub struct TransactionManager {
+    storage: SharedStorage,
+    state: Mutex<MvccState>,
+}
+
+impl TransactionManager {
+    pub fn new(storage: SharedStorage) -> TransactionManager {
+        TransactionManager {
+            storage,
+            state: Mutex::new(MvccState {
+                next_id: 1,
+                clock: 0,
+                active: BTreeMap::new(),
+                versions: VersionStore::new(),
+                indexes: FxHashMap::default(),
+                ids: FxHashMap::default(),
+                dependents: FxHashMap::default(),
+            }),
+        }
+    }
+
+    pub fn storage(&self) -> SharedStorage {
+        self.storage.clone()
+    }
+
+    pub fn begin(self: &Arc<Self>, mode: SqlTransactionMode) -> Transaction {
+        let mut state = self.state.lock().unwrap();
+        let id = state.next_id;
+        let snapshot = state.clock;
+        state.next_id += 1;
+        state.active.insert(id, snapshot);
+
+        Transaction {
+            id,
+            mode,
+            snapshot,
+            manager: self.clone(),
+            writes: BTreeMap::new(),
+            dependencies: BTreeMap::new(),
+            schema_changes: vec![],
+        }
+    }
+
+    /// Builds the index over the documents already in the collection, and
+    /// maintains it from then on.
+    pub fn create_index(
+        &self,
+        collection: &str,
+        definition: IndexDefinition,
+    ) -> Result<(), StorageError> {
+        let mut state = self.state.lock().unwrap();
+        let storage = self.storage.read().unwrap();
+        let index = build_index(&state.versions, &*storage, collection, definition)?;
+        register_index(&mut state, collection, index);
+        Ok(())
+    }
+
+    /// Ids of the documents that may have a key in the range, including
+    /// those whose key only some snapshots see. None if the index is not
+    /// built, which is the case until the transaction creating it commits.
+    fn index_candidates(
+        &self,
+        collection: &str,
+        definition: &IndexDefinition,
+        range: &KeyRange,
+    ) -> Option<Vec<DocumentId>> {
+        let state = self.state.lock().unwrap();
+        state
+            .indexes
+            .get(collection)?
+            .iter()
+            .find(|index| index.definition() == definition)
+            .map(|index| index.candidates(range).into_iter().collect())
+    }
+
+    /// Whether the index is built and holds no values the bounds of the
+    /// range would be converted to when compared by the interpreter.
+    fn index_compares_strictly(
+        &self,
+        collection: &str,
+        definition: &IndexDefinition,
+        range: &KeyRange,
+    ) -> bool {
+        let state = self.state.lock().unwrap();
+        state.indexes.get(collection).is_some_and(|indexes| {
+            indexes
+                .iter()
+                .find(|index| index.definition() == definition)
+                .is_some_and(|index| index.compares_strictly(range))
+        })
+    }
+
+    fn get(
+        &self,
+        collection: &str,
+        id: DocumentId,
+        snapshot: Timestamp,
+    ) -> Result<Option<RV>, StorageError> {
+        let state = self.state.lock().unwrap();
+        // Checking the collection even for tracked documents, so that a
+        // missing collection is reported the same way either way
+        let stored = self.storage.read().unwrap().get(collection, id)?;
+        match state.versions.visible(collection, id, snapshot) {
+            Some(visible) => Ok(visible),
+            None => Ok(stored),
+        }
+    }
+
+    fn range(
+        &self,
+        collection: &str,
+        range: DocumentRange,
+        snapshot: Timestamp,
+    ) -> Result<BTreeMap<DocumentId, RV>, StorageError> {
+        let state = self.state.lock().unwrap();
+        let mut documents: BTreeMap<DocumentId, RV> = self
+            .storage
+            .read()
+            .unwrap()
+            .range(collection, range)?
+            .into_iter()
+            .collect();
+        state
+            .versions
+            .overlay(collection, range, snapshot, &mut documents);
+        Ok(documents)
+    }
+
+    /// The documents visible at the snapshot in the part of the range the
+    /// first `limit` stored documents cover, along with that part. Versions
+    /// are overlaid within it, so documents deleted since the snapshot was
+    /// taken are found there too.
+    fn page(
+        &self,
+        collection: &str,
+        range: DocumentRange,
+        limit: usize,
+        snapshot: Timestamp,
+    ) -> Result<(BTreeMap<DocumentId, RV>, DocumentRange), StorageError> {
+        let state = self.state.lock().unwrap();
+        let stored = self
+            .storage
+            .read()
+            .unwrap()
+            .page(collection, range, limit)?;
+        let covered = match stored.last() {
+            Some((last, _)) if stored.len() == limit => (range.0, Bound::Included(*last)),
+            _ => range,
+        };
+        let mut documents: BTreeMap<DocumentId, RV> = stored.into_iter().collect();
+        state
+            .versions
+            .overlay(collection, covered, snapshot, &mut documents);
+        Ok((documents, covered))
+    }
+
+    /// Hands out a document id larger than the floor, than any stored one
+    /// and than those handed out before. The ids are shared by every
+    /// transaction, so concurrent inserts never pick the same one, and are
+    /// not given back on rollback.
+    fn allocate_id(
+        &self,
+        collection: &str,
+        floor: Option<DocumentId>,
+    ) -> Result<DocumentId, StorageError> {
+        let mut state = self.state.lock().unwrap();
+        let stored = self
+            .storage
+            .read()
+            .unwrap()
+            .last_id(collection, Bound::Unbounded)?;
+        let id = state
+            .ids
+            .get(collection)
+            .copied()
+            .max(stored)
+            .max(floor)
+            .map_or(1, |id| id + 1);
+        state.ids.insert(collection.to_string(), id);
+        Ok(id)
+    }
+
+    /// Largest id up to the bound of the documents visible at the snapshot.
+    /// Documents stored after the snapshot was taken are skipped, and those
+    /// deleted since come from their versions.
+    fn last_id(
+        &self,
+        collection: &str,
+        end: Bound<DocumentId>,
+        snapshot: Timestamp,
+    ) -> Result<Option<DocumentId>, StorageError> {
+        let state = self.state.lock().unwrap();
+        let storage = self.storage.read().unwrap();
+        let mut bound = end;
+        let stored = loop {
+            let Some(id) = storage.last_id(collection, bound)? else {
+                break None;
+            };
+            if let Some(None) = state.versions.visible(collection, id, snapshot) {
+                bound = Bound::Excluded(id);
+                continue;
+            }
+            break Some(id);
+        };
+        Ok(stored.max(state.versions.last_visible(collection, end, snapshot)))
+    }
+
+    fn commit(&self, transaction: &mut Transaction) -> Result<(), TransactionError> {
+        let writes = std::mem::take(&mut transaction.writes);
+        let dependencies = std::mem::take(&mut transaction.dependencies);
+        let schema_changes = std::mem::take(&mut transaction.schema_changes);
+        if writes.is_empty() && schema_changes.is_empty() {
+            return Ok(());
+        }
+
+        let mut state = self.state.lock().unwrap();
+
+        // Documents written, or depended on, since the snapshot was taken,
+        // and documents written that others have come to depend on since
+        let written = writes
+            .iter()
+            .flat_map(|(collection, docs)| docs.keys().map(move |id| (collection, *id, true)));
+        let depended = dependencies
+            .iter()
+            .flat_map(|(collection, ids)| ids.iter().map(move |id| (collection, *id, false)));
+        for (collection, id, is_write) in written.chain(depended) {
+            let mut changed_at = state.versions.last_commit(collection, id);
+            if is_write {
+                let depended_at = state
+                    .dependents
+                    .get(collection)
+                    .and_then(|dependents| dependents.get(&id))
+                    .copied();
+                changed_at = changed_at.max(depended_at);
+            }
+            if changed_at.is_some_and(|committed_at| committed_at > transaction.snapshot) {
+                return Err(TransactionError::SerializationFailure {
+                    collection: collection.clone(),
+                    id,
+                    span: Span::default(),
+                });
+            }
+        }
+
+        let committed_at = state.clock + 1;
+        let mut storage = self.storage.write().unwrap();
+        check_unique(&state, &*storage, &writes, &schema_changes)?;
+
+        let mut batch: Vec<Mutation> = vec![];
+        let mut previous: Vec<Option<RV>> = vec![];
+
+        for (collection, docs) in &writes {
+            for (id, doc) in docs {
+                previous.push(storage.get(collection, *id)?);
+                batch.push(match doc {
+                    Some(document) => Mutation::Insert {
+                        collection: collection.clone(),
+                        id: *id,
+                        document: document.deep_clone(),
+                    },
+                    None => Mutation::Delete {
+                        collection: collection.clone(),
+                        id: *id,
+                    },
+                });
+            }
+        }
+
+        storage.write_batch(batch)?;
+
+        let mut previous = previous.into_iter();
+        for (collection, docs) in writes {
+            for (id, doc) in docs {
+                if let Some(indexes) = state.indexes.get_mut(&collection) {
+                    for index in indexes {
+                        index.add(id, doc.as_ref());
+                    }
+                }
+                state
+                    .versions
+                    .record(&collection, id, previous.next().unwrap(), doc, committed_at);
+            }
+        }
+        for (collection, ids) in dependencies {
+            let dependents = state.dependents.entry(collection).or_default();
+            for id in ids {
+                dependents.insert(id, committed_at);
+            }
+        }
+
+        for change in schema_changes {
+            match change {
+                SchemaChange::CreateIndex {
+                    collection,
+                    definition,
+                } => {
+                    let index = build_index(&state.versions, &*storage, &collection, definition)?;
+                    register_index(&mut state, &collection, index);
+                }
+                SchemaChange::DropIndexes { collection } => {
+                    state.indexes.remove(&collection);
+                    state.ids.remove(&collection);
+                }
+            }
+        }
+        state.clock = committed_at;
+
+        Ok(())
+    }
+
+    /// Reclaims the versions no transaction in progress can see anymore.
+    pub fn vacuum(
+        &self,
+        collection: Option<&str>,
+    ) -> Result<FxHashMap<String, usize>, StorageError> {
+        let mut state = self.state.lock().unwrap();
+
+        if let Some(name) = collection {
+            if !self.storage.read().unwrap().is_open(name) {
+                return Err(StorageError::CollectionNotFound {
+                    name: name.to_string(),
+                });
+            }
+        }
+
+        let horizon = state.active.values().copied().min().unwrap_or(state.clock);
+        let mut reclaimed = state.versions.vacuum(collection, horizon);
+        // Every snapshot left sees the commits of the dependents up to it
+        for (name, dependents) in state.dependents.iter_mut() {
+            if collection.is_none_or(|c| c == name) {
+                dependents.retain(|_, committed_at| *committed_at > horizon);
+            }
+        }
+
+        // Keys of versions that are gone can go too
+        let storage = self.storage.read().unwrap();
+        let MvccState {
+            indexes, versions, ..
+        } = &mut *state;
+        for (name, indexes) in indexes.iter_mut() {
+            if collection.is_some_and(|c| c != name) {
+                continue;
+            }
+            for index in indexes {
+                for id in index.stale() {
+                    if !versions.is_tracked(name, id) {
+                        index.prune(id, storage.get(name, id)?.as_ref());
+                    }
+                }
+            }
+        }
+
+        if let Some(name) = collection {
+            reclaimed.entry(name.to_string()).or_insert(0);
+        }
+        Ok(reclaimed)
+    }
+
+    pub fn version_count(&self) -> usize {
+        self.state.lock().unwrap().versions.version_count()
+    }
+
+    fn finish(&self, id: TransactionId) {
+        self.state.lock().unwrap().active.remove(&id);
+    }
+}
+
+fn build_index(
+    versions: &VersionStore,
+    storage: &dyn StorageEngine,
+    collection: &str,
+    definition: IndexDefinition,
+) -> Result<Index, StorageError> {
+    let mut index = Index::new(definition);
+    for (id, document) in storage.range(collection, (Bound::Unbounded, Bound::Unbounded))? {
+        index.add(id, Some(&document));
+    }
+    for (id, document) in versions.documents(collection) {
+        index.add(id, Some(document));
+    }
+    Ok(index)
+}
+
+fn register_index(state: &mut MvccState, collection: &str, index: Index) {
+    let indexes = state.indexes.entry(collection.to_string()).or_default();
+    indexes.retain(|existing| existing.definition().name != index.definition().name);
+    indexes.push(index);
+}
+
+/// Fails if committing the writes would leave two documents with the same key
+/// in a unique index, including the ones the transaction creates.
+fn check_unique(
+    state: &MvccState,
+    storage: &dyn StorageEngine,
+    writes: &Writes,
+    schema_changes: &[SchemaChange],
+) -> Result<(), TransactionError> {
+    let violation = |collection: &str, definition: &IndexDefinition| {
+        Err(TransactionError::UniqueViolation {
+            collection: collection.to_string(),
+            index: definition.name.clone(),
+            span: Span::default(),
+        })
+    };
+
+    for (collection, docs) in writes {
+        let Some(indexes) = state.indexes.get(collection) else {
+            continue;
+        };
+        for index in indexes.iter().filter(|index| index.definition().unique) {
+            let definition = index.definition();
+            let mut claimed: BTreeMap<IndexKey, DocumentId> = BTreeMap::new();
+
+            for (id, doc) in docs {
+                let Some(doc) = doc else {
+                    continue;
+                };
+                let key = definition.key_of(doc);
+                if !is_unique_key(&key) {
+                    continue;
+                }
+                if claimed.insert(key.clone(), *id).is_some() {
+                    return violation(collection, definition);
+                }
+
+                let range = (Bound::Included(key.clone()), Bound::Included(key.clone()));
+                for candidate in index.candidates(&range) {
+                    if candidate == *id {
+                        continue;
+                    }
+                    let latest = match docs.get(&candidate) {
+                        Some(written) => written.clone(),
+                        None => storage.get(collection, candidate)?,
+                    };
+                    if latest.is_some_and(|latest| definition.key_of(&latest) == key) {
+                        return violation(collection, definition);
+                    }
+                }
+            }
+        }
+    }
+
+    // Indexes created by the transaction are checked against the whole
+    // collection, as it will be once the writes are applied
+    for change in schema_changes {
+        let SchemaChange::CreateIndex {
+            collection,
+            definition,
+        } = change
+        else {
+            continue;
+        };
+        if !definition.unique {
+            continue;
+        }
+
+        let mut documents: BTreeMap<DocumentId, RV> = storage
+            .range(collection, (Bound::Unbounded, Bound::Unbounded))?
+            .into_iter()
+            .collect();
+        for (id, doc) in writes.get(collection).into_iter().flatten() {
+            match doc {
+                Some(doc) => documents.insert(*id, doc.clone()),
+                None => documents.remove(id),
+            };
+        }
+
+        let mut keys: BTreeSet<IndexKey> = BTreeSet::new();
+        for document in documents.values() {
+            let key = definition.key_of(document);
+            if is_unique_key(&key) && !keys.insert(key) {
+                return violation(collection, definition);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Buffers the writes of a transaction until it commits. Reads see the
+/// snapshot the transaction started with, plus its own buffered writes.
+/// Dropping a transaction without committing it rolls it back.
+pub struct Transaction {
+    id: TransactionId,
+    mode: SqlTransactionMode,
+    snapshot: Timestamp,
+    manager: Arc<TransactionManager>,
+    // None marks a deleted document
+    writes: Writes,
+    // Documents the writes rely on staying as they are
+    dependencies: Dependencies,
+    schema_changes: Vec<SchemaChange>,
+}
+
+impl Transaction {
+    pub fn id(&self) -> TransactionId {
+        self.id
+    }
+
+    pub fn mode(&self) -> SqlTransactionMode {
+        self.mode
+    }
+
+    pub fn snapshot(&self) -> Timestamp {
+        self.snapshot
+    }
+
+    pub fn get(&self, collection: &str, id: DocumentId) -> Result<Option<RV>, TransactionError> {
+        if let Some(written) = self.writes.get(collection).and_then(|docs| docs.get(&id)) {
+            return Ok(written.as_ref().map(|doc| doc.deep_clone()));
+        }
+        Ok(self.manager.get(collection, id, self.snapshot)?)
+    }
+
+    pub fn range(
+        &self,
+        collection: &str,
+        range: DocumentRange,
+    ) -> Result<Vec<(DocumentId, RV)>, TransactionError> {
+        let mut documents = self.manager.range(collection, range, self.snapshot)?;
+
+        if let Some(written) = self.writes.get(collection) {
+            for (id, doc) in written.range(range) {
+                match doc {
+                    Some(doc) => documents.insert(*id, doc.deep_clone()),
+                    None => documents.remove(id),
+                };
+            }
+        }
+
+        Ok(documents.into_iter().collect())
+    }
+
+    /// The documents of the first page of the range, along with the rest of
+    /// the range, if any, that the next page is read from.
+    fn page(
+        &self,
+        collection: &str,
+        range: DocumentRange,
+        limit: usize,
+    ) -> Result<Page, TransactionError> {
+        let (mut documents, covered) =
+            self.manager.page(collection, range, limit, self.snapshot)?;
+
+        if let Some(written) = self.writes.get(collection) {
+            for (id, doc) in written.range(covered) {
+                match doc {
+                    Some(doc) => documents.insert(*id, doc.deep_clone()),
+                    None => documents.remove(id),
+                };
+            }
+        }
+
+        let rest = match covered.1 {
+            Bound::Included(last) if covered.1 != range.1 => Some((Bound::Excluded(last), range.1)),
+            _ => None,
+        };
+        Ok((documents.into_iter().collect(), rest))
+    }
+
+    /// Opens a cursor over the documents whose ids fall in the range.
+    pub fn cursor(&self, collection: &str, range: DocumentRange) -> DocumentCursor {
+        DocumentCursor {
+            collection: collection.to_string(),
+            ids: None,
+            rest: Some(range),
+            page: vec![].into_iter(),
+            key: None,
+        }
+    }
+
+    /// Opens a cursor over the documents whose keys in the index fall in the
+    /// range. The ids are looked up in the index right away, while the
+    /// documents are read as the cursor is advanced.
+    pub fn index_cursor(
+        &self,
+        collection: &str,
+        definition: &IndexDefinition,
+        range: &KeyRange,
+    ) -> DocumentCursor {
+        let mut cursor = self.cursor(collection, (Bound::Unbounded, Bound::Unbounded));
+        cursor.key = Some((definition.clone(), range.clone()));
+        if let Some(mut ids) = self.manager.index_candidates(collection, definition, range) {
+            if let Some(written) = self.writes.get(collection) {
+                ids.extend(written.keys());
+                ids.sort_unstable();
+                ids.dedup();
+            }
+            cursor.ids = Some(ids.into_iter());
+        }
+        cursor
+    }
+
+    /// Largest id of the documents in the collection, without reading them
+    /// all.
+    pub fn last_id(&self, collection: &str) -> Result<Option<DocumentId>, TransactionError> {
+        let written = self.writes.get(collection);
+        let mut bound = Bound::Unbounded;
+        let stored = loop {
+            let Some(id) = self.manager.last_id(collection, bound, self.snapshot)? else {
+                break None;
+            };
+            if written.is_some_and(|written| matches!(written.get(&id), Some(None))) {
+                bound = Bound::Excluded(id);
+                continue;
+            }
+            break Some(id);
+        };
+        let inserted = written.and_then(|written| {
+            written
+                .iter()
+                .rev()
+                .find(|(_, document)| document.is_some())
+                .map(|(id, _)| *id)
+        });
+        Ok(stored.max(inserted))
+    }
+
+    /// An id for a new document of the collection, larger than those of the
+    /// documents it holds and of the ones other transactions were given.
+    pub fn next_id(&self, collection: &str) -> Result<DocumentId, TransactionError> {
+        let floor = self.last_id(collection)?;
+        Ok(self.manager.allocate_id(collection, floor)?)
+    }
+
+    /// Records that the writes of the transaction rely on the document as it
+    /// is in the snapshot, like a child on the document it references. The
+    /// commit then fails if another transaction changed the document since,
+    /// and transactions changing it fail to commit after this one does.
+    pub fn depend_on(&mut self, collection: &str, id: DocumentId) {
+        self.dependencies
+            .entry(collection.to_string())
+            .or_default()
+            .insert(id);
+    }
+
+    /// Stores the document under the given id and returns the one it replaced.
+    pub fn insert(
+        &mut self,
+        collection: &str,
+        id: DocumentId,
+        document: RV,
+    ) -> Result<Option<RV>, TransactionError> {
+        self.ensure_writable(collection)?;
+        validate_document(&document)?;
+        let previous = self.get(collection, id)?;
+        self.writes
+            .entry(collection.to_string())
+            .or_default()
+            .insert(id, Some(document.deep_clone()));
+        Ok(previous)
+    }
+
+    pub fn delete(
+        &mut self,
+        collection: &str,
+        id: DocumentId,
+    ) -> Result<Option<RV>, TransactionError> {
+        self.ensure_writable(collection)?;
+        let previous = self.get(collection, id)?;
+        if previous.is_some() {
+            self.writes
+                .entry(collection.to_string())
+                .or_default()
+                .insert(id, None);
+        }
+        Ok(previous)
+    }
+
+    /// Returns the documents whose key in the index falls in the range. The
+    /// index is only consulted once built, until then the whole collection
+    /// is scanned for the keys.
+    pub fn index_scan(
+        &self,
+        collection: &str,
+        definition: &IndexDefinition,
+        range: &KeyRange,
+    ) -> Result<Vec<(DocumentId, RV)>, TransactionError> {
+        let mut cursor = self.index_cursor(collection, definition, range);
+        let mut documents = vec![];
+        while let Some(document) = cursor.next(self)? {
+            documents.push(document);
+        }
+        Ok(documents)
+    }
+
+    /// Whether [`Transaction::index_scan`] returns every document the
+    /// interpreter would find in the range. Index keys are compared by type,
+    /// while the interpreter converts between booleans, numbers and strings,
+    /// so this is not the case once the collection holds values of a type
+    /// other than that of the bounds.
+    pub fn index_compares_strictly(
+        &self,
+        collection: &str,
+        definition: &IndexDefinition,
+        range: &KeyRange,
+    ) -> bool {
+        self.manager
+            .index_compares_strictly(collection, definition, range)
+            && self.writes.get(collection).is_none_or(|written| {
+                written
+                    .values()
+                    .flatten()
+                    .all(|document| compares_strictly(&definition.key_of(document), range))
+            })
+    }
+
+    /// Creates the index once the transaction commits.
+    pub fn create_index(
+        &mut self,
+        collection: &str,
+        definition: IndexDefinition,
+    ) -> Result<(), TransactionError> {
+        self.ensure_writable(collection)?;
+        self.schema_changes.push(SchemaChange::CreateIndex {
+            collection: collection.to_string(),
+            definition,
+        });
+        Ok(())
+    }
+
+    /// Drops every index of the collection once the transaction commits.
+    pub fn drop_indexes(&mut self, collection: &str) -> Result<(), TransactionError> {
+        self.ensure_writable(collection)?;
+        self.schema_changes.push(SchemaChange::DropIndexes {
+            collection: collection.to_string(),
+        });
+        Ok(())
+    }
+
+    /// Makes sure the storage has the collection open. This is not undone on
+    /// rollback, which leaves behind an empty collection at worst.
+    pub fn open_collection(&self, collection: &str) -> Result<(), TransactionError> {
+        self.ensure_writable(collection)?;
+        self.manager
+            .storage
+            .write()
+            .unwrap()
+            .open_collection(collection)?;
+        Ok(())
+    }
+
+    /// Applies every buffered write to the storage as a single batch, unless
+    /// another transaction committed a write to the same documents since this
+    /// one started.
+    pub fn commit(mut self) -> Result<(), TransactionError> {
+        let manager = self.manager.clone();
+        manager.commit(&mut self)
+    }
+
+    pub fn rollback(self) {}
+
+    pub fn savepoint(&self) -> Savepoint {
+        Savepoint {
+            transaction: self.id,
+            writes: self.writes.clone(),
+            dependencies: self.dependencies.clone(),
+            schema_changes: self.schema_changes.len(),
+        }
+    }
+
+    /// Discards the writes made since the savepoint was taken.
+    pub fn rollback_to(&mut self, savepoint: Savepoint) {
+        if savepoint.transaction != self.id {
+            return;
+        }
+        self.writes = savepoint.writes;
+        self.dependencies = savepoint.dependencies;
+        self.schema_changes.truncate(savepoint.schema_changes);
+    }
+
+    fn ensure_writable(&self, collection: &str) -> Result<(), TransactionError> {
+        if self.mode == SqlTransactionMode::ReadOnly {
+            return Err(TransactionError::ReadOnlyTransaction {
+                collection: collection.to_string(),
+                span: Span::default(),
+            });
+        }
+        Ok(())
+    }
+}
+
+impl Drop for Transaction {
+    fn drop(&mut self) {
+        self.manager.finish(self.id);
+    }
+}
+
+/// Periodically reclaims obsolete versions of every collection. `every` has
+/// to be non-zero.
+pub fn spawn_vacuum(manager: SharedTransactionManager, every: Duration) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(every);
+        loop {
+            interval.tick().await;
+            match manager.vacuum(None) {
+                Ok(reclaimed) => {
+                    let total: usize = reclaimed.values().sum();
+                    if total > 0 {
+                        info!("Vacuum reclaimed {} versions", total);
+                    }
+                }
+                Err(err) => error!("Vacuum failed: {:?}", err),
+            }
+        }
+    })
+}
+
+/// Documents read by a cursor at a time. Readers that stop early leave the
+/// pages after the one they stopped in unread.
+const PAGE_SIZE: usize = 64;
+
+/// Reads the documents of a collection in id order a page at a time. The
+/// cursor keeps no locks between calls and is advanced through the
+/// transaction that opened it, whose writes it sees as they are when a page
+/// is read.
+pub struct DocumentCursor {
+    collection: String,
+    /// Ids to read one by one, when an index narrowed them down
+    ids: Option<std::vec::IntoIter<DocumentId>>,
+    rest: Option<DocumentRange>,
+    page: std::vec::IntoIter<(DocumentId, RV)>,
+    /// Index and range the keys of the documents must fall in
+    key: Option<(IndexDefinition, KeyRange)>,
+}
+
+impl DocumentCursor {
+    pub fn next(
+        &mut self,
+        transaction: &Transaction,
+    ) -> Result<Option<(DocumentId, RV)>, TransactionError> {
+        while let Some((id, document)) = self.fetch(transaction)? {
+            match &self.key {
+                Some((definition, range)) if !in_range(&definition.key_of(&document), range) => {}
+                _ => return Ok(Some((id, document))),
+            }
+        }
+        Ok(None)
+    }
+
+    fn fetch(
+        &mut self,
+        transaction: &Transaction,
+    ) -> Result<Option<(DocumentId, RV)>, TransactionError> {
+        if let Some(ids) = &mut self.ids {
+            for id in ids.by_ref() {
+                if let Some(document) = transaction.get(&self.collection, id)? {
+                    return Ok(Some((id, document)));
+                }
+            }
+            return Ok(None);
+        }
+        loop {
+            if let Some(document) = self.page.next() {
+                return Ok(Some(document));
+            }
+            let Some(range) = self.rest.take() else {
+                return Ok(None);
+            };
+            let (documents, rest) = transaction.page(&self.collection, range, PAGE_SIZE)?;
+            self.page = documents.into_iter();
+            self.rest = rest;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ops::Bound;
+
+    use rustc_hash::FxHashMap;
+
+    use super::*;
+    use crate::catalog::{self, CatalogError};
+    use crate::engine::interpreter::{HaltReason, Interpreter};
+    use crate::store::index::IndexValue;
+    use crate::store::memory::MemoryEngine;
+    use crate::store::StorageEngine;
+    use crate::util::alloc_shared;
+
+    fn manager() -> SharedTransactionManager {
+        let mut engine = MemoryEngine::new();
+        engine.open_collection("books").unwrap();
+        Arc::new(TransactionManager::new(alloc_shared(engine)))
+    }
+
+    fn doc(n: usize) -> RV {
+        let mut map = FxHashMap::default();
+        map.insert("n".to_string(), RV::Num(n as f64));
+        RV::Object(alloc_shared(map))
+    }
+
+    fn stored_ids(manager: &SharedTransactionManager) -> Vec<DocumentId> {
+        manager
+            .storage()
+            .read()
+            .unwrap()
+            .range("books", (Bound::Unbounded, Bound::Unbounded))
+            .unwrap()
+            .into_iter()
+            .map(|(id, _)| id)
+            .collect()
+    }
+
+    #[test]
+    fn test_writes_are_visible_after_commit() {
+        let manager = manager();
+        manager
+            .storage()
+            .write()
+            .unwrap()
+            .insert("books", 1, doc(1))
+            .unwrap();
+
+        let mut txn = manager.begin(SqlTransactionMode::ReadWrite);
+        txn.insert("books", 2, doc(2)).unwrap();
+        txn.delete("books", 1).unwrap();
+
+        // Reads within the transaction see its own writes
+        assert!(txn.get("books", 1).unwrap().is_none());
+        let ids: Vec<DocumentId> = txn
+            .range("books", (Bound::Unbounded, Bound::Unbounded))
+            .unwrap()
+            .into_iter()
+            .map(|(id, _)| id)
+            .collect();
+        assert_eq!(ids, vec![2]);
+        assert_eq!(stored_ids(&manager), vec![1]);
+
+        txn.commit().unwrap();
+        assert_eq!(stored_ids(&manager), vec![2]);
+    }
+
+    #[test]
+    fn test_last_id_sees_snapshot() {
+        let manager = manager();
+        let mut setup = manager.begin(SqlTransactionMode::ReadWrite);
+        for id in 1..=3 {
+            setup.insert("books", id, doc(id as usize)).unwrap();
+        }
+        setup.commit().unwrap();
+
+        let mut reader = manager.begin(SqlTransactionMode::ReadWrite);
+        let mut writer = manager.begin(SqlTransactionMode::ReadWrite);
+        writer.insert("books", 4, doc(4)).unwrap();
+        writer.delete("books", 3).unwrap();
+        assert_eq!(writer.last_id("books").unwrap(), Some(4));
+        writer.commit().unwrap();
+
+        // Document 4 was stored after the snapshot, document 3 deleted since
+        assert_eq!(reader.last_id("books").unwrap(), Some(3));
+        reader.delete("books", 3).unwrap();
+        assert_eq!(reader.last_id("books").unwrap(), Some(2));
+
+        let fresh = manager.begin(SqlTransactionMode::ReadOnly);
+        assert_eq!(fresh.last_id("books").unwrap(), Some(4));
+    }
+
+    #[test]
+    fn test_cursor_reads_pages_of_the_snapshot() {
+        let manager = manager();
+        let count = PAGE_SIZE * 2 + 10;
+        let mut setup = manager.begin(SqlTransactionMode::ReadWrite);
+        for id in 1..=count as DocumentId {
+            setup.insert("books", id, doc(id as usize)).unwrap();
+        }
+        setup.commit().unwrap();
+
+        let mut reader = manager.begin(SqlTransactionMode::ReadWrite);
+        reader.delete("books", 2).unwrap();
+        reader.insert("books", 1000, doc(1000)).unwrap();
+        let mut cursor = reader.cursor("books", (Bound::Unbounded, Bound::Unbounded));
+        let first = cursor.next(&reader).unwrap().map(|(id, _)| id);
+        assert_eq!(first, Some(1));
+
+        // Commits made while the cursor is open are not seen by it
+        let mut writer = manager.begin(SqlTransactionMode::ReadWrite);
+        writer.delete("books", 100).unwrap();
+        writer.insert("books", 500, doc(500)).unwrap();
+        writer.commit().unwrap();
+
+        let mut ids = vec![];
+        while let Some((id, _)) = cursor.next(&reader).unwrap() {
+            ids.push(id);
+        }
+        let mut expected: Vec<DocumentId> = (3..=count as DocumentId).collect();
+        expected.push(1000);
+        assert_eq!(ids, expected);
+    }
+
+    #[test]
+    fn test_concurrent_inserts_get_different_ids() {
+        let manager = manager();
+        let mut setup = manager.begin(SqlTransactionMode::ReadWrite);
+        setup.insert("books", 1, doc(1)).unwrap();
+        setup.commit().unwrap();
+
+        let mut first = manager.begin(SqlTransactionMode::ReadWrite);
+        let mut second = manager.begin(SqlTransactionMode::ReadWrite);
+        let first_id = first.next_id("books").unwrap();
+        let second_id = second.next_id("books").unwrap();
+        assert_eq!((first_id, second_id), (2, 3));
+        first.insert("books", first_id, doc(2)).unwrap();
+        second.insert("books", second_id, doc(3)).unwrap();
+        first.commit().unwrap();
+        second.commit().unwrap();
+
+        // Ids written by the transaction itself are skipped, and those of a
+        // rolled back one are not handed out again
+        let mut txn = manager.begin(SqlTransactionMode::ReadWrite);
+        txn.insert("books", 10, doc(10)).unwrap();
+        assert_eq!(txn.next_id("books").unwrap(), 11);
+        txn.rollback();
+        let txn = manager.begin(SqlTransactionMode::ReadWrite);
+        assert_eq!(txn.next_id("books").unwrap(), 12);
+    }
+
+    #[test]
+    fn test_rollback_discards_writes() {
+        let manager = manager();
+
+        let mut txn = manager.begin(SqlTransactionMode::ReadWrite);
+        txn.insert("books", 1, doc(1)).unwrap();
+        txn.rollback();
+
+        assert_eq!(stored_ids(&manager), Vec::<DocumentId>::new());
+    }
+
+    #[test]
+    fn test_read_only_transaction_rejects_writes() {
+        let manager = manager();
+
+        let mut txn = manager.begin(SqlTransactionMode::ReadOnly);
+        assert_eq!(
+            txn.insert("books", 1, doc(1)),
+            Err(TransactionError::ReadOnlyTransaction {
+                collection: "books".to_string(),
+                span: Span::default(),
+            })
+        );
+        assert!(txn.get("books", 1).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_statements_outside_a_block_commit_on_their_own() {
+        let manager = manager();
+        let mut interpreter = Interpreter::with_transactions(None, false, manager.clone());
+
+        interpreter
+            .run_in_transaction(Span::default(), |interpreter| {
+                interpreter
+                    .transaction()
+                    .unwrap()
+                    .insert("books", 1, doc(1))
+                    .map_err(|err| HaltReason::Error(err.into()))
+            })
+            .unwrap();
+
+        assert!(!interpreter.in_transaction());
+        assert_eq!(stored_ids(&manager), vec![1]);
+    }
+
+    #[test]
+    fn test_explicit_transaction_spans_statements() {
+        let manager = manager();
+        let mut interpreter = Interpreter::with_transactions(None, false, manager.clone());
+
+        interpreter.interpret("BEGIN;").unwrap();
+        for id in [1, 2] {
+            interpreter
+                .run_in_transaction(Span::default(), |interpreter| {
+                    interpreter
+                        .transaction()
+                        .unwrap()
+                        .insert("books", id, doc(id as usize))
+                        .map_err(|err| HaltReason::Error(err.into()))
+                })
+                .unwrap();
+        }
+        assert_eq!(stored_ids(&manager), Vec::<DocumentId>::new());
+
+        interpreter.interpret("COMMIT;").unwrap();
+        assert_eq!(stored_ids(&manager), vec![1, 2]);
+    }
+
+    #[test]
+    fn test_disconnect_rolls_back() {
+        let manager = manager();
+
+        {
+            let mut interpreter = Interpreter::with_transactions(None, false, manager.clone());
+            interpreter.interpret("BEGIN;").unwrap();
+            interpreter
+                .transaction()
+                .unwrap()
+                .insert("books", 1, doc(1))
+                .unwrap();
+            // The session goes away without committing
+        }
+
+        assert_eq!(stored_ids(&manager), Vec::<DocumentId>::new());
+    }
+
+    fn n_of(doc: Option<RV>) -> usize {
+        match doc {
+            Some(RV::Object(map)) => match map.read().unwrap().get("n") {
+                Some(RV::Num(n)) => *n as usize,
+                _ => panic!("Expected a number"),
+            },
+            other => panic!("Expected an object, found {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_first_committer_wins() {
+        let manager = manager();
+
+        let mut first = manager.begin(SqlTransactionMode::ReadWrite);
+        let mut second = manager.begin(SqlTransactionMode::ReadWrite);
+        first.insert("books", 1, doc(1)).unwrap();
+        second.insert("books", 1, doc(2)).unwrap();
+
+        first.commit().unwrap();
+        assert_eq!(
+            second.commit(),
+            Err(TransactionError::SerializationFailure {
+                collection: "books".to_string(),
+                id: 1,
+                span: Span::default(),
+            })
+        );
+
+        let reader = manager.begin(SqlTransactionMode::ReadOnly);
+        assert_eq!(n_of(reader.get("books", 1).unwrap()), 1);
+    }
+
+    #[test]
+    fn test_disjoint_writes_both_commit() {
+        let manager = manager();
+
+        let mut first = manager.begin(SqlTransactionMode::ReadWrite);
+        let mut second = manager.begin(SqlTransactionMode::ReadWrite);
+        first.insert("books", 1, doc(1)).unwrap();
+        second.insert("books", 2, doc(2)).unwrap();
+
+        first.commit().unwrap();
+        second.commit().unwrap();
+        assert_eq!(stored_ids(&manager), vec![1, 2]);
+    }
+
+    #[test]
+    fn test_interleaved_sessions_see_their_own_snapshots() {
+        let manager = manager();
+        let mut alice = Interpreter::with_transactions(None, false, manager.clone());
+        let mut bob = Interpreter::with_transactions(None, false, manager.clone());
+
+        let mut setup = manager.begin(SqlTransactionMode::ReadWrite);
+        setup.insert("books", 1, doc(1)).unwrap();
+        setup.insert("books", 2, doc(2)).unwrap();
+        setup.commit().unwrap();
+
+        alice.interpret("BEGIN;").unwrap();
+        bob.interpret("BEGIN READ WRITE;").unwrap();
+
+        let alice_txn = alice.transaction().unwrap();
+        alice_txn.insert("books", 1, doc(10)).unwrap();
+        alice_txn.delete("books", 2).unwrap();
+        alice_txn.insert("books", 3, doc(3)).unwrap();
+
+        // Alice's writes are invisible to Bob both before and after she commits
+        let bob_txn = bob.transaction().unwrap();
+        assert_eq!(n_of(bob_txn.get("books", 1).unwrap()), 1);
+        alice.interpret("COMMIT;").unwrap();
+
+        let bob_txn = bob.transaction().unwrap();
+        assert_eq!(n_of(bob_txn.get("books", 1).unwrap()), 1);
+        assert_eq!(n_of(bob_txn.get("books", 2).unwrap()), 2);
+        assert!(bob_txn.get("books", 3).unwrap().is_none());
+        let ids: Vec<DocumentId> = bob_txn
+            .range("books", (Bound::Unbounded, Bound::Unbounded))
+            .unwrap()
+            .into_iter()
+            .map(|(id, _)| id)
+            .collect();
+        assert_eq!(ids, vec![1, 2]);
+
+        // Alice now sees her committed writes in a fresh snapshot
+        alice.interpret("BEGIN READ ONLY;").unwrap();
+        let alice_txn = alice.transaction().unwrap();
+        assert_eq!(n_of(alice_txn.get("books", 1).unwrap()), 10);
+        assert!(alice_txn.get("books", 2).unwrap().is_none());
+        alice.interpret("ROLLBACK;").unwrap();
+
+        // Bob updates a document Alice changed after his snapshot was taken
+        bob.transaction()
+            .unwrap()
+            .insert("books", 2, doc(20))
+            .unwrap();
+        assert_eq!(
+            bob.interpret("COMMIT;"),
+            Err(ExecutionError::Transaction(
+                TransactionError::SerializationFailure {
+                    collection: "books".to_string(),
+                    id: 2,
+                    span: Span {
+                        start: 0,
+                        end: 6,
+                        line: 0,
+                        line_end: 0,
+                    },
+                }
+            ))
+        );
+        assert!(!bob.in_transaction());
+        assert!(manager
+            .storage()
+            .read()
+            .unwrap()
+            .get("books", 2)
+            .unwrap()
+            .is_none());
+    }
+
+    fn reclaimed(result: RV, collection: &str) -> usize {
+        match result {
+            RV::Object(map) => match map.read().unwrap().get(collection) {
+                Some(RV::Num(n)) => *n as usize,
+                other => panic!("Expected a count, found {:?}", other),
+            },
+            other => panic!("Expected an object, found {:?}", other),
+        }
+    }
+
+    fn catalog_manager() -> SharedTransactionManager {
+        let manager = Arc::new(TransactionManager::new(alloc_shared(MemoryEngine::new())));
+        catalog::bootstrap(&manager).unwrap();
+        manager
+    }
+
+    #[test]
+    fn test_vacuum_keeps_versions_of_active_snapshots() {
+        let manager = catalog_manager();
+        let mut interpreter = Interpreter::with_transactions(None, false, manager.clone());
+        interpreter.interpret("CREATE COLLECTION books;").unwrap();
+        manager.vacuum(None).unwrap();
+
+        for n in 1..=3 {
+            let mut txn = manager.begin(SqlTransactionMode::ReadWrite);
+            txn.insert("public.books", 1, doc(n)).unwrap();
+            txn.commit().unwrap();
+        }
+
+        let reader = manager.begin(SqlTransactionMode::ReadOnly);
+
+        let mut txn = manager.begin(SqlTransactionMode::ReadWrite);
+        txn.insert("public.books", 1, doc(4)).unwrap();
+        txn.commit().unwrap();
+        assert_eq!(manager.version_count(), 5);
+
+        // The reader still needs the third version
+        let result = interpreter.interpret("VACUUM books;").unwrap();
+        assert_eq!(reclaimed(result, "public.books"), 3);
+        assert_eq!(n_of(reader.get("public.books", 1).unwrap()), 3);
+
+        drop(reader);
+        let result = interpreter.interpret("VACUUM;").unwrap();
+        assert_eq!(reclaimed(result, "public.books"), 2);
+        assert_eq!(manager.version_count(), 0);
+
+        let reader = manager.begin(SqlTransactionMode::ReadOnly);
+        assert_eq!(n_of(reader.get("public.books", 1).unwrap()), 4);
+    }
+
+    #[test]
+    fn test_vacuum_unknown_collection() {
+        let mut interpreter = Interpreter::with_transactions(None, false, catalog_manager());
+
+        assert!(matches!(
+            interpreter.interpret("VACUUM authors;"),
+            Err(ExecutionError::Catalog(CatalogError::CollectionNotFound { name, .. }))
+                if name == "authors"
+        ));
+        assert_eq!(
+            manager().vacuum(Some("authors")),
+            Err(StorageError::CollectionNotFound {
+                name: "authors".to_string()
+            })
+        );
+    }
+
+    fn by_n(unique: bool) -> IndexDefinition {
+        IndexDefinition {
+            name: "by_n".to_string(),
+            fields: vec!["n".to_string()],
+            unique,
+        }
+    }
+
+    fn key(n: usize) -> KeyRange {
+        let key = vec![IndexValue::Num(n as f64)];
+        (Bound::Included(key.clone()), Bound::Included(key))
+    }
+
+    fn scanned_ids(txn: &Transaction, range: &KeyRange) -> Vec<DocumentId> {
+        txn.index_scan("public.books", &by_n(false), range)
+            .unwrap()
+            .into_iter()
+            .map(|(id, _)| id)
+            .collect()
+    }
+
+    #[test]
+    fn test_index_scan_sees_snapshot() {
+        let manager = catalog_manager();
+        let mut interpreter = Interpreter::with_transactions(None, false, manager.clone());
+        interpreter
+            .interpret("CREATE COLLECTION books; CREATE INDEX by_n ON books (n);")
+            .unwrap();
+
+        let mut txn = manager.begin(SqlTransactionMode::ReadWrite);
+        for n in 1..=3 {
+            txn.insert("public.books", n as DocumentId, doc(n)).unwrap();
+        }
+        txn.commit().unwrap();
+
+        let reader = manager.begin(SqlTransactionMode::ReadOnly);
+        let mut txn = manager.begin(SqlTransactionMode::ReadWrite);
+        txn.insert("public.books", 1, doc(5)).unwrap();
+        // Uncommitted writes are seen by their own transaction only
+        assert_eq!(scanned_ids(&txn, &key(5)), vec![1]);
+        assert!(scanned_ids(&txn, &key(1)).is_empty());
+        txn.commit().unwrap();
+
+        assert_eq!(scanned_ids(&reader, &key(1)), vec![1]);
+        assert!(scanned_ids(&reader, &key(5)).is_empty());
+
+        let range = (
+            Bound::Excluded(vec![IndexValue::Num(1.0)]),
+            Bound::Unbounded,
+        );
+        let txn = manager.begin(SqlTransactionMode::ReadOnly);
+        assert_eq!(scanned_ids(&txn, &range), vec![1, 2, 3]);
+        assert!(scanned_ids(&txn, &key(1)).is_empty());
+
+        // Old keys are pruned once no snapshot needs them
+        drop(reader);
+        manager.vacuum(None).unwrap();
+        let candidates = manager.index_candidates("public.books", &by_n(false), &key(1));
+        assert_eq!(candidates, Some(vec![]));
+    }
+
+    #[test]
+    fn test_unique_index_rejects_duplicates() {
+        let manager = catalog_manager();
+        let mut interpreter = Interpreter::with_transactions(None, false, manager.clone());
+        interpreter
+            .interpret("CREATE COLLECTION books; CREATE UNIQUE INDEX by_n ON books (n);")
+            .unwrap();
+
+        let violation = Err(TransactionError::UniqueViolation {
+            collection: "public.books".to_string(),
+            index: "by_n".to_string(),
+            span: Span::default(),
+        });
+
+        let mut txn = manager.begin(SqlTransactionMode::ReadWrite);
+        txn.insert("public.books", 1, doc(1)).unwrap();
+        txn.insert("public.books", 2, doc(1)).unwrap();
+        assert_eq!(txn.commit(), violation);
+
+        let mut txn = manager.begin(SqlTransactionMode::ReadWrite);
+        txn.insert("public.books", 1, doc(1)).unwrap();
+        txn.commit().unwrap();
+
+        let mut txn = manager.begin(SqlTransactionMode::ReadWrite);
+        txn.insert("public.books", 2, doc(1)).unwrap();
+        assert_eq!(txn.commit(), violation);
+
+        // The key is free once its holder moves on in the same commit
+        let mut txn = manager.begin(SqlTransactionMode::ReadWrite);
+        txn.insert("public.books", 1, doc(2)).unwrap();
+        txn.insert("public.books", 2, doc(1)).unwrap();
+        txn.commit().unwrap();
+    }
+
+    #[test]
+    fn test_create_unique_index_on_duplicates() {
+        let manager = catalog_manager();
+        let mut interpreter = Interpreter::with_transactions(None, false, manager.clone());
+        interpreter.interpret("CREATE COLLECTION books;").unwrap();
+
+        let mut txn = manager.begin(SqlTransactionMode::ReadWrite);
+        txn.insert("public.books", 1, doc(1)).unwrap();
+        txn.insert("public.books", 2, doc(1)).unwrap();
+        txn.commit().unwrap();
+
+        assert!(matches!(
+            interpreter.interpret("CREATE UNIQUE INDEX by_n ON books (n);"),
+            Err(ExecutionError::Transaction(TransactionError::UniqueViolation { index, .. }))
+                if index == "by_n"
+        ));
+        assert!(matches!(
+            interpreter.interpret("CREATE INDEX by_n ON books (n); CREATE INDEX by_n ON books (n);"),
+            Err(ExecutionError::Catalog(CatalogError::IndexAlreadyExists { name, .. }))
+                if name == "by_n"
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_background_vacuum() {
+        let manager = manager();
+
+        for n in 1..=3 {
+            let mut txn = manager.begin(SqlTransactionMode::ReadWrite);
+            txn.insert("books", n as DocumentId, doc(n)).unwrap();
+            txn.commit().unwrap();
+        }
+        assert_eq!(manager.version_count(), 6);
+
+        let task = spawn_vacuum(manager.clone(), Duration::from_millis(10));
+        for _ in 0..100 {
+            if manager.version_count() == 0 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        task.abort();
+
+        assert_eq!(manager.version_count(), 0);
+        assert_eq!(stored_ids(&manager), vec![1, 2, 3]);
+    }
+}
@@ -0,0 +1,566 @@
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::ops::Bound;
+use std::path::{Path, PathBuf};
+
+use rustc_hash::{FxHashMap, FxHashSet};
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use crate::value::RV;
+
+use super::{validate_document, DocumentId, DocumentRange, Mutation, StorageEngine, StorageError};
+
+pub type TxId = u64;
+
+// length (4) + checksum (4)
+const FRAME_HEADER: usize = 8;
+
+#[derive(Debug, Clone)]
+pub struct WalConfig {
+    pub path: PathBuf,
+    /// The log is checkpointed once it grows beyond this many bytes
+    pub checkpoint_size: u64,
+}
+
+impl Default for WalConfig {
+    fn default() -> Self {
+        WalConfig {
+            path: PathBuf::from("data/wal.log"),
+            checkpoint_size: 4 * 1024 * 1024,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum LogRecord {
+    Begin { txid: TxId },
+    Write { txid: TxId, mutation: Mutation },
+    Commit { txid: TxId },
+}
+
+struct LogFile {
+    file: File,
+    size: u64,
+}
+
+impl LogFile {
+    /// Opens the log and returns the records it holds. A torn or corrupted
+    /// tail, left behind by a crash in the middle of a write, is cut off.
+    fn open(path: &Path) -> Result<(LogFile, Vec<LogRecord>), StorageError> {
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)?;
+
+        let mut bytes = vec![];
+        file.read_to_end(&mut bytes)?;
+
+        let mut records = vec![];
+        let mut offset = 0;
+
+        while offset + FRAME_HEADER <= bytes.len() {
+            let len = u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap()) as usize;
+            let sum = u32::from_le_bytes(bytes[offset + 4..offset + 8].try_into().unwrap());
+            let start = offset + FRAME_HEADER;
+
+            if start + len > bytes.len() || checksum(&bytes[start..start + len]) != sum {
+                break;
+            }
+
+            match bson::from_slice(&bytes[start..start + len]) {
+                Ok(record) => records.push(record),
+                Err(_) => break,
+            }
+
+            offset = start + len;
+        }
+
+        if offset < bytes.len() {
+            warn!(
+                "Discarding {} bytes of incomplete log records from {}",
+                bytes.len() - offset,
+                path.display()
+            );
+            file.set_len(offset as u64)?;
+            file.sync_all()?;
+        }
+
+        file.seek(SeekFrom::Start(offset as u64))?;
+
+        Ok((
+            LogFile {
+                file,
+                size: offset as u64,
+            },
+            records,
+        ))
+    }
+
+    /// Appends the records and waits until they reach the disk.
+    fn append(&mut self, records: &[LogRecord]) -> Result<(), StorageError> {
+        let mut buf = vec![];
+        for record in records {
+            let payload = bson::to_vec(record).map_err(|err| StorageError::InvalidDocument {
+                message: err.to_string(),
+            })?;
+            buf.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+            buf.extend_from_slice(&checksum(&payload).to_le_bytes());
+            buf.extend_from_slice(&payload);
+        }
+
+        self.file.write_all(&buf)?;
+        self.file.sync_data()?;
+        self.size += buf.len() as u64;
+        Ok(())
+    }
+
+    fn truncate(&mut self) -> Result<(), StorageError> {
+        self.file.set_len(0)?;
+        self.file.seek(SeekFrom::Start(0))?;
+        self.file.sync_all()?;
+        self.size = 0;
+        Ok(())
+    }
+}
+
+/// Wraps a storage engine with a write-ahead log. Every batch is appended
+/// to the log and synced before it reaches the wrapped engine, so after a
+/// crash the committed batches since the last checkpoint can be replayed.
+///
+/// Replaying is safe to repeat: inserts replace documents and deletes of
+/// missing documents do nothing. The wrapped engine may write batches to
+/// its files before the next checkpoint, but only ones already in the log,
+/// so replaying the records over them ends with the same documents.
+pub struct WalEngine {
+    inner: Box<dyn StorageEngine>,
+    log: LogFile,
+    checkpoint_size: u64,
+    next_txid: TxId,
+}
+
+impl WalEngine {
+    pub fn open(
+        inner: Box<dyn StorageEngine>,
+        config: WalConfig,
+    ) -> Result<WalEngine, StorageError> {
+        let (log, records) = LogFile::open(&config.path)?;
+
+        let mut engine = WalEngine {
+            inner,
+            log,
+            checkpoint_size: config.checkpoint_size,
+            next_txid: 1,
+        };
+
+        engine.recover(records)?;
+        Ok(engine)
+    }
+
+    /// Flushes the wrapped engine, after which the log is no longer needed.
+    pub fn checkpoint(&mut self) -> Result<(), StorageError> {
+        self.inner.flush()?;
+        self.log.truncate()
+    }
+
+    fn recover(&mut self, records: Vec<LogRecord>) -> Result<(), StorageError> {
+        if records.is_empty() {
+            return Ok(());
+        }
+
+        let mut pending: FxHashMap<TxId, Vec<Mutation>> = FxHashMap::default();
+        let mut opened: FxHashSet<String> = FxHashSet::default();
+        let mut replayed = 0;
+
+        for record in records {
+            self.next_txid = self.next_txid.max(record_txid(&record) + 1);
+            match record {
+                LogRecord::Begin { txid } => {
+                    pending.insert(txid, vec![]);
+                }
+                LogRecord::Write { txid, mutation } => {
+                    pending.entry(txid).or_default().push(mutation);
+                }
+                LogRecord::Commit { txid } => {
+                    let batch = pending.remove(&txid).unwrap_or_default();
+                    for mutation in &batch {
+                        let collection = match mutation {
+                            Mutation::Insert { collection, .. }
+                            | Mutation::Delete { collection, .. } => collection,
+                        };
+                        if !self.inner.is_open(collection) {
+                            self.inner.open_collection(collection)?;
+                            opened.insert(collection.clone());
+                        }
+                    }
+                    self.inner.write_batch(batch)?;
+                    replayed += 1;
+                }
+            }
+        }
+
+        if !pending.is_empty() {
+            warn!("Discarding {} uncommitted transactions", pending.len());
+        }
+
+        tracing::info!("Recovered {} transactions from the log", replayed);
+
+        self.checkpoint()?;
+
+        for collection in opened {
+            self.inner.close_collection(&collection)?;
+        }
+
+        Ok(())
+    }
+
+    fn commit(&mut self, batch: Vec<Mutation>) -> Result<(), StorageError> {
+        // Reject what the wrapped engine would reject before logging anything,
+        // otherwise recovery would fail on the same batch again
+        for mutation in &batch {
+            match mutation {
+                Mutation::Insert {
+                    collection,
+                    id,
+                    document,
+                } => {
+                    validate_document(document)?;
+                    self.inner.get(collection, *id)?;
+                }
+                Mutation::Delete { collection, id } => {
+                    self.inner.get(collection, *id)?;
+                }
+            }
+        }
+
+        let txid = self.next_txid;
+        self.next_txid += 1;
+
+        let mut records = Vec::with_capacity(batch.len() + 2);
+        records.push(LogRecord::Begin { txid });
+        records.extend(batch.iter().map(|mutation| LogRecord::Write {
+            txid,
+            mutation: mutation.clone(),
+        }));
+        records.push(LogRecord::Commit { txid });
+
+        self.log.append(&records)?;
+        self.inner.write_batch(batch)?;
+
+        if self.log.size >= self.checkpoint_size {
+            self.checkpoint()?;
+        }
+
+        Ok(())
+    }
+}
+
+impl StorageEngine for WalEngine {
+    fn open_collection(&mut self, name: &str) -> Result<(), StorageError> {
+        self.inner.open_collection(name)
+    }
+
+    fn close_collection(&mut self, name: &str) -> Result<(), StorageError> {
+        self.inner.close_collection(name)
+    }
+
+    fn is_open(&self, name: &str) -> bool {
+        self.inner.is_open(name)
+    }
+
+    fn insert(
+        &mut self,
+        collection: &str,
+        id: DocumentId,
+        document: RV,
+    ) -> Result<Option<RV>, StorageError> {
+        let previous = self.inner.get(collection, id)?;
+        self.commit(vec![Mutation::Insert {
+            collection: collection.to_string(),
+            id,
+            document,
+        }])?;
+        Ok(previous)
+    }
+
+    fn get(&self, collection: &str, id: DocumentId) -> Result<Option<RV>, StorageError> {
+        self.inner.get(collection, id)
+    }
+
+    fn delete(&mut self, collection: &str, id: DocumentId) -> Result<Option<RV>, StorageError> {
+        let previous = self.inner.get(collection, id)?;
+        if previous.is_some() {
+            self.commit(vec![Mutation::Delete {
+                collection: collection.to_string(),
+                id,
+            }])?;
+        }
+        Ok(previous)
+    }
+
+    fn range(
+        &self,
+        collection: &str,
+        range: DocumentRange,
+    ) -> Result<Vec<(DocumentId, RV)>, StorageError> {
+        self.inner.range(collection, range)
+    }
+
+    fn page(
+        &self,
+        collection: &str,
+        range: DocumentRange,
+        limit: usize,
+    ) -> Result<Vec<(DocumentId, RV)>, StorageError> {
+        self.inner.page(collection, range, limit)
+    }
+
+    fn last_id(
+        &self,
+        collection: &str,
+        end: Bound<DocumentId>,
+    ) -> Result<Option<DocumentId>, StorageError> {
+        self.inner.last_id(collection, end)
+    }
+
+    fn write_batch(&mut self, batch: Vec<Mutation>) -> Result<(), StorageError> {
+        if batch.is_empty() {
+            return Ok(());
+        }
+        self.commit(batch)
+    }
+
+    fn flush(&mut self) -> Result<(), StorageError> {
+        self.checkpoint()
+    }
+}
+
+impl Drop for WalEngine {
+    fn drop(&mut self) {
+        if let Err(err) = self.checkpoint() {
+            tracing::error!("Failed to checkpoint the log: {:?}", err);
+        }
+    }
+}
+
+fn record_txid(record: &LogRecord) -> TxId {
+    match record {
+        LogRecord::Begin { txid } | LogRecord::Write { txid, .. } | LogRecord::Commit { txid } => {
+            *txid
+        }
+    }
+}
+
+/// CRC-32 (IEEE) of the bytes
+pub(crate) fn checksum(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for byte in bytes {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xEDB8_8320 & mask);
+        }
+    }
+    !crc
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ops::Bound;
+    use std::path::Path;
+
+    use rustc_hash::FxHashMap;
+
+    use super::*;
+    use crate::store::disk::tests::TempDir;
+    use crate::store::disk::{DiskEngine, StorageConfig};
+    use crate::util::alloc_shared;
+
+    fn open(dir: &Path, checkpoint_size: u64) -> WalEngine {
+        let disk = DiskEngine::open(StorageConfig {
+            data_dir: dir.to_path_buf(),
+            // Small enough for pages to be evicted all the time
+            page_cache_size: 2,
+        })
+        .unwrap();
+        WalEngine::open(
+            Box::new(disk),
+            WalConfig {
+                path: dir.join("wal.log"),
+                checkpoint_size,
+            },
+        )
+        .unwrap()
+    }
+
+    // Simulates a crash: nothing is flushed or checkpointed
+    fn crash(engine: WalEngine) {
+        std::mem::forget(engine);
+    }
+
+    fn doc(n: usize) -> RV {
+        let mut map = FxHashMap::default();
+        map.insert("n".to_string(), RV::Num(n as f64));
+        RV::Object(alloc_shared(map))
+    }
+
+    fn ids(engine: &mut WalEngine) -> Vec<DocumentId> {
+        engine.open_collection("books").unwrap();
+        engine
+            .range("books", (Bound::Unbounded, Bound::Unbounded))
+            .unwrap()
+            .into_iter()
+            .map(|(id, _)| id)
+            .collect()
+    }
+
+    fn log_size(dir: &Path) -> u64 {
+        std::fs::metadata(dir.join("wal.log")).unwrap().len()
+    }
+
+    fn insert(id: DocumentId) -> Mutation {
+        Mutation::Insert {
+            collection: "books".to_string(),
+            id,
+            document: doc(id as usize),
+        }
+    }
+
+    #[test]
+    fn test_truncated_log_recovers_committed_batches() {
+        let dir = TempDir::new("wal_truncated");
+
+        let mut engine = open(dir.path(), u64::MAX);
+        engine.open_collection("books").unwrap();
+        engine.insert("books", 1, doc(1)).unwrap();
+        engine.checkpoint().unwrap();
+
+        engine.write_batch(vec![insert(2), insert(3)]).unwrap();
+        let before_last = log_size(dir.path());
+        // The last batch only reaches the file once its commit record is in
+        // the log
+        let data = std::fs::read(dir.path().join("books.ldb")).unwrap();
+        engine
+            .write_batch(vec![
+                insert(4),
+                Mutation::Delete {
+                    collection: "books".to_string(),
+                    id: 1,
+                },
+            ])
+            .unwrap();
+        let complete = log_size(dir.path());
+        crash(engine);
+
+        let log = std::fs::read(dir.path().join("wal.log")).unwrap();
+
+        // Cut the log at every byte of the last batch, including right
+        // before its commit record
+        for cut in before_last..complete {
+            let copy = TempDir::new("wal_truncated_copy");
+            std::fs::write(copy.path().join("books.ldb"), &data).unwrap();
+            std::fs::write(copy.path().join("wal.log"), &log[..cut as usize]).unwrap();
+
+            let mut recovered = open(copy.path(), u64::MAX);
+            assert_eq!(ids(&mut recovered), vec![1, 2, 3], "cut at {}", cut);
+            assert_eq!(log_size(copy.path()), 0);
+        }
+
+        let mut recovered = open(dir.path(), u64::MAX);
+        assert_eq!(ids(&mut recovered), vec![2, 3, 4]);
+    }
+
+    #[test]
+    fn test_recovery_after_evictions() {
+        let dir = TempDir::new("wal_evictions");
+
+        let mut engine = open(dir.path(), u64::MAX);
+        engine.open_collection("books").unwrap();
+        engine.write_batch((0..50).map(insert).collect()).unwrap();
+        engine.checkpoint().unwrap();
+        let checkpointed = std::fs::read(dir.path().join("books.ldb")).unwrap();
+
+        // Enough documents to split leaves many times over while only two
+        // pages fit in the cache
+        for id in 50..2000 {
+            engine.insert("books", id, doc(id as usize)).unwrap();
+        }
+        crash(engine);
+
+        // A full cache is flushed before the log is checkpointed
+        assert_ne!(
+            std::fs::read(dir.path().join("books.ldb")).unwrap(),
+            checkpointed
+        );
+
+        let mut recovered = open(dir.path(), u64::MAX);
+        assert_eq!(ids(&mut recovered), (0..2000).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_corrupted_record_is_discarded() {
+        let dir = TempDir::new("wal_corrupted");
+
+        let mut engine = open(dir.path(), u64::MAX);
+        engine.open_collection("books").unwrap();
+        engine.write_batch(vec![insert(1)]).unwrap();
+        engine.write_batch(vec![insert(2)]).unwrap();
+        crash(engine);
+
+        let path = dir.path().join("wal.log");
+        let mut log = std::fs::read(&path).unwrap();
+        let last = log.len() - 3;
+        log[last] ^= 0xff;
+        std::fs::write(&path, log).unwrap();
+
+        let mut recovered = open(dir.path(), u64::MAX);
+        assert_eq!(ids(&mut recovered), vec![1]);
+    }
+
+    #[test]
+    fn test_checkpoint_truncates_log() {
+        let dir = TempDir::new("wal_checkpoint");
+
+        let mut engine = open(dir.path(), 256);
+        engine.open_collection("books").unwrap();
+        for id in 0..20 {
+            engine.insert("books", id, doc(id as usize)).unwrap();
+            assert!(log_size(dir.path()) < 256);
+        }
+        crash(engine);
+
+        let mut recovered = open(dir.path(), 256);
+        assert_eq!(ids(&mut recovered), (0..20).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_rejected_batch_is_not_logged() {
+        let dir = TempDir::new("wal_rejected");
+
+        let mut engine = open(dir.path(), u64::MAX);
+        engine.open_collection("books").unwrap();
+
+        assert!(matches!(
+            engine.write_batch(vec![
+                insert(1),
+                Mutation::Insert {
+                    collection: "books".to_string(),
+                    id: 2,
+                    document: RV::Num(2.0),
+                }
+            ]),
+            Err(StorageError::InvalidDocument { .. })
+        ));
+        assert_eq!(
+            engine.insert("authors", 1, doc(1)),
+            Err(StorageError::CollectionNotFound {
+                name: "authors".to_string()
+            })
+        );
+
+        assert_eq!(log_size(dir.path()), 0);
+        assert_eq!(ids(&mut engine), Vec::<DocumentId>::new());
+    }
+}
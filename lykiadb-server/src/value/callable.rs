@@ -1,7 +1,7 @@
 use super::environment::EnvironmentFrame;
 use super::RV;
 use crate::{
-    engine::interpreter::{HaltReason, Interpreter},
+    engine::interpreter::{HaltReason, InterpretError, Interpreter},
     util::Shared,
 };
 use lykiadb_lang::ast::stmt::Stmt;
@@ -20,6 +20,9 @@ pub struct Callable {
     pub arity: Option<usize>,
     pub kind: CallableKind,
     pub function: Arc<Function>,
+    /// Whether calls have no side effects and return the same value for the
+    /// same arguments, so that the planner may make them ahead of time.
+    pub pure: bool,
 }
 
 impl Callable {
@@ -28,6 +31,33 @@ impl Callable {
             arity,
             kind: call_type,
             function: Arc::new(function),
+            pure: false,
+        }
+    }
+
+    pub fn pure(mut self) -> Self {
+        self.pure = true;
+        self
+    }
+
+    /// An aggregate made of the functions it folds the rows of a group with.
+    /// It takes the arguments its `step` function takes after the state.
+    pub fn aggregator(init: Callable, step: Callable, finalize: Callable) -> Self {
+        Callable::new(
+            step.arity.map(|arity| arity.saturating_sub(1)),
+            CallableKind::Aggregator,
+            Function::Aggregator(Aggregator {
+                init,
+                step,
+                finalize,
+            }),
+        )
+    }
+
+    pub fn as_aggregator(&self) -> Option<&Aggregator> {
+        match (&self.kind, self.function.as_ref()) {
+            (CallableKind::Aggregator, Function::Aggregator(aggregator)) => Some(aggregator),
+            _ => None,
         }
     }
 
@@ -41,10 +71,47 @@ impl Callable {
                 body,
                 ..
             } => interpreter.user_fn_call(body, closure.clone(), parameters, arguments),
+            Function::Aggregator(_) => Err(HaltReason::Error(
+                InterpretError::Other {
+                    message: "Aggregates can only be called in queries".to_owned(),
+                }
+                .into(),
+            )),
         }
     }
 }
 
+/// A user-defined aggregate, computed by folding the rows of a group into a
+/// state: `init` creates the state, `step` folds the arguments of a row into
+/// it and returns the new state, and `finalize` turns it into the result.
+#[derive(Clone, Debug)]
+pub struct Aggregator {
+    pub init: Callable,
+    pub step: Callable,
+    pub finalize: Callable,
+}
+
+impl Aggregator {
+    pub fn init(&self, interpreter: &mut Interpreter) -> Result<RV, HaltReason> {
+        interpreter.call(&self.init, &[])
+    }
+
+    pub fn step(
+        &self,
+        interpreter: &mut Interpreter,
+        state: RV,
+        arguments: &[RV],
+    ) -> Result<RV, HaltReason> {
+        let mut step_arguments = vec![state];
+        step_arguments.extend_from_slice(arguments);
+        interpreter.call(&self.step, &step_arguments)
+    }
+
+    pub fn finalize(&self, interpreter: &mut Interpreter, state: RV) -> Result<RV, HaltReason> {
+        interpreter.call(&self.finalize, &[state])
+    }
+}
+
 pub trait Stateful {
     fn call(&mut self, interpreter: &mut Interpreter, rv: &[RV]) -> Result<RV, HaltReason>;
 }
@@ -61,6 +128,7 @@ pub enum Function {
         closure: Arc<EnvironmentFrame>,
         body: Arc<Vec<Stmt>>,
     },
+    Aggregator(Aggregator),
 }
 
 impl Function {
@@ -68,6 +136,7 @@ impl Function {
         match self {
             Function::Stateful(_) | Function::Lambda { .. } => write!(f, "<native_fn>"),
             Function::UserDefined { .. } => write!(f, "<user_defined_fn>"),
+            Function::Aggregator(_) => write!(f, "<aggregate_fn>"),
         }
     }
 }
@@ -1,4 +1,5 @@
 use super::RV;
+use crate::util::alloc_shared;
 use lykiadb_lang::ast::expr::Operation;
 use std::ops;
 use std::sync::Arc;
@@ -98,6 +99,12 @@ impl ops::Add for RV {
             (RV::Str(s), RV::Bool(bool)) => RV::Str(Arc::new(s.to_string() + &bool.to_string())),
             (RV::Bool(bool), RV::Str(s)) => RV::Str(Arc::new(bool.to_string() + &s.to_string())),
             //
+            (RV::Array(l), RV::Array(r)) => {
+                let mut values = l.read().unwrap().clone();
+                values.extend(r.read().unwrap().iter().cloned());
+                RV::Array(alloc_shared(values))
+            }
+            //
             (_, _) => RV::NaN,
         }
     }
@@ -302,6 +309,26 @@ mod test {
         );
     }
 
+    #[test]
+    fn test_eval_binary_array_concatenation() {
+        let left = RV::Array(alloc_shared(vec![RV::Num(1.0)]));
+        let right = RV::Array(alloc_shared(vec![RV::Num(2.0), RV::Num(3.0)]));
+
+        let RV::Array(values) = eval_binary(left.clone(), right, Operation::Add) else {
+            panic!("Expected an array");
+        };
+        assert_eq!(
+            *values.read().unwrap(),
+            vec![RV::Num(1.0), RV::Num(2.0), RV::Num(3.0)]
+        );
+
+        // The operands are left untouched
+        let RV::Array(values) = left else {
+            unreachable!()
+        };
+        assert_eq!(values.read().unwrap().len(), 1);
+    }
+
     #[test]
     fn test_eval_binary_subtraction() {
         assert_eq!(
@@ -86,6 +86,25 @@ impl RV {
     pub fn not(&self) -> RV {
         RV::Bool(!self.as_bool())
     }
+
+    // Objects and arrays are shared by reference, this copies the whole tree
+    pub fn deep_clone(&self) -> RV {
+        match self {
+            RV::Object(obj) => {
+                let obj = obj.read().unwrap();
+                RV::Object(alloc_shared(
+                    obj.iter()
+                        .map(|(k, v)| (k.clone(), v.deep_clone()))
+                        .collect(),
+                ))
+            }
+            RV::Array(arr) => {
+                let arr = arr.read().unwrap();
+                RV::Array(alloc_shared(arr.iter().map(|v| v.deep_clone()).collect()))
+            }
+            other => other.clone(),
+        }
+    }
 }
 
 impl Display for RV {